@@ -1,6 +1,6 @@
 use anyhow::Result;
 use clap::Parser;
-use pandemic_common::DaemonClient;
+use pandemic_common::{PersistentClient, ReconnectPolicy};
 use pandemic_protocol::PluginInfo;
 use std::collections::HashMap;
 use std::env;
@@ -17,7 +17,7 @@ struct Args {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     let mut config = HashMap::new();
@@ -29,12 +29,16 @@ async fn main() -> Result<()> {
         description: Some("A simple hello world infection plugin".to_string()),
         config: Some(config),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
-    let mut client = DaemonClient::connect(&args.socket_path).await?;
+    let mut client =
+        PersistentClient::connect_with_reconnect(&args.socket_path, ReconnectPolicy::default())
+            .await?;
     info!("Connected to daemon, registering and keeping connection alive...");
 
-    // This will register and keep the connection alive
+    // This will register and keep the connection alive across daemon restarts
     client.register_and_keep_alive(plugin).await?;
 
     Ok(())
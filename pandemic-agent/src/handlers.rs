@@ -1,37 +1,74 @@
-use pandemic_common::RegistryClient;
-use pandemic_protocol::{AgentRequest, Response};
-use serde::{Deserialize, Serialize};
-use tracing::info;
-
-use crate::systemd::{
-    delete_service_override, execute_systemctl, get_service_override, list_pandemic_services,
-    set_service_override,
-};
+use pandemic_common::{DaemonClient, InitBackend, RegistryClient, ServiceAction};
+use pandemic_protocol::{AgentRequest, ErrorCode, Response, ServiceOverrides};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::jobs::JobTable;
 use crate::users::{
     add_user_to_group, create_group, create_user, delete_group, delete_user, list_groups,
-    list_users, remove_user_from_group, update_user,
+    list_users, load_blocklist, remove_user_from_group, update_user, validate_user_config,
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PandemicServiceSummary {
-    pub name: String,
-    pub description: String,
-    pub status: String,
+/// Topic announcing a changed or reset service override
+pub const SERVICE_CONFIG_CHANGED: &str = "service.config.changed";
+
+/// Tell daemon subscribers about an override change. The change has already
+/// been made, so a daemon that can't be reached only costs the event.
+async fn announce_config_change(
+    daemon_socket: &Path,
+    service: &str,
+    overrides: Option<&ServiceOverrides>,
+) {
+    let data = serde_json::json!({"service": service, "overrides": overrides});
+    if let Err(e) = DaemonClient::publish(daemon_socket, SERVICE_CONFIG_CHANGED, data).await {
+        warn!("Failed to announce config change for {}: {}", service, e);
+    }
+}
+
+/// Start `request` as a background job so a slow operation doesn't hold up
+/// the connection, answering with the job's id
+pub fn submit_agent_request(
+    request: AgentRequest,
+    backend: Arc<dyn InitBackend>,
+    daemon_socket: Arc<PathBuf>,
+    jobs: &JobTable,
+) -> Response {
+    info!("Submitting background job: {:?}", request);
+    let table = jobs.clone();
+    match jobs.spawn(async move {
+        handle_agent_request(request, backend.as_ref(), &daemon_socket, &table).await
+    }) {
+        Ok(job_id) => Response::accepted(job_id),
+        Err(response) => response,
+    }
 }
 
-pub async fn handle_agent_request(request: AgentRequest) -> Response {
+pub async fn handle_agent_request(
+    request: AgentRequest,
+    backend: &dyn InitBackend,
+    daemon_socket: &Path,
+    jobs: &JobTable,
+) -> Response {
     match request {
+        AgentRequest::GetJob { job_id } => match jobs.get(&job_id) {
+            Some(job) => Response::success_with_data(serde_json::json!(job)),
+            None => {
+                Response::error_with_code(ErrorCode::NotFound, format!("Unknown job: {}", job_id))
+            }
+        },
+
         AgentRequest::GetHealth => {
             info!("Health check requested");
             Response::success_with_data(serde_json::json!({
                 "status": "healthy",
-                "capabilities": ["systemd"]
+                "capabilities": [backend.name()]
             }))
         }
 
-        AgentRequest::ListServices => {
+        AgentRequest::ListServices { filter } => {
             info!("Service list requested");
-            match list_pandemic_services().await {
+            match backend.list_matching(filter.as_deref()) {
                 Ok(services) => Response::success_with_data(serde_json::json!({
                     "services": services
                 })),
@@ -42,12 +79,15 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
         AgentRequest::GetCapabilities => {
             info!("Capabilities requested");
             Response::success_with_data(serde_json::json!({
-                "capabilities": ["systemd", "service_management", "user_management", "group_management", "service_config", "infection_registry"]
+                "capabilities": [backend.name(), "service_management", "user_management", "group_management", "service_config", "infection_registry"]
             }))
         }
 
         AgentRequest::UserCreate { username, config } => {
             info!("Creating user: {}", username);
+            if let Err(e) = validate_user_config(&config) {
+                return Response::error_with_code(ErrorCode::InvalidRequest, e.to_string());
+            }
             match create_user(&username, &config).await {
                 Ok(_) => Response::success(),
                 Err(e) => Response::error(format!("Failed to create user: {}", e)),
@@ -80,15 +120,33 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
 
         AgentRequest::ServiceConfigOverride { service, overrides } => {
             info!("Setting service config override for: {}", service);
-            match set_service_override(&service, &overrides).await {
-                Ok(_) => Response::success(),
+            match backend.set_override(&service, &overrides) {
+                Ok(_) => {
+                    announce_config_change(daemon_socket, &service, Some(&overrides)).await;
+                    Response::success()
+                }
                 Err(e) => Response::error(format!("Failed to set service override: {}", e)),
             }
         }
 
+        AgentRequest::GetServiceLogs {
+            service,
+            lines,
+            since,
+        } => {
+            info!("Getting {} log lines for: {}", lines, service);
+            match backend.logs(&service, lines, since.as_deref()) {
+                Ok(entries) => Response::success_with_data(serde_json::json!({
+                    "service": service,
+                    "entries": entries
+                })),
+                Err(e) => Response::error(format!("Failed to get service logs: {}", e)),
+            }
+        }
+
         AgentRequest::GetServiceConfig { service } => {
             info!("Getting service config for: {}", service);
-            match get_service_override(&service).await {
+            match backend.get_override(&service) {
                 Ok(config) => Response::success_with_data(serde_json::json!({
                     "service": service,
                     "config": config
@@ -99,21 +157,42 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
 
         AgentRequest::ServiceConfigReset { service } => {
             info!("Resetting service config for: {}", service);
-            match delete_service_override(&service).await {
-                Ok(_) => Response::success(),
+            match backend.reset_override(&service) {
+                Ok(_) => {
+                    announce_config_change(daemon_socket, &service, None).await;
+                    Response::success()
+                }
                 Err(e) => Response::error(format!("Failed to reset service config: {}", e)),
             }
         }
 
         AgentRequest::SystemdControl { action, service } => {
-            info!("Systemd control: {} {}", action, service);
+            info!(
+                "Service control ({}): {} {}",
+                backend.name(),
+                action,
+                service
+            );
 
-            let result = match action.as_str() {
-                "start" | "stop" | "restart" | "enable" | "disable" | "status" => {
-                    execute_systemctl(&action, &service).await
+            let result = match action.parse::<ServiceAction>() {
+                // A stopped or missing service is an answer, not a failure
+                Ok(ServiceAction::Status) => {
+                    return match backend.check_status(&service) {
+                        Ok(status) => Response::success_with_data(serde_json::json!({
+                            "action": action,
+                            "service": service,
+                            "state": status.state,
+                            "output": status.output
+                        })),
+                        Err(e) => Response::error(format!("Service operation failed: {}", e)),
+                    };
                 }
-                _ => {
-                    return Response::error("Invalid systemd action");
+                Ok(parsed) => backend.control(parsed, &service),
+                Err(_) => {
+                    return Response::error_with_code(
+                        ErrorCode::InvalidRequest,
+                        "Invalid service action",
+                    );
                 }
             };
 
@@ -123,7 +202,7 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
                     "service": service,
                     "output": output
                 })),
-                Err(e) => Response::error(format!("Systemd operation failed: {}", e)),
+                Err(e) => Response::error(format!("Service operation failed: {}", e)),
             }
         }
 
@@ -137,6 +216,9 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
 
         AgentRequest::UserModify { username, config } => {
             info!("Modifying user: {}", username);
+            if let Err(e) = validate_user_config(&config) {
+                return Response::error_with_code(ErrorCode::InvalidRequest, e.to_string());
+            }
             match update_user(&username, &config).await {
                 Ok(_) => Response::success(),
                 Err(e) => Response::error(format!("Failed to modify user: {}", e)),
@@ -151,6 +233,11 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
             }
         }
 
+        AgentRequest::GetBlocklist => {
+            info!("Blocklist requested");
+            Response::success_with_data(serde_json::json!(*load_blocklist()))
+        }
+
         AgentRequest::GroupAddUser {
             groupname,
             username,
@@ -215,3 +302,268 @@ pub async fn handle_agent_request(request: AgentRequest) -> Response {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::tests::wait_for_completion;
+    use pandemic_common::{ServiceDefinition, ServiceSummary};
+    use pandemic_protocol::JobState;
+    use std::collections::{BTreeMap, HashMap};
+    use std::process::Command;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Keeps overrides in memory instead of touching unit files
+    #[derive(Default)]
+    struct MemoryBackend {
+        overrides: Mutex<HashMap<String, ServiceOverrides>>,
+        /// What every service command exits with
+        exit_code: i32,
+    }
+
+    impl InitBackend for MemoryBackend {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn command(&self, action: ServiceAction, service: &str) -> Command {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(format!(
+                "echo {} {}; exit {}",
+                action.as_str(),
+                service,
+                self.exit_code
+            ));
+            command
+        }
+
+        fn properties(&self, _service: &str) -> anyhow::Result<BTreeMap<String, String>> {
+            Ok(BTreeMap::new())
+        }
+
+        fn list(&self) -> anyhow::Result<Vec<ServiceSummary>> {
+            Ok(Vec::new())
+        }
+
+        fn install(&self, _service: &str, _definition: &ServiceDefinition) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn uninstall(&self, _service: &str) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn get_override(&self, service: &str) -> anyhow::Result<Option<ServiceOverrides>> {
+            Ok(self.overrides.lock().unwrap().get(service).cloned())
+        }
+
+        fn set_override(&self, service: &str, overrides: &ServiceOverrides) -> anyhow::Result<()> {
+            self.overrides
+                .lock()
+                .unwrap()
+                .insert(service.to_string(), overrides.clone());
+            Ok(())
+        }
+
+        fn reset_override(&self, service: &str) -> anyhow::Result<()> {
+            self.overrides.lock().unwrap().remove(service);
+            Ok(())
+        }
+    }
+
+    /// Acknowledge requests on `listener`, forwarding each one
+    fn mock_daemon(
+        listener: UnixListener,
+    ) -> tokio::sync::mpsc::UnboundedReceiver<serde_json::Value> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                reader
+                    .get_mut()
+                    .write_all(b"{\"status\":\"Success\",\"data\":null}\n")
+                    .await
+                    .unwrap();
+                let _ = tx.send(serde_json::from_str(&line).unwrap());
+            }
+        });
+        rx
+    }
+
+    #[tokio::test]
+    async fn test_override_changes_are_published() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let daemon_socket = temp_dir.path().join("pandemic.sock");
+        let mut published = mock_daemon(UnixListener::bind(&daemon_socket).unwrap());
+        let backend = MemoryBackend::default();
+
+        let overrides = ServiceOverrides {
+            environment: Some(HashMap::from([("PORT".to_string(), "8081".to_string())])),
+            exec_start: None,
+            restart: Some("always".to_string()),
+            user: None,
+            group: None,
+        };
+        let response = handle_agent_request(
+            AgentRequest::ServiceConfigOverride {
+                service: "pandemic-udp".to_string(),
+                overrides,
+            },
+            &backend,
+            &daemon_socket,
+            &JobTable::new(),
+        )
+        .await;
+        assert!(matches!(response, Response::Success { .. }));
+        assert!(backend.get_override("pandemic-udp").unwrap().is_some());
+
+        let request = published.recv().await.unwrap();
+        assert_eq!(request["type"], "Publish");
+        assert_eq!(request["topic"], SERVICE_CONFIG_CHANGED);
+        assert_eq!(request["data"]["service"], "pandemic-udp");
+        assert_eq!(request["data"]["overrides"]["environment"]["PORT"], "8081");
+
+        handle_agent_request(
+            AgentRequest::ServiceConfigReset {
+                service: "pandemic-udp".to_string(),
+            },
+            &backend,
+            &daemon_socket,
+            &JobTable::new(),
+        )
+        .await;
+        let request = published.recv().await.unwrap();
+        assert_eq!(request["data"]["service"], "pandemic-udp");
+        assert!(request["data"]["overrides"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_daemon_does_not_fail_override() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let response = handle_agent_request(
+            AgentRequest::ServiceConfigReset {
+                service: "pandemic-udp".to_string(),
+            },
+            &MemoryBackend::default(),
+            &temp_dir.path().join("missing.sock"),
+            &JobTable::new(),
+        )
+        .await;
+        assert!(matches!(response, Response::Success { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_user_config_is_rejected_before_useradd() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config: pandemic_protocol::UserConfig =
+            serde_json::from_value(serde_json::json!({"shell": "bash"})).unwrap();
+        let response = handle_agent_request(
+            AgentRequest::UserCreate {
+                username: "alice".to_string(),
+                config,
+            },
+            &MemoryBackend::default(),
+            &temp_dir.path().join("missing.sock"),
+            &JobTable::new(),
+        )
+        .await;
+        match response {
+            Response::Error { message, code } => {
+                assert_eq!(code, Some(ErrorCode::InvalidRequest));
+                assert_eq!(message, "Shell must be an absolute path: bash");
+            }
+            other => panic!("Expected an error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submitted_request_completes_as_a_job() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let daemon_socket = Arc::new(temp_dir.path().join("missing.sock"));
+        let backend = Arc::new(MemoryBackend::default());
+        let jobs = JobTable::new();
+
+        let request = AgentRequest::ServiceConfigReset {
+            service: "pandemic-udp".to_string(),
+        };
+        let Response::Accepted { job_id } =
+            submit_agent_request(request, backend.clone(), daemon_socket.clone(), &jobs)
+        else {
+            panic!("expected the request to be accepted");
+        };
+
+        wait_for_completion(&jobs, &job_id).await;
+        let request = AgentRequest::GetJob {
+            job_id: job_id.clone(),
+        };
+        let Response::Success { data: Some(data) } =
+            handle_agent_request(request, backend.as_ref(), &daemon_socket, &jobs).await
+        else {
+            panic!("expected the job");
+        };
+        let job: pandemic_protocol::JobInfo = serde_json::from_value(data).unwrap();
+        assert_eq!(job.job_id, job_id);
+        assert_eq!(job.state, JobState::Completed);
+        assert!(matches!(job.result, Some(Response::Success { .. })));
+
+        let request = AgentRequest::GetJob {
+            job_id: "job-0".to_string(),
+        };
+        assert!(matches!(
+            handle_agent_request(request, backend.as_ref(), &daemon_socket, &jobs).await,
+            Response::Error {
+                code: Some(ErrorCode::NotFound),
+                ..
+            }
+        ));
+    }
+
+    async fn control(action: &str, exit_code: i32) -> Response {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let backend = MemoryBackend {
+            exit_code,
+            ..Default::default()
+        };
+        let request = AgentRequest::SystemdControl {
+            action: action.to_string(),
+            service: "pandemic-udp".to_string(),
+        };
+        handle_agent_request(
+            request,
+            &backend,
+            &temp_dir.path().join("missing.sock"),
+            &JobTable::new(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_status_exit_codes_report_service_state() {
+        for (exit_code, state) in [(0, "active"), (3, "inactive"), (4, "not_found")] {
+            let Response::Success { data: Some(data) } = control("status", exit_code).await else {
+                panic!("expected status data for exit code {}", exit_code);
+            };
+            assert_eq!(data["state"], state);
+            assert_eq!(data["output"], "status pandemic-udp\n");
+        }
+        assert!(matches!(control("status", 1).await, Response::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_other_actions_fail_on_nonzero_exit() {
+        assert!(matches!(
+            control("restart", 0).await,
+            Response::Success { .. }
+        ));
+        for exit_code in [3, 4] {
+            assert!(matches!(
+                control("restart", exit_code).await,
+                Response::Error { .. }
+            ));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use pandemic_protocol::{ErrorCode, JobInfo, JobState, Response};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// Completed jobs kept for polling before the oldest are forgotten
+pub const MAX_COMPLETED_JOBS: usize = 256;
+
+/// Jobs that may run at once; further submissions are refused until one ends
+pub const MAX_RUNNING_JOBS: usize = 32;
+
+#[derive(Default)]
+struct Jobs {
+    jobs: HashMap<String, JobInfo>,
+    /// Completed job ids, oldest first
+    completed: VecDeque<String>,
+}
+
+/// Background jobs started with `AgentMessage::Submit`, shared by every
+/// connection so a job can be polled from any of them
+#[derive(Clone, Default)]
+pub struct JobTable {
+    inner: Arc<Mutex<Jobs>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl JobTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `job` in the background and return the id to poll it with, or the
+    /// response refusing it when [`MAX_RUNNING_JOBS`] are already running.
+    ///
+    /// Agent requests shell out with `std::process::Command`, so the job is
+    /// driven on the blocking pool rather than a runtime worker. A job that
+    /// panics is marked [`JobState::Failed`].
+    pub fn spawn<F>(&self, job: F) -> Result<String, Response>
+    where
+        F: Future<Output = Response> + Send + 'static,
+    {
+        let job_id = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.jobs.len() - inner.completed.len() >= MAX_RUNNING_JOBS {
+                return Err(Response::error_with_code(
+                    ErrorCode::Unavailable,
+                    format!("{} jobs are already running", MAX_RUNNING_JOBS),
+                ));
+            }
+            let job_id = format!("job-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1);
+            inner.jobs.insert(
+                job_id.clone(),
+                JobInfo {
+                    job_id: job_id.clone(),
+                    state: JobState::Running,
+                    result: None,
+                },
+            );
+            job_id
+        };
+
+        let runtime = tokio::runtime::Handle::current();
+        let task = tokio::task::spawn_blocking(move || runtime.block_on(job));
+        let table = self.clone();
+        let id = job_id.clone();
+        tokio::spawn(async move {
+            match task.await {
+                Ok(response) => {
+                    info!("Job {} completed", id);
+                    table.complete(&id, JobState::Completed, response);
+                }
+                Err(e) => {
+                    warn!("Job {} failed: {}", id, e);
+                    let response = Response::error_with_code(
+                        ErrorCode::Internal,
+                        format!("Job failed: {}", e),
+                    );
+                    table.complete(&id, JobState::Failed, response);
+                }
+            }
+        });
+        Ok(job_id)
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<JobInfo> {
+        self.inner.lock().unwrap().jobs.get(job_id).cloned()
+    }
+
+    fn complete(&self, job_id: &str, state: JobState, response: Response) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(job) = inner.jobs.get_mut(job_id) {
+            job.state = state;
+            job.result = Some(response);
+        }
+        inner.completed.push_back(job_id.to_string());
+        while inner.completed.len() > MAX_COMPLETED_JOBS {
+            if let Some(oldest) = inner.completed.pop_front() {
+                inner.jobs.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    pub(crate) async fn wait_for_completion(table: &JobTable, job_id: &str) -> JobInfo {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let job = table.get(job_id).unwrap();
+                if job.state != JobState::Running {
+                    return job;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("job never completed")
+    }
+
+    #[tokio::test]
+    async fn test_job_runs_to_completion_with_its_result() {
+        let table = JobTable::new();
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let job_id = table
+            .spawn(async move {
+                released.await.unwrap();
+                Response::success_with_data(serde_json::json!({"output": "restarted"}))
+            })
+            .unwrap();
+
+        let job = table.get(&job_id).unwrap();
+        assert_eq!(job.state, JobState::Running);
+        assert!(job.result.is_none());
+
+        release.send(()).unwrap();
+        let job = wait_for_completion(&table, &job_id).await;
+        match job.result {
+            Some(Response::Success { data: Some(data) }) => assert_eq!(data["output"], "restarted"),
+            other => panic!("Expected the job's response, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_panicked_job_is_marked_failed() {
+        let table = JobTable::new();
+        let job_id = table.spawn(async { panic!("job blew up") }).unwrap();
+
+        let job = wait_for_completion(&table, &job_id).await;
+        assert_eq!(job.state, JobState::Failed);
+        assert!(matches!(
+            job.result,
+            Some(Response::Error {
+                code: Some(ErrorCode::Internal),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_running_jobs_are_bounded() {
+        let table = JobTable::new();
+        let (release, released) = tokio::sync::watch::channel(false);
+        let job_ids: Vec<String> = (0..MAX_RUNNING_JOBS)
+            .map(|_| {
+                let mut released = released.clone();
+                table
+                    .spawn(async move {
+                        released.wait_for(|released| *released).await.unwrap();
+                        Response::success()
+                    })
+                    .unwrap()
+            })
+            .collect();
+
+        assert!(matches!(
+            table.spawn(async { Response::success() }),
+            Err(Response::Error {
+                code: Some(ErrorCode::Unavailable),
+                ..
+            })
+        ));
+
+        release.send(true).unwrap();
+        for job_id in &job_ids {
+            wait_for_completion(&table, job_id).await;
+        }
+        assert!(table.spawn(async { Response::success() }).is_ok());
+    }
+}
@@ -1,17 +1,20 @@
 mod handlers;
+mod jobs;
 mod socket;
-mod systemd;
 mod users;
 
 use anyhow::Result;
 use clap::Parser;
-use pandemic_protocol::{AgentMessage, Response};
+use pandemic_common::{InitBackend, InitSystem};
+use pandemic_protocol::{AgentMessage, ErrorCode, Response};
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
 use tracing::{error, info, warn};
 
-use handlers::handle_agent_request;
+use handlers::{handle_agent_request, submit_agent_request};
+use jobs::JobTable;
 use socket::setup_socket_permissions;
 
 #[derive(Parser)]
@@ -26,11 +29,15 @@ pub struct Args {
 
     #[arg(long, default_value = "pandemic")]
     pub group: String,
+
+    /// Daemon socket that service config changes are published to
+    #[arg(long, default_value = "/var/run/pandemic/pandemic.sock")]
+    pub daemon_socket: PathBuf,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     // Ensure we're running as root
@@ -40,6 +47,9 @@ async fn main() -> Result<()> {
 
     info!("Starting pandemic-agent as root");
 
+    let backend: Arc<dyn InitBackend> = Arc::from(InitSystem::detect().backend());
+    info!("Managing services with {}", backend.name());
+
     // Remove existing socket if it exists
     if args.socket_path.exists() {
         std::fs::remove_file(&args.socket_path)?;
@@ -59,10 +69,17 @@ async fn main() -> Result<()> {
     info!("Agent listening on {:?}", args.socket_path);
 
     // Accept connections
+    let daemon_socket = Arc::new(args.daemon_socket);
+    let jobs = JobTable::new();
     loop {
         match listener.accept().await {
             Ok((stream, _)) => {
-                tokio::spawn(handle_connection(stream));
+                tokio::spawn(handle_connection(
+                    stream,
+                    backend.clone(),
+                    daemon_socket.clone(),
+                    jobs.clone(),
+                ));
             }
             Err(e) => {
                 error!("Failed to accept connection: {}", e);
@@ -71,7 +88,12 @@ async fn main() -> Result<()> {
     }
 }
 
-async fn handle_connection(mut stream: UnixStream) -> Result<()> {
+async fn handle_connection(
+    mut stream: UnixStream,
+    backend: Arc<dyn InitBackend>,
+    daemon_socket: Arc<PathBuf>,
+    jobs: JobTable,
+) -> Result<()> {
     let (reader, mut writer) = stream.split();
     let mut buf_reader = BufReader::new(reader);
     let mut line = String::new();
@@ -84,11 +106,18 @@ async fn handle_connection(mut stream: UnixStream) -> Result<()> {
         }
 
         let response = match serde_json::from_str::<AgentMessage>(trimmed) {
-            Ok(AgentMessage::Request(request)) => handle_agent_request(request).await,
-            Ok(_) => Response::error("Expected request message"),
+            Ok(AgentMessage::Request(request)) => {
+                handle_agent_request(request, backend.as_ref(), &daemon_socket, &jobs).await
+            }
+            Ok(AgentMessage::Submit(request)) => {
+                submit_agent_request(request, backend.clone(), daemon_socket.clone(), &jobs)
+            }
+            Ok(_) => {
+                Response::error_with_code(ErrorCode::InvalidRequest, "Expected request message")
+            }
             Err(e) => {
                 warn!("Failed to parse message: {}", e);
-                Response::error("Invalid message format")
+                Response::error_with_code(ErrorCode::InvalidRequest, "Invalid message format")
             }
         };
 
@@ -1,42 +1,95 @@
-use std::{collections::HashSet, process::Command};
-
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, LazyLock},
+    time::SystemTime,
+};
+
+use arc_swap::ArcSwapOption;
+use pandemic_common::init::is_valid_account_name;
 use pandemic_protocol::UserConfig;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::warn;
 
+const BLOCKLIST_PATH: &str = "/etc/pandemic/blocklist.toml";
+
 #[derive(Debug, Deserialize)]
 struct BlocklistConfig {
     blocklist: Blocklist,
 }
 
-#[derive(Debug, Deserialize)]
-struct Blocklist {
-    users: Vec<String>,
-    groups: Vec<String>,
+/// Users and groups the agent refuses to modify
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Blocklist {
+    pub users: BTreeSet<String>,
+    pub groups: BTreeSet<String>,
+}
+
+struct CachedBlocklist {
+    modified: Option<SystemTime>,
+    blocklist: Arc<Blocklist>,
+}
+
+/// Parsed blocklist file, re-read only when its mtime changes
+pub struct BlocklistCache {
+    path: PathBuf,
+    cached: ArcSwapOption<CachedBlocklist>,
 }
 
-fn load_blocklist() -> (HashSet<String>, HashSet<String>) {
-    let config_content =
-        std::fs::read_to_string("/etc/pandemic/blocklist.toml").unwrap_or_else(|_| {
+impl BlocklistCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cached: ArcSwapOption::empty(),
+        }
+    }
+
+    pub fn load(&self) -> Arc<Blocklist> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+        if let Some(cached) = self.cached.load().as_ref() {
+            if cached.modified == modified {
+                return Arc::clone(&cached.blocklist);
+            }
+        }
+
+        let blocklist = Arc::new(self.read());
+        self.cached.store(Some(Arc::new(CachedBlocklist {
+            modified,
+            blocklist: Arc::clone(&blocklist),
+        })));
+        blocklist
+    }
+
+    fn read(&self) -> Blocklist {
+        let config_content = std::fs::read_to_string(&self.path).unwrap_or_else(|_| {
             warn!("No blocklist config found, using built-in defaults");
             get_default_blocklist_config()
         });
 
-    match toml::from_str::<BlocklistConfig>(&config_content) {
-        Ok(config) => (
-            config.blocklist.users.into_iter().collect(),
-            config.blocklist.groups.into_iter().collect(),
-        ),
-        Err(e) => {
-            warn!(
-                "Failed to parse blocklist config: {}, using built-in defaults",
-                e
-            );
-            get_default_blocklist()
+        match toml::from_str::<BlocklistConfig>(&config_content) {
+            Ok(config) => config.blocklist,
+            Err(e) => {
+                warn!(
+                    "Failed to parse blocklist config: {}, using built-in defaults",
+                    e
+                );
+                get_default_blocklist()
+            }
         }
     }
 }
 
+static BLOCKLIST: LazyLock<BlocklistCache> = LazyLock::new(|| BlocklistCache::new(BLOCKLIST_PATH));
+
+/// The effective blocklist, reloaded when `/etc/pandemic/blocklist.toml` changes
+pub fn load_blocklist() -> Arc<Blocklist> {
+    BLOCKLIST.load()
+}
+
 fn get_default_users() -> Vec<&'static str> {
     vec![
         "root",
@@ -149,13 +202,131 @@ groups = {:?}"#,
     )
 }
 
-fn get_default_blocklist() -> (HashSet<String>, HashSet<String>) {
-    let users = get_default_users().into_iter().map(String::from).collect();
-    let groups = get_default_groups().into_iter().map(String::from).collect();
-    (users, groups)
+/// Refuse names `useradd`/`groupadd` wouldn't accept, which also keeps a
+/// name from being taken as an option by the commands it is passed to
+fn check_name(kind: &str, name: &str) -> anyhow::Result<()> {
+    if !is_valid_account_name(name) {
+        return Err(anyhow::anyhow!("Invalid {} name: {:?}", kind, name));
+    }
+    Ok(())
+}
+
+fn get_default_blocklist() -> Blocklist {
+    Blocklist {
+        users: get_default_users().into_iter().map(String::from).collect(),
+        groups: get_default_groups().into_iter().map(String::from).collect(),
+    }
+}
+
+/// Build the `chpasswd -e` invocation and its stdin when `config` carries a
+/// password hash. The hash goes over stdin so it never shows up in `ps`.
+fn password_command(
+    username: &str,
+    config: &UserConfig,
+) -> anyhow::Result<Option<(Command, String)>> {
+    let Some(hash) = &config.password_hash else {
+        return Ok(None);
+    };
+
+    // Only crypt(3) hashes like `$6$salt$hash` are accepted, never plaintext
+    let is_crypt_hash =
+        hash.starts_with('$') && hash.split('$').count() >= 4 && !hash.contains([':', '\n', '\r']);
+    if !is_crypt_hash {
+        return Err(anyhow::anyhow!(
+            "password_hash for {} must be a pre-hashed crypt(3) value",
+            username
+        ));
+    }
+
+    let blocklist = load_blocklist();
+    if blocklist.users.contains(username) {
+        return Err(anyhow::anyhow!(
+            "Cannot set password for blocked user: {}",
+            username
+        ));
+    }
+
+    let mut cmd = Command::new("chpasswd");
+    cmd.arg("-e");
+    Ok(Some((cmd, format!("{}:{}\n", username, hash))))
+}
+
+fn set_password(username: &str, config: &UserConfig) -> anyhow::Result<()> {
+    let Some((mut cmd, input)) = password_command(username, config)? else {
+        return Ok(());
+    };
+
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "chpasswd failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check the parts of `config` that `useradd`/`usermod` would otherwise
+/// reject with an opaque error: the shell must be an existing absolute path,
+/// the home directory absolute and every group must already exist
+pub fn validate_user_config(config: &UserConfig) -> anyhow::Result<()> {
+    if let Some(shell) = &config.shell {
+        if !Path::new(shell).is_absolute() {
+            return Err(anyhow::anyhow!("Shell must be an absolute path: {}", shell));
+        }
+        if !Path::new(shell).is_file() {
+            return Err(anyhow::anyhow!("Shell does not exist: {}", shell));
+        }
+    }
+    if let Some(home) = &config.home_dir {
+        if !Path::new(home).is_absolute() {
+            return Err(anyhow::anyhow!(
+                "Home directory must be an absolute path: {}",
+                home
+            ));
+        }
+    }
+    for group in config.groups.iter().flatten() {
+        if !group_exists(group)? {
+            return Err(anyhow::anyhow!("Group does not exist: {}", group));
+        }
+    }
+    Ok(())
+}
+
+fn group_exists(group: &str) -> anyhow::Result<bool> {
+    check_name("group", group)?;
+    let output = Command::new("getent").arg("group").arg(group).output()?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        // getent exits 2 when the key isn't in the database
+        Some(2) => Ok(false),
+        _ => Err(anyhow::anyhow!(
+            "getent group {} failed: {}",
+            group,
+            String::from_utf8_lossy(&output.stderr)
+        )),
+    }
 }
 
 pub async fn create_user(username: &str, config: &UserConfig) -> anyhow::Result<()> {
+    check_name("user", username)?;
+    for group in config.groups.iter().flatten() {
+        check_name("group", group)?;
+    }
+    // Reject a bad hash before the account exists
+    password_command(username, config)?;
+
     let mut cmd = Command::new("useradd");
 
     if let Some(shell) = &config.shell {
@@ -192,12 +363,18 @@ pub async fn create_user(username: &str, config: &UserConfig) -> anyhow::Result<
         }
     }
 
+    set_password(username, config)?;
+
     Ok(())
 }
 
 pub async fn update_user(username: &str, config: &UserConfig) -> anyhow::Result<()> {
-    let (blocklist_users, blocklist_groups) = load_blocklist();
-    if blocklist_users.contains(username) {
+    check_name("user", username)?;
+    for group in config.groups.iter().flatten() {
+        check_name("group", group)?;
+    }
+    let blocklist = load_blocklist();
+    if blocklist.users.contains(username) {
         return Err(anyhow::anyhow!("Cannot update blocked user: {}", username));
     }
 
@@ -211,7 +388,7 @@ pub async fn update_user(username: &str, config: &UserConfig) -> anyhow::Result<
     }
     if let Some(groups) = &config.groups {
         for group in groups {
-            if blocklist_groups.contains(group) {
+            if blocklist.groups.contains(group) {
                 warn!("Cannot add user {} to blocked group {}", username, group);
                 continue;
             }
@@ -237,18 +414,22 @@ pub async fn update_user(username: &str, config: &UserConfig) -> anyhow::Result<
         ));
     }
 
+    set_password(username, config)?;
+
     Ok(())
 }
 
 pub async fn add_user_to_group(username: &str, group: &str) -> anyhow::Result<()> {
-    let (blocklist_users, blocklist_groups) = load_blocklist();
-    if blocklist_users.contains(username) {
+    check_name("user", username)?;
+    check_name("group", group)?;
+    let blocklist = load_blocklist();
+    if blocklist.users.contains(username) {
         return Err(anyhow::anyhow!(
             "Cannot add blocked user to group: {}",
             username
         ));
     }
-    if blocklist_groups.contains(group) {
+    if blocklist.groups.contains(group) {
         return Err(anyhow::anyhow!(
             "Cannot add user to blocked group: {}",
             group
@@ -270,14 +451,16 @@ pub async fn add_user_to_group(username: &str, group: &str) -> anyhow::Result<()
 }
 
 pub async fn remove_user_from_group(username: &str, group: &str) -> anyhow::Result<()> {
-    let (blocklist_users, blocklist_groups) = load_blocklist();
-    if blocklist_users.contains(username) {
+    check_name("user", username)?;
+    check_name("group", group)?;
+    let blocklist = load_blocklist();
+    if blocklist.users.contains(username) {
         return Err(anyhow::anyhow!(
             "Cannot add blocked user to group: {}",
             username
         ));
     }
-    if blocklist_groups.contains(group) {
+    if blocklist.groups.contains(group) {
         return Err(anyhow::anyhow!(
             "Cannot add user to blocked group: {}",
             group
@@ -298,8 +481,9 @@ pub async fn remove_user_from_group(username: &str, group: &str) -> anyhow::Resu
 }
 
 pub async fn delete_user(username: &str) -> anyhow::Result<()> {
-    let (blocklist_users, _) = load_blocklist();
-    if blocklist_users.contains(username) {
+    check_name("user", username)?;
+    let blocklist = load_blocklist();
+    if blocklist.users.contains(username) {
         return Err(anyhow::anyhow!("Cannot delete blocked user: {}", username));
     }
     let output = Command::new("userdel").arg("-r").arg(username).output()?;
@@ -318,12 +502,12 @@ pub async fn list_users() -> anyhow::Result<Vec<String>> {
         return Err(anyhow::anyhow!("getent passwd failed"));
     }
 
-    let (blocklist_users, _) = load_blocklist();
+    let blocklist = load_blocklist();
     let users: Vec<String> = String::from_utf8_lossy(&output.stdout)
         .lines()
         .map(|line| line.split(':').next().unwrap_or("").to_string())
         .filter(|u| !u.is_empty())
-        .filter(|u| !blocklist_users.contains(u))
+        .filter(|u| !blocklist.users.contains(u))
         .collect();
 
     Ok(users)
@@ -335,18 +519,19 @@ pub async fn list_groups() -> anyhow::Result<Vec<String>> {
         return Err(anyhow::anyhow!("getent group failed"));
     }
 
-    let (_, blocklist_groups) = load_blocklist();
+    let blocklist = load_blocklist();
     let groups: Vec<String> = String::from_utf8_lossy(&output.stdout)
         .lines()
         .map(|line| line.split(':').next().unwrap_or("").to_string())
         .filter(|g| !g.is_empty())
-        .filter(|g| !blocklist_groups.contains(g))
+        .filter(|g| !blocklist.groups.contains(g))
         .collect();
 
     Ok(groups)
 }
 
 pub async fn create_group(groupname: &str) -> anyhow::Result<()> {
+    check_name("group", groupname)?;
     let output = Command::new("groupadd").arg(groupname).output()?;
     if !output.status.success() {
         return Err(anyhow::anyhow!(
@@ -358,8 +543,9 @@ pub async fn create_group(groupname: &str) -> anyhow::Result<()> {
 }
 
 pub async fn delete_group(groupname: &str) -> anyhow::Result<()> {
-    let (_, blocklist_groups) = load_blocklist();
-    if blocklist_groups.contains(groupname) {
+    check_name("group", groupname)?;
+    let blocklist = load_blocklist();
+    if blocklist.groups.contains(groupname) {
         return Err(anyhow::anyhow!(
             "Cannot delete blocked group: {}",
             groupname
@@ -374,3 +560,159 @@ pub async fn delete_group(groupname: &str) -> anyhow::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_config(password_hash: Option<&str>) -> UserConfig {
+        UserConfig {
+            shell: None,
+            home_dir: None,
+            groups: None,
+            system_user: None,
+            password_hash: password_hash.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_password_command_only_with_hash() {
+        assert!(password_command("alice", &user_config(None))
+            .unwrap()
+            .is_none());
+
+        let hash = "$6$saltsalt$c2VjcmV0aGFzaA";
+        let (cmd, input) = password_command("alice", &user_config(Some(hash)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(cmd.get_program(), "chpasswd");
+        assert_eq!(cmd.get_args().collect::<Vec<_>>(), ["-e"]);
+        assert_eq!(input, format!("alice:{}\n", hash));
+    }
+
+    #[test]
+    fn test_password_command_rejects_plaintext() {
+        for password in [
+            "hunter2",
+            "$plain",
+            "$6$salt$hash:extra",
+            "$6$salt$hash\nroot:x",
+        ] {
+            assert!(
+                password_command("alice", &user_config(Some(password))).is_err(),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[test]
+    fn test_blocklist_reloads_when_file_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("blocklist.toml");
+        std::fs::write(
+            &path,
+            "[blocklist]\nusers = [\"root\"]\ngroups = [\"sudo\"]\n",
+        )
+        .unwrap();
+        let cache = BlocklistCache::new(&path);
+
+        let blocklist = cache.load();
+        assert!(blocklist.users.contains("root"));
+        assert!(!blocklist.users.contains("alice"));
+        assert!(Arc::ptr_eq(&blocklist, &cache.load()));
+
+        std::fs::write(
+            &path,
+            "[blocklist]\nusers = [\"root\", \"alice\"]\ngroups = [\"sudo\"]\n",
+        )
+        .unwrap();
+        // Coarse filesystem timestamps may not tick between two quick writes
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        let blocklist = cache.load();
+        assert_eq!(
+            serde_json::json!(*blocklist),
+            serde_json::json!({ "users": ["alice", "root"], "groups": ["sudo"] })
+        );
+    }
+
+    #[test]
+    fn test_validate_user_config_accepts_existing_values() {
+        let config = UserConfig {
+            shell: Some("/bin/sh".to_string()),
+            home_dir: Some("/home/alice".to_string()),
+            groups: Some(vec!["root".to_string()]),
+            ..user_config(None)
+        };
+        validate_user_config(&config).unwrap();
+        validate_user_config(&user_config(None)).unwrap();
+    }
+
+    #[test]
+    fn test_validate_user_config_rejects_invalid_values() {
+        for (config, expected) in [
+            (
+                UserConfig {
+                    shell: Some("bash".to_string()),
+                    ..user_config(None)
+                },
+                "Shell must be an absolute path: bash",
+            ),
+            (
+                UserConfig {
+                    shell: Some("/bin/pandemic-no-such-shell".to_string()),
+                    ..user_config(None)
+                },
+                "Shell does not exist: /bin/pandemic-no-such-shell",
+            ),
+            (
+                UserConfig {
+                    home_dir: Some("home/alice".to_string()),
+                    ..user_config(None)
+                },
+                "Home directory must be an absolute path: home/alice",
+            ),
+            (
+                UserConfig {
+                    groups: Some(vec![
+                        "root".to_string(),
+                        "pandemic-no-such-group".to_string(),
+                    ]),
+                    ..user_config(None)
+                },
+                "Group does not exist: pandemic-no-such-group",
+            ),
+            (
+                UserConfig {
+                    groups: Some(vec!["--help".to_string()]),
+                    ..user_config(None)
+                },
+                "Invalid group name: \"--help\"",
+            ),
+        ] {
+            let error = validate_user_config(&config).unwrap_err().to_string();
+            assert_eq!(error, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commands_reject_invalid_names() {
+        let error = create_user("-o", &user_config(None)).await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid user name: \"-o\"");
+        let error = add_user_to_group("alice", "wheel\nroot").await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid group name: \"wheel\\nroot\"");
+        assert!(create_group("").await.is_err());
+        assert!(delete_user("../alice").await.is_err());
+    }
+
+    #[test]
+    fn test_password_command_respects_blocklist() {
+        let error = password_command("root", &user_config(Some("$6$salt$hash")))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("blocked user"), "{}", error);
+    }
+}
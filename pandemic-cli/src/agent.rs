@@ -1,37 +1,30 @@
+use crate::output::OutputFormat;
 use crate::{system, AgentAction};
 use anyhow::Result;
+use pandemic_common::ServiceDefinition;
 use std::path::Path;
 
-pub fn handle_agent_command(action: AgentAction) -> Result<()> {
+pub fn handle_agent_command(action: AgentAction, output: OutputFormat) -> Result<()> {
     match action {
-        AgentAction::Install { binary_path } => install_agent(&binary_path),
-        AgentAction::Uninstall => system::uninstall_service("agent"),
-        AgentAction::Start => system::start_service("agent"),
-        AgentAction::Stop => system::stop_service("agent"),
-        AgentAction::Restart => system::restart_service("agent"),
-        AgentAction::Status => system::status_service("agent"),
+        AgentAction::Install { binary_path } => install_agent(&binary_path, output),
+        AgentAction::Uninstall => system::uninstall_service("agent", output),
+        AgentAction::Start => system::start_service("agent", output),
+        AgentAction::Stop => system::stop_service("agent", output),
+        AgentAction::Restart => system::restart_service("agent", output),
+        AgentAction::Status => system::status_service("agent", output),
     }
 }
 
-pub fn install_agent(binary_path: &Path) -> Result<()> {
-    let service_content = format!(
-        r#"[Unit]
-Description=Pandemic Agent - Privileged Operations Service
-After=network.target
+pub fn install_agent(binary_path: &Path, output: OutputFormat) -> Result<()> {
+    let definition = ServiceDefinition {
+        description: "Pandemic Agent - Privileged Operations Service".to_string(),
+        exec_start: binary_path.display().to_string(),
+        user: "root".to_string(),
+        group: "root".to_string(),
+        requires: Vec::new(),
+        runtime_directory: None,
+        notify: false,
+    };
 
-[Service]
-Type=simple
-ExecStart={}
-Restart=always
-RestartSec=5
-User=root
-Group=root
-
-[Install]
-WantedBy=multi-user.target
-"#,
-        binary_path.display()
-    );
-
-    system::install_service("agent", &service_content)
+    system::install_service("agent", &definition, output)
 }
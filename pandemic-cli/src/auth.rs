@@ -0,0 +1,29 @@
+use crate::AuthAction;
+use anyhow::Result;
+use pandemic_common::api_key;
+use std::io::BufRead;
+
+pub fn handle_auth_command(action: AuthAction) -> Result<()> {
+    match action {
+        AuthAction::HashKey { key } => hash_key(key),
+    }
+}
+
+fn hash_key(key: Option<String>) -> Result<()> {
+    let key = match key {
+        Some(key) => key,
+        None => {
+            // Reading from stdin keeps the key out of shell history
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    if key.is_empty() {
+        anyhow::bail!("API key must not be empty");
+    }
+
+    println!("api_key_hash = \"{}\"", api_key::hash_key(&key));
+    Ok(())
+}
@@ -1,48 +1,40 @@
 use anyhow::Result;
+use pandemic_common::ServiceDefinition;
 use std::path::Path;
 
+use crate::output::OutputFormat;
 use crate::{agent, system, BootstrapAction};
 
-pub fn handle_bootstrap_command(action: BootstrapAction) -> Result<()> {
+pub fn handle_bootstrap_command(action: BootstrapAction, output: OutputFormat) -> Result<()> {
     match action {
         BootstrapAction::Install {
             binary_path,
             with_agent,
-        } => install_daemon(&binary_path, with_agent),
-        BootstrapAction::Uninstall => system::uninstall_service("pandemic"),
-        BootstrapAction::Start => system::start_service("pandemic"),
-        BootstrapAction::Stop => system::stop_service("pandemic"),
-        BootstrapAction::Restart => system::restart_service("pandemic"),
-        BootstrapAction::Status => system::status_service("pandemic"),
+        } => install_daemon(&binary_path, with_agent, output),
+        BootstrapAction::Uninstall => system::uninstall_service("pandemic", output),
+        BootstrapAction::Start => system::start_service("pandemic", output),
+        BootstrapAction::Stop => system::stop_service("pandemic", output),
+        BootstrapAction::Restart => system::restart_service("pandemic", output),
+        BootstrapAction::Status => system::status_service("pandemic", output),
     }
 }
 
-fn install_daemon(binary_path: &Path, with_agent: bool) -> Result<()> {
-    let service_content = format!(
-        r#"[Unit]
-Description=Pandemic Daemon
-After=network.target
+fn install_daemon(binary_path: &Path, with_agent: bool, output: OutputFormat) -> Result<()> {
+    let definition = ServiceDefinition {
+        description: "Pandemic Daemon".to_string(),
+        exec_start: binary_path.display().to_string(),
+        user: "pandemic".to_string(),
+        group: "pandemic".to_string(),
+        requires: Vec::new(),
+        runtime_directory: Some("pandemic".to_string()),
+        // The daemon tells systemd it is ready once its socket accepts connections
+        notify: true,
+    };
 
-[Service]
-Type=simple
-ExecStart={}
-Restart=always
-RestartSec=5
-User=pandemic
-Group=pandemic
-RuntimeDirectory=pandemic
-RuntimeDirectoryMode=0755
-
-[Install]
-WantedBy=multi-user.target
-"#,
-        binary_path.display()
-    );
-
-    system::install_service("pandemic", &service_content)?;
+    system::install_service("pandemic", &definition, output)?;
 
     if with_agent {
-        agent::install_agent(Path::new("/usr/local/bin/pandemic-agent"))?;
+        agent::install_agent(Path::new("/usr/local/bin/pandemic-agent"), output)?;
     }
 
     Ok(())
@@ -1,38 +1,220 @@
-use anyhow::Result;
-use pandemic_common::DaemonClient;
-use pandemic_protocol::{Request, Response};
+use anyhow::{bail, Context, Result};
+use pandemic_common::{DaemonClient, PersistentClient, ReconnectPolicy, DEFAULT_REQUEST_TIMEOUT};
+use pandemic_protocol::{DaemonInfo, Event, Request, Response};
+use serde_json::{json, Value};
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::Instant;
 
+use crate::output::{display_timestamp, print_json, OutputFormat};
 use crate::DaemonAction;
 
-pub async fn handle_daemon_command(socket_path: &PathBuf, action: DaemonAction) -> Result<()> {
+pub async fn handle_daemon_command(
+    socket_path: &PathBuf,
+    action: DaemonAction,
+    output: OutputFormat,
+) -> Result<()> {
+    let is_list = matches!(action, DaemonAction::List);
     let request = match action {
         DaemonAction::List => Request::ListPlugins,
         DaemonAction::Get { name } => Request::GetPlugin { name },
         DaemonAction::Deregister { name } => Request::Deregister { name },
-        DaemonAction::Status => {
-            println!("Daemon is running at {:?}", socket_path);
-            return Ok(());
-        }
+        DaemonAction::Status => return status(socket_path, output).await,
+        DaemonAction::Ping => return ping(socket_path, output).await,
         DaemonAction::Health => Request::GetHealth,
+        DaemonAction::Watch { topics } => return watch_events(socket_path, topics, output).await,
+        DaemonAction::Publish { topic, data } => Request::Publish {
+            topic,
+            data: parse_payload(&data)?,
+            retain: false,
+        },
     };
 
-    let response = DaemonClient::send_request(socket_path, &request).await?;
+    let response =
+        DaemonClient::send_request_timeout(socket_path, &request, DEFAULT_REQUEST_TIMEOUT).await?;
+    if output == OutputFormat::Json {
+        // Successful data goes out verbatim; anything else as the full response
+        return match &response {
+            Response::Success { data: Some(data) } => print_json(data),
+            other => print_json(other),
+        };
+    }
+
     match response {
-        Response::Success { data } => {
-            if let Some(data) = data {
-                println!("{}", serde_json::to_string_pretty(&data)?);
-            } else {
-                println!("Success");
-            }
-        }
-        Response::Error { message } => {
+        Response::Success { data } => match data {
+            Some(data) if is_list => print_plugins(&data),
+            Some(data) => println!("{}", serde_json::to_string_pretty(&data)?),
+            None => println!("Success"),
+        },
+        Response::Error { message, .. } => {
             eprintln!("Error: {}", message);
         }
         Response::NotFound { message } => {
             eprintln!("Not Found: {}", message);
         }
+        Response::Conflict { message } => {
+            eprintln!("Conflict: {}", message);
+        }
+        Response::Accepted { job_id } => {
+            println!("Accepted as job {}", job_id);
+        }
+        Response::DependenciesUnmet { missing } => {
+            eprintln!("Missing dependencies: {}", missing.join(", "));
+        }
+        Response::IncompatibleVersion {
+            server,
+            min_supported,
+        } => {
+            eprintln!(
+                "Incompatible daemon protocol version {} (minimum supported {})",
+                server, min_supported
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Show the daemon's version, uptime and features from `Info`
+async fn status(socket_path: &PathBuf, output: OutputFormat) -> Result<()> {
+    let response =
+        DaemonClient::send_request_timeout(socket_path, &Request::Info, DEFAULT_REQUEST_TIMEOUT)
+            .await?;
+    let Response::Success { data: Some(data) } = response else {
+        bail!("Daemon did not return its info: {:?}", response);
+    };
+    let mut info: DaemonInfo =
+        serde_json::from_value(data.clone()).context("Daemon returned malformed info")?;
+    info.socket_path.get_or_insert_with(|| socket_path.clone());
+
+    match output {
+        OutputFormat::Json => print_json(&info)?,
+        OutputFormat::Text => {
+            println!(
+                "Daemon is running at {:?}",
+                info.socket_path.unwrap_or_default()
+            );
+            println!(
+                "   Version: {} (protocol {})",
+                info.version, info.protocol_version
+            );
+            println!("   Uptime: {}s", info.uptime_seconds);
+            if let Some(started_at) = data.get("started_at").and_then(Value::as_str) {
+                println!("   Started: {}", display_timestamp(started_at));
+            }
+            if !info.features.is_empty() {
+                println!("   Features: {}", info.features.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Time one `Ping` round trip, failing unless the daemon answers with success
+async fn ping(socket_path: &PathBuf, output: OutputFormat) -> Result<()> {
+    let started = Instant::now();
+    let response =
+        DaemonClient::send_request_timeout(socket_path, &Request::Ping, DEFAULT_REQUEST_TIMEOUT)
+            .await?;
+    let latency = started.elapsed();
+    if !matches!(response, Response::Success { .. }) {
+        bail!("Daemon did not answer the ping: {:?}", response);
+    }
+
+    let latency_ms = latency.as_secs_f64() * 1000.0;
+    match output {
+        OutputFormat::Text => println!("Daemon responded in {:.2} ms", latency_ms),
+        OutputFormat::Json => print_json(&json!({ "latency_ms": latency_ms }))?,
+    }
+    Ok(())
+}
+
+/// Parse an event payload as JSON, reading it from stdin when `data` is `-`
+fn parse_payload(data: &str) -> Result<Value> {
+    let data = if data == "-" {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+        input
+    } else {
+        data.to_string()
+    };
+    serde_json::from_str(&data).context("Event data is not valid JSON")
+}
+
+/// Print events on `topics` as they arrive, riding out daemon restarts
+async fn watch_events(
+    socket_path: &PathBuf,
+    topics: Vec<String>,
+    output: OutputFormat,
+) -> Result<()> {
+    let topics = if topics.is_empty() {
+        vec!["*".to_string()]
+    } else {
+        topics
+    };
+
+    let mut client =
+        PersistentClient::connect_with_reconnect(socket_path, ReconnectPolicy::default()).await?;
+
+    client
+        .subscribe_transient(
+            "pandemic-cli-watch",
+            "Event watcher started from pandemic-cli",
+            topics.clone(),
+        )
+        .await
+        .context("Failed to start watching")?;
+
+    if output == OutputFormat::Text {
+        eprintln!("Watching {} (Ctrl-C to stop)", topics.join(", "));
+    }
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            event = client.read_event() => match event? {
+                Some(event) => print_event(&event, output)?,
+                None => {
+                    eprintln!("Connection to daemon closed");
+                    break;
+                }
+            },
+        }
     }
 
     Ok(())
 }
+
+fn print_event(event: &Event, output: OutputFormat) -> Result<()> {
+    match output {
+        OutputFormat::Json => print_json(event)?,
+        OutputFormat::Text => println!("{} [{}] {}", event.topic, event.source, event.data),
+    }
+    Ok(())
+}
+
+fn print_plugins(data: &Value) {
+    let plugins = data.as_array().map(Vec::as_slice).unwrap_or_default();
+    if plugins.is_empty() {
+        println!("No plugins registered");
+        return;
+    }
+
+    println!("{} plugin(s) registered:", plugins.len());
+    println!();
+    for plugin in plugins {
+        let field = |key: &str| plugin.get(key).and_then(Value::as_str);
+        println!(
+            "📦 {} ({})",
+            field("name").unwrap_or("?"),
+            field("version").unwrap_or("?")
+        );
+        if let Some(description) = field("description") {
+            println!("   Description: {}", description);
+        }
+        if let Some(registered_at) = field("registered_at") {
+            println!("   Registered: {}", display_timestamp(registered_at));
+        }
+        println!();
+    }
+}
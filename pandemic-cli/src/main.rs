@@ -1,12 +1,16 @@
 mod agent;
+mod auth;
 mod bootstrap;
 mod daemon;
+mod output;
 mod registry;
+mod scaffold;
 mod service;
 mod system;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand};
+use output::OutputFormat;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -16,6 +20,10 @@ struct Args {
     #[arg(long, default_value = "/var/run/pandemic/pandemic.sock")]
     socket_path: PathBuf,
 
+    /// Print results as human-readable text or raw JSON
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    output: OutputFormat,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -44,9 +52,37 @@ enum Commands {
     },
     /// Search and install infections from registry
     Registry {
+        /// Always fetch the registry index instead of revalidating a cached copy
+        #[arg(long, global = true)]
+        no_cache: bool,
         #[command(subcommand)]
         action: RegistryAction,
     },
+    /// Generate a new infection project
+    New {
+        /// Infection name, used for the crate, binary and plugin
+        name: String,
+        /// Directory to create the project in (defaults to ./<name>)
+        #[arg(long)]
+        dir: Option<PathBuf>,
+    },
+    /// Manage REST API credentials
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Print a shell completion script to stdout
+    #[command(hide = true)]
+    Completions { shell: clap_complete::Shell },
+}
+
+#[derive(Subcommand)]
+enum AuthAction {
+    /// Hash an API key for use as `api_key_hash` in the REST auth config
+    HashKey {
+        /// API key to hash, read from stdin when omitted
+        key: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -69,7 +105,7 @@ enum RegistryAction {
     },
     /// Install an infection from the registry
     Install {
-        /// Infection name
+        /// Infection name, optionally pinned as `name@<semver>` (e.g. `hello@^1.2`)
         name: String,
         /// Registry URL to use
         #[arg(long)]
@@ -91,10 +127,24 @@ enum DaemonAction {
         /// Plugin name
         name: String,
     },
-    /// Check daemon status
+    /// Show the daemon's version, protocol version, uptime and features
     Status,
+    /// Check that the daemon answers and report the round-trip latency
+    Ping,
     /// Get health metrics
     Health,
+    /// Stream events as they are published, until Ctrl-C
+    Watch {
+        /// Topic patterns to watch (defaults to every topic)
+        topics: Vec<String>,
+    },
+    /// Publish an event
+    Publish {
+        /// Event topic
+        topic: String,
+        /// JSON payload, read from stdin when `-`
+        data: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -169,6 +219,16 @@ enum ServiceAction {
         /// Service name
         name: String,
     },
+    /// Start an infection service at boot
+    Enable {
+        /// Service name
+        name: String,
+    },
+    /// Stop starting an infection service at boot
+    Disable {
+        /// Service name
+        name: String,
+    },
     /// Displays the service status
     Status {
         /// Service name
@@ -203,19 +263,28 @@ enum ServiceAction {
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     match args.command {
         Commands::Daemon { action } => {
-            daemon::handle_daemon_command(&args.socket_path, action).await?
+            daemon::handle_daemon_command(&args.socket_path, action, args.output).await?
         }
-        Commands::Service { action } => service::handle_service_command(action)?,
-        Commands::Bootstrap { action } => bootstrap::handle_bootstrap_command(action)?,
-        Commands::Agent { action } => agent::handle_agent_command(action)?,
-        Commands::Registry { action } => {
-            registry::handle_registry_command(&args.socket_path, action).await?
+        Commands::Service { action } => service::handle_service_command(action, args.output)?,
+        Commands::Bootstrap { action } => bootstrap::handle_bootstrap_command(action, args.output)?,
+        Commands::Agent { action } => agent::handle_agent_command(action, args.output)?,
+        Commands::Registry { no_cache, action } => {
+            registry::handle_registry_command(&args.socket_path, action, no_cache, args.output)
+                .await?
         }
+        Commands::New { name, dir } => scaffold::handle_new_command(&name, dir)?,
+        Commands::Auth { action } => auth::handle_auth_command(action)?,
+        Commands::Completions { shell } => clap_complete::generate(
+            shell,
+            &mut Args::command(),
+            "pandemic-cli",
+            &mut std::io::stdout(),
+        ),
     }
 
     Ok(())
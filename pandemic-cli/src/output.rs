@@ -0,0 +1,29 @@
+use anyhow::Result;
+use serde::Serialize;
+
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// Raw JSON on stdout, one document per command
+    Json,
+}
+
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Show an RFC 3339 timestamp from the daemon as `2024-01-02 03:04:05 UTC`,
+/// passing anything else through as sent
+pub fn display_timestamp(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|datetime| {
+            datetime
+                .with_timezone(&chrono::Utc)
+                .format("%Y-%m-%d %H:%M:%S UTC")
+                .to_string()
+        })
+        .unwrap_or_else(|_| timestamp.to_string())
+}
@@ -1,34 +1,58 @@
+use crate::output::{print_json, OutputFormat};
 use crate::RegistryAction;
 use anyhow::Result;
 use pandemic_common::RegistryClient;
+use semver::VersionReq;
+use serde_json::json;
 use std::path::PathBuf;
 use tracing::{error, info};
 
-pub async fn handle_registry_command(_socket_path: &PathBuf, action: RegistryAction) -> Result<()> {
+pub async fn handle_registry_command(
+    _socket_path: &PathBuf,
+    action: RegistryAction,
+    no_cache: bool,
+    output: OutputFormat,
+) -> Result<()> {
     match action {
         RegistryAction::Search {
             query,
             registry_url,
-        } => search_infections(&query, registry_url).await,
+        } => search_infections(&query, registry_client(registry_url, no_cache), output).await,
         RegistryAction::Get { name, registry_url } => {
-            get_infection_manifest(&name, registry_url).await
+            get_infection_manifest(&name, registry_client(registry_url, no_cache), output).await
         }
         RegistryAction::Install { name, registry_url } => {
-            install_infection(&name, registry_url).await
+            install_infection(&name, registry_client(registry_url, no_cache), output).await
         }
     }
 }
 
-async fn search_infections(query: &str, registry_url: Option<String>) -> Result<()> {
+fn registry_client(registry_url: Option<String>, no_cache: bool) -> RegistryClient {
     let registry = match registry_url {
         Some(url) => RegistryClient::with_registry_url(url),
         None => RegistryClient::new(),
     };
 
+    if no_cache {
+        registry.without_cache()
+    } else {
+        registry
+    }
+}
+
+async fn search_infections(
+    query: &str,
+    registry: RegistryClient,
+    output: OutputFormat,
+) -> Result<()> {
     info!("Searching for infections matching '{}'...", query);
 
     match registry.search_infections(query).await {
         Ok(infections) => {
+            if output == OutputFormat::Json {
+                return print_json(&infections);
+            }
+
             if infections.is_empty() {
                 println!("No infections found matching '{}'", query);
                 return Ok(());
@@ -53,16 +77,19 @@ async fn search_infections(query: &str, registry_url: Option<String>) -> Result<
     Ok(())
 }
 
-async fn get_infection_manifest(name: &str, registry_url: Option<String>) -> Result<()> {
-    let registry = match registry_url {
-        Some(url) => RegistryClient::with_registry_url(url),
-        None => RegistryClient::new(),
-    };
-
+async fn get_infection_manifest(
+    name: &str,
+    registry: RegistryClient,
+    output: OutputFormat,
+) -> Result<()> {
     info!("Getting manifest for infection '{}'...", name);
 
     match registry.get_infection_manifest(name).await {
         Ok(manifest) => {
+            if output == OutputFormat::Json {
+                return print_json(&manifest);
+            }
+
             println!("📋 Infection Manifest: {}", manifest.name);
             println!("   Version: {}", manifest.version);
             println!("   Description: {}", manifest.description);
@@ -96,25 +123,48 @@ async fn get_infection_manifest(name: &str, registry_url: Option<String>) -> Res
     Ok(())
 }
 
-async fn install_infection(name: &str, registry_url: Option<String>) -> Result<()> {
-    let registry = match registry_url {
-        Some(url) => RegistryClient::with_registry_url(url),
-        None => RegistryClient::new(),
-    };
+/// Split `name@<semver>` into the infection name and an optional version requirement
+fn parse_install_spec(spec: &str) -> Result<(&str, Option<VersionReq>)> {
+    match spec.split_once('@') {
+        Some((name, req)) => {
+            let req = VersionReq::parse(req)
+                .map_err(|e| anyhow::anyhow!("Invalid version requirement '{}': {}", req, e))?;
+            Ok((name, Some(req)))
+        }
+        None => Ok((spec, None)),
+    }
+}
+
+async fn install_infection(
+    spec: &str,
+    registry: RegistryClient,
+    output: OutputFormat,
+) -> Result<()> {
+    let (name, req) = parse_install_spec(spec)?;
 
-    info!("Installing infection '{}'...", name);
+    info!("Installing infection '{}'...", spec);
 
     // Get the manifest first
-    let manifest = registry.get_infection_manifest(name).await?;
+    let manifest = match &req {
+        Some(req) => registry.get_infection_manifest_version(name, req).await?,
+        None => registry.get_infection_manifest(name).await?,
+    };
 
     // Download to a default location
     let target_path = format!("/tmp/{}", name);
 
     match registry.download_infection(&manifest, &target_path).await {
+        Ok(()) if output == OutputFormat::Json => {
+            print_json(&json!({
+                "name": name,
+                "version": manifest.version,
+                "path": target_path,
+            }))?;
+        }
         Ok(()) => {
             println!(
-                "✅ Successfully downloaded infection '{}' to {}",
-                name, target_path
+                "✅ Successfully downloaded infection '{}' {} to {}",
+                name, manifest.version, target_path
             );
             println!(
                 "   To install as a service, use: pandemic-cli service install {} {}",
@@ -0,0 +1,60 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const CARGO_TOML: &str = include_str!("../templates/infection/Cargo.toml.tmpl");
+const MAIN_RS: &str = include_str!("../templates/infection/main.rs.tmpl");
+const INFECTION_TOML: &str = include_str!("../templates/infection/infection.toml.tmpl");
+
+pub fn handle_new_command(name: &str, dir: Option<PathBuf>) -> Result<()> {
+    let dir = dir.unwrap_or_else(|| PathBuf::from(name));
+    generate(name, &dir)?;
+
+    println!("Created infection '{}' in {}", name, dir.display());
+    println!("Build it with `cargo build --release`, then install the service with:");
+    println!(
+        "  pandemic-cli service install {} target/release/{}",
+        name, name
+    );
+    Ok(())
+}
+
+/// Write a new infection project for `name` into `dir`, which must not
+/// already hold any files
+pub fn generate(name: &str, dir: &Path) -> Result<()> {
+    validate_name(name)?;
+    if dir.exists() && dir.read_dir()?.next().is_some() {
+        anyhow::bail!("{} already exists and is not empty", dir.display());
+    }
+
+    std::fs::create_dir_all(dir.join("src"))
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    for (path, template) in [
+        ("Cargo.toml", CARGO_TOML),
+        ("src/main.rs", MAIN_RS),
+        ("infection.toml", INFECTION_TOML),
+    ] {
+        std::fs::write(dir.join(path), render(template, name))
+            .with_context(|| format!("Failed to write {}", path))?;
+    }
+    Ok(())
+}
+
+fn render(template: &str, name: &str) -> String {
+    template.replace("{{name}}", name)
+}
+
+/// The name becomes the package, binary and plugin name, so it has to be
+/// a valid crate name
+fn validate_name(name: &str) -> Result<()> {
+    let starts_with_letter = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+    let valid_chars = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !starts_with_letter || !valid_chars {
+        anyhow::bail!(
+            "Invalid infection name '{}': use letters, digits, '-' and '_', starting with a letter",
+            name
+        );
+    }
+    Ok(())
+}
@@ -1,56 +1,52 @@
 use anyhow::Result;
+use pandemic_common::ServiceDefinition;
+use serde_json::json;
 use std::path::Path;
 use std::process::Command;
 
+use crate::output::{print_json, OutputFormat};
 use crate::{system, ServiceAction};
 
-pub fn handle_service_command(action: ServiceAction) -> Result<()> {
+pub fn handle_service_command(action: ServiceAction, output: OutputFormat) -> Result<()> {
     match action {
-        ServiceAction::Install { name, binary_path } => install_service(&name, &binary_path),
-        ServiceAction::Uninstall { name } => system::uninstall_service(&name),
-        ServiceAction::Start { name } => system::start_service(&name),
-        ServiceAction::Stop { name } => system::stop_service(&name),
-        ServiceAction::Restart { name } => system::restart_service(&name),
-        ServiceAction::Status { name } => system::status_service(&name),
+        ServiceAction::Install { name, binary_path } => {
+            install_service(&name, &binary_path, output)
+        }
+        ServiceAction::Uninstall { name } => system::uninstall_service(&name, output),
+        ServiceAction::Start { name } => system::start_service(&name, output),
+        ServiceAction::Stop { name } => system::stop_service(&name, output),
+        ServiceAction::Restart { name } => system::restart_service(&name, output),
+        ServiceAction::Enable { name } => system::enable_service(&name, output),
+        ServiceAction::Disable { name } => system::disable_service(&name, output),
+        ServiceAction::Status { name } => system::status_service(&name, output),
         ServiceAction::Logs {
             name,
             follow,
             lines,
-        } => logs_service(&name, follow, lines),
+        } => logs_service(&name, follow, lines, output),
         ServiceAction::Config {
             name,
             show,
             reset,
             args,
-        } => config_service(&name, show, reset, args),
+        } => config_service(&name, show, reset, args, output),
     }
 }
 
-fn install_service(name: &str, binary_path: &Path) -> Result<()> {
-    let service_content = format!(
-        r#"[Unit]
-Description=Pandemic Infection: {}
-After=pandemic.service
-Requires=pandemic.service
-
-[Service]
-Type=simple
-ExecStart={}
-Restart=always
-RestartSec=5
-User=pandemic
-Group=pandemic
-
-[Install]
-WantedBy=multi-user.target
-"#,
-        name,
-        binary_path.display()
-    );
-    system::install_service(name, &service_content)
+fn install_service(name: &str, binary_path: &Path, output: OutputFormat) -> Result<()> {
+    let definition = ServiceDefinition {
+        description: format!("Pandemic Infection: {}", name),
+        exec_start: binary_path.display().to_string(),
+        user: "pandemic".to_string(),
+        group: "pandemic".to_string(),
+        requires: vec!["pandemic".to_string()],
+        runtime_directory: None,
+        notify: false,
+    };
+    system::install_service(name, &definition, output)
 }
 
-fn logs_service(name: &str, follow: bool, lines: u32) -> Result<()> {
+fn logs_service(name: &str, follow: bool, lines: u32, output: OutputFormat) -> Result<()> {
     let service_name = if name.starts_with("pandemic") {
         name.to_string()
     } else {
@@ -63,34 +59,61 @@ fn logs_service(name: &str, follow: bool, lines: u32) -> Result<()> {
     if follow {
         cmd.arg("-f");
     }
+    if output == OutputFormat::Json {
+        // journalctl writes one JSON object per entry
+        cmd.args(["-o", "json"]);
+    }
 
     cmd.status()?;
     Ok(())
 }
 
-fn config_service(name: &str, show: bool, reset: bool, args: Vec<String>) -> Result<()> {
+fn config_service(
+    name: &str,
+    show: bool,
+    reset: bool,
+    args: Vec<String>,
+    output: OutputFormat,
+) -> Result<()> {
     let service_name = format!("pandemic-{}", name);
     let override_dir = format!("/etc/systemd/system/{}.service.d", service_name);
     let override_file = format!("{}/override.conf", override_dir);
 
     if show {
-        if std::path::Path::new(&override_file).exists() {
-            let content = std::fs::read_to_string(&override_file)?;
-            println!("Current configuration for {}:", service_name);
-            println!("{}", content);
+        let content = if std::path::Path::new(&override_file).exists() {
+            Some(std::fs::read_to_string(&override_file)?)
         } else {
-            println!("No custom configuration for {}", service_name);
+            None
+        };
+        match (output, content) {
+            (OutputFormat::Json, content) => {
+                print_json(&json!({"service": service_name, "override": content}))?
+            }
+            (OutputFormat::Text, Some(content)) => {
+                println!("Current configuration for {}:", service_name);
+                println!("{}", content);
+            }
+            (OutputFormat::Text, None) => {
+                println!("No custom configuration for {}", service_name)
+            }
         }
         return Ok(());
     }
 
     if reset {
-        if std::path::Path::new(&override_dir).exists() {
+        let existed = std::path::Path::new(&override_dir).exists();
+        if existed {
             std::fs::remove_dir_all(&override_dir)?;
             Command::new("systemctl").args(["daemon-reload"]).status()?;
-            println!("Reset {} to default configuration", service_name);
-        } else {
-            println!("{} already using default configuration", service_name);
+        }
+        match output {
+            OutputFormat::Json => print_json(
+                &json!({"service": service_name, "action": "reset", "changed": existed}),
+            )?,
+            OutputFormat::Text if existed => {
+                println!("Reset {} to default configuration", service_name)
+            }
+            OutputFormat::Text => println!("{} already using default configuration", service_name),
         }
         return Ok(());
     }
@@ -109,9 +132,18 @@ fn config_service(name: &str, show: bool, reset: bool, args: Vec<String>) -> Res
 
     Command::new("systemctl").args(["daemon-reload"]).status()?;
 
-    println!("Updated {} configuration:", service_name);
-    println!("ExecStart={}", exec_start);
-    println!("Run 'systemctl restart {}' to apply changes", service_name);
+    match output {
+        OutputFormat::Json => print_json(&json!({
+            "service": service_name,
+            "action": "config",
+            "exec_start": exec_start,
+        }))?,
+        OutputFormat::Text => {
+            println!("Updated {} configuration:", service_name);
+            println!("ExecStart={}", exec_start);
+            println!("Run 'systemctl restart {}' to apply changes", service_name);
+        }
+    }
 
     Ok(())
 }
@@ -1,5 +1,17 @@
 use anyhow::Result;
-use std::process::Command;
+use pandemic_common::{InitBackend, InitSystem, ServiceAction, ServiceDefinition};
+use serde_json::json;
+
+use crate::output::{print_json, OutputFormat};
+
+/// Report that `action` completed for `service_name`
+fn report(output: OutputFormat, service_name: &str, action: &str, message: &str) -> Result<()> {
+    match output {
+        OutputFormat::Text => println!("{}: {}", message, service_name),
+        OutputFormat::Json => print_json(&json!({"service": service_name, "action": action}))?,
+    }
+    Ok(())
+}
 
 fn system_name(service: &str) -> String {
     if service.starts_with("pandemic") {
@@ -9,66 +21,111 @@ fn system_name(service: &str) -> String {
     }
 }
 
-pub fn install_service(service: &str, service_content: &str) -> Result<()> {
+fn backend() -> Box<dyn InitBackend> {
+    InitSystem::detect().backend()
+}
+
+pub fn install_service(
+    service: &str,
+    definition: &ServiceDefinition,
+    output: OutputFormat,
+) -> Result<()> {
     let service_name = system_name(service);
-    let service_path = format!("/etc/systemd/system/{}.service", service_name);
-    std::fs::write(&service_path, service_content)?;
-    Command::new("systemctl").args(["daemon-reload"]).status()?;
-    Command::new("systemctl")
-        .args(["enable", &service_name])
-        .status()?;
-    println!("Installed service: {}", service_name);
-    Ok(())
+    backend().install(&service_name, definition)?;
+    report(output, &service_name, "install", "Installed service")
 }
 
-pub fn uninstall_service(service: &str) -> Result<()> {
+pub fn uninstall_service(service: &str, output: OutputFormat) -> Result<()> {
     let service_name = system_name(service);
-    Command::new("systemctl")
-        .args(["disable", &service_name])
-        .status()?;
-    Command::new("systemctl")
-        .args(["stop", &service_name])
-        .status()?;
-
-    let service_path = format!("/etc/systemd/system/{}.service", service_name);
-    std::fs::remove_file(&service_path)?;
-
-    Command::new("systemctl").args(["daemon-reload"]).status()?;
-    println!("Uninstalled service: {}", service_name);
-    Ok(())
+    backend().uninstall(&service_name)?;
+    report(output, &service_name, "uninstall", "Uninstalled service")
 }
 
-pub fn start_service(service: &str) -> Result<()> {
+pub fn start_service(service: &str, output: OutputFormat) -> Result<()> {
     let service_name = system_name(service);
-    Command::new("systemctl")
-        .args(["start", &service_name])
-        .status()?;
-    println!("Started service: {}", service_name);
-    Ok(())
+    backend().start(&service_name)?;
+    report(output, &service_name, "start", "Started service")
 }
 
-pub fn stop_service(service: &str) -> Result<()> {
+pub fn stop_service(service: &str, output: OutputFormat) -> Result<()> {
     let service_name = system_name(service);
-    Command::new("systemctl")
-        .args(["stop", &service_name])
-        .status()?;
-    println!("Stopped service: {}", service_name);
-    Ok(())
+    backend().stop(&service_name)?;
+    report(output, &service_name, "stop", "Stopped service")
 }
 
-pub fn restart_service(service: &str) -> Result<()> {
+pub fn restart_service(service: &str, output: OutputFormat) -> Result<()> {
     let service_name = system_name(service);
-    Command::new("systemctl")
-        .args(["restart", &service_name])
-        .status()?;
-    println!("Restarted service: {}", service_name);
-    Ok(())
+    backend().restart(&service_name)?;
+    report(output, &service_name, "restart", "Restarted service")
+}
+
+pub fn enable_service(service: &str, output: OutputFormat) -> Result<()> {
+    let service_name = system_name(service);
+    backend().enable(&service_name)?;
+    report(output, &service_name, "enable", "Enabled service")
+}
+
+pub fn disable_service(service: &str, output: OutputFormat) -> Result<()> {
+    let service_name = system_name(service);
+    backend().disable(&service_name)?;
+    report(output, &service_name, "disable", "Disabled service")
 }
 
-pub fn status_service(service: &str) -> Result<()> {
+pub fn status_service(service: &str, output: OutputFormat) -> Result<()> {
     let service_name = system_name(service);
-    Command::new("systemctl")
-        .args(["status", &service_name])
-        .status()?;
+    let backend = backend();
+    match output {
+        OutputFormat::Text => {
+            // Let the init system print straight to the terminal
+            backend
+                .command(ServiceAction::Status, &service_name)
+                .status()?;
+        }
+        OutputFormat::Json => {
+            let mut status = backend.properties(&service_name)?;
+            status.insert("service".to_string(), service_name);
+            print_json(&status)?;
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_common::{OpenRcBackend, SystemdBackend};
+
+    fn args(command: &std::process::Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_system_name_adds_prefix_once() {
+        assert_eq!(system_name("udp"), "pandemic-udp");
+        assert_eq!(system_name("pandemic-udp"), "pandemic-udp");
+        assert_eq!(system_name("pandemic"), "pandemic");
+    }
+
+    #[test]
+    fn test_enable_and_disable_commands() {
+        let service_name = system_name("udp");
+        let systemd = SystemdBackend::default();
+        assert_eq!(
+            args(&systemd.command(ServiceAction::Enable, &service_name)),
+            ["systemctl", "enable", "pandemic-udp"]
+        );
+        assert_eq!(
+            args(&systemd.command(ServiceAction::Disable, &service_name)),
+            ["systemctl", "disable", "pandemic-udp"]
+        );
+
+        let openrc = OpenRcBackend::default();
+        assert_eq!(
+            args(&openrc.command(ServiceAction::Enable, &service_name)),
+            ["rc-update", "add", "pandemic-udp", "default"]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use std::process::Command;
+
+fn completions(shell: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(["completions", shell])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_bash_completions_cover_subcommands() {
+    let script = completions("bash");
+    assert!(script.contains("pandemic-cli"));
+    assert!(script.contains("service"));
+    assert!(script.contains("enable"));
+    assert!(script.contains("--output"));
+}
+
+#[test]
+fn test_every_shell_is_supported() {
+    for shell in ["bash", "zsh", "fish", "powershell", "elvish"] {
+        assert!(!completions(shell).is_empty(), "{}", shell);
+    }
+}
@@ -0,0 +1,309 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Answer every `ListPlugins` request with two fixed plugins
+fn mock_daemon(socket_path: &Path) {
+    let listener = UnixListener::bind(socket_path).unwrap();
+    let response = serde_json::json!({
+        "status": "Success",
+        "data": [
+            {
+                "name": "hello-infection",
+                "version": "1.2.3",
+                "description": "A simple hello world infection plugin",
+                "config": null,
+                "registered_at": "2024-01-02T03:04:05Z"
+            },
+            {
+                "name": "pandemic-udp",
+                "version": "0.4.0",
+                "description": null,
+                "config": {"protocol": "UDP"},
+                "registered_at": null
+            }
+        ]
+    })
+    .to_string();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(request["type"], "ListPlugins");
+            writeln!(stream, "{}", response).unwrap();
+        }
+    });
+}
+
+fn daemon_list(socket_path: &Path, output: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(["--output", output, "--socket-path"])
+        .arg(socket_path)
+        .args(["daemon", "list"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_daemon_list_text_and_json_output() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    mock_daemon(&socket_path);
+
+    let text = daemon_list(&socket_path, "text");
+    assert!(text.contains("2 plugin(s) registered"), "{}", text);
+    assert!(text.contains("📦 hello-infection (1.2.3)"), "{}", text);
+    assert!(
+        text.contains("Registered: 2024-01-02 03:04:05 UTC"),
+        "{}",
+        text
+    );
+    assert!(serde_json::from_str::<serde_json::Value>(&text).is_err());
+
+    let json = daemon_list(&socket_path, "json");
+    assert_eq!(json.lines().count(), 1, "{}", json);
+    let plugins: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let plugins = plugins.as_array().unwrap();
+    assert_eq!(plugins.len(), 2);
+    assert_eq!(plugins[0]["name"], "hello-infection");
+    assert_eq!(plugins[1]["config"]["protocol"], "UDP");
+}
+
+/// Accept a single watcher, acknowledge its session and publish one event
+/// once it subscribes
+fn mock_event_daemon(socket_path: &Path) -> mpsc::Receiver<serde_json::Value> {
+    let listener = UnixListener::bind(socket_path).unwrap();
+    let (requests_tx, requests_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        for line in reader.lines() {
+            let Ok(line) = line else { break };
+            let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+            let subscribe = request["type"] == "Subscribe";
+            // Record before replying so the test sees every request once the
+            // event has been printed
+            let _ = requests_tx.send(request);
+            writeln!(
+                stream,
+                "{}",
+                serde_json::json!({"status": "Success", "data": null})
+            )
+            .unwrap();
+
+            if subscribe {
+                let event = serde_json::json!({
+                    "type": "Event",
+                    "topic": "custom.greeting",
+                    "source": "tester",
+                    "data": {"message": "hello"},
+                    "timestamp": null
+                });
+                writeln!(stream, "{}", event).unwrap();
+            }
+        }
+    });
+
+    requests_rx
+}
+
+#[test]
+fn test_daemon_watch_prints_published_events() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    let requests = mock_event_daemon(&socket_path);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(["--output", "json", "--socket-path"])
+        .arg(&socket_path)
+        .args(["daemon", "watch", "custom.#"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let stdout = child.stdout.take().unwrap();
+    let (line_tx, line_rx) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let _ = line_tx.send(line.unwrap());
+        }
+    });
+    let line = line_rx.recv_timeout(Duration::from_secs(10));
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let event: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+    assert_eq!(event["topic"], "custom.greeting");
+    assert_eq!(event["source"], "tester");
+    assert_eq!(event["data"]["message"], "hello");
+
+    let types: Vec<_> = requests
+        .try_iter()
+        .map(|request| request["type"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(types, vec!["Hello", "Register", "Subscribe"]);
+}
+
+/// Acknowledge every request, forwarding each one for inspection
+fn mock_recording_daemon(socket_path: &Path) -> mpsc::Receiver<serde_json::Value> {
+    let listener = UnixListener::bind(socket_path).unwrap();
+    let (requests_tx, requests_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            writeln!(
+                stream,
+                "{}",
+                serde_json::json!({"status": "Success", "data": null})
+            )
+            .unwrap();
+            let _ = requests_tx.send(serde_json::from_str(&line).unwrap());
+        }
+    });
+
+    requests_rx
+}
+
+#[test]
+fn test_daemon_publish_sends_parsed_payload() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    let requests = mock_recording_daemon(&socket_path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .args([
+            "daemon",
+            "publish",
+            "custom.greeting",
+            r#"{"message":"hello"}"#,
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request["type"], "Publish");
+    assert_eq!(request["topic"], "custom.greeting");
+    assert_eq!(request["data"], serde_json::json!({"message": "hello"}));
+    assert_eq!(request["retain"], false);
+
+    // `-` reads the payload from stdin
+    let mut child = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .args(["daemon", "publish", "custom.count", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"[1, 2, 3]\n")
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request["topic"], "custom.count");
+    assert_eq!(request["data"], serde_json::json!([1, 2, 3]));
+}
+
+#[test]
+fn test_daemon_publish_rejects_invalid_json() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    let requests = mock_recording_daemon(&socket_path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .args(["daemon", "publish", "custom.greeting", "{not json"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("not valid JSON"));
+    assert!(requests.try_recv().is_err());
+}
+
+#[test]
+fn test_daemon_ping_reports_latency() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    let requests = mock_recording_daemon(&socket_path);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(["--output", "json", "--socket-path"])
+        .arg(&socket_path)
+        .args(["daemon", "ping"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report["latency_ms"].as_f64().unwrap() >= 0.0);
+
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(request, serde_json::json!({"type": "Ping"}));
+}
+
+#[test]
+fn test_daemon_status_renders_info() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("pandemic.sock");
+    let listener = UnixListener::bind(&socket_path).unwrap();
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(request["type"], "Info");
+        let response = serde_json::json!({
+            "status": "Success",
+            "data": {
+                "version": "9.8.7",
+                "protocol_version": 1,
+                "uptime_seconds": 42,
+                "features": ["msgpack", "acl"],
+                "started_at": "2024-01-02T03:04:05Z"
+            }
+        });
+        writeln!(stream, "{}", response).unwrap();
+    });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .arg("--socket-path")
+        .arg(&socket_path)
+        .args(["daemon", "status"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.contains("Version: 9.8.7 (protocol 1)"), "{}", text);
+    assert!(text.contains("Uptime: 42s"), "{}", text);
+    assert!(
+        text.contains("Started: 2024-01-02 03:04:05 UTC"),
+        "{}",
+        text
+    );
+    assert!(text.contains("Features: msgpack, acl"), "{}", text);
+}
@@ -0,0 +1,52 @@
+use std::process::Command;
+
+fn pandemic_cli(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_new_generates_an_infection_project() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path().join("my-infection");
+
+    let output = pandemic_cli(&["new", "my-infection", "--dir", dir.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let cargo: toml::Value =
+        toml::from_str(&std::fs::read_to_string(dir.join("Cargo.toml")).unwrap()).unwrap();
+    assert_eq!(cargo["package"]["name"].as_str(), Some("my-infection"));
+    assert!(cargo["dependencies"].get("pandemic-common").is_some());
+    assert!(cargo["dependencies"].get("pandemic-protocol").is_some());
+
+    let main_rs = std::fs::read_to_string(dir.join("src/main.rs")).unwrap();
+    assert!(main_rs.contains("register_and_keep_alive"));
+    assert!(main_rs.contains(r#"name: "my-infection".to_string()"#));
+    assert!(!main_rs.contains("{{name}}"));
+
+    let infection: toml::Value =
+        toml::from_str(&std::fs::read_to_string(dir.join("infection.toml")).unwrap()).unwrap();
+    assert_eq!(
+        infection["infection"]["name"].as_str(),
+        Some("my-infection")
+    );
+}
+
+#[test]
+fn test_new_refuses_invalid_names_and_non_empty_dirs() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let dir = temp_dir.path().join("bad");
+    assert!(
+        !pandemic_cli(&["new", "9lives", "--dir", dir.to_str().unwrap()])
+            .status
+            .success()
+    );
+    assert!(!dir.exists());
+
+    std::fs::write(temp_dir.path().join("existing"), "").unwrap();
+    let output = pandemic_cli(&["new", "taken", "--dir", temp_dir.path().to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("Cargo.toml").exists());
+}
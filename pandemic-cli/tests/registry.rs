@@ -0,0 +1,115 @@
+use pandemic_common::testing::MockServer;
+use std::collections::HashMap;
+use std::process::Command;
+
+/// Serve `index.json` and the hello-infection manifest until the test process exits
+fn mock_registry() -> String {
+    let server = MockServer::bind();
+    let index = serde_json::json!({
+        "name": "mock",
+        "description": "Mock registry",
+        "infections": {
+            "hello-infection": {
+                "name": "hello-infection",
+                "latest_version": "1.2.3",
+                "type_": "binary",
+                "description": "A simple hello world infection plugin",
+                "manifest_url": format!("{}/hello-infection.json", server.url()),
+            },
+            "other": {
+                "name": "other",
+                "latest_version": "0.1.0",
+                "type_": "binary",
+                "description": "Unrelated",
+                "manifest_url": format!("{}/other.json", server.url()),
+            }
+        }
+    })
+    .to_string();
+    let manifest = serde_json::json!({
+        "name": "hello-infection",
+        "version": "1.2.3",
+        "description": "A simple hello world infection plugin",
+        "author": "tester",
+        "homepage": null,
+        "license": null,
+        "keywords": [],
+        "dependencies": [],
+        "platforms": [],
+        "versions": [
+            { "version": "1.0.0", "platforms": [] }
+        ]
+    })
+    .to_string();
+
+    let mut routes = HashMap::new();
+    routes.insert("/index.json".to_string(), index.into_bytes());
+    routes.insert("/hello-infection.json".to_string(), manifest.into_bytes());
+    server.serve_routes(routes)
+}
+
+#[test]
+fn test_registry_search_against_mock_registry() {
+    let registry_url = mock_registry();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args([
+            "registry",
+            "search",
+            "hello",
+            "--registry-url",
+            &registry_url,
+            "--no-cache",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Found 1 infection(s)"), "{}", stdout);
+    assert!(stdout.contains("hello-infection"), "{}", stdout);
+    assert!(stdout.contains("Version: 1.2.3"), "{}", stdout);
+    assert!(!stdout.contains("Unrelated"), "{}", stdout);
+}
+
+#[test]
+fn test_registry_install_rejects_unsatisfiable_version() {
+    let registry_url = mock_registry();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args([
+            "registry",
+            "install",
+            "hello-infection@^2",
+            "--registry-url",
+            &registry_url,
+            "--no-cache",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No version of 'hello-infection' satisfies '^2'"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("1.2.3, 1.0.0"), "{}", stderr);
+}
+
+#[test]
+fn test_registry_install_rejects_invalid_version_requirement() {
+    let output = Command::new(env!("CARGO_BIN_EXE_pandemic-cli"))
+        .args(["registry", "install", "hello-infection@not-a-version"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Invalid version requirement 'not-a-version'"),
+        "{}",
+        stderr
+    );
+}
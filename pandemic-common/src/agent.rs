@@ -1,10 +1,12 @@
 use anyhow::Result;
 use pandemic_protocol::{AgentMessage, AgentRequest, Response};
 
+use std::io;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
+use tracing::debug;
 
 const AGENT_SOCKET_PATH: &str = "/var/run/pandemic/admin.sock";
 const CACHE_DURATION: Duration = Duration::from_secs(30);
@@ -29,8 +31,8 @@ impl AgentStatus {
         self.last_check.elapsed() > CACHE_DURATION
     }
 
-    pub async fn refresh() -> Self {
-        match AgentClient::new().ping().await {
+    pub async fn refresh(client: &AgentClient) -> Self {
+        match client.ping().await {
             Ok(capabilities) => Self {
                 available: true,
                 capabilities,
@@ -51,34 +53,109 @@ impl Default for AgentStatus {
     }
 }
 
+/// How [`AgentClient`] retries connecting while the agent is restarting.
+///
+/// Only connecting is retried: once a request has been written it is never
+/// resent, and an error response from the agent is returned as is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Connection attempts in total, including the first
+    pub attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Give up after the first failed connection
+    pub fn none() -> Self {
+        Self {
+            attempts: 1,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Errors that mean nothing is listening yet, as while the agent restarts
+fn is_retryable(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound
+    )
+}
+
 pub struct AgentClient {
     socket_path: PathBuf,
+    retry: RetryPolicy,
 }
 
 impl AgentClient {
     pub fn new() -> Self {
-        Self {
-            socket_path: PathBuf::from(AGENT_SOCKET_PATH),
-        }
+        Self::with_socket_path(AGENT_SOCKET_PATH)
     }
 
     pub fn with_socket_path<P: AsRef<Path>>(path: P) -> Self {
         Self {
             socket_path: path.as_ref().to_path_buf(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Connect to the agent, retrying per the client's [`RetryPolicy`]
     pub async fn connect(&self) -> Result<UnixStream> {
-        let stream = UnixStream::connect(&self.socket_path).await?;
-        Ok(stream)
+        let mut delay = self.retry.base_delay;
+        let mut attempt = 1;
+        loop {
+            match UnixStream::connect(&self.socket_path).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if is_retryable(&e) && attempt < self.retry.attempts => {
+                    debug!(
+                        "Agent at {} unavailable, retrying in {:?}: {}",
+                        self.socket_path.display(),
+                        delay,
+                        e
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = (delay * 2).min(self.retry.max_delay);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
     }
 
     pub async fn send_agent_request(&self, request: &AgentRequest) -> Result<Response> {
+        self.send_message(&AgentMessage::Request(request.clone()))
+            .await
+    }
+
+    /// Start `request` as a background job on the agent. A successful
+    /// submission answers `Response::Accepted`; poll the job with
+    /// `AgentRequest::GetJob`.
+    pub async fn submit_agent_request(&self, request: &AgentRequest) -> Result<Response> {
+        self.send_message(&AgentMessage::Submit(request.clone()))
+            .await
+    }
+
+    async fn send_message(&self, message: &AgentMessage) -> Result<Response> {
         let stream = self.connect().await?;
         let mut buf_reader = BufReader::new(stream);
 
-        let message = AgentMessage::Request(request.clone());
-        let request_json = serde_json::to_string(&message)?;
+        let request_json = serde_json::to_string(message)?;
         buf_reader
             .get_mut()
             .write_all(request_json.as_bytes())
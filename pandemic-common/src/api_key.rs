@@ -0,0 +1,37 @@
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::Argon2;
+use ring::digest;
+
+/// Hash an API key for storage as an identity's `api_key_hash`
+pub fn hash_key(api_key: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(api_key.as_bytes(), &salt)
+        .expect("argon2 hashing with default parameters cannot fail")
+        .to_string()
+}
+
+/// Whether `api_key` is the key `hash` was made from. A hash that isn't a
+/// valid PHC string matches nothing.
+///
+/// Argon2 is deliberately slow, so callers checking many hashes should
+/// remember keys that already verified (see [`key_digest`]).
+pub fn verify_key(api_key: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(api_key.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// A cheap fingerprint to index already verified keys by. It is a plain
+/// SHA-256, so it belongs in memory only, never in place of a stored hash.
+pub fn key_digest(api_key: &str) -> [u8; 32] {
+    let mut fingerprint = [0; 32];
+    fingerprint.copy_from_slice(digest::digest(&digest::SHA256, api_key.as_bytes()).as_ref());
+    fingerprint
+}
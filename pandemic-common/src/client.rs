@@ -1,16 +1,101 @@
 use anyhow::Result;
-use pandemic_protocol::{Event, Message, Request, Response};
+use futures_util::{FutureExt, Stream};
+use pandemic_protocol::{
+    Encoding, Event, FrameError, Framing, Message, PluginInfo, Request, RequestEnvelope, Response,
+    ResponseEnvelope, MAX_FRAME_LEN, PROTOCOL_VERSION,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::UnixStream;
-use tokio::sync::mpsc;
-use tracing::info;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf,
+};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::transport::{DaemonAddr, Transport};
+
+/// Typed errors surfaced by the daemon clients, carried inside `anyhow::Error`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    IncompatibleVersion {
+        server: u32,
+        min_supported: u32,
+    },
+    /// The daemon accepted the request but didn't answer in time
+    Timeout(Duration),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::IncompatibleVersion {
+                server,
+                min_supported,
+            } => write!(
+                f,
+                "Daemon rejected protocol version {} (server {}, minimum supported {})",
+                PROTOCOL_VERSION, server, min_supported
+            ),
+            ClientError::Timeout(timeout) => {
+                write!(f, "Daemon did not respond within {:?}", timeout)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// How long transient requests wait for the daemon before giving up
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events a background reader holds for the application before it stops
+/// reading (`into_background`) or drops them (`into_multiplexed`)
+pub const EVENT_BUFFER: usize = 1024;
+
+/// Numbers transient subscribers within this process
+static TRANSIENT_COUNTER: AtomicU64 = AtomicU64::new(1);
 
 pub struct DaemonClient;
 
 pub struct PersistentClient {
-    stream: BufReader<UnixStream>,
-    event_rx: Option<mpsc::UnboundedReceiver<Event>>,
+    stream: BufReader<Transport>,
+    framing: Framing,
+    encoding: Encoding,
+    /// What each new connection starts with and asks for in its `Hello`
+    initial_framing: Framing,
+    preferred_encoding: Encoding,
+    reconnect: Option<ReconnectState>,
+}
+
+/// Exponential backoff between reconnection attempts.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+/// What a reconnecting client needs to restore its session on a new connection
+struct ReconnectState {
+    addr: DaemonAddr,
+    policy: ReconnectPolicy,
+    plugin: Option<PluginInfo>,
+    topics: Vec<String>,
 }
 
 impl DaemonClient {
@@ -19,7 +104,7 @@ impl DaemonClient {
         socket_path: P,
         request: &Request,
     ) -> Result<Response> {
-        let stream = UnixStream::connect(socket_path).await?;
+        let stream = Transport::connect(&socket_path.as_ref().into()).await?;
         let mut reader = BufReader::new(stream);
 
         let request_json = serde_json::to_string(request)?;
@@ -33,31 +118,311 @@ impl DaemonClient {
         Ok(response)
     }
 
+    /// Like `send_request`, but fails with [`ClientError::Timeout`] when the
+    /// daemon hasn't answered within `timeout`
+    pub async fn send_request_timeout<P: AsRef<Path>>(
+        socket_path: P,
+        request: &Request,
+        timeout: Duration,
+    ) -> Result<Response> {
+        tokio::time::timeout(timeout, Self::send_request(socket_path, request))
+            .await
+            .map_err(|_| ClientError::Timeout(timeout))?
+    }
+
+    /// Publish one non-retained event over a transient connection, for
+    /// processes that don't otherwise talk to the daemon
+    pub async fn publish<P: AsRef<Path>>(
+        socket_path: P,
+        topic: impl Into<String>,
+        data: serde_json::Value,
+    ) -> Result<()> {
+        let request = Request::Publish {
+            topic: topic.into(),
+            data,
+            retain: false,
+        };
+        expect_success(
+            Self::send_request_timeout(socket_path, &request, DEFAULT_REQUEST_TIMEOUT).await?,
+        )
+    }
+
     /// Create a persistent connection (for long-running plugins)
     pub async fn connect<P: AsRef<Path>>(socket_path: P) -> Result<PersistentClient> {
-        let stream = UnixStream::connect(socket_path).await?;
+        Self::connect_with(socket_path, Framing::Newline, Encoding::Json).await
+    }
+
+    /// Create a persistent connection to `addr`, given as `unix:///path` or
+    /// `tcp://host:port`; a bare path is taken as a Unix socket
+    pub async fn connect_addr(addr: &str) -> Result<PersistentClient> {
+        Self::connect_to(&addr.parse()?, Framing::Newline, Encoding::Json).await
+    }
+
+    /// Create a persistent connection using length-prefixed framing, so
+    /// messages may carry raw newlines
+    pub async fn connect_framed<P: AsRef<Path>>(socket_path: P) -> Result<PersistentClient> {
+        Self::connect_with(socket_path, Framing::LengthPrefixed, Encoding::Json).await
+    }
+
+    /// Create a persistent connection that asks the daemon for `encoding` in
+    /// its handshake. Daemons that don't offer it keep the connection on JSON.
+    pub async fn connect_with_encoding<P: AsRef<Path>>(
+        socket_path: P,
+        encoding: Encoding,
+    ) -> Result<PersistentClient> {
+        Self::connect_with(socket_path, Framing::Newline, encoding).await
+    }
+
+    async fn connect_with<P: AsRef<Path>>(
+        socket_path: P,
+        framing: Framing,
+        encoding: Encoding,
+    ) -> Result<PersistentClient> {
+        Self::connect_to(&socket_path.as_ref().into(), framing, encoding).await
+    }
+
+    async fn connect_to(
+        addr: &DaemonAddr,
+        framing: Framing,
+        encoding: Encoding,
+    ) -> Result<PersistentClient> {
+        let stream = Transport::connect(addr).await?;
         let reader = BufReader::new(stream);
 
-        Ok(PersistentClient {
+        let mut client = PersistentClient {
             stream: reader,
-            event_rx: None,
-        })
+            framing,
+            encoding: Encoding::Json,
+            initial_framing: framing,
+            preferred_encoding: encoding,
+            reconnect: None,
+        };
+        client.hello().await?;
+        Ok(client)
+    }
+}
+
+fn client_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_string())
+}
+
+/// Read the next frame body, or `None` once the daemon closes the connection
+async fn read_frame<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    framing: Framing,
+) -> Result<Option<Vec<u8>>> {
+    match framing {
+        Framing::Newline => {
+            let mut line = String::new();
+            match reader.read_line(&mut line).await? {
+                0 => Ok(None),
+                _ => Ok(Some(line.trim().as_bytes().to_vec())),
+            }
+        }
+        Framing::LengthPrefixed => {
+            let mut len_bytes = [0u8; 4];
+            match reader.read_exact(&mut len_bytes).await {
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+            let len = u32::from_be_bytes(len_bytes) as usize;
+            if len > MAX_FRAME_LEN {
+                return Err(FrameError::TooLarge(len).into());
+            }
+            let mut body = vec![0u8; len];
+            reader.read_exact(&mut body).await?;
+            Ok(Some(body))
+        }
     }
 }
 
 impl PersistentClient {
-    pub async fn send_request(&mut self, request: &Request) -> Result<Response> {
-        let request_json = serde_json::to_string(request)?;
+    /// Create a persistent connection that survives daemon restarts, restoring
+    /// the registered plugin and subscriptions after each reconnect
+    pub async fn connect_with_reconnect<P: AsRef<Path>>(
+        socket_path: P,
+        policy: ReconnectPolicy,
+    ) -> Result<PersistentClient> {
+        let mut client = DaemonClient::connect(&socket_path).await?;
+        client.reconnect = Some(ReconnectState {
+            addr: socket_path.as_ref().into(),
+            policy,
+            plugin: None,
+            topics: Vec::new(),
+        });
+        Ok(client)
+    }
+
+    /// Remember session state so it can be replayed after a reconnect
+    fn track(&mut self, request: &Request) {
+        let Some(state) = self.reconnect.as_mut() else {
+            return;
+        };
+        match request {
+            Request::Register { plugin, .. } => state.plugin = Some(plugin.clone()),
+            Request::Deregister { name }
+                if state.plugin.as_ref().is_some_and(|p| &p.name == name) =>
+            {
+                state.plugin = None;
+                state.topics.clear();
+            }
+            Request::Subscribe { topics } => {
+                for topic in topics {
+                    if !state.topics.contains(topic) {
+                        state.topics.push(topic.clone());
+                    }
+                }
+            }
+            Request::Unsubscribe { topics } => state.topics.retain(|t| !topics.contains(t)),
+            _ => {}
+        }
+    }
+
+    /// Retry with exponential backoff until the daemon is back, then replay
+    /// registration and subscriptions
+    async fn reconnect(&mut self) -> Result<()> {
+        let Some(state) = self.reconnect.as_ref() else {
+            return Ok(());
+        };
+        let addr = state.addr.clone();
+        let policy = state.policy.clone();
+        let plugin = state.plugin.clone();
+        let topics = state.topics.clone();
+
+        let mut delay = policy.base_delay;
+        loop {
+            tokio::time::sleep(delay).await;
+            match self.restore(&addr, plugin.clone(), topics.clone()).await {
+                Ok(()) => {
+                    info!("Reconnected to daemon at {}", addr);
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!("Reconnect failed, retrying in {:?}: {}", delay, e);
+                    delay = (delay * 2).min(policy.max_delay);
+                }
+            }
+        }
+    }
+
+    async fn restore(
+        &mut self,
+        addr: &DaemonAddr,
+        plugin: Option<PluginInfo>,
+        topics: Vec<String>,
+    ) -> Result<()> {
+        let stream = Transport::connect(addr).await?;
+        self.stream = BufReader::new(stream);
+        self.hello().await?;
+
+        if let Some(plugin) = plugin {
+            // Our previous connection is gone, so reclaim the name even if the
+            // daemon hasn't noticed yet. Dependencies were met when we first
+            // registered and may still be reconnecting themselves.
+            let request = Request::Register {
+                plugin,
+                force: true,
+                ignore_dependencies: true,
+            };
+            match self.send_request(&request).await? {
+                Response::Success { .. } => {}
+                other => anyhow::bail!("Re-registration rejected: {:?}", other),
+            }
+        }
+        if !topics.is_empty() {
+            match self.send_request(&Request::Subscribe { topics }).await? {
+                Response::Success { .. } => {}
+                other => anyhow::bail!("Re-subscription rejected: {:?}", other),
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_message<T: Serialize>(&mut self, message: &T) -> Result<()> {
+        let body = self.encoding.encode(message)?;
         self.stream
             .get_mut()
-            .write_all(request_json.as_bytes())
+            .write_all(&self.framing.encode(&body))
             .await?;
-        self.stream.get_mut().write_all(b"\n").await?;
+        Ok(())
+    }
 
-        let mut response_line = String::new();
-        self.stream.read_line(&mut response_line).await?;
+    /// Whether the daemon has closed the connection, checked without waiting.
+    /// Anything already sent by the daemon stays buffered for the next read.
+    pub(crate) fn is_closed(&mut self) -> bool {
+        match self.stream.fill_buf().now_or_never() {
+            None => false,
+            Some(Ok(buffered)) => buffered.is_empty(),
+            Some(Err(_)) => true,
+        }
+    }
 
-        let response: Response = serde_json::from_str(&response_line)?;
+    async fn read_message(&mut self) -> Result<Option<Vec<u8>>> {
+        read_frame(&mut self.stream, self.framing).await
+    }
+
+    /// Announce our protocol version and fail if the daemon can't speak it,
+    /// then switch to the preferred encoding if the daemon agreed to it
+    async fn hello(&mut self) -> Result<()> {
+        // Every connection starts out on JSON
+        self.framing = self.initial_framing;
+        self.encoding = Encoding::Json;
+
+        let preferred = self.preferred_encoding;
+        let request = Request::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            client: client_name(),
+            encoding: (preferred != Encoding::Json).then_some(preferred),
+        };
+
+        match self.send_request(&request).await? {
+            Response::Success { data } => {
+                // Daemons without encoding support leave it out of the reply
+                let agreed = data
+                    .and_then(|data| data.get("encoding").cloned())
+                    .and_then(|encoding| serde_json::from_value(encoding).ok())
+                    .unwrap_or_default();
+                if agreed == preferred {
+                    self.encoding = agreed;
+                    self.framing = agreed.framing(self.initial_framing);
+                } else {
+                    warn!("Daemon declined {:?} encoding, staying on JSON", preferred);
+                }
+                Ok(())
+            }
+            Response::IncompatibleVersion {
+                server,
+                min_supported,
+            } => Err(ClientError::IncompatibleVersion {
+                server,
+                min_supported,
+            }
+            .into()),
+            Response::Error { message, .. } => {
+                // Daemons predating the handshake reject the unknown request
+                warn!("Daemon does not support version negotiation: {}", message);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn send_request(&mut self, request: &Request) -> Result<Response> {
+        self.write_message(request).await?;
+
+        let response_body = self
+            .read_message()
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Daemon closed the connection"))?;
+
+        let response: Response = self.encoding.decode(&response_body)?;
+        if matches!(response, Response::Success { .. }) {
+            self.track(request);
+        }
         Ok(response)
     }
 
@@ -68,40 +433,122 @@ impl PersistentClient {
         Ok(())
     }
 
-    /// Read the next event from the stream (blocking)
+    /// Register as a throwaway plugin named `<prefix>-<pid>-<n>` and
+    /// subscribe it to `topics`, failing if the daemon rejects either.
+    /// Registering names the subscriber in health output and topic ACL
+    /// checks; the plugin goes away with the connection.
+    pub async fn subscribe_transient(
+        &mut self,
+        prefix: &str,
+        description: &str,
+        topics: Vec<String>,
+    ) -> Result<()> {
+        let plugin = PluginInfo {
+            name: format!(
+                "{}-{}-{}",
+                prefix,
+                std::process::id(),
+                TRANSIENT_COUNTER.fetch_add(1, Ordering::Relaxed)
+            ),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            description: Some(description.to_string()),
+            config: None,
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        };
+        let register = Request::Register {
+            plugin,
+            force: false,
+            ignore_dependencies: false,
+        };
+        expect_success(self.send_request(&register).await?)?;
+        expect_success(self.send_request(&Request::Subscribe { topics }).await?)
+    }
+
+    /// Stop receiving events for topics passed to an earlier `subscribe`
+    pub async fn unsubscribe(&mut self, topics: Vec<String>) -> Result<()> {
+        let request = Request::Unsubscribe { topics };
+        expect_success(self.send_request(&request).await?)
+    }
+
+    /// Publish a non-retained event as this connection's plugin
+    pub async fn publish(
+        &mut self,
+        topic: impl Into<String>,
+        data: serde_json::Value,
+    ) -> Result<()> {
+        let request = Request::Publish {
+            topic: topic.into(),
+            data,
+            retain: false,
+        };
+        expect_success(self.send_request(&request).await?)
+    }
+
+    /// Read the next event from the stream (blocking). Clients created with
+    /// `connect_with_reconnect` reconnect instead of returning `None`.
     pub async fn read_event(&mut self) -> Result<Option<Event>> {
         loop {
-            let mut line = String::new();
-
-            match self.stream.read_line(&mut line).await? {
-                0 => return Ok(None), // Connection closed
-                _ => {
-                    if let Ok(Message::Event(event)) = serde_json::from_str::<Message>(line.trim())
-                    {
+            let message = match self.read_message().await {
+                Err(e) if self.reconnect.is_some() => {
+                    warn!("Lost connection to daemon: {}", e);
+                    None
+                }
+                result => result?,
+            };
+            match message {
+                None if self.reconnect.is_some() => self.reconnect().await?,
+                None => return Ok(None), // Connection closed
+                Some(body) => {
+                    if let Ok(Message::Event(event)) = self.encoding.decode::<Message>(&body) {
                         return Ok(Some(event));
                     }
-                    // Invalid JSON or not an event, continue loop to read next line
+                    // Undecodable or not an event, continue loop to read next message
                 }
             }
         }
     }
 
-    /// Try to receive an event without blocking
-    pub async fn try_recv_event(&mut self) -> Option<Event> {
-        if let Some(ref mut rx) = self.event_rx {
-            rx.try_recv().ok()
-        } else {
-            None
-        }
+    /// Consume the client as a stream of events. The stream ends when the
+    /// daemon closes the connection, or after yielding a read error.
+    /// Reconnecting clients keep streaming across reconnects.
+    pub fn event_stream(self) -> impl Stream<Item = Result<Event>> {
+        futures_util::stream::unfold(Some(self), |client| async move {
+            let mut client = client?;
+            match client.read_event().await {
+                Ok(Some(event)) => Some((Ok(event), Some(client))),
+                Ok(None) => None,
+                Err(e) => Some((Err(e), None)),
+            }
+        })
     }
 
-    /// Wait for the next event
-    pub async fn recv_event(&mut self) -> Option<Event> {
-        if let Some(ref mut rx) = self.event_rx {
-            rx.recv().await
-        } else {
-            None
-        }
+    /// Hand the connection to a background task that reads events as they
+    /// arrive, so they can be polled with [`EventReceiver::try_recv_event`].
+    /// Once [`EVENT_BUFFER`] events are waiting the task stops reading until
+    /// some are received, leaving the daemon's overflow policy to apply.
+    /// Reconnecting clients keep reading across reconnects. The task stops
+    /// when the connection closes, a read fails or the receiver is dropped.
+    pub fn into_background(mut self) -> EventReceiver {
+        let (event_tx, events) = mpsc::channel(EVENT_BUFFER);
+        let reader = tokio::spawn(async move {
+            loop {
+                match self.read_event().await {
+                    Ok(Some(event)) => {
+                        if event_tx.send(event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Background event reader stopped: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        EventReceiver { events, reader }
     }
 
     pub async fn register_and_keep_alive(
@@ -110,27 +557,171 @@ impl PersistentClient {
     ) -> Result<()> {
         let request = Request::Register {
             plugin: plugin_info,
+            force: false,
+            ignore_dependencies: false,
         };
         let _response = self.send_request(&request).await?;
 
         // Keep connection alive by reading events
-        let mut line = String::new();
-        while self.stream.read_line(&mut line).await? > 0 {
-            if let Ok(Message::Event(event)) = serde_json::from_str::<Message>(line.trim()) {
-                // Handle incoming events (plugins can override this behavior)
-                info!("Received event: {:?}", event);
-            }
-            line.clear();
+        while let Some(event) = self.read_event().await? {
+            // Handle incoming events (plugins can override this behavior)
+            info!("Received event: {:?}", event);
         }
 
         Ok(())
     }
+
+    /// Hand the connection to a background reader so requests can be issued
+    /// concurrently from multiple tasks. The reader can't wait on a slow
+    /// event consumer without stalling responses, so events beyond
+    /// [`EVENT_BUFFER`] that haven't been received are dropped.
+    pub fn into_multiplexed(self) -> MultiplexedClient {
+        let framing = self.framing;
+        let encoding = self.encoding;
+        let (read_half, write_half) = tokio::io::split(self.stream);
+        let pending: PendingResponses = Arc::new(std::sync::Mutex::new(HashMap::new()));
+        let (event_tx, event_rx) = mpsc::channel(EVENT_BUFFER);
+
+        tokio::spawn(route_responses(
+            BufReader::new(read_half),
+            framing,
+            encoding,
+            pending.clone(),
+            event_tx,
+        ));
+
+        MultiplexedClient {
+            writer: Arc::new(Mutex::new(write_half)),
+            framing,
+            encoding,
+            pending,
+            next_id: Arc::new(AtomicU64::new(1)),
+            event_rx: Arc::new(Mutex::new(event_rx)),
+        }
+    }
+}
+
+/// Events read off a daemon connection by a background task, created with
+/// [`PersistentClient::into_background`]
+pub struct EventReceiver {
+    events: mpsc::Receiver<Event>,
+    reader: JoinHandle<()>,
+}
+
+impl EventReceiver {
+    /// The next event already read from the daemon, without waiting
+    pub fn try_recv_event(&mut self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Wait for the next event. `None` once the connection has closed and
+    /// every event read before that has been received.
+    pub async fn recv_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
 }
 
-impl Drop for PersistentClient {
+impl Drop for EventReceiver {
     fn drop(&mut self) {
-        // The UnixStream will be automatically closed when dropped,
-        // which will signal the daemon to clean up this connection
-        tracing::info!("PersistentClient connection dropped");
+        // The reader owns the connection; stopping it closes the connection
+        self.reader.abort();
+    }
+}
+
+/// Turn anything but a success response into an error
+fn expect_success(response: Response) -> Result<()> {
+    match response {
+        Response::Success { .. } => Ok(()),
+        other => anyhow::bail!("Daemon rejected the request: {:?}", other),
+    }
+}
+
+type PendingResponses = Arc<std::sync::Mutex<HashMap<String, oneshot::Sender<Response>>>>;
+
+/// A cloneable handle to one daemon connection that matches responses to
+/// requests by correlation id instead of by arrival order.
+#[derive(Clone)]
+pub struct MultiplexedClient {
+    writer: Arc<Mutex<WriteHalf<BufReader<Transport>>>>,
+    framing: Framing,
+    encoding: Encoding,
+    pending: PendingResponses,
+    next_id: Arc<AtomicU64>,
+    event_rx: Arc<Mutex<mpsc::Receiver<Event>>>,
+}
+
+impl MultiplexedClient {
+    pub async fn send_request_multiplexed(&self, request: &Request) -> Result<Response> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), tx);
+
+        let envelope = RequestEnvelope {
+            id: Some(id.clone()),
+            request: request.clone(),
+        };
+        let frame = self.framing.encode(&self.encoding.encode(&envelope)?);
+        if let Err(e) = self.writer.lock().await.write_all(&frame).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e.into());
+        }
+
+        rx.await
+            .map_err(|_| anyhow::anyhow!("Daemon closed the connection"))
+    }
+
+    /// Wait for the next event pushed by the daemon
+    pub async fn recv_event(&self) -> Option<Event> {
+        self.event_rx.lock().await.recv().await
+    }
+
+    /// Close our side of the connection. The daemon then drops the
+    /// connection, which also stops the background reader.
+    pub async fn close(&self) -> Result<()> {
+        self.writer.lock().await.shutdown().await?;
+        Ok(())
+    }
+}
+
+async fn route_responses(
+    mut reader: BufReader<ReadHalf<BufReader<Transport>>>,
+    framing: Framing,
+    encoding: Encoding,
+    pending: PendingResponses,
+    event_tx: mpsc::Sender<Event>,
+) {
+    loop {
+        let body = match read_frame(&mut reader, framing).await {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Multiplexed connection read failed: {}", e);
+                break;
+            }
+        };
+
+        if let Ok(Message::Event(event)) = encoding.decode::<Message>(&body) {
+            if let Err(TrySendError::Full(event)) = event_tx.try_send(event) {
+                warn!("Dropping event {}, event buffer full", event.topic);
+            }
+            continue;
+        }
+
+        match encoding.decode::<ResponseEnvelope>(&body) {
+            Ok(ResponseEnvelope {
+                id: Some(id),
+                response,
+            }) => match pending.lock().unwrap().remove(&id) {
+                Some(tx) => {
+                    let _ = tx.send(response);
+                }
+                None => warn!("Dropping response for unknown request id {}", id),
+            },
+            Ok(_) => warn!("Dropping response without a request id"),
+            Err(e) => warn!("Invalid message from daemon: {}", e),
+        }
     }
+
+    // Fail any requests still waiting on this connection
+    pending.lock().unwrap().clear();
 }
@@ -0,0 +1,313 @@
+use anyhow::{Context, Result};
+use futures_util::Stream;
+use notify::{RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Quiet period after a file change before the config is re-read, so an
+/// editor's write-rename-chmod sequence yields a single update
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Supported config file formats, in lookup order. TOML wins when a plugin
+/// has more than one file in the same directory.
+const FORMATS: [ConfigFormat; 4] = [
+    ConfigFormat::Toml,
+    ConfigFormat::Yaml("yaml"),
+    ConfigFormat::Yaml("yml"),
+    ConfigFormat::Json,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfigFormat {
+    Toml,
+    Yaml(&'static str),
+    Json,
+}
+
+impl ConfigFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml(extension) => extension,
+            ConfigFormat::Json => "json",
+        }
+    }
+
+    fn parse(self, contents: &str) -> Result<Value> {
+        Ok(match self {
+            ConfigFormat::Toml => serde_json::to_value(toml::from_str::<toml::Value>(contents)?)?,
+            ConfigFormat::Yaml(_) => serde_yaml::from_str(contents)?,
+            ConfigFormat::Json => serde_json::from_str(contents)?,
+        })
+    }
+
+    fn render(self, config: &Value) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+            ConfigFormat::Yaml(_) => serde_yaml::to_string(config)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(config)?,
+        })
+    }
+}
+
+/// Per-plugin configuration built from shipped defaults plus local overrides
+pub trait ConfigManager {
+    /// Defaults merged with overrides, or an empty object when neither exists
+    fn get_config(&self, plugin_name: &str) -> Result<Value>;
+    fn set_override(&self, plugin_name: &str, config: &Value) -> Result<()>;
+    fn clear_override(&self, plugin_name: &str) -> Result<()>;
+}
+
+/// Reads `<plugin>.toml`, `<plugin>.yaml`/`.yml` or `<plugin>.json` from a
+/// defaults directory and an overrides directory
+#[derive(Debug, Clone)]
+pub struct FileConfigManager {
+    defaults_dir: PathBuf,
+    overrides_dir: PathBuf,
+}
+
+impl FileConfigManager {
+    pub fn new(defaults_dir: impl Into<PathBuf>, overrides_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            defaults_dir: defaults_dir.into(),
+            overrides_dir: overrides_dir.into(),
+        }
+    }
+
+    pub fn defaults_dir(&self) -> &Path {
+        &self.defaults_dir
+    }
+
+    pub fn overrides_dir(&self) -> &Path {
+        &self.overrides_dir
+    }
+
+    /// Stream the merged config for `plugin_name` each time its defaults or
+    /// overrides file changes. Values that fail to parse or match the last
+    /// emitted config are skipped.
+    pub fn watch(&self, plugin_name: &str) -> Result<impl Stream<Item = Value>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })?;
+
+        std::fs::create_dir_all(&self.overrides_dir)?;
+        watcher.watch(&self.overrides_dir, RecursiveMode::NonRecursive)?;
+        if self.defaults_dir.is_dir() {
+            watcher.watch(&self.defaults_dir, RecursiveMode::NonRecursive)?;
+        }
+
+        let state = WatchState {
+            manager: self.clone(),
+            plugin_name: plugin_name.to_string(),
+            last: self.get_config(plugin_name).ok(),
+            events: rx,
+            _watcher: watcher,
+        };
+
+        Ok(futures_util::stream::unfold(
+            state,
+            |mut state| async move {
+                let config = state.next_change().await?;
+                Some((config, state))
+            },
+        ))
+    }
+
+    fn load_config_file(&self, dir: &Path, plugin_name: &str) -> Result<Option<Value>> {
+        let Some((path, format)) = locate_config_file(dir, plugin_name) else {
+            return Ok(None);
+        };
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config = format
+            .parse(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        Ok(Some(config))
+    }
+}
+
+impl Default for FileConfigManager {
+    fn default() -> Self {
+        Self::new("/etc/pandemic/config", "/var/lib/pandemic/config")
+    }
+}
+
+impl ConfigManager for FileConfigManager {
+    fn get_config(&self, plugin_name: &str) -> Result<Value> {
+        let mut config = self
+            .load_config_file(&self.defaults_dir, plugin_name)?
+            .unwrap_or_else(|| Value::Object(Default::default()));
+        if let Some(overrides) = self.load_config_file(&self.overrides_dir, plugin_name)? {
+            merge_json(&mut config, overrides);
+        }
+        Ok(config)
+    }
+
+    fn set_override(&self, plugin_name: &str, config: &Value) -> Result<()> {
+        // Keep whatever format the operator already chose for this plugin
+        let (path, format) =
+            locate_config_file(&self.overrides_dir, plugin_name).unwrap_or_else(|| {
+                let format = ConfigFormat::Toml;
+                (
+                    config_path(&self.overrides_dir, plugin_name, format),
+                    format,
+                )
+            });
+        std::fs::create_dir_all(&self.overrides_dir)?;
+        std::fs::write(&path, format.render(config)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(())
+    }
+
+    fn clear_override(&self, plugin_name: &str) -> Result<()> {
+        for format in FORMATS {
+            let path = config_path(&self.overrides_dir, plugin_name, format);
+            if path.exists() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct WatchState {
+    manager: FileConfigManager,
+    plugin_name: String,
+    last: Option<Value>,
+    events: mpsc::UnboundedReceiver<notify::Result<notify::Event>>,
+    // Dropping the watcher stops the notifications
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl WatchState {
+    async fn next_change(&mut self) -> Option<Value> {
+        loop {
+            let event = self.events.recv().await?;
+            if !self.is_relevant(event) {
+                continue;
+            }
+
+            // Debounce: wait until the files have been quiet for a while; a
+            // closed channel means the watcher is gone
+            while let Ok(event) = tokio::time::timeout(WATCH_DEBOUNCE, self.events.recv()).await {
+                let _ = event?;
+            }
+
+            match self.manager.get_config(&self.plugin_name) {
+                Ok(config) if self.last.as_ref() != Some(&config) => {
+                    self.last = Some(config.clone());
+                    return Some(config);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to reload config for {}: {}", self.plugin_name, e)
+                }
+            }
+        }
+    }
+
+    fn is_relevant(&self, event: notify::Result<notify::Event>) -> bool {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!("Config watch error for {}: {}", self.plugin_name, e);
+                return false;
+            }
+        };
+        event.paths.iter().any(|path| {
+            path.file_stem() == Some(self.plugin_name.as_ref())
+                && path.extension().is_some_and(|extension| {
+                    FORMATS
+                        .into_iter()
+                        .any(|format| extension == format.extension())
+                })
+        })
+    }
+}
+
+fn config_path(dir: &Path, plugin_name: &str, format: ConfigFormat) -> PathBuf {
+    dir.join(format!("{}.{}", plugin_name, format.extension()))
+}
+
+fn locate_config_file(dir: &Path, plugin_name: &str) -> Option<(PathBuf, ConfigFormat)> {
+    FORMATS
+        .into_iter()
+        .map(|format| (config_path(dir, plugin_name, format), format))
+        .find(|(path, _)| path.is_file())
+}
+
+/// Recursively merge `overrides` into `base`. Objects merge key by key;
+/// any other value in `overrides` replaces the one in `base`.
+pub fn merge_json(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Parse a TOML config, first replacing every string that is exactly
+/// `${env:NAME}` with that environment variable and every `${file:/path}`
+/// with the file's contents, minus trailing newlines. This keeps secrets out
+/// of the file itself, e.g. in systemd credentials. Other strings are left
+/// as written.
+pub fn parse_toml_with_secrets<T: DeserializeOwned>(contents: &str) -> Result<T> {
+    let mut value: toml::Value = toml::from_str(contents)?;
+    resolve_secrets(&mut value)?;
+    Ok(value.try_into()?)
+}
+
+fn resolve_secrets(value: &mut toml::Value) -> Result<()> {
+    match value {
+        toml::Value::String(text) => {
+            if let Some(secret) = resolve_secret(text)? {
+                *text = secret;
+            }
+        }
+        toml::Value::Array(items) => {
+            for item in items {
+                resolve_secrets(item)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                resolve_secrets(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn resolve_secret(text: &str) -> Result<Option<String>> {
+    let Some(reference) = text
+        .strip_prefix("${")
+        .and_then(|rest| rest.strip_suffix('}'))
+    else {
+        return Ok(None);
+    };
+    if let Some(name) = reference.strip_prefix("env:") {
+        let secret = std::env::var(name)
+            .with_context(|| format!("Environment variable {} is not set", name))?;
+        return Ok(Some(secret));
+    }
+    if let Some(path) = reference.strip_prefix("file:") {
+        let secret = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read secret file {}", path))?;
+        return Ok(Some(secret.trim_end_matches(['\n', '\r']).to_string()));
+    }
+    Ok(None)
+}
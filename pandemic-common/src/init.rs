@@ -0,0 +1,969 @@
+use anyhow::{anyhow, Result};
+use pandemic_protocol::ServiceOverrides;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Lifecycle actions every init backend supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+    Status,
+    Enable,
+    Disable,
+}
+
+impl FromStr for ServiceAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "start" => Ok(Self::Start),
+            "stop" => Ok(Self::Stop),
+            "restart" => Ok(Self::Restart),
+            "status" => Ok(Self::Status),
+            "enable" => Ok(Self::Enable),
+            "disable" => Ok(Self::Disable),
+            _ => Err(anyhow!("Invalid service action: {}", s)),
+        }
+    }
+}
+
+impl ServiceAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Restart => "restart",
+            Self::Status => "status",
+            Self::Enable => "enable",
+            Self::Disable => "disable",
+        }
+    }
+}
+
+/// Whether a service is running, as told by its `status` exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceState {
+    Active,
+    Inactive,
+    NotFound,
+}
+
+/// The outcome of a `status` action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    pub output: String,
+}
+
+impl ServiceStatus {
+    /// Read a status command's result. Following the LSB convention, exit
+    /// code 3 means the service isn't running and 4 that it doesn't exist;
+    /// any other failure is an error.
+    pub fn from_exit(code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> Result<Self> {
+        let state = match code {
+            Some(0) => ServiceState::Active,
+            Some(3) => ServiceState::Inactive,
+            Some(4) => ServiceState::NotFound,
+            _ => {
+                return Err(anyhow!(
+                    "status failed: {}",
+                    String::from_utf8_lossy(stderr)
+                ))
+            }
+        };
+        Ok(Self {
+            state,
+            output: String::from_utf8_lossy(stdout).to_string(),
+        })
+    }
+}
+
+/// One journal entry of a service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Microseconds since the Unix epoch
+    pub timestamp_us: u64,
+    /// syslog priority, from 0 (emerg) to 7 (debug)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    pub message: String,
+}
+
+/// A pandemic service as reported by the init system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceSummary {
+    pub name: String,
+    pub description: String,
+    /// systemd's active state (`active`, `failed`, ...) or the OpenRC state
+    /// (`started`, `stopped`, ...)
+    pub status: String,
+    /// systemd's load state, e.g. `loaded` or `not-found`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_state: Option<String>,
+    /// systemd's sub state, e.g. `running` or `exited`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub_state: Option<String>,
+}
+
+/// Match `name` against a shell-style glob supporting `*` and `?`.
+/// Runs in O(pattern × name) time, however many `*`s the pattern has.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` seen and the name position it resumes from.
+    // Only the last star ever needs to absorb more, so there's no deeper
+    // backtracking.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// What an init system needs to know to run a service, rendered into a
+/// systemd unit or an OpenRC script by the backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDefinition {
+    pub description: String,
+    /// Binary followed by its arguments
+    pub exec_start: String,
+    pub user: String,
+    pub group: String,
+    /// Services that must be running first
+    pub requires: Vec<String>,
+    /// Directory under `/run` created for the service on start
+    pub runtime_directory: Option<String>,
+    /// The service tells systemd when it is ready (`Type=notify`) instead of
+    /// counting as started once launched
+    pub notify: bool,
+}
+
+/// The init systems pandemic can manage services with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitSystem {
+    Systemd,
+    OpenRc,
+}
+
+impl InitSystem {
+    /// The init system running this host
+    pub fn detect() -> Self {
+        Self::detect_in(Path::new("/"))
+    }
+
+    /// Detect the init system of the filesystem rooted at `root`. systemd is
+    /// checked first since OpenRC tooling can be installed alongside it, and
+    /// is also the fallback when neither is recognized.
+    pub fn detect_in(root: &Path) -> Self {
+        if root.join("run/systemd/system").is_dir() {
+            Self::Systemd
+        } else if root.join("run/openrc").is_dir() || root.join("sbin/openrc-run").exists() {
+            Self::OpenRc
+        } else {
+            Self::Systemd
+        }
+    }
+
+    pub fn backend(self) -> Box<dyn InitBackend> {
+        match self {
+            Self::Systemd => Box::new(SystemdBackend::default()),
+            Self::OpenRc => Box::new(OpenRcBackend::default()),
+        }
+    }
+}
+
+/// Service management for one init system
+pub trait InitBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The command that performs `action` on `service`
+    fn command(&self, action: ServiceAction, service: &str) -> Command;
+
+    /// Run `action` on `service`, returning its output
+    fn control(&self, action: ServiceAction, service: &str) -> Result<String> {
+        run(self.command(action, service))
+    }
+
+    fn start(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Start, service)
+    }
+
+    fn stop(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Stop, service)
+    }
+
+    fn restart(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Restart, service)
+    }
+
+    fn status(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Status, service)
+    }
+
+    /// Run `status` on `service`, reporting a stopped or unknown service
+    /// instead of failing like `status` does
+    fn check_status(&self, service: &str) -> Result<ServiceStatus> {
+        let output = self.command(ServiceAction::Status, service).output()?;
+        ServiceStatus::from_exit(output.status.code(), &output.stdout, &output.stderr)
+    }
+
+    /// Start `service` at boot
+    fn enable(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Enable, service)
+    }
+
+    /// Stop starting `service` at boot, leaving it running if it is
+    fn disable(&self, service: &str) -> Result<String> {
+        self.control(ServiceAction::Disable, service)
+    }
+
+    /// Key/value state of `service` for machine-readable output
+    fn properties(&self, service: &str) -> Result<BTreeMap<String, String>>;
+
+    /// Installed pandemic services and their state
+    fn list(&self) -> Result<Vec<ServiceSummary>>;
+
+    /// Like `list`, keeping only services whose name matches `pattern`
+    fn list_matching(&self, pattern: Option<&str>) -> Result<Vec<ServiceSummary>> {
+        let mut services = self.list()?;
+        if let Some(pattern) = pattern {
+            services.retain(|service| glob_matches(pattern, &service.name));
+        }
+        Ok(services)
+    }
+
+    /// Write the service definition and enable it at boot
+    fn install(&self, service: &str, definition: &ServiceDefinition) -> Result<()>;
+
+    /// Disable and stop the service, then remove its definition
+    fn uninstall(&self, service: &str) -> Result<()>;
+
+    fn get_override(&self, service: &str) -> Result<Option<ServiceOverrides>>;
+
+    fn set_override(&self, service: &str, overrides: &ServiceOverrides) -> Result<()>;
+
+    fn reset_override(&self, service: &str) -> Result<()>;
+
+    /// The last `lines` log entries of `service`, only those newer than
+    /// `since` (a journalctl time such as `1 hour ago`) when given
+    fn logs(&self, _service: &str, _lines: u32, _since: Option<&str>) -> Result<Vec<LogEntry>> {
+        Err(anyhow!("{} does not keep service logs", self.name()))
+    }
+}
+
+fn run(mut command: Command) -> Result<String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command.output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+fn command(program: &str, args: &[&str]) -> Command {
+    let mut command = Command::new(program);
+    command.args(args);
+    command
+}
+
+fn empty_overrides() -> ServiceOverrides {
+    ServiceOverrides {
+        environment: None,
+        exec_start: None,
+        restart: None,
+        user: None,
+        group: None,
+    }
+}
+
+/// Manages services through `systemctl` and unit files
+pub struct SystemdBackend {
+    unit_dir: PathBuf,
+}
+
+impl Default for SystemdBackend {
+    fn default() -> Self {
+        Self::new("/etc/systemd/system")
+    }
+}
+
+impl SystemdBackend {
+    pub fn new(unit_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            unit_dir: unit_dir.into(),
+        }
+    }
+
+    fn unit_path(&self, service: &str) -> PathBuf {
+        self.unit_dir.join(format!("{}.service", service))
+    }
+
+    fn override_dir(&self, service: &str) -> PathBuf {
+        self.unit_dir.join(format!("{}.d", service))
+    }
+
+    fn daemon_reload(&self) -> Result<()> {
+        run(command("systemctl", &["daemon-reload"])).map(|_| ())
+    }
+
+    /// Render `definition` as a unit file
+    pub fn unit_file(definition: &ServiceDefinition) -> String {
+        let mut unit = format!("[Unit]\nDescription={}\n", definition.description);
+        if definition.requires.is_empty() {
+            unit.push_str("After=network.target\n");
+        }
+        for required in &definition.requires {
+            unit.push_str(&format!(
+                "After={0}.service\nRequires={0}.service\n",
+                required
+            ));
+        }
+        unit.push_str(&format!(
+            "\n[Service]\nType={}\nExecStart={}\nRestart=always\nRestartSec=5\nUser={}\nGroup={}\n",
+            if definition.notify {
+                "notify"
+            } else {
+                "simple"
+            },
+            definition.exec_start,
+            definition.user,
+            definition.group
+        ));
+        if let Some(dir) = &definition.runtime_directory {
+            unit.push_str(&format!(
+                "RuntimeDirectory={}\nRuntimeDirectoryMode=0755\n",
+                dir
+            ));
+        }
+        unit.push_str("\n[Install]\nWantedBy=multi-user.target\n");
+        unit
+    }
+
+    /// Parse the output of `systemctl list-units --output=json`
+    pub fn parse_units(json: &str) -> Result<Vec<ServiceSummary>> {
+        #[derive(Deserialize)]
+        struct Unit {
+            unit: String,
+            load: String,
+            active: String,
+            sub: String,
+            #[serde(default)]
+            description: String,
+        }
+
+        let units: Vec<Unit> = serde_json::from_str(json)?;
+        Ok(units
+            .into_iter()
+            .map(|unit| ServiceSummary {
+                name: unit.unit,
+                description: unit.description,
+                status: unit.active,
+                load_state: Some(unit.load),
+                sub_state: Some(unit.sub),
+            })
+            .collect())
+    }
+
+    /// Parse the output of `journalctl --output=json`, one object per line
+    pub fn parse_journal(output: &str) -> Result<Vec<LogEntry>> {
+        #[derive(Deserialize)]
+        struct Entry {
+            #[serde(rename = "__REALTIME_TIMESTAMP")]
+            realtime_timestamp: String,
+            #[serde(rename = "PRIORITY")]
+            priority: Option<String>,
+            #[serde(rename = "_PID")]
+            pid: Option<String>,
+            #[serde(rename = "MESSAGE", default)]
+            message: serde_json::Value,
+        }
+
+        output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let entry: Entry = serde_json::from_str(line)?;
+                let message = match entry.message {
+                    serde_json::Value::String(message) => message,
+                    // Messages that aren't valid UTF-8 come as a byte array
+                    serde_json::Value::Array(bytes) => {
+                        let bytes: Vec<u8> = bytes
+                            .iter()
+                            .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+                            .collect();
+                        String::from_utf8_lossy(&bytes).to_string()
+                    }
+                    _ => String::new(),
+                };
+                Ok(LogEntry {
+                    timestamp_us: entry.realtime_timestamp.parse()?,
+                    priority: entry.priority.and_then(|p| p.parse().ok()),
+                    pid: entry.pid.and_then(|pid| pid.parse().ok()),
+                    message,
+                })
+            })
+            .collect()
+    }
+}
+
+impl InitBackend for SystemdBackend {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn command(&self, action: ServiceAction, service: &str) -> Command {
+        command("systemctl", &[action.as_str(), service])
+    }
+
+    fn properties(&self, service: &str) -> Result<BTreeMap<String, String>> {
+        let output = run(command(
+            "systemctl",
+            &[
+                "show",
+                service,
+                "--property=LoadState,ActiveState,SubState,MainPID",
+            ],
+        ))?;
+        Ok(output
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect())
+    }
+
+    fn list(&self) -> Result<Vec<ServiceSummary>> {
+        let output = run(command(
+            "systemctl",
+            &["list-units", "--output=json", "pandemic*"],
+        ))?;
+        Self::parse_units(&output)
+    }
+
+    fn logs(&self, service: &str, lines: u32, since: Option<&str>) -> Result<Vec<LogEntry>> {
+        let lines = lines.to_string();
+        let mut journalctl = command(
+            "journalctl",
+            &[
+                "--unit",
+                service,
+                "--lines",
+                &lines,
+                "--output=json",
+                "--no-pager",
+            ],
+        );
+        if let Some(since) = since {
+            journalctl.arg(format!("--since={}", since));
+        }
+        Self::parse_journal(&run(journalctl)?)
+    }
+
+    fn install(&self, service: &str, definition: &ServiceDefinition) -> Result<()> {
+        std::fs::write(self.unit_path(service), Self::unit_file(definition))?;
+        self.daemon_reload()?;
+        self.enable(service).map(|_| ())
+    }
+
+    fn uninstall(&self, service: &str) -> Result<()> {
+        // The service may already be disabled or stopped
+        let _ = self.disable(service);
+        let _ = self.stop(service);
+        std::fs::remove_file(self.unit_path(service))?;
+        self.daemon_reload()
+    }
+
+    fn get_override(&self, service: &str) -> Result<Option<ServiceOverrides>> {
+        let override_file = self.override_dir(service).join("override.conf");
+        if !override_file.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(override_file)?;
+        let mut overrides = empty_overrides();
+        for line in content.lines() {
+            if let Some((key, value)) = line.split_once('=') {
+                match key {
+                    "User" => overrides.user = Some(value.to_string()),
+                    "Group" => overrides.group = Some(value.to_string()),
+                    "Restart" => overrides.restart = Some(value.to_string()),
+                    "ExecStart" => overrides.exec_start = Some(value.to_string()),
+                    "Environment" => {
+                        if let Some((env_key, env_value)) = value.split_once('=') {
+                            overrides
+                                .environment
+                                .get_or_insert_with(Default::default)
+                                .insert(env_key.to_string(), env_value.to_string());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(Some(overrides))
+    }
+
+    fn set_override(&self, service: &str, overrides: &ServiceOverrides) -> Result<()> {
+        let content = Self::override_file(overrides)?;
+        let override_dir = self.override_dir(service);
+        std::fs::create_dir_all(&override_dir)?;
+        std::fs::write(override_dir.join("override.conf"), content)?;
+        self.daemon_reload()
+    }
+
+    fn reset_override(&self, service: &str) -> Result<()> {
+        let override_dir = self.override_dir(service);
+        let override_file = override_dir.join("override.conf");
+        if override_file.exists() {
+            std::fs::remove_file(override_file)?;
+            std::fs::remove_dir_all(override_dir)?;
+        }
+        self.daemon_reload()
+    }
+}
+
+impl SystemdBackend {
+    /// Render `overrides` as a drop-in `override.conf`
+    pub fn override_file(overrides: &ServiceOverrides) -> Result<String> {
+        validate_overrides(overrides)?;
+
+        let mut content = String::from("[Service]\n");
+        if let Some(user) = &overrides.user {
+            content.push_str(&format!("User={}\n", user));
+        }
+        if let Some(group) = &overrides.group {
+            content.push_str(&format!("Group={}\n", group));
+        }
+        if let Some(restart) = &overrides.restart {
+            content.push_str(&format!("Restart={}\n", restart));
+        }
+        if let Some(exec_start) = expanded_exec_start(overrides, "$${") {
+            content.push_str("ExecStart=\n");
+            content.push_str(&format!("ExecStart={}\n", exec_start));
+        }
+        if let Some(env) = &overrides.environment {
+            for (key, value) in env {
+                content.push_str(&format!("Environment={}={}\n", key, value));
+            }
+        }
+        Ok(content)
+    }
+}
+
+/// Manages services through `rc-service`/`rc-update`, init scripts in
+/// `init.d` and overrides in `conf.d`
+pub struct OpenRcBackend {
+    etc_dir: PathBuf,
+}
+
+impl Default for OpenRcBackend {
+    fn default() -> Self {
+        Self::new("/etc")
+    }
+}
+
+impl OpenRcBackend {
+    /// Manage scripts under `etc_dir/init.d` and `etc_dir/conf.d`
+    pub fn new(etc_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            etc_dir: etc_dir.into(),
+        }
+    }
+
+    fn script_path(&self, service: &str) -> PathBuf {
+        self.etc_dir.join("init.d").join(service)
+    }
+
+    fn conf_path(&self, service: &str) -> PathBuf {
+        self.etc_dir.join("conf.d").join(service)
+    }
+
+    /// Render `definition` as an `openrc-run` script supervised by
+    /// supervise-daemon, which restarts it when it exits
+    pub fn init_script(definition: &ServiceDefinition) -> String {
+        let (program, args) = split_command(&definition.exec_start);
+        let mut script = format!(
+            "#!/sbin/openrc-run\n\n\
+             description={}\n\
+             command={}\n\
+             command_args={}\n\
+             command_user={}\n\
+             supervisor=supervise-daemon\n\
+             respawn_delay=5\n\n\
+             depend() {{\n\
+             \tneed net{}\n\
+             }}\n",
+            shell_quote(&definition.description),
+            shell_quote(program),
+            shell_quote(args),
+            shell_quote(&format!("{}:{}", definition.user, definition.group)),
+            definition
+                .requires
+                .iter()
+                .map(|service| format!(" {}", service))
+                .collect::<String>(),
+        );
+        if let Some(dir) = &definition.runtime_directory {
+            script.push_str(&format!(
+                "\nstart_pre() {{\n\tcheckpath -d -m 0755 -o {}:{} /run/{}\n}}\n",
+                definition.user, definition.group, dir
+            ));
+        }
+        script
+    }
+
+    /// Render `overrides` as a `conf.d` file, which OpenRC sources after the
+    /// init script so its variables win
+    pub fn conf_file(overrides: &ServiceOverrides) -> Result<String> {
+        validate_overrides(overrides)?;
+
+        let mut content = String::new();
+        match (&overrides.user, &overrides.group) {
+            (Some(user), Some(group)) => content.push_str(&format!(
+                "command_user={}\n",
+                shell_quote(&format!("{}:{}", user, group))
+            )),
+            (Some(user), None) => {
+                content.push_str(&format!("command_user={}\n", shell_quote(user)))
+            }
+            (None, Some(_)) => {
+                return Err(anyhow!("OpenRC needs a User override to set a Group"));
+            }
+            (None, None) => {}
+        }
+        if let Some(restart) = &overrides.restart {
+            // supervise-daemon always respawns, so only that policy can be honored
+            if restart != "always" {
+                return Err(anyhow!(
+                    "OpenRC services always restart, Restart={} is not supported",
+                    restart
+                ));
+            }
+        }
+        if let Some(exec_start) = expanded_exec_start(overrides, "${") {
+            let (program, args) = split_command(&exec_start);
+            content.push_str(&format!("command={}\n", shell_quote(program)));
+            content.push_str(&format!("command_args={}\n", shell_quote(args)));
+        }
+        if let Some(env) = &overrides.environment {
+            let mut env: Vec<_> = env.iter().collect();
+            env.sort();
+            for (key, value) in env {
+                content.push_str(&format!("export {}={}\n", key, shell_quote(value)));
+            }
+        }
+        Ok(content)
+    }
+
+    /// Read back a `conf.d` file written by [`OpenRcBackend::conf_file`]
+    pub fn parse_conf_file(content: &str) -> ServiceOverrides {
+        let mut overrides = empty_overrides();
+        let mut command = None;
+        let mut command_args = None;
+        for line in content.lines() {
+            let line = line.trim();
+            let (is_export, line) = match line.strip_prefix("export ") {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = shell_unquote(value);
+            match key {
+                _ if is_export => {
+                    overrides
+                        .environment
+                        .get_or_insert_with(Default::default)
+                        .insert(key.to_string(), value);
+                }
+                "command_user" => match value.split_once(':') {
+                    Some((user, group)) => {
+                        overrides.user = Some(user.to_string());
+                        overrides.group = Some(group.to_string());
+                    }
+                    None => overrides.user = Some(value),
+                },
+                "command" => command = Some(value),
+                "command_args" => command_args = Some(value),
+                _ => {}
+            }
+        }
+        overrides.exec_start = command.map(|program| match command_args {
+            Some(args) if !args.is_empty() => format!("{} {}", program, args),
+            _ => program,
+        });
+        overrides
+    }
+}
+
+impl InitBackend for OpenRcBackend {
+    fn name(&self) -> &'static str {
+        "openrc"
+    }
+
+    fn command(&self, action: ServiceAction, service: &str) -> Command {
+        match action {
+            ServiceAction::Enable => command("rc-update", &["add", service, "default"]),
+            ServiceAction::Disable => command("rc-update", &["del", service, "default"]),
+            _ => command("rc-service", &[service, action.as_str()]),
+        }
+    }
+
+    fn properties(&self, service: &str) -> Result<BTreeMap<String, String>> {
+        // `rc-service <name> status` prints " * status: started" and exits
+        // non-zero for stopped services, so read the output either way
+        let output = self.command(ServiceAction::Status, service).output()?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let status = stdout
+            .lines()
+            .find_map(|line| line.split_once("status:"))
+            .map(|(_, status)| status.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+        Ok(BTreeMap::from([("status".to_string(), status)]))
+    }
+
+    fn list(&self) -> Result<Vec<ServiceSummary>> {
+        // Lines look like " pandemic-udp      [  started  ]"
+        let output = run(command("rc-status", &["--all"]))?;
+        Ok(output
+            .lines()
+            .filter_map(|line| {
+                let (name, rest) = line.trim().split_once(char::is_whitespace)?;
+                if !name.starts_with("pandemic") {
+                    return None;
+                }
+                let status = rest.trim().trim_start_matches('[').trim_end_matches(']');
+                Some(ServiceSummary {
+                    name: name.to_string(),
+                    description: String::new(),
+                    status: status.trim().to_string(),
+                    load_state: None,
+                    sub_state: None,
+                })
+            })
+            .collect())
+    }
+
+    fn install(&self, service: &str, definition: &ServiceDefinition) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script_path = self.script_path(service);
+        std::fs::write(&script_path, Self::init_script(definition))?;
+        std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))?;
+        self.enable(service).map(|_| ())
+    }
+
+    fn uninstall(&self, service: &str) -> Result<()> {
+        // The service may already be disabled or stopped
+        let _ = self.disable(service);
+        let _ = self.stop(service);
+        std::fs::remove_file(self.script_path(service))?;
+        let conf_path = self.conf_path(service);
+        if conf_path.exists() {
+            std::fs::remove_file(conf_path)?;
+        }
+        Ok(())
+    }
+
+    fn get_override(&self, service: &str) -> Result<Option<ServiceOverrides>> {
+        let conf_path = self.conf_path(service);
+        if !conf_path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(conf_path)?;
+        Ok(Some(Self::parse_conf_file(&content)))
+    }
+
+    fn set_override(&self, service: &str, overrides: &ServiceOverrides) -> Result<()> {
+        let content = Self::conf_file(overrides)?;
+        let conf_path = self.conf_path(service);
+        if let Some(parent) = conf_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(conf_path, content)?;
+        Ok(())
+    }
+
+    fn reset_override(&self, service: &str) -> Result<()> {
+        let conf_path = self.conf_path(service);
+        if conf_path.exists() {
+            std::fs::remove_file(conf_path)?;
+        }
+        Ok(())
+    }
+}
+
+fn split_command(exec_start: &str) -> (&str, &str) {
+    let exec_start = exec_start.trim();
+    match exec_start.split_once(char::is_whitespace) {
+        Some((program, args)) => (program, args.trim()),
+        None => (exec_start, ""),
+    }
+}
+
+/// Substitute `${NAME}` in `value` with entries of `environment`.
+///
+/// Names the map doesn't define are left as written, so the init system can
+/// still resolve them at runtime. `$${` is an escape that skips substitution
+/// and is written as `literal`: systemd expands the result again, so units
+/// keep its own `$${` escape, while OpenRC's quoted `command_args` take a
+/// plain `${`.
+pub fn expand_variables(
+    value: &str,
+    environment: &HashMap<String, String>,
+    literal: &str,
+) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(escaped) = rest.strip_prefix("$${") {
+            expanded.push_str(literal);
+            rest = escaped;
+            continue;
+        }
+        let reference = rest
+            .strip_prefix("${")
+            .and_then(|inner| inner.split_once('}'))
+            .and_then(|(name, after)| environment.get(name).map(|value| (value, after)));
+        match reference {
+            Some((value, after)) => {
+                expanded.push_str(value);
+                rest = after;
+            }
+            None => {
+                expanded.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// `ExecStart` with references to the override's own environment expanded,
+/// writing escaped `$${` as `literal`
+fn expanded_exec_start(overrides: &ServiceOverrides, literal: &str) -> Option<String> {
+    let empty = HashMap::new();
+    let environment = overrides.environment.as_ref().unwrap_or(&empty);
+    overrides
+        .exec_start
+        .as_deref()
+        .map(|exec_start| expand_variables(exec_start, environment, literal))
+}
+
+/// Quote `value` for a shell-sourced file so it can't expand or break out
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn shell_unquote(value: &str) -> String {
+    value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .map(|v| v.replace("'\\''", "'"))
+        .unwrap_or_else(|| value.trim_matches('"').to_string())
+}
+
+/// Values systemd accepts for `Restart=`
+const RESTART_VALUES: [&str; 7] = [
+    "no",
+    "on-success",
+    "on-failure",
+    "on-abnormal",
+    "on-watchdog",
+    "on-abort",
+    "always",
+];
+
+/// A user or group name as systemd accepts it, or a numeric id
+pub fn is_valid_account_name(name: &str) -> bool {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+        return true;
+    }
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    name.len() <= 32
+        && (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn reject_line_breaks(field: &str, value: &str) -> Result<()> {
+    if value.contains(['\n', '\r']) {
+        return Err(anyhow!("{} must not contain line breaks", field));
+    }
+    Ok(())
+}
+
+/// Reject values that would corrupt the service definition or inject extra
+/// directives
+pub fn validate_overrides(overrides: &ServiceOverrides) -> Result<()> {
+    for (field, value) in [("User", &overrides.user), ("Group", &overrides.group)] {
+        if let Some(value) = value {
+            reject_line_breaks(field, value)?;
+            if !is_valid_account_name(value) {
+                return Err(anyhow!("Invalid {} name: {:?}", field, value));
+            }
+        }
+    }
+    if let Some(restart) = &overrides.restart {
+        if !RESTART_VALUES.contains(&restart.as_str()) {
+            return Err(anyhow!(
+                "Invalid Restart value {:?}, expected one of: {}",
+                restart,
+                RESTART_VALUES.join(", ")
+            ));
+        }
+    }
+    if let Some(exec_start) = &overrides.exec_start {
+        reject_line_breaks("ExecStart", exec_start)?;
+    }
+    if let Some(env) = &overrides.environment {
+        for (key, value) in env {
+            let valid_key = !key.is_empty()
+                && !key.starts_with(|c: char| c.is_ascii_digit())
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid_key {
+                return Err(anyhow!("Invalid Environment name: {:?}", key));
+            }
+            reject_line_breaks(&format!("Environment {}", key), value)?;
+        }
+    }
+    Ok(())
+}
@@ -1,9 +1,34 @@
 pub mod agent;
+#[cfg(feature = "api-key")]
+pub mod api_key;
 pub mod client;
+pub mod config;
+pub mod init;
+pub mod logging;
+pub mod pool;
 pub mod registry;
+#[cfg(any(test, feature = "test-support"))]
+pub mod testing;
 mod tests;
+#[cfg(feature = "server")]
+pub mod tls;
+pub mod transport;
 
 // Re-export public APIs for easy access
-pub use agent::{AgentClient, AgentStatus};
-pub use client::{DaemonClient, PersistentClient};
-pub use registry::{InfectionManifest, InfectionSummary, RegistryClient};
+pub use agent::{AgentClient, AgentStatus, RetryPolicy};
+pub use client::{
+    ClientError, DaemonClient, EventReceiver, MultiplexedClient, PersistentClient, ReconnectPolicy,
+    DEFAULT_REQUEST_TIMEOUT, EVENT_BUFFER,
+};
+pub use config::{merge_json, parse_toml_with_secrets, ConfigManager, FileConfigManager};
+pub use init::{
+    InitBackend, InitSystem, LogEntry, OpenRcBackend, ServiceAction, ServiceDefinition,
+    ServiceState, ServiceStatus, ServiceSummary, SystemdBackend,
+};
+pub use logging::{init_logging, LogFormat, RotatingFile, Rotation};
+pub use pool::DaemonPool;
+pub use registry::{
+    ChecksumAlgorithm, InfectionManifest, InfectionSummary, ManifestVersion, RegistryClient,
+    SignatureAlgorithm, VerificationError,
+};
+pub use transport::{DaemonAddr, Transport};
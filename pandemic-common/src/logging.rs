@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tracing::Subscriber;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Layer;
+
+/// Log output format, chosen with `PANDEMIC_LOG_FORMAT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    /// Read `PANDEMIC_LOG_FORMAT`, defaulting to text for unset or unknown values
+    pub fn from_env() -> Self {
+        match std::env::var("PANDEMIC_LOG_FORMAT") {
+            Ok(value) if value.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Text,
+        }
+    }
+}
+
+/// Install the global tracing subscriber for a pandemic binary.
+///
+/// The format comes from `PANDEMIC_LOG_FORMAT` (`text` or `json`) and the
+/// level from `RUST_LOG` (e.g. `debug` or `info,pandemic=trace`), defaulting
+/// to `info`. JSON output writes one object per line with event fields such
+/// as `plugin` as top-level keys, and span fields such as `connection_id`
+/// under `span` (the innermost span) and `spans` (every span, outermost first).
+pub fn init_logging() {
+    let filter = std::env::var("RUST_LOG")
+        .ok()
+        .and_then(|value| value.parse::<Targets>().ok())
+        .unwrap_or_else(|| Targets::new().with_default(tracing::Level::INFO));
+    let subscriber = subscriber(LogFormat::from_env(), filter, std::io::stderr);
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
+
+pub(crate) fn subscriber<W>(
+    format: LogFormat,
+    filter: Targets,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().with_writer(writer).boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .flatten_event(true)
+            .with_writer(writer)
+            .boxed(),
+    };
+    Box::new(tracing_subscriber::registry().with(layer).with(filter))
+}
+
+/// Size limit for a [`RotatingFile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    /// Roll over before a write would grow the file past this many bytes
+    pub max_bytes: u64,
+    /// How many rolled-over files (`name.1` .. `name.N`) to keep
+    pub max_files: usize,
+}
+
+/// An append-only file that rolls over by size: `name` becomes `name.1`,
+/// `name.1` becomes `name.2` and the oldest beyond `max_files` is deleted.
+///
+/// Each `append` lands whole in one file, and appends from concurrent tasks
+/// are serialized so a rollover never splits or interleaves them.
+pub struct RotatingFile {
+    path: PathBuf,
+    rotation: Option<Rotation>,
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl RotatingFile {
+    /// A file that is never rotated unless `rotation` is given
+    pub fn new(path: impl Into<PathBuf>, rotation: Option<Rotation>) -> Self {
+        Self {
+            path: path.into(),
+            rotation,
+            lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the `index`th rolled-over file
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    pub async fn append(&self, data: &[u8]) -> Result<()> {
+        let _guard = self.lock.lock().await;
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        if let Some(rotation) = self.rotation {
+            // Sized on every write so external truncation is picked up too
+            let len = match tokio::fs::metadata(&self.path).await {
+                Ok(metadata) => metadata.len(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+                Err(e) => return Err(e.into()),
+            };
+            // An empty file takes any write, so oversized records still land
+            if len > 0 && len + data.len() as u64 > rotation.max_bytes {
+                self.rotate(rotation.max_files)
+                    .await
+                    .with_context(|| format!("Failed to rotate {}", self.path.display()))?;
+            }
+        }
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(data).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn rotate(&self, max_files: usize) -> io::Result<()> {
+        if max_files == 0 {
+            return remove_if_exists(&self.path).await;
+        }
+        remove_if_exists(&self.rotated_path(max_files)).await?;
+        for index in (1..max_files).rev() {
+            let from = self.rotated_path(index);
+            if tokio::fs::try_exists(&from).await? {
+                tokio::fs::rename(&from, self.rotated_path(index + 1)).await?;
+            }
+        }
+        tokio::fs::rename(&self.path, self.rotated_path(1)).await
+    }
+}
+
+async fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
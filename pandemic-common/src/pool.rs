@@ -0,0 +1,87 @@
+use anyhow::Result;
+use pandemic_protocol::{Request, Response};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+use crate::client::{ClientError, DaemonClient, PersistentClient, DEFAULT_REQUEST_TIMEOUT};
+
+/// Up to `size` persistent daemon connections shared by request/response
+/// callers such as HTTP handlers, so each request skips connecting and the
+/// `Hello` handshake.
+///
+/// Idle connections the daemon has closed are dropped when checked out, and
+/// a connection is only returned to the pool after a complete exchange. When
+/// every connection is busy, requests fall back to a one-shot connection.
+pub struct DaemonPool {
+    socket_path: PathBuf,
+    idle: Mutex<Vec<PersistentClient>>,
+    permits: Semaphore,
+    timeout: Duration,
+}
+
+impl DaemonPool {
+    pub fn new(socket_path: impl AsRef<Path>, size: usize) -> Self {
+        Self {
+            socket_path: socket_path.as_ref().to_path_buf(),
+            idle: Mutex::new(Vec::new()),
+            permits: Semaphore::new(size),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// How long a request may take, including connecting; defaults to
+    /// [`DEFAULT_REQUEST_TIMEOUT`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Connections waiting to be reused
+    pub fn idle_count(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    pub async fn send_request(&self, request: &Request) -> Result<Response> {
+        let Ok(_permit) = self.permits.try_acquire() else {
+            debug!("Daemon pool exhausted, using a one-shot connection");
+            return DaemonClient::send_request_timeout(&self.socket_path, request, self.timeout)
+                .await;
+        };
+
+        let exchange = async {
+            let mut client = match self.checkout() {
+                Some(client) => client,
+                None => DaemonClient::connect(&self.socket_path).await?,
+            };
+            let response = client.send_request(request).await?;
+            Ok::<_, anyhow::Error>((client, response))
+        };
+        // A connection that timed out or failed mid-exchange may still have a
+        // response in flight, so it is dropped rather than pooled
+        let (client, response) = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| ClientError::Timeout(self.timeout))??;
+
+        self.idle.lock().unwrap().push(client);
+        Ok(response)
+    }
+
+    /// Take an idle connection the daemon hasn't closed
+    fn checkout(&self) -> Option<PersistentClient> {
+        let mut idle = self.idle.lock().unwrap();
+        while let Some(mut client) = idle.pop() {
+            if !client.is_closed() {
+                return Some(client);
+            }
+            debug!("Dropping pooled connection closed by the daemon");
+        }
+        None
+    }
+}
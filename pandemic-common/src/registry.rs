@@ -1,6 +1,14 @@
 use anyhow::Result;
+use base64::Engine;
+use reqwest::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use ring::digest;
+use ring::signature::{UnparsedPublicKey, VerificationAlgorithm, ECDSA_P256_SHA256_ASN1, ED25519};
+use semver::{Version, VersionReq};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InfectionManifest {
@@ -13,6 +21,59 @@ pub struct InfectionManifest {
     pub keywords: Vec<String>,
     pub dependencies: Vec<String>,
     pub platforms: Vec<Platform>,
+    /// Other published releases, for installing something other than `version`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub versions: Vec<ManifestVersion>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestVersion {
+    pub version: String,
+    pub platforms: Vec<Platform>,
+}
+
+impl InfectionManifest {
+    /// Narrow this manifest to the highest release satisfying `req`
+    pub fn resolve_version(&self, req: &VersionReq) -> Result<InfectionManifest> {
+        let releases = std::iter::once((&self.version, &self.platforms))
+            .chain(self.versions.iter().map(|v| (&v.version, &v.platforms)));
+
+        let mut best: Option<(Version, &Vec<Platform>)> = None;
+        for (version, platforms) in releases {
+            let Ok(parsed) = Version::parse(version) else {
+                tracing::warn!("Skipping invalid version '{}' of {}", version, self.name);
+                continue;
+            };
+            if req.matches(&parsed) && best.as_ref().is_none_or(|(v, _)| parsed > *v) {
+                best = Some((parsed, platforms));
+            }
+        }
+
+        let (version, platforms) = best.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No version of '{}' satisfies '{}' (available: {})",
+                self.name,
+                req,
+                self.available_versions().join(", ")
+            )
+        })?;
+
+        Ok(InfectionManifest {
+            version: version.to_string(),
+            platforms: platforms.clone(),
+            ..self.clone()
+        })
+    }
+
+    fn available_versions(&self) -> Vec<&str> {
+        let mut versions = vec![self.version.as_str()];
+        for release in &self.versions {
+            if !versions.contains(&release.version.as_str()) {
+                versions.push(&release.version);
+            }
+        }
+        versions
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,7 +81,101 @@ pub struct Platform {
     pub os: String,
     pub arch: String,
     pub binary_url: String,
+    /// Hex digest of the binary, computed with `checksum_algo` and optionally
+    /// prefixed with its name (`sha256:...`)
     pub checksum: String,
+    /// `sha256` (the default) or `sha512`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_algo: Option<String>,
+    /// Base64 detached signature over the binary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_url: Option<String>,
+    /// `ed25519` (the default) or `ecdsa-p256`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_algo: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Sha512,
+}
+
+impl ChecksumAlgorithm {
+    fn digest_algorithm(self) -> &'static digest::Algorithm {
+        match self {
+            ChecksumAlgorithm::Sha256 => &digest::SHA256,
+            ChecksumAlgorithm::Sha512 => &digest::SHA512,
+        }
+    }
+
+    /// Lowercase hex digest of `data`
+    pub fn hex_digest(self, data: &[u8]) -> String {
+        digest::digest(self.digest_algorithm(), data)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+        }
+    }
+
+    /// `checksum` as lowercase hex, tolerating surrounding whitespace,
+    /// uppercase digits and a prefix naming this algorithm (`sha256:...`);
+    /// `None` when what's left isn't a hex digest of this algorithm's length
+    pub fn normalize(self, checksum: &str) -> Option<String> {
+        let checksum = checksum.trim();
+        let checksum = match checksum.split_once(':') {
+            Some((prefix, digest)) if prefix.trim().eq_ignore_ascii_case(self.name()) => {
+                digest.trim()
+            }
+            Some(_) => return None,
+            None => checksum,
+        };
+        let well_formed = checksum.len() == self.digest_algorithm().output_len() * 2
+            && checksum.chars().all(|c| c.is_ascii_hexdigit());
+        well_formed.then(|| checksum.to_ascii_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    /// Raw 32-byte public key, 64-byte signature
+    Ed25519,
+    /// Uncompressed SEC1 public key, ASN.1 DER signature over SHA-256
+    EcdsaP256,
+}
+
+impl SignatureAlgorithm {
+    fn verification_algorithm(self) -> &'static dyn VerificationAlgorithm {
+        match self {
+            SignatureAlgorithm::Ed25519 => &ED25519,
+            SignatureAlgorithm::EcdsaP256 => &ECDSA_P256_SHA256_ASN1,
+        }
+    }
+}
+
+impl Platform {
+    pub fn checksum_algorithm(&self) -> Result<ChecksumAlgorithm, VerificationError> {
+        match self.checksum_algo.as_deref().unwrap_or("sha256") {
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            "sha512" => Ok(ChecksumAlgorithm::Sha512),
+            other => Err(VerificationError::UnsupportedAlgorithm(other.to_string())),
+        }
+    }
+
+    pub fn signature_algorithm(&self) -> Result<SignatureAlgorithm, VerificationError> {
+        match self.signature_algo.as_deref().unwrap_or("ed25519") {
+            "ed25519" => Ok(SignatureAlgorithm::Ed25519),
+            "ecdsa-p256" => Ok(SignatureAlgorithm::EcdsaP256),
+            other => Err(VerificationError::UnsupportedAlgorithm(other.to_string())),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,9 +194,55 @@ pub struct InfectionSummary {
     pub manifest_url: String,
 }
 
+/// Downloaded binary failed verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationError {
+    ChecksumMismatch(String),
+    SignatureMismatch(String),
+    MissingSignature(String),
+    /// The manifest names a checksum or signature algorithm we don't know
+    UnsupportedAlgorithm(String),
+    /// The manifest's checksum isn't a hex digest for its `checksum_algo`
+    MalformedChecksum(String),
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerificationError::ChecksumMismatch(name) => {
+                write!(f, "Checksum mismatch for {}", name)
+            }
+            VerificationError::SignatureMismatch(name) => {
+                write!(f, "Signature verification failed for {}", name)
+            }
+            VerificationError::MissingSignature(name) => {
+                write!(f, "No signature published for {}", name)
+            }
+            VerificationError::UnsupportedAlgorithm(algorithm) => {
+                write!(f, "Unsupported verification algorithm '{}'", algorithm)
+            }
+            VerificationError::MalformedChecksum(name) => {
+                write!(f, "Checksum for {} does not match its checksum_algo", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// On-disk copy of a registry index with its revalidation headers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedIndex {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    index: RegistryIndex,
+}
+
 pub struct RegistryClient {
     registries: Vec<String>,
     client: reqwest::Client,
+    trusted_key: Option<Vec<u8>>,
+    cache_dir: Option<PathBuf>,
 }
 
 impl RegistryClient {
@@ -49,24 +250,39 @@ impl RegistryClient {
         let default_url = "https://philcali.github.io/rustic/registry/".to_string();
         let registry_url = std::env::var("PANDEMIC_REGISTRY_URL").unwrap_or(default_url);
 
-        Self {
-            registries: vec![registry_url],
-            client: reqwest::Client::new(),
-        }
+        Self::with_registries(vec![registry_url])
     }
 
     pub fn with_registries(registries: Vec<String>) -> Self {
         Self {
             registries,
             client: reqwest::Client::new(),
+            trusted_key: trusted_key_from_env(),
+            cache_dir: default_cache_dir(),
         }
     }
 
     pub fn with_registry_url(url: String) -> Self {
-        Self {
-            registries: vec![url],
-            client: reqwest::Client::new(),
-        }
+        Self::with_registries(vec![url])
+    }
+
+    /// Require binaries to carry a valid signature from this public key: raw
+    /// bytes for ed25519, an uncompressed point for ecdsa-p256
+    pub fn with_trusted_key(mut self, public_key: Vec<u8>) -> Self {
+        self.trusted_key = Some(public_key);
+        self
+    }
+
+    /// Cache registry indexes under `cache_dir` instead of the user cache directory
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    /// Always fetch registry indexes in full, bypassing the on-disk cache
+    pub fn without_cache(mut self) -> Self {
+        self.cache_dir = None;
+        self
     }
 
     pub async fn search_infections(&self, query: &str) -> Result<Vec<InfectionSummary>> {
@@ -111,21 +327,61 @@ impl RegistryClient {
         ))
     }
 
+    pub async fn get_infection_manifest_version(
+        &self,
+        name: &str,
+        req: &VersionReq,
+    ) -> Result<InfectionManifest> {
+        self.get_infection_manifest(name)
+            .await?
+            .resolve_version(req)
+    }
+
     pub async fn download_infection(
         &self,
         manifest: &InfectionManifest,
         target_path: &str,
     ) -> Result<()> {
         let platform = self.get_current_platform(manifest)?;
+        let checksum_algorithm = platform.checksum_algorithm()?;
+        let expected_checksum = checksum_algorithm
+            .normalize(&platform.checksum)
+            .ok_or_else(|| VerificationError::MalformedChecksum(manifest.name.clone()))?;
+        let signature_algorithm = platform.signature_algorithm()?;
 
-        let response = self.client.get(&platform.binary_url).send().await?;
+        let response = self
+            .client
+            .get(&platform.binary_url)
+            .send()
+            .await?
+            .error_for_status()?;
 
         let bytes = response.bytes().await?;
 
         // Verify checksum
-        let actual_checksum = sha256::digest(&*bytes);
-        if actual_checksum != platform.checksum {
-            return Err(anyhow::anyhow!("Checksum mismatch for {}", manifest.name));
+        let actual_checksum = checksum_algorithm.hex_digest(&bytes);
+        if actual_checksum != expected_checksum {
+            return Err(VerificationError::ChecksumMismatch(manifest.name.clone()).into());
+        }
+
+        // The checksum comes from the registry itself, so only a signature
+        // from a trusted key proves who built the binary
+        if let Some(public_key) = &self.trusted_key {
+            let signature_url = platform
+                .signature_url
+                .as_ref()
+                .ok_or_else(|| VerificationError::MissingSignature(manifest.name.clone()))?;
+            let response = self.client.get(signature_url).send().await?;
+            if response.status() == StatusCode::NOT_FOUND {
+                return Err(VerificationError::MissingSignature(manifest.name.clone()).into());
+            }
+            let signature = response.error_for_status()?.text().await?;
+            let signature = base64::engine::general_purpose::STANDARD
+                .decode(signature.trim())
+                .map_err(|_| VerificationError::SignatureMismatch(manifest.name.clone()))?;
+            UnparsedPublicKey::new(signature_algorithm.verification_algorithm(), public_key)
+                .verify(&bytes, &signature)
+                .map_err(|_| VerificationError::SignatureMismatch(manifest.name.clone()))?;
         }
 
         std::fs::write(target_path, bytes)?;
@@ -144,13 +400,54 @@ impl RegistryClient {
 
     async fn fetch_registry_index(&self, registry_url: &str) -> Result<RegistryIndex> {
         let index_url = format!("{}/index.json", registry_url);
-        let index = self
-            .client
-            .get(&index_url)
-            .send()
-            .await?
-            .json::<RegistryIndex>()
-            .await?;
+        let cache_path = self
+            .cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", sha256::digest(registry_url))));
+        let cached = cache_path.as_deref().and_then(read_cached_index);
+
+        let mut request = self.client.get(&index_url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(cached) = cached {
+                tracing::debug!("Registry index {} not modified, using cache", index_url);
+                return Ok(cached.index);
+            }
+        }
+
+        let response = response.error_for_status()?;
+        let headers = response.headers();
+        let etag = headers
+            .get(ETAG)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let last_modified = headers
+            .get(LAST_MODIFIED)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from);
+        let index = response.json::<RegistryIndex>().await?;
+
+        if let Some(cache_path) = &cache_path {
+            let cached = CachedIndex {
+                etag,
+                last_modified,
+                index,
+            };
+            if let Err(e) = write_cached_index(cache_path, &cached) {
+                tracing::warn!("Failed to cache registry index {}: {}", index_url, e);
+            }
+            return Ok(cached.index);
+        }
+
         Ok(index)
     }
 
@@ -166,6 +463,40 @@ impl RegistryClient {
     }
 }
 
+/// Base64 ed25519 public key from `PANDEMIC_REGISTRY_PUBLIC_KEY`
+fn trusted_key_from_env() -> Option<Vec<u8>> {
+    let key = std::env::var("PANDEMIC_REGISTRY_PUBLIC_KEY").ok()?;
+    match base64::engine::general_purpose::STANDARD.decode(key.trim()) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            // Fail closed: an unusable key rejects every download
+            tracing::warn!("Invalid PANDEMIC_REGISTRY_PUBLIC_KEY: {}", e);
+            Some(Vec::new())
+        }
+    }
+}
+
+/// `$XDG_CACHE_HOME/pandemic/registry`, falling back to `~/.cache/pandemic/registry`
+fn default_cache_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("pandemic").join("registry"))
+}
+
+fn read_cached_index(path: &Path) -> Option<CachedIndex> {
+    let contents = std::fs::read(path).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+fn write_cached_index(path: &Path, cached: &CachedIndex) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_vec(cached)?)?;
+    Ok(())
+}
+
 impl Default for RegistryClient {
     fn default() -> Self {
         Self::new()
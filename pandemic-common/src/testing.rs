@@ -0,0 +1,153 @@
+//! Helpers shared by tests across the workspace. Built for this crate's own
+//! tests and, through the `test-support` feature, for other crates' tests.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+/// A minimal HTTP/1.1 server on a background thread, for pointing clients at
+/// canned responses. Every response closes its connection.
+pub struct MockServer {
+    listener: TcpListener,
+    url: String,
+}
+
+/// The request line and headers of one request; bodies are not read
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+}
+
+pub struct MockResponse {
+    status: &'static str,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl MockServer {
+    /// Bind to an ephemeral local port. Nothing is served until `serve`, so
+    /// responses can refer to `url` first.
+    pub fn bind() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        Self { listener, url }
+    }
+
+    /// Base URL, without a trailing slash
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Answer every request with `handler` until the test process exits,
+    /// returning the base URL
+    pub fn serve<F>(self, handler: F) -> String
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let handler = Arc::new(handler);
+        let listener = self.listener;
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let handler = Arc::clone(&handler);
+                thread::spawn(move || {
+                    let Some(request) = MockRequest::read(&mut stream) else {
+                        return;
+                    };
+                    let _ = handler(&request).write(&mut stream);
+                });
+            }
+        });
+        self.url
+    }
+
+    /// Serve fixed bodies by path, answering 404 to anything else
+    pub fn serve_routes(self, routes: HashMap<String, Vec<u8>>) -> String {
+        self.serve(move |request| match routes.get(&request.path) {
+            Some(body) => MockResponse::ok(body.clone()),
+            None => MockResponse::not_found(),
+        })
+    }
+}
+
+impl MockRequest {
+    fn read(stream: &mut impl Read) -> Option<Self> {
+        let mut head = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+            match stream.read(&mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(n) => head.extend_from_slice(&buf[..n]),
+            }
+        }
+
+        let head = String::from_utf8_lossy(&head);
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split_whitespace();
+        let method = request_line.next()?.to_string();
+        let path = request_line.next()?.to_string();
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        Some(Self {
+            method,
+            path,
+            headers,
+        })
+    }
+
+    /// Value of the first header called `name`, compared case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl MockResponse {
+    /// `status` is the status line after the protocol, e.g. `"304 Not Modified"`
+    pub fn new(status: &'static str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK").with_body(body)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new("404 Not Found")
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn write(&self, stream: &mut impl Write) -> std::io::Result<()> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        ));
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)
+    }
+}
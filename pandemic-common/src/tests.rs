@@ -1,11 +1,15 @@
 #[cfg(test)]
 mod client_tests {
-    use crate::client::DaemonClient;
-    use pandemic_protocol::{PluginInfo, Request, Response};
+    use crate::client::{ClientError, DaemonClient, PersistentClient, ReconnectPolicy};
+    use futures_util::StreamExt;
+    use pandemic_protocol::{
+        Encoding, Event, Framing, Message, PluginInfo, Request, RequestEnvelope, Response,
+        ResponseEnvelope, PROTOCOL_VERSION,
+    };
     use std::collections::HashMap;
     use std::sync::atomic::{AtomicU32, Ordering};
     use tempfile::TempDir;
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
     use tokio::net::UnixListener;
 
     static COUNTER: AtomicU32 = AtomicU32::new(0);
@@ -22,6 +26,9 @@ mod client_tests {
                 let request: Request = serde_json::from_str(line.trim()).unwrap();
 
                 let response = match request {
+                    Request::Hello { .. } => Response::success_with_data(
+                        serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+                    ),
                     Request::ListPlugins => Response::success_with_data(serde_json::json!([])),
                     Request::GetPlugin { name } => {
                         if name == "test-plugin" {
@@ -31,6 +38,8 @@ mod client_tests {
                                 description: Some("Test plugin".to_string()),
                                 config: None,
                                 registered_at: None,
+                                heartbeat_interval: None,
+                                dependencies: vec![],
                             };
                             Response::success_with_data(serde_json::json!(plugin))
                         } else {
@@ -38,6 +47,7 @@ mod client_tests {
                         }
                     }
                     Request::Register { .. } => Response::success(),
+                    Request::Heartbeat { .. } => Response::success(),
                     Request::Deregister { name } => {
                         if name == "test-plugin" {
                             Response::success()
@@ -45,9 +55,19 @@ mod client_tests {
                             Response::not_found("Plugin not found")
                         }
                     }
+                    Request::DeregisterMatching { .. } => {
+                        Response::success_with_data(serde_json::json!([]))
+                    }
+                    Request::UpdatePluginConfig { .. } => Response::success(),
+                    Request::GetConfig { .. } => Response::success_with_data(serde_json::json!({})),
+                    Request::SetConfigOverride { .. } => Response::success(),
+                    Request::ClearConfigOverride { .. } => Response::success(),
                     Request::Publish { .. } => Response::success(),
                     Request::Unsubscribe { .. } => Response::success(),
                     Request::Subscribe { .. } => Response::success(),
+                    Request::ListSubscriptions => {
+                        Response::success_with_data(serde_json::json!({}))
+                    }
                     Request::GetHealth => {
                         let health = serde_json::json!({
                             "active_plugins": 1,
@@ -61,6 +81,12 @@ mod client_tests {
                         });
                         Response::success_with_data(health)
                     }
+                    Request::GetMetrics => Response::success_with_data(serde_json::json!({})),
+                    Request::Ping => Response::success(),
+                    Request::Info => Response::success_with_data(serde_json::json!({})),
+                    Request::GetEventHistory { .. } => {
+                        Response::success_with_data(serde_json::json!([]))
+                    }
                 };
 
                 let response_json = serde_json::to_string(&response).unwrap();
@@ -74,112 +100,659 @@ mod client_tests {
         }
     }
 
+    async fn mock_hello_server(socket_path: String, response: Response) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        if let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+
+            if reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Request = serde_json::from_str(line.trim()).unwrap();
+                assert!(matches!(request, Request::Hello { .. }));
+
+                let response_json = serde_json::to_string(&response).unwrap();
+                reader
+                    .get_mut()
+                    .write_all(response_json.as_bytes())
+                    .await
+                    .unwrap();
+                reader.get_mut().write_all(b"\n").await.unwrap();
+            }
+        }
+    }
+
+    /// Echoes `Publish` data back, detecting the framing like the daemon does
+    async fn mock_framed_echo_server(socket_path: String) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        if let Ok((mut stream, _)) = listener.accept().await {
+            let mut buf = Vec::new();
+            let mut framing = None;
+
+            while stream.read_buf(&mut buf).await.unwrap() > 0 {
+                let framing = *framing.get_or_insert_with(|| Framing::detect(buf[0]));
+                while let Some(frame) = framing.decode(&mut buf).unwrap() {
+                    let response = match serde_json::from_slice::<Request>(&frame).unwrap() {
+                        Request::Publish { data, .. } => Response::success_with_data(data),
+                        _ => Response::success_with_data(
+                            serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+                        ),
+                    };
+                    let body = serde_json::to_vec(&response).unwrap();
+                    stream.write_all(&framing.encode(&body)).await.unwrap();
+                }
+            }
+        }
+    }
+
     #[tokio::test]
-    async fn test_list_plugins() {
+    async fn test_payload_with_newlines_in_both_framings() {
+        for framed in [false, true] {
+            let temp_dir = TempDir::new().unwrap();
+            let socket_path = temp_dir.path().join(format!(
+                "test_{}.sock",
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+
+            tokio::spawn(mock_framed_echo_server(
+                socket_path.to_str().unwrap().to_string(),
+            ));
+            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+            let mut client = if framed {
+                DaemonClient::connect_framed(&socket_path).await.unwrap()
+            } else {
+                DaemonClient::connect(&socket_path).await.unwrap()
+            };
+
+            let data = serde_json::json!({ "text": "line one\nline two" });
+            let response = client
+                .send_request(&Request::Publish {
+                    topic: "test.topic".to_string(),
+                    data: data.clone(),
+                    retain: false,
+                })
+                .await
+                .unwrap();
+
+            match response {
+                Response::Success { data: Some(echoed) } => assert_eq!(echoed, data),
+                _ => panic!("Expected echoed payload, got {:?}", response),
+            }
+        }
+    }
+
+    /// Agrees to the encoding asked for in the JSON handshake, then echoes
+    /// `Publish` data back followed by an event carrying the same data
+    async fn mock_encoding_echo_server(socket_path: String) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        if let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            let encoding = match serde_json::from_str::<Request>(line.trim()).unwrap() {
+                Request::Hello { encoding, .. } => encoding.unwrap_or_default(),
+                other => panic!("Expected Hello, got {:?}", other),
+            };
+            let reply = Response::success_with_data(serde_json::json!({
+                "protocol_version": PROTOCOL_VERSION,
+                "encoding": encoding,
+            }));
+            let mut json = serde_json::to_vec(&reply).unwrap();
+            json.push(b'\n');
+            reader.get_mut().write_all(&json).await.unwrap();
+
+            let framing = encoding.framing(Framing::Newline);
+            let mut stream = reader.into_inner();
+            let mut buf = Vec::new();
+            while stream.read_buf(&mut buf).await.unwrap() > 0 {
+                while let Some(frame) = framing.decode(&mut buf).unwrap() {
+                    let data = match encoding.decode::<Request>(&frame).unwrap() {
+                        Request::Publish { data, .. } => data,
+                        other => panic!("Expected Publish, got {:?}", other),
+                    };
+                    let event = Message::Event(Event {
+                        topic: "echo".to_string(),
+                        source: "test".to_string(),
+                        data: data.clone(),
+                        timestamp: None,
+                    });
+                    for body in [
+                        encoding.encode(&Response::success_with_data(data)).unwrap(),
+                        encoding.encode(&event).unwrap(),
+                    ] {
+                        stream.write_all(&framing.encode(&body)).await.unwrap();
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_message_pack_round_trip() {
         let temp_dir = TempDir::new().unwrap();
         let socket_path = temp_dir.path().join(format!(
             "test_{}.sock",
             COUNTER.fetch_add(1, Ordering::SeqCst)
         ));
-        let socket_path_str = socket_path.to_str().unwrap();
 
-        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::spawn(mock_encoding_echo_server(
+            socket_path.to_str().unwrap().to_string(),
+        ));
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        let request = Request::ListPlugins;
-        let response = DaemonClient::send_request(&socket_path, &request)
+        let mut client = DaemonClient::connect_with_encoding(&socket_path, Encoding::MessagePack)
             .await
             .unwrap();
 
+        let data = serde_json::json!({ "text": "line one\nline two", "values": [1, -2, 3.5] });
+        let response = client
+            .send_request(&Request::Publish {
+                topic: "test.topic".to_string(),
+                data: data.clone(),
+                retain: false,
+            })
+            .await
+            .unwrap();
         match response {
-            Response::Success { data } => assert!(data.is_some()),
-            _ => panic!("Expected success response"),
+            Response::Success { data: Some(echoed) } => assert_eq!(echoed, data),
+            _ => panic!("Expected echoed payload, got {:?}", response),
+        }
+
+        let event = client.read_event().await.unwrap().unwrap();
+        assert_eq!(event.topic, "echo");
+        assert_eq!(event.data, data);
+    }
+
+    /// Answers the handshake, then holds three requests and replies in reverse order
+    async fn mock_out_of_order_server(socket_path: String) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+
+        if let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+            let mut held = Vec::new();
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let envelope: RequestEnvelope = serde_json::from_str(line.trim()).unwrap();
+                line.clear();
+
+                let replies = match envelope.request {
+                    Request::Hello { .. } => vec![ResponseEnvelope {
+                        id: envelope.id,
+                        response: Response::success_with_data(
+                            serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+                        ),
+                    }],
+                    Request::GetPlugin { name } => {
+                        held.push(ResponseEnvelope {
+                            id: envelope.id,
+                            response: Response::success_with_data(serde_json::json!(name)),
+                        });
+                        if held.len() < 3 {
+                            continue;
+                        }
+                        held.drain(..).rev().collect()
+                    }
+                    _ => vec![ResponseEnvelope {
+                        id: envelope.id,
+                        response: Response::error("unexpected request"),
+                    }],
+                };
+
+                for reply in replies {
+                    let mut json = serde_json::to_vec(&reply).unwrap();
+                    json.push(b'\n');
+                    reader.get_mut().write_all(&json).await.unwrap();
+                }
+            }
         }
     }
 
     #[tokio::test]
-    async fn test_get_existing_plugin() {
+    async fn test_multiplexed_concurrent_requests() {
         let temp_dir = TempDir::new().unwrap();
         let socket_path = temp_dir.path().join(format!(
             "test_{}.sock",
             COUNTER.fetch_add(1, Ordering::SeqCst)
         ));
-        let socket_path_str = socket_path.to_str().unwrap();
 
-        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::spawn(mock_out_of_order_server(
+            socket_path.to_str().unwrap().to_string(),
+        ));
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        let request = Request::GetPlugin {
-            name: "test-plugin".to_string(),
-        };
-        let response = DaemonClient::send_request(&socket_path, &request)
+        let client = DaemonClient::connect(&socket_path)
             .await
-            .unwrap();
+            .unwrap()
+            .into_multiplexed();
 
-        match response {
-            Response::Success { data } => assert!(data.is_some()),
-            _ => panic!("Expected success response"),
+        let handles: Vec<_> = ["alpha", "beta", "gamma"]
+            .into_iter()
+            .map(|name| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    let response = client
+                        .send_request_multiplexed(&Request::GetPlugin {
+                            name: name.to_string(),
+                        })
+                        .await
+                        .unwrap();
+                    (name, response)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let (name, response) = handle.await.unwrap();
+            match response {
+                Response::Success { data: Some(data) } => assert_eq!(data, name),
+                _ => panic!("Unexpected response for {}: {:?}", name, response),
+            }
+        }
+    }
+
+    /// Serves a single session: records registration and subscriptions, pushes
+    /// one event once subscribed, then shuts down as if the daemon died
+    /// Acknowledge every request and, once subscribed, push one event per
+    /// topic and hang up
+    async fn mock_event_daemon(socket_path: String, topics: Vec<&'static str>) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let request: Request = serde_json::from_str(line.trim()).unwrap();
+            line.clear();
+
+            let mut reply = serde_json::to_vec(&Response::success()).unwrap();
+            reply.push(b'\n');
+            reader.get_mut().write_all(&reply).await.unwrap();
+
+            if matches!(request, Request::Subscribe { .. }) {
+                for topic in &topics {
+                    let event = Message::Event(Event {
+                        topic: topic.to_string(),
+                        source: "daemon".to_string(),
+                        data: serde_json::json!({}),
+                        timestamp: None,
+                    });
+                    let mut frame = serde_json::to_vec(&event).unwrap();
+                    frame.push(b'\n');
+                    reader.get_mut().write_all(&frame).await.unwrap();
+                }
+                break;
+            }
+        }
+    }
+
+    /// Acknowledge every request, forwarding each one to `requests`
+    async fn mock_recording_daemon(
+        socket_path: String,
+        requests: tokio::sync::mpsc::UnboundedSender<Request>,
+    ) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let request: Request = serde_json::from_str(line.trim()).unwrap();
+            line.clear();
+            let _ = requests.send(request);
+
+            let mut reply = serde_json::to_vec(&Response::success()).unwrap();
+            reply.push(b'\n');
+            reader.get_mut().write_all(&reply).await.unwrap();
+        }
+    }
+
+    async fn mock_restarting_daemon(
+        socket_path: String,
+        event_topic: &str,
+    ) -> (Option<String>, Vec<String>) {
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let mut registered = None;
+        let mut subscribed = Vec::new();
+
+        if let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let request: Request = serde_json::from_str(line.trim()).unwrap();
+                line.clear();
+
+                let done = match request {
+                    Request::Register { plugin, .. } => {
+                        registered = Some(plugin.name);
+                        false
+                    }
+                    Request::Subscribe { topics } => {
+                        subscribed.extend(topics);
+                        true
+                    }
+                    _ => false,
+                };
+
+                let mut reply = serde_json::to_vec(&Response::success()).unwrap();
+                reply.push(b'\n');
+                if done {
+                    let event = Message::Event(Event {
+                        topic: event_topic.to_string(),
+                        source: "daemon".to_string(),
+                        data: serde_json::json!({}),
+                        timestamp: None,
+                    });
+                    reply.extend(serde_json::to_vec(&event).unwrap());
+                    reply.push(b'\n');
+                }
+                reader.get_mut().write_all(&reply).await.unwrap();
+                if done {
+                    break;
+                }
+            }
         }
+
+        (registered, subscribed)
     }
 
     #[tokio::test]
-    async fn test_get_nonexistent_plugin() {
+    async fn test_reconnect_restores_session() {
         let temp_dir = TempDir::new().unwrap();
         let socket_path = temp_dir.path().join(format!(
             "test_{}.sock",
             COUNTER.fetch_add(1, Ordering::SeqCst)
         ));
-        let socket_path_str = socket_path.to_str().unwrap();
+        let socket_path_str = socket_path.to_str().unwrap().to_string();
 
-        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        let first = tokio::spawn(mock_restarting_daemon(
+            socket_path_str.clone(),
+            "first.event",
+        ));
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        let request = Request::GetPlugin {
-            name: "nonexistent".to_string(),
-        };
-        let response = DaemonClient::send_request(&socket_path, &request)
+        let mut client = PersistentClient::connect_with_reconnect(
+            &socket_path,
+            ReconnectPolicy {
+                base_delay: std::time::Duration::from_millis(10),
+                max_delay: std::time::Duration::from_millis(50),
+            },
+        )
+        .await
+        .unwrap();
+
+        client
+            .send_request(&Request::Register {
+                plugin: PluginInfo {
+                    name: "resilient".to_string(),
+                    version: "1.0.0".to_string(),
+                    description: None,
+                    config: None,
+                    registered_at: None,
+                    heartbeat_interval: None,
+                    dependencies: vec![],
+                },
+                force: false,
+                ignore_dependencies: false,
+            })
+            .await
+            .unwrap();
+        client
+            .subscribe(vec!["first.event".to_string(), "second.event".to_string()])
             .await
             .unwrap();
 
-        match response {
-            Response::NotFound { .. } => {}
-            _ => panic!("Expected not found response"),
-        }
+        let event = client.read_event().await.unwrap().unwrap();
+        assert_eq!(event.topic, "first.event");
+        first.await.unwrap();
+
+        // Restart the daemon after the client has already noticed it's gone
+        let restarted = tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+            mock_restarting_daemon(socket_path_str, "second.event").await
+        });
+
+        let event = client.read_event().await.unwrap().unwrap();
+        assert_eq!(event.topic, "second.event");
+
+        let (registered, subscribed) = restarted.await.unwrap();
+        assert_eq!(registered.as_deref(), Some("resilient"));
+        assert_eq!(subscribed, vec!["first.event", "second.event"]);
     }
 
     #[tokio::test]
-    async fn test_register_plugin() {
+    async fn test_event_stream_ends_on_eof() {
         let temp_dir = TempDir::new().unwrap();
         let socket_path = temp_dir.path().join(format!(
             "test_{}.sock",
             COUNTER.fetch_add(1, Ordering::SeqCst)
         ));
-        let socket_path_str = socket_path.to_str().unwrap();
 
-        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::spawn(mock_event_daemon(
+            socket_path.to_str().unwrap().to_string(),
+            vec!["first.event", "second.event", "third.event"],
+        ));
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        let plugin = PluginInfo {
-            name: "test-plugin".to_string(),
-            version: "1.0.0".to_string(),
-            description: Some("Test plugin".to_string()),
-            config: Some(HashMap::new()),
-            registered_at: None,
-        };
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        client.subscribe(vec!["#".to_string()]).await.unwrap();
 
-        let request = Request::Register { plugin };
-        let response = DaemonClient::send_request(&socket_path, &request)
+        let topics: Vec<String> = client
+            .event_stream()
+            .map(|event| event.unwrap().topic)
+            .collect()
+            .await;
+        assert_eq!(topics, vec!["first.event", "second.event", "third.event"]);
+    }
+
+    #[tokio::test]
+    async fn test_background_reader_feeds_event_receiver() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        tokio::spawn(mock_event_daemon(
+            socket_path.to_str().unwrap().to_string(),
+            vec!["first.event", "second.event", "third.event"],
+        ));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        client.subscribe(vec!["#".to_string()]).await.unwrap();
+        let mut events = client.into_background();
+
+        assert_eq!(events.recv_event().await.unwrap().topic, "first.event");
+        // The rest arrive without anyone waiting on the connection
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        assert_eq!(events.try_recv_event().unwrap().topic, "second.event");
+        assert_eq!(events.try_recv_event().unwrap().topic, "third.event");
+        assert!(events.try_recv_event().is_none());
+        assert!(events.recv_event().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_and_publish_send_requests() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(mock_recording_daemon(
+            socket_path.to_str().unwrap().to_string(),
+            tx,
+        ));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        assert!(matches!(rx.recv().await, Some(Request::Hello { .. })));
+
+        client
+            .unsubscribe(vec!["custom.event".to_string()])
             .await
             .unwrap();
+        match rx.recv().await {
+            Some(Request::Unsubscribe { topics }) => assert_eq!(topics, vec!["custom.event"]),
+            other => panic!("unexpected request: {:?}", other),
+        }
 
-        match response {
-            Response::Success { .. } => {}
-            _ => panic!("Expected success response"),
+        client
+            .publish("custom.event", serde_json::json!({"value": 1}))
+            .await
+            .unwrap();
+        match rx.recv().await {
+            Some(Request::Publish {
+                topic,
+                data,
+                retain,
+            }) => {
+                assert_eq!(topic, "custom.event");
+                assert_eq!(data, serde_json::json!({"value": 1}));
+                assert!(!retain);
+            }
+            other => panic!("unexpected request: {:?}", other),
         }
     }
 
     #[tokio::test]
-    async fn test_get_health() {
+    async fn test_subscribe_transient_registers_unique_names() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        tokio::spawn(mock_recording_daemon(
+            socket_path.to_str().unwrap().to_string(),
+            tx,
+        ));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let mut client = DaemonClient::connect(&socket_path).await.unwrap();
+        assert!(matches!(rx.recv().await, Some(Request::Hello { .. })));
+
+        let mut names = Vec::new();
+        for _ in 0..2 {
+            client
+                .subscribe_transient("test-watch", "Test watcher", vec!["#".to_string()])
+                .await
+                .unwrap();
+            match rx.recv().await {
+                Some(Request::Register { plugin, force, .. }) => {
+                    assert!(plugin
+                        .name
+                        .starts_with(&format!("test-watch-{}-", std::process::id())));
+                    assert_eq!(plugin.description.as_deref(), Some("Test watcher"));
+                    assert!(!force);
+                    names.push(plugin.name);
+                }
+                other => panic!("unexpected request: {:?}", other),
+            }
+            match rx.recv().await {
+                Some(Request::Subscribe { topics }) => assert_eq!(topics, vec!["#"]),
+                other => panic!("unexpected request: {:?}", other),
+            }
+        }
+        assert_ne!(names[0], names[1]);
+    }
+
+    #[tokio::test]
+    async fn test_connect_negotiates_version() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_hello_server(
+            socket_path_str.to_string(),
+            Response::success_with_data(
+                serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+            ),
+        ));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        assert!(DaemonClient::connect(&socket_path).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_version_mismatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_hello_server(
+            socket_path_str.to_string(),
+            Response::IncompatibleVersion {
+                server: PROTOCOL_VERSION + 1,
+                min_supported: PROTOCOL_VERSION + 1,
+            },
+        ));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let error = match DaemonClient::connect(&socket_path).await {
+            Ok(_) => panic!("Expected version mismatch error"),
+            Err(e) => e,
+        };
+        assert_eq!(
+            error.downcast_ref::<ClientError>(),
+            Some(&ClientError::IncompatibleVersion {
+                server: PROTOCOL_VERSION + 1,
+                min_supported: PROTOCOL_VERSION + 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_request_timeout_fires() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        // Accept the connection and read the request, but never answer
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            std::future::pending::<()>().await;
+        });
+
+        let timeout = std::time::Duration::from_millis(100);
+        let error =
+            DaemonClient::send_request_timeout(&socket_path, &Request::ListPlugins, timeout)
+                .await
+                .unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<ClientError>(),
+            Some(&ClientError::Timeout(timeout))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_plugins() {
         let temp_dir = TempDir::new().unwrap();
         let socket_path = temp_dir.path().join(format!(
             "test_{}.sock",
@@ -190,13 +763,123 @@ mod client_tests {
         tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
         tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
 
-        let request = Request::GetHealth;
+        let request = Request::ListPlugins;
         let response = DaemonClient::send_request(&socket_path, &request)
             .await
             .unwrap();
 
         match response {
-            Response::Success { data } => {
+            Response::Success { data } => assert!(data.is_some()),
+            _ => panic!("Expected success response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_existing_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let request = Request::GetPlugin {
+            name: "test-plugin".to_string(),
+        };
+        let response = DaemonClient::send_request(&socket_path, &request)
+            .await
+            .unwrap();
+
+        match response {
+            Response::Success { data } => assert!(data.is_some()),
+            _ => panic!("Expected success response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_nonexistent_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let request = Request::GetPlugin {
+            name: "nonexistent".to_string(),
+        };
+        let response = DaemonClient::send_request(&socket_path, &request)
+            .await
+            .unwrap();
+
+        match response {
+            Response::NotFound { .. } => {}
+            _ => panic!("Expected not found response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_plugin() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let plugin = PluginInfo {
+            name: "test-plugin".to_string(),
+            version: "1.0.0".to_string(),
+            description: Some("Test plugin".to_string()),
+            config: Some(HashMap::new()),
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        };
+
+        let request = Request::Register {
+            plugin,
+            force: false,
+            ignore_dependencies: false,
+        };
+        let response = DaemonClient::send_request(&socket_path, &request)
+            .await
+            .unwrap();
+
+        match response {
+            Response::Success { .. } => {}
+            _ => panic!("Expected success response"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_health() {
+        let temp_dir = TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join(format!(
+            "test_{}.sock",
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let socket_path_str = socket_path.to_str().unwrap();
+
+        tokio::spawn(mock_daemon_server(socket_path_str.to_string()));
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        let request = Request::GetHealth;
+        let response = DaemonClient::send_request(&socket_path, &request)
+            .await
+            .unwrap();
+
+        match response {
+            Response::Success { data } => {
                 assert!(data.is_some());
                 let health_data = data.unwrap();
                 assert!(health_data["active_plugins"].is_number());
@@ -207,3 +890,1544 @@ mod client_tests {
         }
     }
 }
+
+#[cfg(test)]
+mod logging_tests {
+    use crate::logging::{subscriber, LogFormat, RotatingFile, Rotation};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::filter::Targets;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_format_emits_parseable_lines() {
+        let buffer = Buffer::default();
+        let filter = Targets::new().with_default(tracing::Level::INFO);
+        let subscriber = subscriber(LogFormat::Json, filter, buffer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("connection", connection_id = "conn_1");
+            let _guard = span.enter();
+            tracing::info!(plugin = "hello", "Registering plugin");
+            tracing::warn!(attempt = 2, "Retrying \"quoted\"\nmessage");
+            tracing::debug!("Filtered out");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["message"], "Registering plugin");
+        assert_eq!(lines[0]["plugin"], "hello");
+        assert_eq!(lines[0]["span"]["connection_id"], "conn_1");
+        assert_eq!(lines[0]["spans"][0]["name"], "connection");
+        assert!(lines[0]["timestamp"].is_string());
+
+        assert_eq!(lines[1]["level"], "WARN");
+        assert_eq!(lines[1]["attempt"], 2);
+        assert_eq!(lines[1]["message"], "Retrying \"quoted\"\nmessage");
+    }
+
+    fn read(path: &std::path::Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rotating_file_rolls_over_by_size() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = RotatingFile::new(
+            temp_dir.path().join("audit.log"),
+            Some(Rotation {
+                max_bytes: 20,
+                max_files: 2,
+            }),
+        );
+
+        // 7 bytes each, so two fit per file
+        for i in 0..7 {
+            file.append(format!("line-{}\n", i).as_bytes())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(read(file.path()), "line-6\n");
+        assert_eq!(file.rotated_path(1), temp_dir.path().join("audit.log.1"));
+        assert_eq!(read(&file.rotated_path(1)), "line-4\nline-5\n");
+        assert_eq!(read(&file.rotated_path(2)), "line-2\nline-3\n");
+        assert!(!file.rotated_path(3).exists());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_appends_are_never_split() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = Arc::new(RotatingFile::new(
+            temp_dir.path().join("audit.log"),
+            Some(Rotation {
+                max_bytes: 100,
+                max_files: 100,
+            }),
+        ));
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for i in 0..50 {
+            let file = file.clone();
+            tasks.spawn(async move { file.append(format!("task-{:02}\n", i).as_bytes()).await });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.unwrap().unwrap();
+        }
+
+        let mut files = vec![file.path().to_path_buf()];
+        files.extend(
+            (1..=100)
+                .map(|i| file.rotated_path(i))
+                .filter(|p| p.exists()),
+        );
+        assert!(files.len() > 1);
+
+        let mut lines = Vec::new();
+        for path in &files {
+            let contents = read(path);
+            assert!(
+                contents.len() <= 100,
+                "{} is {} bytes",
+                path.display(),
+                contents.len()
+            );
+            lines.extend(contents.lines().map(str::to_string));
+        }
+        lines.sort();
+        let expected: Vec<String> = (0..50).map(|i| format!("task-{:02}", i)).collect();
+        assert_eq!(lines, expected);
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use crate::registry::{
+        ChecksumAlgorithm, InfectionManifest, Platform, RegistryClient, VerificationError,
+    };
+    use crate::testing::MockServer;
+    use base64::Engine;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, Ed25519KeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn manifest(base_url: &str, binary: &[u8]) -> InfectionManifest {
+        InfectionManifest {
+            name: "hello-infection".to_string(),
+            version: "1.0.0".to_string(),
+            description: "Test infection".to_string(),
+            author: "tester".to_string(),
+            homepage: None,
+            license: None,
+            keywords: vec![],
+            dependencies: vec![],
+            platforms: vec![Platform {
+                os: std::env::consts::OS.to_string(),
+                arch: std::env::consts::ARCH.to_string(),
+                binary_url: format!("{}/hello-infection", base_url),
+                checksum: sha256::digest(binary),
+                checksum_algo: None,
+                signature_url: Some(format!("{}/hello-infection.sig", base_url)),
+                signature_algo: None,
+            }],
+            versions: vec![],
+        }
+    }
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn encode_signature(key_pair: &Ed25519KeyPair, data: &[u8]) -> Vec<u8> {
+        base64::engine::general_purpose::STANDARD
+            .encode(key_pair.sign(data))
+            .into_bytes()
+    }
+
+    /// Serve `binary` and `signature`, then download with a client trusting `key_pair`
+    async fn download(
+        key_pair: &Ed25519KeyPair,
+        binary: &[u8],
+        signature: Vec<u8>,
+    ) -> (anyhow::Result<()>, TempDir) {
+        download_with(key_pair.public_key().as_ref(), binary, signature, |_| {}).await
+    }
+
+    /// Like `download`, trusting `public_key` and letting `adjust` edit the
+    /// platform entry first
+    async fn download_with(
+        public_key: &[u8],
+        binary: &[u8],
+        signature: Vec<u8>,
+        adjust: impl FnOnce(&mut Platform),
+    ) -> (anyhow::Result<()>, TempDir) {
+        let mut routes = HashMap::new();
+        routes.insert("/hello-infection".to_string(), binary.to_vec());
+        routes.insert("/hello-infection.sig".to_string(), signature);
+        let base_url = MockServer::bind().serve_routes(routes);
+
+        let mut manifest = manifest(&base_url, binary);
+        adjust(&mut manifest.platforms[0]);
+
+        let temp_dir = TempDir::new().unwrap();
+        let target_path = temp_dir.path().join("hello-infection");
+        let client = RegistryClient::with_registry_url(base_url.clone())
+            .with_trusted_key(public_key.to_vec());
+        let result = client
+            .download_infection(&manifest, target_path.to_str().unwrap())
+            .await;
+        (result, temp_dir)
+    }
+
+    fn verification_error(result: anyhow::Result<()>) -> VerificationError {
+        result
+            .unwrap_err()
+            .downcast_ref::<VerificationError>()
+            .cloned()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_download_ed25519_signed_sha512_artifact() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        let (result, temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| {
+                platform.checksum = ChecksumAlgorithm::Sha512.hex_digest(binary);
+                platform.checksum_algo = Some("sha512".to_string());
+                platform.signature_algo = Some("ed25519".to_string());
+            },
+        )
+        .await;
+
+        result.unwrap();
+        let target_path = temp_dir.path().join("hello-infection");
+        assert_eq!(std::fs::read(target_path).unwrap(), binary);
+    }
+
+    #[tokio::test]
+    async fn test_download_ecdsa_p256_signature() {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+        let key_pair =
+            EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng)
+                .unwrap();
+        let binary = b"#!/bin/sh\necho hello\n";
+        let signature = base64::engine::general_purpose::STANDARD
+            .encode(key_pair.sign(&rng, binary).unwrap())
+            .into_bytes();
+
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            signature.clone(),
+            |platform| platform.signature_algo = Some("ecdsa-p256".to_string()),
+        )
+        .await;
+        result.unwrap();
+
+        // The same signature doesn't pass as ed25519
+        let (result, _temp_dir) =
+            download_with(key_pair.public_key().as_ref(), binary, signature, |_| {}).await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::SignatureMismatch("hello-infection".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_checksum_must_match_declared_algorithm() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        // A sha256 digest declared as sha512
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| platform.checksum_algo = Some("sha512".to_string()),
+        )
+        .await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::MalformedChecksum("hello-infection".to_string())
+        );
+    }
+
+    #[test]
+    fn test_checksum_normalization() {
+        let digest = sha256::digest(b"hello");
+        let sha256 = ChecksumAlgorithm::Sha256;
+
+        assert_eq!(sha256.normalize(&digest), Some(digest.clone()));
+        assert_eq!(
+            sha256.normalize(&format!("sha256:{}", digest)),
+            Some(digest.clone())
+        );
+        assert_eq!(
+            sha256.normalize(&format!("  SHA256:{}\n", digest.to_uppercase())),
+            Some(digest.clone())
+        );
+
+        // Wrong algorithm prefix, wrong length, non-hex, empty
+        assert_eq!(sha256.normalize(&format!("sha512:{}", digest)), None);
+        assert_eq!(sha256.normalize(&digest[1..]), None);
+        assert_eq!(sha256.normalize(&format!("{}zz", &digest[2..])), None);
+        assert_eq!(sha256.normalize("sha256:"), None);
+        assert_eq!(sha256.normalize(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_uppercase_checksum_downloads() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        let (result, temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| {
+                platform.checksum = format!(" sha256:{}\n", platform.checksum.to_uppercase())
+            },
+        )
+        .await;
+        result.unwrap();
+        assert!(temp_dir.path().join("hello-infection").exists());
+
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| platform.checksum = "sha256:not-a-digest".to_string(),
+        )
+        .await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::MalformedChecksum("hello-infection".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_algorithms_are_rejected() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| platform.checksum_algo = Some("md5".to_string()),
+        )
+        .await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::UnsupportedAlgorithm("md5".to_string())
+        );
+
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| platform.signature_algo = Some("rsa".to_string()),
+        )
+        .await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::UnsupportedAlgorithm("rsa".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_downloads_are_not_verified() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        // A 404 body is an HTTP error, not a binary that failed its checksum
+        let (result, temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| platform.binary_url.push_str("-missing"),
+        )
+        .await;
+        let error = result.unwrap_err();
+        assert!(
+            error.downcast_ref::<VerificationError>().is_none(),
+            "{}",
+            error
+        );
+        assert!(!temp_dir.path().join("hello-infection").exists());
+
+        let (result, _temp_dir) = download_with(
+            key_pair.public_key().as_ref(),
+            binary,
+            encode_signature(&key_pair, binary),
+            |platform| {
+                let url = platform.signature_url.as_mut().unwrap();
+                url.push_str("-missing");
+            },
+        )
+        .await;
+        assert_eq!(
+            verification_error(result),
+            VerificationError::MissingSignature("hello-infection".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_with_valid_signature() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+
+        let (result, temp_dir) =
+            download(&key_pair, binary, encode_signature(&key_pair, binary)).await;
+
+        result.unwrap();
+        let target_path = temp_dir.path().join("hello-infection");
+        assert_eq!(std::fs::read(target_path).unwrap(), binary);
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_tampered_binary() {
+        let key_pair = key_pair();
+        let signature = encode_signature(&key_pair, b"#!/bin/sh\necho hello\n");
+
+        // A compromised registry can serve a matching checksum, but not a signature
+        let (result, temp_dir) = download(&key_pair, b"#!/bin/sh\necho pwned\n", signature).await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<VerificationError>(),
+            Some(&VerificationError::SignatureMismatch(
+                "hello-infection".to_string()
+            ))
+        );
+        assert!(!temp_dir.path().join("hello-infection").exists());
+    }
+
+    #[tokio::test]
+    async fn test_download_rejects_tampered_signature() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+        let signature = encode_signature(&key_pair, b"something else");
+
+        let (result, temp_dir) = download(&key_pair, binary, signature).await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<VerificationError>(),
+            Some(&VerificationError::SignatureMismatch(
+                "hello-infection".to_string()
+            ))
+        );
+        assert!(!temp_dir.path().join("hello-infection").exists());
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_is_distinct_from_signature_mismatch() {
+        let key_pair = key_pair();
+        let binary = b"#!/bin/sh\necho hello\n";
+        let mut routes = HashMap::new();
+        routes.insert("/hello-infection".to_string(), b"truncated".to_vec());
+        routes.insert(
+            "/hello-infection.sig".to_string(),
+            encode_signature(&key_pair, binary),
+        );
+        let base_url = MockServer::bind().serve_routes(routes);
+
+        let temp_dir = TempDir::new().unwrap();
+        let target_path = temp_dir.path().join("hello-infection");
+        let client = RegistryClient::with_registry_url(base_url.clone())
+            .with_trusted_key(key_pair.public_key().as_ref().to_vec());
+        let error = client
+            .download_infection(&manifest(&base_url, binary), target_path.to_str().unwrap())
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref::<VerificationError>(),
+            Some(&VerificationError::ChecksumMismatch(
+                "hello-infection".to_string()
+            ))
+        );
+    }
+}
+
+#[cfg(test)]
+mod registry_cache_tests {
+    use crate::registry::RegistryClient;
+    use crate::testing::{MockResponse, MockServer};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    const ETAG: &str = "\"v1\"";
+
+    /// Serve `index.json` with an ETag, answering 304 to matching conditional
+    /// requests. Returns the base URL and the number of 304s sent.
+    fn serve_index() -> (String, Arc<AtomicUsize>) {
+        let server = MockServer::bind();
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let index = serde_json::json!({
+            "name": "mock",
+            "description": "Mock registry",
+            "infections": {
+                "hello-infection": {
+                    "name": "hello-infection",
+                    "latest_version": "1.0.0",
+                    "type_": "binary",
+                    "description": "A simple hello world infection plugin",
+                    "manifest_url": format!("{}/hello-infection.json", server.url()),
+                }
+            }
+        })
+        .to_string();
+
+        let counter = Arc::clone(&not_modified);
+        let base_url = server.serve(move |request| {
+            if request.header("If-None-Match") == Some(ETAG) {
+                counter.fetch_add(1, Ordering::SeqCst);
+                MockResponse::new("304 Not Modified")
+            } else {
+                MockResponse::ok(index.clone()).with_header("ETag", ETAG)
+            }
+        });
+
+        (base_url, not_modified)
+    }
+
+    #[tokio::test]
+    async fn test_not_modified_uses_cached_index() {
+        let (base_url, not_modified) = serve_index();
+        let cache_dir = TempDir::new().unwrap();
+        let client = RegistryClient::with_registry_url(base_url)
+            .with_cache_dir(cache_dir.path().to_path_buf());
+
+        let first = client.search_infections("hello").await.unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(not_modified.load(Ordering::SeqCst), 0);
+
+        let second = client.search_infections("hello").await.unwrap();
+        assert_eq!(not_modified.load(Ordering::SeqCst), 1);
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].name, "hello-infection");
+        assert_eq!(second[0].latest_version, "1.0.0");
+    }
+
+    #[tokio::test]
+    async fn test_without_cache_skips_conditional_requests() {
+        let (base_url, not_modified) = serve_index();
+        let cache_dir = TempDir::new().unwrap();
+        let client = RegistryClient::with_registry_url(base_url)
+            .with_cache_dir(cache_dir.path().to_path_buf())
+            .without_cache();
+
+        client.search_infections("hello").await.unwrap();
+        client.search_infections("hello").await.unwrap();
+
+        assert_eq!(not_modified.load(Ordering::SeqCst), 0);
+        assert_eq!(std::fs::read_dir(cache_dir.path()).unwrap().count(), 0);
+    }
+}
+
+#[cfg(test)]
+mod registry_version_tests {
+    use crate::registry::{InfectionManifest, ManifestVersion, Platform};
+    use semver::VersionReq;
+
+    fn platform(version: &str) -> Platform {
+        Platform {
+            os: "linux".to_string(),
+            arch: "x86_64".to_string(),
+            binary_url: format!("https://example.com/{}/hello-infection", version),
+            checksum: "abc123".to_string(),
+            checksum_algo: None,
+            signature_url: None,
+            signature_algo: None,
+        }
+    }
+
+    fn manifest() -> InfectionManifest {
+        let release = |version: &str| ManifestVersion {
+            version: version.to_string(),
+            platforms: vec![platform(version)],
+        };
+
+        InfectionManifest {
+            name: "hello-infection".to_string(),
+            version: "2.0.0".to_string(),
+            description: "Test infection".to_string(),
+            author: "tester".to_string(),
+            homepage: None,
+            license: None,
+            keywords: vec![],
+            dependencies: vec![],
+            platforms: vec![platform("2.0.0")],
+            versions: vec![
+                release("1.0.0"),
+                release("1.2.0"),
+                release("1.3.1"),
+                release("1.10.0-beta.1"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_caret_requirement_picks_highest_compatible() {
+        let resolved = manifest()
+            .resolve_version(&VersionReq::parse("^1.2").unwrap())
+            .unwrap();
+
+        assert_eq!(resolved.version, "1.3.1");
+        assert_eq!(
+            resolved.platforms[0].binary_url,
+            "https://example.com/1.3.1/hello-infection"
+        );
+    }
+
+    #[test]
+    fn test_exact_requirement_pins_release() {
+        let resolved = manifest()
+            .resolve_version(&VersionReq::parse("=1.0.0").unwrap())
+            .unwrap();
+
+        assert_eq!(resolved.version, "1.0.0");
+        assert_eq!(
+            resolved.platforms[0].binary_url,
+            "https://example.com/1.0.0/hello-infection"
+        );
+    }
+
+    #[test]
+    fn test_latest_version_is_a_candidate() {
+        let resolved = manifest()
+            .resolve_version(&VersionReq::parse(">=1.0").unwrap())
+            .unwrap();
+
+        assert_eq!(resolved.version, "2.0.0");
+    }
+
+    #[test]
+    fn test_unsatisfiable_requirement_errors() {
+        let error = manifest()
+            .resolve_version(&VersionReq::parse("^3").unwrap())
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("No version of 'hello-infection' satisfies '^3'"));
+        assert!(error.contains("2.0.0, 1.0.0, 1.2.0, 1.3.1"), "{}", error);
+    }
+
+    #[test]
+    fn test_manifest_without_versions_deserializes() {
+        let manifest: InfectionManifest = serde_json::from_value(serde_json::json!({
+            "name": "hello-infection",
+            "version": "1.0.0",
+            "description": "Test infection",
+            "author": "tester",
+            "homepage": null,
+            "license": null,
+            "keywords": [],
+            "dependencies": [],
+            "platforms": []
+        }))
+        .unwrap();
+
+        assert!(manifest.versions.is_empty());
+        assert!(manifest
+            .resolve_version(&VersionReq::parse("=1.0.0").unwrap())
+            .is_ok());
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use crate::config::{merge_json, parse_toml_with_secrets, ConfigManager, FileConfigManager};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_secret_references_are_resolved() {
+        let temp_dir = TempDir::new().unwrap();
+        let secret_file = temp_dir.path().join("password");
+        std::fs::write(&secret_file, "from-file\n").unwrap();
+        std::env::set_var("PANDEMIC_TEST_CONFIG_SECRET", "from-env");
+
+        let config: HashMap<String, String> = parse_toml_with_secrets(&format!(
+            r#"
+env = "${{env:PANDEMIC_TEST_CONFIG_SECRET}}"
+file = "${{file:{}}}"
+literal = "plain ${{value}}"
+"#,
+            secret_file.display()
+        ))
+        .unwrap();
+        assert_eq!(config["env"], "from-env");
+        assert_eq!(config["file"], "from-file");
+        assert_eq!(config["literal"], "plain ${value}");
+
+        let missing = parse_toml_with_secrets::<HashMap<String, String>>(
+            r#"key = "${env:PANDEMIC_TEST_CONFIG_UNSET}""#,
+        );
+        assert!(missing.is_err());
+    }
+
+    fn manager() -> (FileConfigManager, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = FileConfigManager::new(
+            temp_dir.path().join("defaults"),
+            temp_dir.path().join("overrides"),
+        );
+        std::fs::create_dir_all(manager.defaults_dir()).unwrap();
+        std::fs::create_dir_all(manager.overrides_dir()).unwrap();
+        (manager, temp_dir)
+    }
+
+    #[test]
+    fn test_merge_json_recurses_into_objects() {
+        let mut base = json!({ "server": { "port": 8080, "host": "0.0.0.0" }, "tags": ["a"] });
+        merge_json(
+            &mut base,
+            json!({ "server": { "port": 9090 }, "tags": ["b"] }),
+        );
+
+        assert_eq!(
+            base,
+            json!({ "server": { "port": 9090, "host": "0.0.0.0" }, "tags": ["b"] })
+        );
+    }
+
+    #[test]
+    fn test_yaml_defaults_with_toml_override() {
+        let (manager, _temp_dir) = manager();
+        std::fs::write(
+            manager.defaults_dir().join("hello.yaml"),
+            "server:\n  port: 8080\n  host: 0.0.0.0\nenabled: true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            manager.overrides_dir().join("hello.toml"),
+            "[server]\nport = 9090\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            manager.get_config("hello").unwrap(),
+            json!({ "server": { "port": 9090, "host": "0.0.0.0" }, "enabled": true })
+        );
+    }
+
+    #[test]
+    fn test_toml_defaults_with_yaml_override() {
+        let (manager, _temp_dir) = manager();
+        std::fs::write(
+            manager.defaults_dir().join("hello.toml"),
+            "enabled = true\n\n[server]\nport = 8080\nhost = \"0.0.0.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            manager.overrides_dir().join("hello.yml"),
+            "server:\n  host: 127.0.0.1\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            manager.get_config("hello").unwrap(),
+            json!({ "server": { "port": 8080, "host": "127.0.0.1" }, "enabled": true })
+        );
+    }
+
+    #[test]
+    fn test_json_override_and_toml_preferred() {
+        let (manager, _temp_dir) = manager();
+        std::fs::write(
+            manager.defaults_dir().join("hello.toml"),
+            "level = \"info\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            manager.defaults_dir().join("hello.json"),
+            r#"{"level": "trace"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            manager.overrides_dir().join("hello.json"),
+            r#"{"extra": 1}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manager.get_config("hello").unwrap(),
+            json!({ "level": "info", "extra": 1 })
+        );
+    }
+
+    #[test]
+    fn test_missing_files_yield_empty_config() {
+        let (manager, _temp_dir) = manager();
+
+        assert_eq!(manager.get_config("missing").unwrap(), json!({}));
+    }
+
+    #[test]
+    fn test_set_override_keeps_existing_format() {
+        let (manager, _temp_dir) = manager();
+        std::fs::write(
+            manager.defaults_dir().join("hello.toml"),
+            "level = \"info\"\n",
+        )
+        .unwrap();
+        std::fs::write(manager.overrides_dir().join("hello.yaml"), "level: debug\n").unwrap();
+
+        manager
+            .set_override("hello", &json!({ "level": "warn" }))
+            .unwrap();
+
+        assert!(!manager.overrides_dir().join("hello.toml").exists());
+        assert_eq!(
+            manager.get_config("hello").unwrap(),
+            json!({ "level": "warn" })
+        );
+
+        manager.clear_override("hello").unwrap();
+        assert!(!manager.overrides_dir().join("hello.yaml").exists());
+        assert_eq!(
+            manager.get_config("hello").unwrap(),
+            json!({ "level": "info" })
+        );
+    }
+}
+
+#[cfg(test)]
+mod config_watch_tests {
+    use crate::config::{ConfigManager, FileConfigManager};
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_watch_yields_merged_config_on_override_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = FileConfigManager::new(
+            temp_dir.path().join("defaults"),
+            temp_dir.path().join("overrides"),
+        );
+        std::fs::create_dir_all(manager.defaults_dir()).unwrap();
+        std::fs::write(
+            manager.defaults_dir().join("hello.toml"),
+            "level = \"info\"\nport = 8080\n",
+        )
+        .unwrap();
+
+        let stream = manager.watch("hello").unwrap();
+        tokio::pin!(stream);
+
+        // Several quick writes collapse into one update with the final content
+        std::fs::write(
+            manager.overrides_dir().join("hello.toml"),
+            "level = \"warn\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            manager.overrides_dir().join("hello.toml"),
+            "level = \"debug\"\n",
+        )
+        .unwrap();
+        // Other plugins' files are ignored
+        std::fs::write(
+            manager.overrides_dir().join("other.toml"),
+            "level = \"error\"\n",
+        )
+        .unwrap();
+
+        let config = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config, json!({ "level": "debug", "port": 8080 }));
+        assert_eq!(manager.get_config("hello").unwrap(), config);
+
+        manager.clear_override("hello").unwrap();
+        let config = tokio::time::timeout(Duration::from_secs(5), stream.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(config, json!({ "level": "info", "port": 8080 }));
+    }
+}
+
+#[cfg(test)]
+mod init_tests {
+    use crate::init::{
+        expand_variables, glob_matches, validate_overrides, InitBackend, InitSystem, LogEntry,
+        OpenRcBackend, ServiceAction, ServiceDefinition, ServiceSummary, SystemdBackend,
+    };
+    use pandemic_protocol::ServiceOverrides;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn overrides() -> ServiceOverrides {
+        ServiceOverrides {
+            environment: None,
+            exec_start: None,
+            restart: None,
+            user: None,
+            group: None,
+        }
+    }
+
+    fn args(command: &std::process::Command) -> Vec<String> {
+        std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_detects_init_system_from_run_dirs() {
+        let root = TempDir::new().unwrap();
+        assert_eq!(InitSystem::detect_in(root.path()), InitSystem::Systemd);
+
+        std::fs::create_dir_all(root.path().join("run/openrc")).unwrap();
+        assert_eq!(InitSystem::detect_in(root.path()), InitSystem::OpenRc);
+
+        std::fs::create_dir_all(root.path().join("run/systemd/system")).unwrap();
+        assert_eq!(InitSystem::detect_in(root.path()), InitSystem::Systemd);
+
+        assert_eq!(InitSystem::OpenRc.backend().name(), "openrc");
+        assert_eq!(InitSystem::Systemd.backend().name(), "systemd");
+    }
+
+    #[test]
+    fn test_service_action_parsing() {
+        assert_eq!(
+            "restart".parse::<ServiceAction>().unwrap(),
+            ServiceAction::Restart
+        );
+        assert!("reload".parse::<ServiceAction>().is_err());
+    }
+
+    #[test]
+    fn test_openrc_commands() {
+        let backend = OpenRcBackend::default();
+        assert_eq!(
+            args(&backend.command(ServiceAction::Start, "pandemic-udp")),
+            ["rc-service", "pandemic-udp", "start"]
+        );
+        assert_eq!(
+            args(&backend.command(ServiceAction::Status, "pandemic-udp")),
+            ["rc-service", "pandemic-udp", "status"]
+        );
+        assert_eq!(
+            args(&backend.command(ServiceAction::Enable, "pandemic-udp")),
+            ["rc-update", "add", "pandemic-udp", "default"]
+        );
+        assert_eq!(
+            args(&backend.command(ServiceAction::Disable, "pandemic-udp")),
+            ["rc-update", "del", "pandemic-udp", "default"]
+        );
+
+        let systemd = SystemdBackend::default();
+        assert_eq!(
+            args(&systemd.command(ServiceAction::Restart, "pandemic")),
+            ["systemctl", "restart", "pandemic"]
+        );
+    }
+
+    #[test]
+    fn test_openrc_init_script() {
+        let script = OpenRcBackend::init_script(&ServiceDefinition {
+            description: "Pandemic Infection: udp".to_string(),
+            exec_start: "/usr/local/bin/pandemic-udp --port 8080".to_string(),
+            user: "pandemic".to_string(),
+            group: "pandemic".to_string(),
+            requires: vec!["pandemic".to_string()],
+            runtime_directory: Some("pandemic".to_string()),
+            notify: false,
+        });
+
+        assert!(script.starts_with("#!/sbin/openrc-run\n"));
+        assert!(script.contains("command='/usr/local/bin/pandemic-udp'\n"));
+        assert!(script.contains("command_args='--port 8080'\n"));
+        assert!(script.contains("command_user='pandemic:pandemic'\n"));
+        assert!(script.contains("need net pandemic\n"));
+        assert!(script.contains("checkpath -d -m 0755 -o pandemic:pandemic /run/pandemic"));
+    }
+
+    #[test]
+    fn test_openrc_overrides_round_trip() {
+        let etc = TempDir::new().unwrap();
+        let backend = OpenRcBackend::new(etc.path());
+        let overrides = ServiceOverrides {
+            environment: Some(HashMap::from([(
+                "GREETING".to_string(),
+                "it's $HOME".to_string(),
+            )])),
+            exec_start: Some("/usr/local/bin/hello --port 8080".to_string()),
+            restart: Some("always".to_string()),
+            user: Some("pandemic".to_string()),
+            group: Some("1000".to_string()),
+        };
+
+        assert!(backend.get_override("hello").unwrap().is_none());
+        backend.set_override("hello", &overrides).unwrap();
+        let content = std::fs::read_to_string(etc.path().join("conf.d/hello")).unwrap();
+        assert!(content.contains("export GREETING='it'\\''s $HOME'\n"));
+
+        let read = backend.get_override("hello").unwrap().unwrap();
+        assert_eq!(read.environment, overrides.environment);
+        assert_eq!(read.exec_start, overrides.exec_start);
+        assert_eq!(read.user, overrides.user);
+        assert_eq!(read.group, overrides.group);
+
+        backend.reset_override("hello").unwrap();
+        assert!(backend.get_override("hello").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_openrc_rejects_unsupported_overrides() {
+        let restart = ServiceOverrides {
+            restart: Some("on-failure".to_string()),
+            ..overrides()
+        };
+        assert!(OpenRcBackend::conf_file(&restart).is_err());
+
+        let group_only = ServiceOverrides {
+            group: Some("pandemic".to_string()),
+            ..overrides()
+        };
+        assert!(OpenRcBackend::conf_file(&group_only).is_err());
+    }
+
+    #[test]
+    fn test_parse_systemd_unit_list() {
+        let services =
+            SystemdBackend::parse_units(include_str!("../testdata/systemctl-list-units.json"))
+                .unwrap();
+        assert_eq!(services.len(), 3);
+        assert_eq!(
+            services[1],
+            ServiceSummary {
+                name: "pandemic-udp.service".to_string(),
+                description: "Pandemic Infection: udp".to_string(),
+                status: "failed".to_string(),
+                load_state: Some("loaded".to_string()),
+                sub_state: Some("failed".to_string()),
+            }
+        );
+        assert_eq!(services[0].sub_state.as_deref(), Some("running"));
+        assert_eq!(services[2].description, "Pandemic Daemon");
+
+        assert!(SystemdBackend::parse_units("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_journal_entries() {
+        let entries =
+            SystemdBackend::parse_journal(include_str!("../testdata/journalctl-output.json"))
+                .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0],
+            LogEntry {
+                timestamp_us: 1_760_600_000_000_000,
+                priority: Some(6),
+                pid: Some(4242),
+                message: "Listening on 0.0.0.0:9000".to_string(),
+            }
+        );
+        // Non-UTF-8 messages arrive as byte arrays
+        assert_eq!(entries[1].message, "bad \u{FFFD} packet");
+        assert_eq!(entries[1].priority, Some(3));
+        assert_eq!(entries[2].pid, None);
+
+        assert!(SystemdBackend::parse_journal("").unwrap().is_empty());
+        assert!(SystemdBackend::parse_journal("not json").is_err());
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(glob_matches("pandemic-udp*", "pandemic-udp.service"));
+        assert!(glob_matches("pandemic-???.service", "pandemic-udp.service"));
+        assert!(glob_matches("*", "pandemic"));
+        assert!(!glob_matches("pandemic-udp*", "pandemic-rest.service"));
+        assert!(!glob_matches("pandemic", "pandemic.service"));
+        assert!(glob_matches("*udp*", "pandemic-udp.service"));
+        assert!(glob_matches("pandemic-*-*", "pandemic-cli-watch-1"));
+        assert!(glob_matches("**", ""));
+        assert!(!glob_matches("?", ""));
+        assert!(!glob_matches("*a", "pandemic-udp"));
+    }
+
+    #[test]
+    fn test_glob_matching_is_not_exponential() {
+        // A backtracking matcher takes hours on this
+        let pattern = format!("{}b", "*a".repeat(30));
+        let name = "a".repeat(200);
+        let started = std::time::Instant::now();
+        assert!(!glob_matches(&pattern, &name));
+        assert!(glob_matches(&pattern, &format!("{}b", name)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        let environment = HashMap::from([
+            ("PORT".to_string(), "8080".to_string()),
+            ("BIN".to_string(), "/usr/local/bin".to_string()),
+        ]);
+        assert_eq!(
+            expand_variables("${BIN}/hello --port ${PORT}", &environment, "${"),
+            "/usr/local/bin/hello --port 8080"
+        );
+        // Unknown names and bare `$` reach the init system untouched
+        assert_eq!(
+            expand_variables("hello ${HOME} $PORT ${PORT", &environment, "${"),
+            "hello ${HOME} $PORT ${PORT"
+        );
+        assert_eq!(
+            expand_variables("echo $${PORT} costs $$5", &environment, "${"),
+            "echo ${PORT} costs $$5"
+        );
+        assert_eq!(
+            expand_variables("echo $${PORT} costs $$5", &environment, "$${"),
+            "echo $${PORT} costs $$5"
+        );
+    }
+
+    #[test]
+    fn test_escaped_variables_stay_literal_for_each_backend() {
+        let overrides = ServiceOverrides {
+            environment: Some(HashMap::from([("PORT".to_string(), "9090".to_string())])),
+            exec_start: Some("/usr/local/bin/hello --port ${PORT} --label $${PORT}".to_string()),
+            ..overrides()
+        };
+
+        // systemd would expand a bare `${PORT}` from Environment=, so the
+        // unit keeps its own `$${` escape
+        let unit = SystemdBackend::override_file(&overrides).unwrap();
+        assert!(unit.contains("ExecStart=/usr/local/bin/hello --port 9090 --label $${PORT}\n"));
+
+        let conf = OpenRcBackend::conf_file(&overrides).unwrap();
+        assert!(conf.contains("command_args='--port 9090 --label ${PORT}'\n"));
+    }
+
+    #[test]
+    fn test_exec_start_expands_override_environment() {
+        let overrides = ServiceOverrides {
+            environment: Some(HashMap::from([("PORT".to_string(), "9090".to_string())])),
+            exec_start: Some("/usr/local/bin/hello --port ${PORT} --home ${HOME}".to_string()),
+            ..overrides()
+        };
+
+        let unit = SystemdBackend::override_file(&overrides).unwrap();
+        assert!(unit
+            .contains("ExecStart=\nExecStart=/usr/local/bin/hello --port 9090 --home ${HOME}\n"));
+        assert!(unit.contains("Environment=PORT=9090\n"));
+
+        let conf = OpenRcBackend::conf_file(&overrides).unwrap();
+        assert!(conf.contains("command_args='--port 9090 --home ${HOME}'\n"));
+    }
+
+    #[test]
+    fn test_systemd_unit_file() {
+        let unit = SystemdBackend::unit_file(&ServiceDefinition {
+            description: "Pandemic Infection: udp".to_string(),
+            exec_start: "/usr/local/bin/pandemic-udp".to_string(),
+            user: "pandemic".to_string(),
+            group: "pandemic".to_string(),
+            requires: vec!["pandemic".to_string()],
+            runtime_directory: None,
+            notify: false,
+        });
+
+        assert!(unit.contains("After=pandemic.service\nRequires=pandemic.service\n"));
+        assert!(unit.contains("Type=simple\n"));
+        assert!(unit.contains("ExecStart=/usr/local/bin/pandemic-udp\n"));
+        assert!(!unit.contains("network.target"));
+        assert!(!unit.contains("RuntimeDirectory"));
+    }
+
+    #[test]
+    fn test_systemd_notify_unit_file() {
+        let unit = SystemdBackend::unit_file(&ServiceDefinition {
+            description: "Pandemic Daemon".to_string(),
+            exec_start: "/usr/local/bin/pandemic".to_string(),
+            user: "pandemic".to_string(),
+            group: "pandemic".to_string(),
+            requires: Vec::new(),
+            runtime_directory: Some("pandemic".to_string()),
+            notify: true,
+        });
+
+        assert!(unit.contains("Type=notify\n"));
+        assert!(!unit.contains("Type=simple"));
+    }
+
+    #[test]
+    fn test_valid_overrides_pass() {
+        let overrides = ServiceOverrides {
+            environment: Some(HashMap::from([(
+                "RUST_LOG".to_string(),
+                "debug".to_string(),
+            )])),
+            exec_start: Some("/usr/local/bin/hello --port 8080".to_string()),
+            restart: Some("on-failure".to_string()),
+            user: Some("pandemic".to_string()),
+            group: Some("1000".to_string()),
+        };
+
+        validate_overrides(&overrides).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_newline_injection() {
+        let exec_start = ServiceOverrides {
+            exec_start: Some("/bin/true\nExecStartPre=/bin/sh -c evil".to_string()),
+            ..overrides()
+        };
+        let error = validate_overrides(&exec_start).unwrap_err().to_string();
+        assert_eq!(error, "ExecStart must not contain line breaks");
+
+        let user = ServiceOverrides {
+            user: Some("pandemic\nUser=root".to_string()),
+            ..overrides()
+        };
+        assert!(validate_overrides(&user).is_err());
+
+        let environment = ServiceOverrides {
+            environment: Some(HashMap::from([(
+                "RUST_LOG".to_string(),
+                "info\r\nExecStartPre=/bin/sh".to_string(),
+            )])),
+            ..overrides()
+        };
+        assert!(validate_overrides(&environment).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_restart() {
+        let overrides = ServiceOverrides {
+            restart: Some("sometimes".to_string()),
+            ..overrides()
+        };
+
+        let error = validate_overrides(&overrides).unwrap_err().to_string();
+        assert!(
+            error.starts_with("Invalid Restart value \"sometimes\""),
+            "{}",
+            error
+        );
+        assert!(error.contains("on-failure"), "{}", error);
+    }
+
+    #[test]
+    fn test_rejects_invalid_account_names() {
+        for name in ["", "-leading-dash", "has space", "a:b", &"x".repeat(33)] {
+            let overrides = ServiceOverrides {
+                group: Some(name.to_string()),
+                ..overrides()
+            };
+            assert!(validate_overrides(&overrides).is_err(), "{:?}", name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod transport_tests {
+    use crate::transport::DaemonAddr;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_daemon_addr() {
+        assert_eq!(
+            "unix:///var/run/pandemic/pandemic.sock"
+                .parse::<DaemonAddr>()
+                .unwrap(),
+            DaemonAddr::Unix(PathBuf::from("/var/run/pandemic/pandemic.sock"))
+        );
+        assert_eq!(
+            "/tmp/pandemic.sock".parse::<DaemonAddr>().unwrap(),
+            DaemonAddr::Unix(PathBuf::from("/tmp/pandemic.sock"))
+        );
+        assert_eq!(
+            "tcp://127.0.0.1:7878".parse::<DaemonAddr>().unwrap(),
+            DaemonAddr::Tcp("127.0.0.1:7878".to_string())
+        );
+        assert_eq!(
+            "tcp://127.0.0.1:7878"
+                .parse::<DaemonAddr>()
+                .unwrap()
+                .to_string(),
+            "tcp://127.0.0.1:7878"
+        );
+
+        for invalid in ["unix://", "tcp://localhost", "tcp://:80", "http://host:80"] {
+            assert!(invalid.parse::<DaemonAddr>().is_err(), "{}", invalid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod agent_tests {
+    use crate::agent::{AgentClient, AgentStatus, RetryPolicy};
+    use pandemic_protocol::{AgentRequest, Response};
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Leave a socket file nothing listens on, so connecting is refused
+    fn refusing_socket(path: &Path) {
+        drop(std::os::unix::net::UnixListener::bind(path).unwrap());
+    }
+
+    /// Answer every request with `response`, counting connections
+    fn mock_agent(listener: UnixListener, response: Response) -> Arc<AtomicUsize> {
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                let mut body = serde_json::to_vec(&response).unwrap();
+                body.push(b'\n');
+                reader.get_mut().write_all(&body).await.unwrap();
+            }
+        });
+        connections
+    }
+
+    fn policy(attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            attempts,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_millis(200),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_is_retried() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("admin.sock");
+        refusing_socket(&socket_path);
+
+        // The agent comes back well before the second attempt
+        let restart_path = socket_path.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::remove_file(&restart_path).unwrap();
+            let listener = UnixListener::bind(&restart_path).unwrap();
+            mock_agent(
+                listener,
+                Response::success_with_data(serde_json::json!({"capabilities": ["systemd"]})),
+            );
+        });
+
+        let client = AgentClient::with_socket_path(&socket_path).with_retry(policy(2));
+        let status = AgentStatus::refresh(&client).await;
+        assert!(status.available);
+        assert_eq!(status.capabilities, vec!["systemd".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_refused_connection_fails_without_retries() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("admin.sock");
+        refusing_socket(&socket_path);
+
+        let client = AgentClient::with_socket_path(&socket_path).with_retry(RetryPolicy::none());
+        let error = client.ping().await.unwrap_err();
+        let io_error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(io_error.kind(), std::io::ErrorKind::ConnectionRefused);
+    }
+
+    #[tokio::test]
+    async fn test_error_response_is_not_retried() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("admin.sock");
+        let connections = mock_agent(
+            UnixListener::bind(&socket_path).unwrap(),
+            Response::error("no such service"),
+        );
+
+        let client = AgentClient::with_socket_path(&socket_path).with_retry(policy(3));
+        let response = client
+            .send_agent_request(&AgentRequest::GetCapabilities)
+            .await
+            .unwrap();
+        assert!(matches!(response, Response::Error { .. }));
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[cfg(test)]
+mod pool_tests {
+    use crate::pool::DaemonPool;
+    use pandemic_protocol::{Request, Response, PROTOCOL_VERSION};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Answers every request, counting accepted connections. Each connection
+    /// is closed after `close_after` requests besides its `Hello`, if given.
+    fn counting_daemon(listener: UnixListener, close_after: Option<usize>) -> Arc<AtomicUsize> {
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(stream);
+                    let mut line = String::new();
+                    let mut answered = 0;
+                    while reader.read_line(&mut line).await.unwrap() > 0 {
+                        let response = match serde_json::from_str(line.trim()).unwrap() {
+                            Request::Hello { .. } => Response::success_with_data(
+                                serde_json::json!({ "protocol_version": PROTOCOL_VERSION }),
+                            ),
+                            _ => {
+                                answered += 1;
+                                Response::success()
+                            }
+                        };
+                        line.clear();
+                        let mut body = serde_json::to_vec(&response).unwrap();
+                        body.push(b'\n');
+                        reader.get_mut().write_all(&body).await.unwrap();
+                        if close_after == Some(answered) {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        accepted
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuses_one_connection() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let accepted = counting_daemon(UnixListener::bind(&socket_path).unwrap(), None);
+
+        let pool = DaemonPool::new(&socket_path, 4);
+        for _ in 0..20 {
+            let response = pool.send_request(&Request::Ping).await.unwrap();
+            assert!(matches!(response, Response::Success { .. }));
+        }
+        // One-shot requests would have connected 20 times
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.idle_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pool_replaces_connection_closed_by_daemon() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let accepted = counting_daemon(UnixListener::bind(&socket_path).unwrap(), Some(1));
+
+        let pool = DaemonPool::new(&socket_path, 4);
+        pool.send_request(&Request::Ping).await.unwrap();
+        // Let the close reach the client before the connection is reused
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let response = pool.send_request(&Request::Ping).await.unwrap();
+        assert!(matches!(response, Response::Success { .. }));
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_pool_falls_back_to_one_shot() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let accepted = counting_daemon(UnixListener::bind(&socket_path).unwrap(), None);
+
+        let pool = DaemonPool::new(&socket_path, 0);
+        for _ in 0..2 {
+            let response = pool.send_request(&Request::Ping).await.unwrap();
+            assert!(matches!(response, Response::Success { .. }));
+        }
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.idle_count(), 0);
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tls_tests {
+    use crate::tls::Tls;
+    use std::path::Path;
+
+    #[test]
+    fn test_tls_needs_certificate_and_key() {
+        assert!(Tls::load(None, None, None).unwrap().is_none());
+
+        let cert = Path::new("/nonexistent/cert.pem");
+        let client_ca = Path::new("/nonexistent/ca.pem");
+        let error = Tls::load(Some(cert), None, None).err().unwrap();
+        assert!(error.to_string().contains("certificate and a private key"));
+        assert!(Tls::load(None, None, Some(client_ca)).is_err());
+    }
+}
+
+#[cfg(all(test, feature = "api-key"))]
+mod api_key_tests {
+    use crate::api_key::{hash_key, key_digest, verify_key};
+
+    #[test]
+    fn test_hashed_key_verifies() {
+        let hash = hash_key("secret-key");
+        assert!(hash.starts_with("$argon2"));
+        assert!(!hash.contains("secret-key"));
+        assert_ne!(hash, hash_key("secret-key"));
+
+        assert!(verify_key("secret-key", &hash));
+        assert!(!verify_key("wrong-key", &hash));
+        assert!(!verify_key("secret-key", "not-a-hash"));
+        assert_eq!(key_digest("secret-key"), key_digest("secret-key"));
+        assert_ne!(key_digest("secret-key"), key_digest("wrong-key"));
+    }
+}
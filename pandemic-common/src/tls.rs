@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result};
+use axum::middleware::AddExtension;
+use axum::{Extension, Router};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures_util::future::BoxFuture;
+use rustls::pki_types::CertificateDer;
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use rustls_pemfile::{certs, private_key};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tower::Layer;
+
+/// How an HTTP server's listener is secured
+pub enum Tls {
+    /// Clients authenticate at the HTTP layer, if at all
+    Server(ServerConfig),
+    /// Clients must present a certificate, which is attached to each of
+    /// their requests as a [`PeerCertificate`]
+    Mutual(ServerConfig),
+}
+
+impl Tls {
+    /// The listener `cert` and `key` describe, requiring client certificates
+    /// issued by `client_ca` when given. Without a certificate and key the
+    /// server speaks plain HTTP.
+    pub fn load(
+        cert: Option<&Path>,
+        key: Option<&Path>,
+        client_ca: Option<&Path>,
+    ) -> Result<Option<Self>> {
+        match (cert, key, client_ca) {
+            (Some(cert), Some(key), Some(client_ca)) => Ok(Some(Tls::Mutual(
+                load_mtls_server_config(cert, key, client_ca)?,
+            ))),
+            (Some(cert), Some(key), None) => Ok(Some(Tls::Server(load_server_config(cert, key)?))),
+            (None, None, None) => Ok(None),
+            _ => Err(anyhow!(
+                "TLS needs both a certificate and a private key, and a client CA needs both"
+            )),
+        }
+    }
+}
+
+/// The certificate a client presented to a [`Tls::Mutual`] listener, already
+/// verified against its client CA
+#[derive(Debug, Clone)]
+pub struct PeerCertificate(pub CertificateDer<'static>);
+
+/// Serve `app` on `listener`, over HTTPS when a TLS config is given
+pub async fn serve_app(
+    listener: std::net::TcpListener,
+    app: Router,
+    tls: Option<Tls>,
+) -> Result<()> {
+    listener.set_nonblocking(true)?;
+    match tls {
+        Some(Tls::Server(config)) => {
+            let config = RustlsConfig::from_config(Arc::new(config));
+            axum_server::from_tcp_rustls(listener, config)
+                .serve(app.into_make_service())
+                .await?
+        }
+        Some(Tls::Mutual(config)) => {
+            let config = RustlsConfig::from_config(Arc::new(config));
+            axum_server::from_tcp(listener)
+                .acceptor(PeerCertAcceptor::new(config))
+                .serve(app.into_make_service())
+                .await?
+        }
+        None => axum::serve(tokio::net::TcpListener::from_std(listener)?, app).await?,
+    }
+    Ok(())
+}
+
+/// Completes the TLS handshake of a listener that requires client
+/// certificates, and attaches the verified certificate to every request on
+/// the connection
+#[derive(Clone)]
+struct PeerCertAcceptor {
+    inner: RustlsAcceptor,
+}
+
+impl PeerCertAcceptor {
+    fn new(config: RustlsConfig) -> Self {
+        Self {
+            inner: RustlsAcceptor::new(config),
+        }
+    }
+}
+
+impl<I, S> Accept<I, S> for PeerCertAcceptor
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Send + 'static,
+{
+    type Stream = <RustlsAcceptor as Accept<I, S>>::Stream;
+    type Service = AddExtension<S, PeerCertificate>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let handshake = self.inner.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = handshake.await?;
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .map(|der| PeerCertificate(der.clone().into_owned()))
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "Client certificate required",
+                    )
+                })?;
+            Ok((stream, Extension(certificate).layer(service)))
+        })
+    }
+}
+
+/// Build a rustls server config from a PEM certificate chain and private key,
+/// for the HTTP servers' optional TLS listeners
+pub fn load_server_config<P: AsRef<Path>>(cert_path: P, key_path: P) -> Result<ServerConfig> {
+    build_server_config(cert_path.as_ref(), key_path.as_ref(), None)
+}
+
+/// Like [`load_server_config`], also requiring every client to present a
+/// certificate issued by one of the PEM certificates in `client_ca_path`
+pub fn load_mtls_server_config<P: AsRef<Path>>(
+    cert_path: P,
+    key_path: P,
+    client_ca_path: P,
+) -> Result<ServerConfig> {
+    build_server_config(
+        cert_path.as_ref(),
+        key_path.as_ref(),
+        Some(client_ca_path.as_ref()),
+    )
+}
+
+fn build_server_config(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+) -> Result<ServerConfig> {
+    // Load certificate chain
+    let cert_pem = fs::read_to_string(cert_path)?;
+    let mut cert_reader = cert_pem.as_bytes();
+    let cert_chain: Vec<_> = certs(&mut cert_reader).collect::<Result<Vec<_>, _>>()?;
+
+    if cert_chain.is_empty() {
+        return Err(anyhow!("No certificate found"));
+    }
+
+    // Load private key (PKCS#8, PKCS#1 or SEC1)
+    let key_pem = fs::read_to_string(key_path)?;
+    let mut key_reader = key_pem.as_bytes();
+    let key = private_key(&mut key_reader)?.ok_or_else(|| anyhow!("No private key found"))?;
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match client_ca_path {
+        Some(client_ca_path) => {
+            let ca_pem = fs::read_to_string(client_ca_path)?;
+            let mut roots = RootCertStore::empty();
+            for cert in certs(&mut ca_pem.as_bytes()) {
+                roots.add(cert?)?;
+            }
+            if roots.is_empty() {
+                return Err(anyhow!("No client CA certificate found"));
+            }
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_single_cert(cert_chain, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
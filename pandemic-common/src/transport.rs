@@ -0,0 +1,118 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpStream, UnixStream};
+
+/// Where a daemon listens: `unix:///path/to.sock` or `tcp://host:port`.
+/// A bare path is read as a Unix socket.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DaemonAddr {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl FromStr for DaemonAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(path) = s.strip_prefix("unix://") {
+            if path.is_empty() {
+                bail!("Missing socket path in '{}'", s);
+            }
+            return Ok(DaemonAddr::Unix(PathBuf::from(path)));
+        }
+        if let Some(address) = s.strip_prefix("tcp://") {
+            if address
+                .rsplit_once(':')
+                .is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err())
+            {
+                bail!("Expected tcp://host:port, got '{}'", s);
+            }
+            return Ok(DaemonAddr::Tcp(address.to_string()));
+        }
+        if let Some((scheme, _)) = s.split_once("://") {
+            bail!("Unsupported daemon address scheme '{}'", scheme);
+        }
+        Ok(DaemonAddr::Unix(PathBuf::from(s)))
+    }
+}
+
+impl fmt::Display for DaemonAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DaemonAddr::Unix(path) => write!(f, "unix://{}", path.display()),
+            DaemonAddr::Tcp(address) => write!(f, "tcp://{}", address),
+        }
+    }
+}
+
+impl From<&Path> for DaemonAddr {
+    fn from(path: &Path) -> Self {
+        DaemonAddr::Unix(path.to_path_buf())
+    }
+}
+
+/// A connection to the daemon over either transport
+pub enum Transport {
+    Unix(UnixStream),
+    Tcp(TcpStream),
+}
+
+impl Transport {
+    pub async fn connect(addr: &DaemonAddr) -> io::Result<Self> {
+        match addr {
+            DaemonAddr::Unix(path) => UnixStream::connect(path).await.map(Transport::Unix),
+            DaemonAddr::Tcp(address) => {
+                let stream = TcpStream::connect(address).await?;
+                // Requests are small and latency matters more than batching
+                stream.set_nodelay(true)?;
+                Ok(Transport::Tcp(stream))
+            }
+        }
+    }
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
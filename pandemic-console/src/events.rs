@@ -0,0 +1,278 @@
+use anyhow::Result;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use futures_util::{sink::SinkExt, stream::StreamExt};
+use pandemic_common::{DaemonClient, PersistentClient};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tracing::{error, info, warn};
+
+#[derive(Clone)]
+pub struct ConsoleState {
+    pub socket_path: PathBuf,
+    /// Shared secret required by the event stream; streaming is disabled without one
+    pub events_token: Option<Arc<str>>,
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    token: Option<String>,
+    topics: Option<String>, // Comma-separated topics like "plugin.*,health.*"
+}
+
+/// Compare `token` to `expected` in constant time. Both are hashed first so
+/// the comparison doesn't reveal the expected token's length either.
+fn token_matches(token: &str, expected: &str) -> bool {
+    let token = Sha256::digest(token.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    token[..].ct_eq(&expected[..]).into()
+}
+
+/// Forward daemon events matching `topics` to the browser as JSON text frames
+pub async fn events_stream(
+    ws: WebSocketUpgrade,
+    Query(params): Query<EventsQuery>,
+    State(state): State<ConsoleState>,
+) -> Response {
+    let Some(expected) = state.events_token.as_deref() else {
+        return (
+            StatusCode::FORBIDDEN,
+            "Event streaming is disabled, start the console with --events-token",
+        )
+            .into_response();
+    };
+    if !params
+        .token
+        .as_deref()
+        .is_some_and(|token| token_matches(token, expected))
+    {
+        error!("Event stream upgrade failed: missing or invalid token");
+        return (StatusCode::UNAUTHORIZED, "Invalid token").into_response();
+    }
+
+    let topics: Vec<String> = params
+        .topics
+        .unwrap_or_else(|| "*".to_string())
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .collect();
+
+    info!("Event stream opened with topics: {:?}", topics);
+
+    ws.on_upgrade(move |socket| handle_socket(socket, state, topics))
+}
+
+fn error_message(message: impl std::fmt::Display) -> Message {
+    Message::Text(json!({"type": "error", "message": message.to_string()}).to_string())
+}
+
+/// Register a transient plugin for this socket and subscribe it to `topics`
+async fn connect_subscriber(state: &ConsoleState, topics: &[String]) -> Result<PersistentClient> {
+    let mut client = DaemonClient::connect(&state.socket_path).await?;
+    client
+        .subscribe_transient(
+            "pandemic-console-ws",
+            "Web console event subscriber",
+            topics.to_vec(),
+        )
+        .await?;
+    Ok(client)
+}
+
+async fn handle_socket(socket: WebSocket, state: ConsoleState, topics: Vec<String>) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let client = match connect_subscriber(&state, &topics).await {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to subscribe to topics: {}", e);
+            let _ = sender
+                .send(error_message(format!(
+                    "Failed to subscribe to topics: {}",
+                    e
+                )))
+                .await;
+            return;
+        }
+    };
+
+    let _ = sender
+        .send(Message::Text(
+            json!({"type": "connected", "topics": topics}).to_string(),
+        ))
+        .await;
+
+    // Dropping the stream closes the daemon connection, which removes the
+    // transient plugin along with it
+    let events = client.event_stream();
+    tokio::pin!(events);
+
+    loop {
+        tokio::select! {
+            event = events.next() => {
+                let message = match event {
+                    Some(Ok(event)) => Message::Text(json!({"type": "event", "data": event}).to_string()),
+                    Some(Err(e)) => {
+                        warn!("Failed to read daemon event: {}", e);
+                        let _ = sender.send(error_message("Daemon connection lost")).await;
+                        break;
+                    }
+                    None => {
+                        let _ = sender.send(error_message("Daemon connection closed")).await;
+                        break;
+                    }
+                };
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+            message = receiver.next() => match message {
+                Some(Ok(Message::Ping(data))) => {
+                    let _ = sender.send(Message::Pong(data)).await;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            },
+        }
+    }
+
+    info!("Event stream closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::{
+        Event, Request, RequestEnvelope, Response as PandemicResponse, ResponseEnvelope,
+        PROTOCOL_VERSION,
+    };
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+    use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
+
+    /// Answers every request with success and sends one event on each
+    /// subscribed topic right after the subscription
+    async fn mock_daemon(listener: UnixListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+
+        while reader.read_line(&mut line).await.unwrap() > 0 {
+            let envelope: RequestEnvelope = serde_json::from_str(line.trim()).unwrap();
+            line.clear();
+            let mut outgoing = Vec::new();
+            let response = match envelope.request {
+                Request::Hello { .. } => PandemicResponse::success_with_data(
+                    json!({ "protocol_version": PROTOCOL_VERSION }),
+                ),
+                Request::Subscribe { topics } => {
+                    outgoing.extend(topics.into_iter().map(|topic| {
+                        serde_json::to_vec(&pandemic_protocol::Message::Event(Event {
+                            topic,
+                            source: "test".to_string(),
+                            data: json!({"value": 1}),
+                            timestamp: None,
+                        }))
+                        .unwrap()
+                    }));
+                    PandemicResponse::success()
+                }
+                _ => PandemicResponse::success(),
+            };
+            outgoing.insert(
+                0,
+                serde_json::to_vec(&ResponseEnvelope {
+                    id: envelope.id,
+                    response,
+                })
+                .unwrap(),
+            );
+            for message in outgoing {
+                reader.get_mut().write_all(&message).await.unwrap();
+                reader.get_mut().write_all(b"\n").await.unwrap();
+            }
+        }
+    }
+
+    async fn next_json(
+        socket: &mut WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
+    ) -> serde_json::Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+                .await
+                .expect("timed out waiting for websocket message")
+                .unwrap()
+                .unwrap();
+            if let tungstenite::Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    async fn serve(state: ConsoleState) -> std::net::SocketAddr {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, crate::app(state)).await.unwrap() });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_events_flow_to_websocket() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        tokio::spawn(mock_daemon(UnixListener::bind(&socket_path).unwrap()));
+        let addr = serve(ConsoleState {
+            socket_path,
+            events_token: Some("secret".into()),
+        })
+        .await;
+
+        let (mut socket, _) = connect_async(format!(
+            "ws://{}/api/events/stream?token=secret&topics=health.status",
+            addr
+        ))
+        .await
+        .unwrap();
+
+        assert_eq!(next_json(&mut socket).await["type"], "connected");
+        let event = next_json(&mut socket).await;
+        assert_eq!(event["type"], "event");
+        assert_eq!(event["data"]["topic"], "health.status");
+        assert_eq!(event["data"]["data"]["value"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_stream_requires_token() {
+        let addr = serve(ConsoleState {
+            socket_path: PathBuf::from("/nonexistent.sock"),
+            events_token: Some("secret".into()),
+        })
+        .await;
+        let url = format!("ws://{}/api/events/stream?token=wrong", addr);
+        match connect_async(url).await {
+            Err(tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), StatusCode::UNAUTHORIZED)
+            }
+            other => panic!("Expected rejected upgrade, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_token_matches_only_the_exact_token() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret-but-longer", "secret"));
+        assert!(!token_matches("", "secret"));
+    }
+}
@@ -1,20 +1,26 @@
+mod events;
+
 use anyhow::Result;
 use axum::{
-    http::{header, StatusCode, Uri},
-    response::{Html, IntoResponse, Response},
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
     routing::get,
     Router,
 };
 use clap::Parser;
-use include_dir::{include_dir, Dir};
+use include_dir::{include_dir, Dir, File};
 use pandemic_common::DaemonClient;
 use pandemic_protocol::{PluginInfo, Request};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 use tower::ServiceBuilder;
-use tower_http::trace::TraceLayer;
+use tower_http::{compression::CompressionLayer, trace::TraceLayer};
 use tracing::info;
 
+use events::{events_stream, ConsoleState};
+
 static ASSETS_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/web/dist");
 
 #[derive(Parser)]
@@ -29,11 +35,16 @@ struct Args {
 
     #[arg(long, default_value = "3000")]
     port: u16,
+
+    /// Shared secret clients pass as `?token=` to stream daemon events;
+    /// streaming is disabled without it
+    #[arg(long)]
+    events_token: Option<String>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     // Register with pandemic daemon
@@ -48,60 +59,191 @@ async fn main() -> Result<()> {
             config
         }),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
     let mut client = DaemonClient::connect(&args.socket_path).await?;
     client
         .send_request(&Request::Register {
             plugin: plugin_info,
+            force: false,
+            ignore_dependencies: false,
         })
         .await?;
 
     info!("Registered with pandemic daemon");
 
-    // Build the router
-    let app = Router::new()
-        .route("/", get(serve_index))
-        .route("/*file", get(serve_static))
-        .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()));
-
     // Start the server
     let bind_addr = format!("{}:{}", args.bind_address, args.port);
     let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
     info!("Console server listening on {}", bind_addr);
 
-    axum::serve(listener, app).await?;
+    let state = ConsoleState {
+        socket_path: args.socket_path,
+        events_token: args.events_token.map(Into::into),
+    };
+    axum::serve(listener, app(state)).await?;
 
     Ok(())
 }
 
-async fn serve_index() -> impl IntoResponse {
-    serve_static_file("index.html").await
+/// Serves the embedded assets, gzip or brotli compressed when the client's
+/// `Accept-Encoding` allows it, and the daemon event stream
+fn app(state: ConsoleState) -> Router {
+    Router::new()
+        .route("/api/events/stream", get(events_stream))
+        .route("/", get(serve_index))
+        .route("/*file", get(serve_static))
+        .layer(
+            ServiceBuilder::new()
+                .layer(TraceLayer::new_for_http())
+                .layer(CompressionLayer::new()),
+        )
+        .with_state(state)
+}
+
+async fn serve_index(headers: HeaderMap) -> Response {
+    serve_static_file("index.html", &headers)
 }
 
-async fn serve_static(uri: Uri) -> impl IntoResponse {
+async fn serve_static(uri: Uri, headers: HeaderMap) -> Response {
     let path = uri.path().trim_start_matches('/');
-    serve_static_file(path).await
+    serve_static_file(path, &headers)
 }
 
-async fn serve_static_file(path: &str) -> Response {
-    match ASSETS_DIR.get_file(path) {
-        Some(file) => {
-            let mime_type = mime_guess::from_path(path).first_or_octet_stream();
-            (
-                StatusCode::OK,
-                [(header::CONTENT_TYPE, mime_type.as_ref())],
-                file.contents(),
-            )
-                .into_response()
-        }
-        None => {
-            // For SPA routing, serve index.html for unknown routes
-            if let Some(index) = ASSETS_DIR.get_file("index.html") {
-                Html(std::str::from_utf8(index.contents()).unwrap_or("")).into_response()
-            } else {
-                (StatusCode::NOT_FOUND, "File not found").into_response()
-            }
+fn serve_static_file(path: &str, headers: &HeaderMap) -> Response {
+    if let Some(file) = ASSETS_DIR.get_file(path) {
+        return serve_file(path, file, headers);
+    }
+
+    // Only extensionless paths are SPA routes; a missing `.js` or `.css`
+    // must not silently turn into HTML
+    let is_asset = Path::new(path).extension().is_some();
+    match ASSETS_DIR.get_file("index.html") {
+        Some(index) if !is_asset => serve_file("index.html", index, headers),
+        _ => (StatusCode::NOT_FOUND, "File not found").into_response(),
+    }
+}
+
+fn serve_file(path: &str, file: &'static File<'static>, headers: &HeaderMap) -> Response {
+    let etag = etag(path, file);
+    // Vite fingerprints everything under assets/, so those never change;
+    // everything else is revalidated against its ETag
+    let cache_control = if path.starts_with("assets/") {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+    if not_modified {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache_control.to_string()),
+            ],
+        )
+            .into_response();
+    }
+
+    let mime_type = mime_guess::from_path(path).first_or_octet_stream();
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, mime_type.to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control.to_string()),
+        ],
+        file.contents(),
+    )
+        .into_response()
+}
+
+/// Strong ETag from the file's SHA-256, computed once per embedded file
+fn etag(path: &str, file: &File<'_>) -> String {
+    static ETAGS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    let mut etags = ETAGS.get_or_init(Default::default).lock().unwrap();
+    etags
+        .entry(path.to_string())
+        .or_insert_with(|| {
+            let digest = Sha256::digest(file.contents());
+            format!("\"{}\"", hex::encode(&digest[..16]))
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::Request as HttpRequest;
+    use tower::ServiceExt;
+
+    fn state() -> ConsoleState {
+        ConsoleState {
+            socket_path: PathBuf::from("/nonexistent.sock"),
+            events_token: None,
         }
     }
+
+    #[tokio::test]
+    async fn test_assets_are_gzipped_when_accepted() {
+        let request = HttpRequest::get("/index.html")
+            .header(header::ACCEPT_ENCODING, "gzip")
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+    }
+
+    async fn get(uri: &str) -> Response {
+        let request = HttpRequest::get(uri).body(Body::empty()).unwrap();
+        app(state()).oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_spa_route_serves_index() {
+        let response = get("/plugins/pandemic-udp").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn test_missing_asset_is_not_found() {
+        let response = get("/assets/missing.js").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_hit_sets_etag_and_honors_if_none_match() {
+        let response = get("/index.html").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with('"'));
+
+        let request = HttpRequest::get("/index.html")
+            .header(header::IF_NONE_MATCH, etag.clone())
+            .body(Body::empty())
+            .unwrap();
+        let response = app(state()).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag);
+    }
+
+    #[tokio::test]
+    async fn test_assets_are_plain_without_accept_encoding() {
+        let request = HttpRequest::get("/index.html").body(Body::empty()).unwrap();
+        let response = app(state()).oneshot(request).await.unwrap();
+
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
 }
@@ -0,0 +1,80 @@
+//! Publishes one event among a growing number of subscribers, only one of
+//! which matches. Subscribers to exact topics are found through the
+//! bus's topic index, so that case stays flat as subscribers grow; wildcard
+//! subscribers are still checked one by one and grow linearly.
+//!
+//! Run with `cargo bench -p pandemic-daemon`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use pandemic_daemon::daemon::ConnectionContext;
+use pandemic_daemon::event_bus::EventBus;
+use pandemic_protocol::Event;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+fn sample_event() -> Event {
+    Event {
+        topic: "sensor-0.reading".to_string(),
+        source: "bench".to_string(),
+        data: serde_json::json!({"value": 21.75}),
+        timestamp: None,
+    }
+}
+
+/// A bus with `count` connections, the i-th subscribed to `pattern(i)`
+fn bus_with_subscribers(
+    count: usize,
+    pattern: impl Fn(usize) -> String,
+) -> (
+    EventBus,
+    HashMap<String, ConnectionContext>,
+    Vec<mpsc::Receiver<Event>>,
+) {
+    let mut bus = EventBus::new();
+    let mut connections = HashMap::new();
+    let mut receivers = Vec::new();
+    for i in 0..count {
+        let connection_id = format!("connection-{}", i);
+        let (event_sender, event_rx) = mpsc::channel(16);
+        connections.insert(
+            connection_id.clone(),
+            ConnectionContext {
+                plugin_name: Some(format!("plugin-{}", i)),
+                event_sender,
+                peer_uid: None,
+            },
+        );
+        receivers.push(event_rx);
+        bus.subscribe(&connection_id, vec![pattern(i)]);
+    }
+    bus.set_history_capacity(0);
+    (bus, connections, receivers)
+}
+
+fn bench_publish(c: &mut Criterion) {
+    let mut group = c.benchmark_group("publish_one_match");
+    let event = sample_event();
+
+    for count in [10, 100, 1000] {
+        for (name, pattern) in [
+            (
+                "exact",
+                (|i| format!("sensor-{}.reading", i)) as fn(usize) -> String,
+            ),
+            ("wildcard", |i| format!("sensor-{}.#", i)),
+        ] {
+            let (mut bus, connections, mut receivers) = bus_with_subscribers(count, pattern);
+            group.bench_with_input(BenchmarkId::new(name, count), &count, |b, _| {
+                b.iter(|| {
+                    bus.publish(black_box(event.clone()), &connections);
+                    // Only sensor-0 matches; drain it so its buffer never fills
+                    while receivers[0].try_recv().is_ok() {}
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_publish);
+criterion_main!(benches);
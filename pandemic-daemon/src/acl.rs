@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::path::Path;
+
+use crate::event_bus::topic_matches;
+
+/// Which topics each plugin may publish to and subscribe to.
+///
+/// Loaded from a TOML file keyed by plugin name pattern, using the same
+/// pattern syntax as subscriptions (so `*` matches every plugin):
+///
+/// ```toml
+/// [plugins."pandemic-udp"]
+/// publish = ["udp.#"]
+/// subscribe = ["plugin.#"]
+/// ```
+///
+/// A plugin is allowed an action when any entry matching its name allows
+/// it. Connections that never registered are checked as `unknown`.
+///
+/// `configure` lists the plugins, by name pattern, whose config overrides
+/// an entry may set or clear; a plugin may always change its own.
+///
+/// Plugins choose their own names, so the names each client may register
+/// are granted per OS user, keyed by user name, numeric uid or `*` for any
+/// peer. TCP clients have no uid and only get the `*` entry. A peer entry's
+/// `configure` works like a plugin entry's, for clients that don't register,
+/// such as the REST API.
+///
+/// ```toml
+/// [peers.pandemic]
+/// register = ["pandemic-*"]
+///
+/// [peers.pandemic-rest]
+/// configure = ["*"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TopicAcl {
+    #[serde(default)]
+    pub plugins: BTreeMap<String, AclEntry>,
+    #[serde(default)]
+    pub peers: BTreeMap<String, PeerEntry>,
+    /// The uid each `peers` key given as a user name resolved to at load
+    #[serde(skip)]
+    peer_uids: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AclEntry {
+    #[serde(default)]
+    pub publish: Vec<String>,
+    #[serde(default)]
+    pub subscribe: Vec<String>,
+    #[serde(default)]
+    pub configure: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PeerEntry {
+    /// Plugin name patterns the peer may register
+    #[serde(default)]
+    pub register: Vec<String>,
+    /// Plugin name patterns whose config overrides the peer may change
+    #[serde(default)]
+    pub configure: Vec<String>,
+}
+
+impl TopicAcl {
+    /// Read the ACL at `path`, or `None` when the file doesn't exist
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut acl: Self = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        // Users are looked up once here rather than on every registration
+        for peer in acl.peers.keys() {
+            if peer != "*" && peer.parse::<u32>().is_err() {
+                acl.peer_uids.insert(peer.clone(), user_uid(peer)?);
+            }
+        }
+        Ok(Some(acl))
+    }
+
+    pub fn can_publish(&self, plugin_name: &str, topic: &str) -> bool {
+        self.entries_for(plugin_name).any(|entry| {
+            entry
+                .publish
+                .iter()
+                .any(|allowed| topic_matches(allowed, topic))
+        })
+    }
+
+    /// Whether `plugin_name` may subscribe to `pattern`. The requested pattern
+    /// is matched literally against the allowed ones, so `health.+.status`
+    /// falls under `health.#` but `#` is only covered by `#` itself.
+    pub fn can_subscribe(&self, plugin_name: &str, pattern: &str) -> bool {
+        self.entries_for(plugin_name).any(|entry| {
+            entry
+                .subscribe
+                .iter()
+                .any(|allowed| topic_matches(allowed, pattern))
+        })
+    }
+
+    /// Whether `plugin_name` may change the config override of `target`
+    pub fn can_configure(&self, plugin_name: &str, target: &str) -> bool {
+        self.entries_for(plugin_name).any(|entry| {
+            entry
+                .configure
+                .iter()
+                .any(|allowed| topic_matches(allowed, target))
+        })
+    }
+
+    /// Whether a client running as `peer_uid` may register as `plugin_name`.
+    /// `None` is a peer without credentials, such as a TCP client.
+    pub fn can_register(&self, peer_uid: Option<u32>, plugin_name: &str) -> bool {
+        self.peer_entries(peer_uid).any(|entry| {
+            entry
+                .register
+                .iter()
+                .any(|allowed| topic_matches(allowed, plugin_name))
+        })
+    }
+
+    /// Whether a client running as `peer_uid` may change the config override
+    /// of `target`, whatever name it registered
+    pub fn peer_can_configure(&self, peer_uid: Option<u32>, target: &str) -> bool {
+        self.peer_entries(peer_uid).any(|entry| {
+            entry
+                .configure
+                .iter()
+                .any(|allowed| topic_matches(allowed, target))
+        })
+    }
+
+    fn peer_entries(&self, peer_uid: Option<u32>) -> impl Iterator<Item = &PeerEntry> {
+        self.peers
+            .iter()
+            .filter(move |(peer, _)| {
+                *peer == "*"
+                    || peer_uid.is_some_and(|uid| {
+                        peer.parse::<u32>()
+                            .ok()
+                            .or(self.peer_uids.get(*peer).copied())
+                            == Some(uid)
+                    })
+            })
+            .map(|(_, entry)| entry)
+    }
+
+    fn entries_for<'a>(&'a self, plugin_name: &'a str) -> impl Iterator<Item = &'a AclEntry> {
+        self.plugins
+            .iter()
+            .filter(move |(name_pattern, _)| topic_matches(name_pattern, plugin_name))
+            .map(|(_, entry)| entry)
+    }
+}
+
+fn user_uid(user: &str) -> Result<u32> {
+    let user_cstr = CString::new(user.as_bytes())?;
+    let passwd = unsafe { libc::getpwnam(user_cstr.as_ptr()) };
+    if passwd.is_null() {
+        return Err(anyhow!("ACL peer '{}' is not a known user", user));
+    }
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acl() -> TopicAcl {
+        toml::from_str(
+            r#"
+            [plugins."pandemic-udp"]
+            publish = ["udp.#"]
+            subscribe = ["plugin.#"]
+
+            [plugins."*"]
+            subscribe = ["health.#"]
+
+            [plugins.unknown]
+            configure = ["pandemic-*"]
+
+            [peers."1000"]
+            register = ["sensor-*"]
+            configure = ["sensor-*"]
+
+            [peers."*"]
+            register = ["pandemic-cli-*"]
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_publish_rules() {
+        let acl = acl();
+        assert!(acl.can_publish("pandemic-udp", "udp.packet"));
+        assert!(!acl.can_publish("pandemic-udp", "plugin.deregistered"));
+        assert!(!acl.can_publish("other", "udp.packet"));
+    }
+
+    #[test]
+    fn test_subscribe_rules_combine_matching_entries() {
+        let acl = acl();
+        assert!(acl.can_subscribe("pandemic-udp", "plugin.#"));
+        assert!(acl.can_subscribe("pandemic-udp", "health.+.status"));
+        assert!(acl.can_subscribe("other", "health.#"));
+        assert!(!acl.can_subscribe("other", "plugin.#"));
+        assert!(!acl.can_subscribe("other", "#"));
+    }
+
+    #[test]
+    fn test_configure_rules() {
+        let acl = acl();
+        assert!(acl.can_configure("unknown", "pandemic-udp"));
+        assert!(!acl.can_configure("unknown", "sensor"));
+        assert!(!acl.can_configure("pandemic-udp", "pandemic-tcp"));
+        assert!(acl.peer_can_configure(Some(1000), "sensor-1"));
+        assert!(!acl.peer_can_configure(Some(1000), "pandemic-udp"));
+        assert!(!acl.peer_can_configure(None, "sensor-1"));
+    }
+
+    #[test]
+    fn test_register_rules_follow_the_peer() {
+        let acl = acl();
+        assert!(acl.can_register(Some(1000), "sensor-1"));
+        assert!(!acl.can_register(Some(1000), "pandemic-udp"));
+        assert!(!acl.can_register(Some(1001), "sensor-1"));
+        assert!(acl.can_register(Some(1001), "pandemic-cli-watch-1"));
+        assert!(acl.can_register(None, "pandemic-cli-watch-1"));
+        assert!(!acl.can_register(None, "sensor-1"));
+    }
+
+    #[test]
+    fn test_peers_resolve_user_names() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("acl.toml");
+        std::fs::write(&path, "[peers.root]\nregister = [\"pandemic\"]\n").unwrap();
+        let acl = TopicAcl::load(&path).unwrap().unwrap();
+        assert!(acl.can_register(Some(0), "pandemic"));
+        assert!(!acl.can_register(Some(1000), "pandemic"));
+
+        std::fs::write(&path, "[peers.no-such-user]\nregister = [\"*\"]\n").unwrap();
+        assert!(TopicAcl::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_missing_file_means_no_acl() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(TopicAcl::load(&temp_dir.path().join("acl.toml"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,62 +1,91 @@
 use anyhow::Result;
-use pandemic_protocol::{Event, Message, Request, Response};
+use pandemic_protocol::{
+    Encoding, ErrorCode, Event, Framing, Message, Request, RequestEnvelope, Response,
+    ResponseEnvelope,
+};
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::UnixStream;
-use tokio::sync::{mpsc, Mutex};
-use tracing::{error, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{error, info, warn};
 
 use crate::daemon::Daemon;
+use crate::handlers::{is_read_only, ConfigChange};
 
-pub async fn handle_connection(
-    stream: UnixStream,
+/// Serve one client connection over any transport until it closes
+pub async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
     connection_id: String,
-    daemon: Arc<Mutex<Daemon>>,
-    mut event_rx: mpsc::UnboundedReceiver<Event>,
+    daemon: Arc<RwLock<Daemon>>,
+    mut event_rx: mpsc::Receiver<Event>,
 ) -> Result<()> {
-    let mut reader = BufReader::new(stream);
-    let mut line = String::new();
+    let mut buf = Vec::new();
+    // Framing is decided by the first byte the client sends
+    let mut framing: Option<Framing> = None;
+    // JSON until the client negotiates something else in its Hello
+    let mut encoding = Encoding::Json;
+    // I/O failure that ended the connection, reported when it is removed
+    let mut close_error: Option<String> = None;
 
-    loop {
+    'outer: loop {
         tokio::select! {
-            result = reader.read_line(&mut line) => {
+            result = stream.read_buf(&mut buf) => {
                 match result {
-                    Ok(0) => break,
-                    Ok(_) => {
-                        let trimmed = line.trim();
-                        if !trimmed.is_empty() {
-                            let response = {
-                                let mut daemon_guard = daemon.lock().await;
-                                match serde_json::from_str::<Request>(trimmed) {
-                                    Ok(request) => daemon_guard.handle_request(request, &connection_id),
+                    Ok(0) => {
+                        // Tolerate a final newline-delimited request without a trailing newline
+                        if framing == Some(Framing::Newline) && !buf.is_empty() {
+                            let frame = std::mem::take(&mut buf);
+                            if let Err(e) = handle_frame(&mut stream, Framing::Newline, encoding, &frame, &connection_id, &daemon).await {
+                                warn!("Failed to send response: {}", e);
+                                close_error = Some(e.to_string());
+                            }
+                        }
+                        break;
+                    }
+                    Ok(_) => loop {
+                        let current = *framing.get_or_insert_with(|| Framing::detect(buf[0]));
+                        match current.decode(&mut buf) {
+                            Ok(Some(frame)) => {
+                                match handle_frame(&mut stream, current, encoding, &frame, &connection_id, &daemon).await {
+                                    Ok(Some(negotiated)) => {
+                                        info!("Switching connection to {:?} encoding", negotiated);
+                                        encoding = negotiated;
+                                        framing = Some(negotiated.framing(current));
+                                    }
+                                    Ok(None) => {}
                                     Err(e) => {
-                                        warn!("Invalid request: {}", e);
-                                        Response::error(format!("Invalid request: {}", e))
+                                        warn!("Failed to send response: {}", e);
+                                        close_error = Some(e.to_string());
+                                        break 'outer;
                                     }
                                 }
-                            };
-
-                            let response_json = serde_json::to_string(&response)?;
-                            reader.get_mut().write_all(response_json.as_bytes()).await?;
-                            reader.get_mut().write_all(b"\n").await?;
+                            }
+                            Ok(None) => break,
+                            Err(e) => {
+                                warn!("Invalid frame: {}", e);
+                                break 'outer;
+                            }
                         }
-                        line.clear();
-                    }
+                    },
                     Err(e) => {
                         error!("Read error: {}", e);
+                        close_error = Some(e.to_string());
                         break;
                     }
                 }
             }
             event = event_rx.recv() => {
                 if let Some(event) = event {
-                    let event_json = serde_json::to_string(&Message::Event(event))?;
-                    if let Err(e) = reader.get_mut().write_all(event_json.as_bytes()).await {
+                    let body = match encoding.encode(&Message::Event(event)) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            warn!("Failed to serialize event: {}", e);
+                            continue;
+                        }
+                    };
+                    let frame = framing.unwrap_or(Framing::Newline).encode(&body);
+                    if let Err(e) = stream.write_all(&frame).await {
                         warn!("Failed to send event: {}", e);
-                        break;
-                    }
-                    if let Err(e) = reader.get_mut().write_all(b"\n").await {
-                        warn!("Failed to send event newline: {}", e);
+                        close_error = Some(e.to_string());
                         break;
                     }
                 } else {
@@ -67,9 +96,266 @@ pub async fn handle_connection(
     }
 
     {
-        let mut daemon_guard = daemon.lock().await;
-        daemon_guard.remove_connection(&connection_id);
+        let mut daemon_guard = daemon.write().await;
+        daemon_guard.remove_connection(&connection_id, close_error.as_deref());
     }
 
     Ok(())
 }
+
+/// Answer one request frame, returning the encoding the connection switches
+/// to when the frame was a successful `Hello` asking for one
+async fn handle_frame<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    framing: Framing,
+    encoding: Encoding,
+    frame: &[u8],
+    connection_id: &str,
+    daemon: &Arc<RwLock<Daemon>>,
+) -> Result<Option<Encoding>> {
+    if encoding == Encoding::Json && frame.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+
+    let mut negotiated = None;
+    let response = match encoding.decode::<RequestEnvelope>(frame) {
+        Ok(envelope) => {
+            let requested = match &envelope.request {
+                Request::Hello { encoding, .. } => *encoding,
+                _ => None,
+            };
+            // Reads share the lock; anything that changes state, including
+            // every publish, takes it exclusively so events keep their order
+            let response = if is_read_only(&envelope.request) {
+                daemon.read().await.handle_read_request(envelope.request)
+            } else if let Some(change) = ConfigChange::from_request(&envelope.request) {
+                // Override files are written without holding the daemon, so
+                // a slow disk doesn't stall every other connection
+                let authorized = {
+                    let daemon = daemon.read().await;
+                    daemon.count_request(&envelope.request);
+                    daemon.authorize_config_change(&change, connection_id)
+                };
+                match authorized.and_then(|manager| change.apply(&manager)) {
+                    Ok(config) => daemon.write().await.config_changed(change.plugin(), config),
+                    Err(response) => response,
+                }
+            } else {
+                daemon
+                    .write()
+                    .await
+                    .handle_request(envelope.request, connection_id)
+            };
+            if matches!(response, Response::Success { .. }) {
+                negotiated = requested.filter(|requested| *requested != encoding);
+            }
+            // Echo the correlation id so multiplexed clients can route the reply
+            ResponseEnvelope {
+                id: envelope.id,
+                response,
+            }
+        }
+        Err(e) => {
+            warn!("Invalid request: {}", e);
+            ResponseEnvelope {
+                id: None,
+                response: Response::error_with_code(
+                    ErrorCode::InvalidRequest,
+                    format!("Invalid request: {}", e),
+                ),
+            }
+        }
+    };
+
+    // The reply to a negotiating Hello still uses the old encoding
+    let body = encoding.encode(&response)?;
+    stream.write_all(&framing.encode(&body)).await?;
+    Ok(negotiated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::{PluginInfo, PROTOCOL_VERSION};
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::UnixStream;
+
+    async fn send_msgpack(stream: &mut UnixStream, request: Request) {
+        let body = Encoding::MessagePack
+            .encode(&RequestEnvelope { id: None, request })
+            .unwrap();
+        stream
+            .write_all(&Framing::LengthPrefixed.encode(&body))
+            .await
+            .unwrap();
+    }
+
+    async fn read_msgpack<T: serde::de::DeserializeOwned>(stream: &mut UnixStream) -> T {
+        let len = stream.read_u32().await.unwrap() as usize;
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await.unwrap();
+        Encoding::MessagePack.decode(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_message_pack_round_trip() {
+        let daemon = Arc::new(RwLock::new(Daemon::new()));
+        let event_rx = daemon.write().await.add_connection("conn".to_string());
+        let (client, server) = UnixStream::pair().unwrap();
+        tokio::spawn(handle_connection(
+            server,
+            "conn".to_string(),
+            daemon.clone(),
+            event_rx,
+        ));
+
+        // The handshake itself is plain newline-delimited JSON
+        let mut reader = BufReader::new(client);
+        let hello = serde_json::json!({
+            "type": "Hello",
+            "protocol_version": PROTOCOL_VERSION,
+            "client": "test",
+            "encoding": "msgpack",
+        });
+        reader
+            .get_mut()
+            .write_all(format!("{}\n", hello).as_bytes())
+            .await
+            .unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let reply: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(reply["data"]["encoding"], "msgpack");
+        let mut client = reader.into_inner();
+
+        let plugin = PluginInfo {
+            name: "packed".to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            config: None,
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        };
+        for request in [
+            Request::Register {
+                plugin,
+                force: false,
+                ignore_dependencies: false,
+            },
+            Request::Subscribe {
+                topics: vec!["packed.#".to_string()],
+            },
+            Request::Publish {
+                topic: "packed.reading".to_string(),
+                data: serde_json::json!({"value": 42, "unit": "C"}),
+                retain: false,
+            },
+        ] {
+            send_msgpack(&mut client, request).await;
+            let response: ResponseEnvelope = read_msgpack(&mut client).await;
+            assert!(
+                matches!(response.response, Response::Success { .. }),
+                "{:?}",
+                response
+            );
+        }
+
+        match read_msgpack::<Message>(&mut client).await {
+            Message::Event(event) => {
+                assert_eq!(event.topic, "packed.reading");
+                assert_eq!(event.data, serde_json::json!({"value": 42, "unit": "C"}));
+            }
+            other => panic!("Expected event, got {:?}", other),
+        }
+    }
+
+    /// Serve `count` newline-framed connections on `daemon`
+    async fn connect_many(
+        daemon: &Arc<RwLock<Daemon>>,
+        count: usize,
+    ) -> Vec<BufReader<UnixStream>> {
+        let mut clients = Vec::new();
+        for i in 0..count {
+            let connection_id = format!("conn_{}", i);
+            let event_rx = daemon.write().await.add_connection(connection_id.clone());
+            let (client, server) = UnixStream::pair().unwrap();
+            tokio::spawn(handle_connection(
+                server,
+                connection_id,
+                daemon.clone(),
+                event_rx,
+            ));
+            clients.push(BufReader::new(client));
+        }
+        clients
+    }
+
+    async fn send_line(client: &mut BufReader<UnixStream>, request: &Request) {
+        let mut json = serde_json::to_vec(request).unwrap();
+        json.push(b'\n');
+        client.get_mut().write_all(&json).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reads_proceed_concurrently_and_writes_wait() {
+        let daemon = Arc::new(RwLock::new(Daemon::new()));
+        let mut clients = connect_many(&daemon, 32).await;
+        let mut writer = clients.pop().unwrap();
+
+        // A read that stays in progress for the whole test
+        let long_read = daemon.read().await;
+
+        let mut readers = tokio::task::JoinSet::new();
+        for (i, mut client) in clients.into_iter().enumerate() {
+            readers.spawn(async move {
+                let request = if i % 2 == 0 {
+                    Request::ListPlugins
+                } else {
+                    Request::GetMetrics
+                };
+                send_line(&mut client, &request).await;
+                let mut line = String::new();
+                client.read_line(&mut line).await.unwrap();
+                serde_json::from_str::<Response>(&line).unwrap()
+            });
+        }
+        let responses = tokio::time::timeout(std::time::Duration::from_secs(5), readers.join_all())
+            .await
+            .expect("reads were serialized behind another reader");
+        assert_eq!(responses.len(), 31);
+        assert!(responses
+            .iter()
+            .all(|response| matches!(response, Response::Success { .. })));
+
+        let register = Request::Register {
+            plugin: PluginInfo {
+                name: "writer".to_string(),
+                version: "1.0.0".to_string(),
+                description: None,
+                config: None,
+                registered_at: None,
+                heartbeat_interval: None,
+                dependencies: vec![],
+            },
+            force: false,
+            ignore_dependencies: false,
+        };
+        send_line(&mut writer, &register).await;
+        let mut line = String::new();
+        assert!(tokio::time::timeout(
+            std::time::Duration::from_millis(200),
+            writer.read_line(&mut line)
+        )
+        .await
+        .is_err());
+
+        drop(long_read);
+        writer.read_line(&mut line).await.unwrap();
+        assert!(matches!(
+            serde_json::from_str::<Response>(&line).unwrap(),
+            Response::Success { .. }
+        ));
+        assert!(daemon.read().await.plugins.contains_key("writer"));
+    }
+}
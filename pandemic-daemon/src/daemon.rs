@@ -1,23 +1,62 @@
-use pandemic_protocol::{Event, HealthMetrics, PluginInfo};
-use std::collections::HashMap;
-use std::time::{Duration, SystemTime};
+use pandemic_common::FileConfigManager;
+use pandemic_protocol::{
+    DaemonInfo, DaemonMetrics, Event, HealthMetrics, PluginHealth, PluginInfo, PROTOCOL_VERSION,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime};
 use sysinfo::System;
 use tokio::sync::mpsc;
-use tracing::info;
+use tracing::{info, warn};
 
-use crate::event_bus::EventBus;
+use crate::acl::TopicAcl;
+use crate::event_bus::{EventBus, OverflowPolicy};
+
+/// How long plugins restored from the state file get to register again
+pub const DEFAULT_RESTORE_GRACE: Duration = Duration::from_secs(300);
 
 pub struct ConnectionContext {
     pub plugin_name: Option<String>,
-    pub event_sender: mpsc::UnboundedSender<Event>,
+    pub event_sender: mpsc::Sender<Event>,
+    /// The client's uid from its socket credentials; `None` over TCP
+    pub peer_uid: Option<u32>,
 }
 
 pub struct Daemon {
     pub plugins: HashMap<String, PluginInfo>,
     pub event_bus: EventBus,
     pub connections: HashMap<String, ConnectionContext>,
+    /// Plugins loaded from the state file that haven't registered again yet
+    pub restored: HashSet<String>,
+    /// How long after startup `restored` plugins are deregistered
+    pub restore_grace: Duration,
+    /// When each plugin with a heartbeat interval was last heard from
+    pub last_seen: HashMap<String, Instant>,
+    /// Requests handled since start, by request type. Locked separately so
+    /// read-only requests can count themselves through a shared reference.
+    pub requests_handled: Mutex<BTreeMap<String, u64>>,
+    /// Topic restrictions for publish and subscribe; `None` allows everything
+    pub acl: Option<TopicAcl>,
+    /// Socket the daemon was bound to, reported by `Info`
+    pub socket_path: Option<PathBuf>,
+    /// Plugin configuration behind `GetConfig` and the override requests,
+    /// which fail while this is `None`
+    pub config: Option<FileConfigManager>,
+    /// Where systemd readiness notifications go; none are sent while this
+    /// is `None`
+    pub notify_socket: Option<PathBuf>,
+    state_writer: Option<StateWriter>,
+    restored_at: Option<Instant>,
     start_time: SystemTime,
-    system: System,
+    system: Mutex<System>,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Daemon {
@@ -26,23 +65,85 @@ impl Daemon {
             plugins: HashMap::new(),
             event_bus: EventBus::new(),
             connections: HashMap::new(),
+            restored: HashSet::new(),
+            restore_grace: DEFAULT_RESTORE_GRACE,
+            last_seen: HashMap::new(),
+            requests_handled: Mutex::new(BTreeMap::new()),
+            acl: None,
+            socket_path: None,
+            config: None,
+            notify_socket: None,
+            state_writer: None,
+            restored_at: None,
             start_time: SystemTime::now(),
-            system: System::new_all(),
+            system: Mutex::new(System::new_all()),
+        }
+    }
+
+    /// Create a daemon that persists its plugin registry to `state_file`,
+    /// restoring whatever was saved by a previous run
+    pub fn with_state_file(state_file: PathBuf) -> Self {
+        let mut daemon = Self::new();
+        match load_plugins(&state_file) {
+            Ok(plugins) => {
+                info!("Restored {} plugin(s) from {:?}", plugins.len(), state_file);
+                daemon.restored = plugins.keys().cloned().collect();
+                daemon.restored_at = Some(Instant::now());
+                // Restored plugins get a fresh TTL to come back and heartbeat
+                let now = Instant::now();
+                daemon.last_seen = plugins
+                    .values()
+                    .filter(|plugin| plugin.heartbeat_interval.is_some())
+                    .map(|plugin| (plugin.name.clone(), now))
+                    .collect();
+                daemon.plugins = plugins;
+            }
+            Err(e) => warn!("Ignoring unreadable state file {:?}: {}", state_file, e),
+        }
+        daemon.state_writer = Some(StateWriter::spawn(state_file));
+        daemon
+    }
+
+    /// Queue the plugin registry to be written to the state file, if one is
+    /// configured. The write happens on a background thread.
+    pub fn save_state(&self) {
+        if let Some(writer) = &self.state_writer {
+            writer.save(self.plugins.clone());
+        }
+    }
+
+    /// Deregister restored plugins that haven't registered again within
+    /// `restore_grace` of startup, returning their names
+    pub fn expire_restored_plugins(&mut self, now: Instant) -> Vec<String> {
+        let Some(restored_at) = self.restored_at else {
+            return Vec::new();
+        };
+        if now.saturating_duration_since(restored_at) <= self.restore_grace {
+            return Vec::new();
+        }
+        self.restored_at = None;
+
+        let expired: Vec<String> = self.restored.iter().cloned().collect();
+        for name in &expired {
+            warn!(plugin = %name, "Restored plugin never registered again, deregistering");
+            self.deregister(name);
         }
+        expired
     }
 
-    pub fn collect_health_metrics(&mut self) -> HealthMetrics {
-        self.system.refresh_all();
+    pub fn collect_health_metrics(&self) -> HealthMetrics {
+        let mut system = self.system.lock().unwrap();
+        system.refresh_all();
 
         let uptime = self
             .start_time
             .elapsed()
             .unwrap_or(Duration::ZERO)
             .as_secs();
-        let memory = self.system.total_memory() / 1024 / 1024;
-        let memory_used = self.system.used_memory() / 1024 / 1024;
+        let memory = system.total_memory() / 1024 / 1024;
+        let memory_used = system.used_memory() / 1024 / 1024;
 
-        let cpu_usage = self.system.global_cpu_info().cpu_usage();
+        let cpu_usage = system.global_cpu_info().cpu_usage();
         let load_avg = System::load_average();
 
         HealthMetrics {
@@ -50,6 +151,8 @@ impl Daemon {
             total_connections: self.connections.len(),
             event_bus_subscribers: self.event_bus.subscribers.len(),
             uptime_seconds: uptime,
+            dropped_events: self.event_bus.dropped_events,
+            plugins: self.plugin_health(),
             memory_used_mb: memory_used,
             memory_total_mb: memory,
             cpu_usage_percent: cpu_usage,
@@ -61,30 +164,532 @@ impl Daemon {
         }
     }
 
-    pub fn add_connection(&mut self, connection_id: String) -> mpsc::UnboundedReceiver<Event> {
-        let (tx, rx) = mpsc::unbounded_channel();
+    /// Monotonic counters since the daemon started
+    pub fn metrics(&self) -> DaemonMetrics {
+        DaemonMetrics {
+            uptime_seconds: self
+                .start_time
+                .elapsed()
+                .unwrap_or(Duration::ZERO)
+                .as_secs(),
+            events_published: self.event_bus.total_published,
+            events_delivered: self.event_bus.total_delivered,
+            events_dropped: self.event_bus.dropped_events,
+            requests: self.requests_handled.lock().unwrap().clone(),
+        }
+    }
+
+    /// Version and enabled features, for telling deployments apart
+    pub fn info(&self) -> DaemonInfo {
+        let mut features = vec!["msgpack".to_string(), "retained-events".to_string()];
+        if self.event_bus.history_capacity() > 0 {
+            features.push("event-history".to_string());
+        }
+        if self.acl.is_some() {
+            features.push("acl".to_string());
+        }
+        if self.state_writer.is_some() {
+            features.push("state-file".to_string());
+        }
+        if self.config.is_some() {
+            features.push("config".to_string());
+        }
+
+        DaemonInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            protocol_version: PROTOCOL_VERSION,
+            uptime_seconds: self
+                .start_time
+                .elapsed()
+                .unwrap_or(Duration::ZERO)
+                .as_secs(),
+            features,
+            started_at: Some(self.start_time),
+            socket_path: self.socket_path.clone(),
+        }
+    }
+
+    /// Id of the connection currently registered as `plugin_name`
+    pub fn connection_of(&self, plugin_name: &str) -> Option<&String> {
+        self.connections
+            .iter()
+            .find(|(_, context)| context.plugin_name.as_deref() == Some(plugin_name))
+            .map(|(connection_id, _)| connection_id)
+    }
+
+    /// Per-plugin connection, subscription and delivery details, by name
+    fn plugin_health(&self) -> Vec<PluginHealth> {
+        let mut plugins: Vec<PluginHealth> = self
+            .plugins
+            .values()
+            .map(|plugin| PluginHealth {
+                name: plugin.name.clone(),
+                registered_at: plugin.registered_at,
+                connected: self
+                    .connections
+                    .values()
+                    .any(|context| context.plugin_name.as_ref() == Some(&plugin.name)),
+                subscribed_topics: self
+                    .connection_of(&plugin.name)
+                    .and_then(|connection_id| self.event_bus.subscribers.get(connection_id))
+                    .map_or(0, Vec::len),
+                events_delivered: self
+                    .event_bus
+                    .delivered
+                    .get(&plugin.name)
+                    .copied()
+                    .unwrap_or(0),
+            })
+            .collect();
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins
+    }
+
+    /// Announce shutdown to subscribers, flush state and drop every
+    /// connection's event sender so connection tasks wind down. Plugins stay
+    /// registered, so they're still in the saved state on the next start.
+    pub fn shutdown(&mut self) {
+        let event = Event {
+            topic: "daemon.shutdown".to_string(),
+            source: "pandemic".to_string(),
+            data: serde_json::json!({}),
+            timestamp: Some(SystemTime::now()),
+        };
+        self.publish(event);
+        self.save_state();
+        if let Some(writer) = &self.state_writer {
+            writer.flush();
+        }
+        self.connections.clear();
+    }
+
+    /// Bound each connection's event buffer and choose what happens when a
+    /// subscriber can't keep up
+    pub fn set_event_limits(&mut self, capacity: usize, policy: OverflowPolicy) {
+        self.event_bus.capacity = capacity.max(1);
+        self.event_bus.overflow_policy = policy;
+    }
+
+    /// Publish `event` to subscribers, evicting any that have fallen too far
+    /// behind. Evicted plugins are deregistered, which announces them on
+    /// `plugin.deregistered` to the remaining subscribers.
+    pub fn publish(&mut self, event: Event) {
+        for connection_id in self.event_bus.publish(event, &self.connections) {
+            let Some(context) = self.connections.remove(&connection_id) else {
+                continue;
+            };
+            self.event_bus.remove_connection(&connection_id);
+            // Dropping the sender ends the connection's event loop
+            if let Some(plugin_name) = context.plugin_name {
+                self.deregister(&plugin_name);
+            }
+        }
+    }
+
+    pub fn add_connection(&mut self, connection_id: String) -> mpsc::Receiver<Event> {
+        self.add_peer_connection(connection_id, None)
+    }
+
+    /// Like [`Daemon::add_connection`], for a client whose socket reported
+    /// `peer_uid`
+    pub fn add_peer_connection(
+        &mut self,
+        connection_id: String,
+        peer_uid: Option<u32>,
+    ) -> mpsc::Receiver<Event> {
+        let (tx, rx) = mpsc::channel(self.event_bus.capacity);
         let context = ConnectionContext {
             plugin_name: None,
             event_sender: tx,
+            peer_uid,
         };
         self.connections.insert(connection_id, context);
         rx
     }
 
-    pub fn remove_connection(&mut self, connection_id: &str) {
-        if let Some(context) = self.connections.remove(connection_id) {
-            if let Some(plugin_name) = &context.plugin_name {
-                if self.event_bus.subscribers.contains_key(plugin_name) {
-                    self.event_bus.remove_plugin(plugin_name);
-                    self.plugins.remove(plugin_name);
-                    info!(
-                        "Removed plugin {} due to persistent connection close",
-                        plugin_name
-                    );
-                } else {
-                    info!("Transient connection for plugin {} closed", plugin_name);
+    /// Drop a closed connection and its subscriptions. `error` is the I/O
+    /// failure that ended it, if any. When the connection belonged to a
+    /// plugin, subscribers are told
+    /// on `plugin.connection_closed` whether the plugin deregistered first
+    /// (`deregistered`) or went away while still registered (`disconnected`,
+    /// or `error` along with the failure).
+    pub fn remove_connection(&mut self, connection_id: &str, error: Option<&str>) {
+        let context = self.connections.remove(connection_id);
+        let subscribed = self.event_bus.remove_connection(connection_id);
+        let Some(plugin_name) = context.and_then(|context| context.plugin_name) else {
+            return;
+        };
+
+        let mut data = if !self.plugins.contains_key(&plugin_name) {
+            serde_json::json!({"reason": "deregistered"})
+        } else if subscribed {
+            self.event_bus.remove_plugin(&plugin_name);
+            self.event_bus.clear_retained_from(&plugin_name);
+            self.plugins.remove(&plugin_name);
+            self.last_seen.remove(&plugin_name);
+            self.save_state();
+            info!(
+                plugin = %plugin_name,
+                "Removed plugin due to persistent connection close"
+            );
+            match error {
+                Some(error) => serde_json::json!({"reason": "error", "error": error}),
+                None => serde_json::json!({"reason": "disconnected"}),
+            }
+        } else {
+            // The registration outlives one-shot connections
+            info!(plugin = %plugin_name, "Transient connection for plugin closed");
+            return;
+        };
+        data["name"] = serde_json::json!(plugin_name);
+
+        self.publish(Event {
+            topic: "plugin.connection_closed".to_string(),
+            source: "pandemic".to_string(),
+            data,
+            timestamp: Some(SystemTime::now()),
+        });
+    }
+}
+
+fn load_plugins(path: &Path) -> anyhow::Result<HashMap<String, PluginInfo>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+enum StateMessage {
+    Save(HashMap<String, PluginInfo>),
+    /// Answered once every snapshot queued before it has been written
+    Flush(std_mpsc::Sender<()>),
+}
+
+/// Writes plugin registry snapshots to the state file on its own thread, so
+/// the daemon lock is never held across file I/O. Snapshots queued behind a
+/// write collapse into the newest one.
+struct StateWriter {
+    messages: Option<std_mpsc::Sender<StateMessage>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StateWriter {
+    fn spawn(path: PathBuf) -> Self {
+        let (messages, rx) = std_mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            while let Ok(message) = rx.recv() {
+                let mut latest = None;
+                let mut flushed = Vec::new();
+                for message in std::iter::once(message).chain(rx.try_iter()) {
+                    match message {
+                        StateMessage::Save(plugins) => latest = Some(plugins),
+                        StateMessage::Flush(done) => flushed.push(done),
+                    }
+                }
+                if let Some(plugins) = latest {
+                    if let Err(e) = save_plugins(&path, &plugins) {
+                        warn!("Failed to save state to {:?}: {}", path, e);
+                    }
+                }
+                for done in flushed {
+                    let _ = done.send(());
                 }
             }
+        });
+        Self {
+            messages: Some(messages),
+            thread: Some(thread),
         }
     }
+
+    fn save(&self, plugins: HashMap<String, PluginInfo>) {
+        if let Some(messages) = &self.messages {
+            let _ = messages.send(StateMessage::Save(plugins));
+        }
+    }
+
+    /// Block until everything saved so far is on disk
+    fn flush(&self) {
+        let (done, wait) = std_mpsc::channel();
+        if let Some(messages) = &self.messages {
+            if messages.send(StateMessage::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+}
+
+impl Drop for StateWriter {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish the queued writes
+        self.messages.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn save_plugins(path: &Path, plugins: &HashMap<String, PluginInfo>) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Write then rename so a crash never leaves a half-written file behind
+    let tmp = path.with_extension("tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(plugins)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::Request;
+
+    fn register(daemon: &mut Daemon, connection_id: &str, name: &str) {
+        let plugin = PluginInfo {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            config: None,
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        };
+        let request = Request::Register {
+            plugin,
+            force: false,
+            ignore_dependencies: false,
+        };
+        daemon.handle_request(request, connection_id);
+    }
+
+    #[test]
+    fn test_state_file_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_file = temp_dir.path().join("state.json");
+
+        let mut daemon = Daemon::with_state_file(state_file.clone());
+        let _rx = daemon.add_connection("conn_1".to_string());
+        register(&mut daemon, "conn_1", "alpha");
+        register(&mut daemon, "conn_1", "beta");
+        daemon.handle_request(
+            Request::Deregister {
+                name: "beta".to_string(),
+            },
+            "conn_1",
+        );
+        let registered_at = daemon.plugins["alpha"].registered_at;
+        drop(daemon);
+
+        let mut restored = Daemon::with_state_file(state_file.clone());
+        assert_eq!(restored.plugins.len(), 1);
+        assert!(restored.plugins.contains_key("alpha"));
+        assert!(restored.restored.contains("alpha"));
+
+        // Re-registering reconciles with the restored entry instead of duplicating it
+        let _rx = restored.add_connection("conn_2".to_string());
+        register(&mut restored, "conn_2", "alpha");
+        assert_eq!(restored.plugins.len(), 1);
+        assert!(restored.restored.is_empty());
+        assert_eq!(
+            restored.plugins["alpha"]
+                .registered_at
+                .map(|t| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()),
+            registered_at.map(|t| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs())
+        );
+    }
+
+    #[test]
+    fn test_restored_plugins_expire_after_grace() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_file = temp_dir.path().join("state.json");
+
+        let mut daemon = Daemon::with_state_file(state_file.clone());
+        let _rx = daemon.add_connection("conn_1".to_string());
+        register(&mut daemon, "conn_1", "alpha");
+        register(&mut daemon, "conn_1", "beta");
+        // Shutdown writes the state without waiting for the daemon to drop
+        daemon.shutdown();
+        let saved = load_plugins(&state_file).unwrap();
+        assert_eq!(saved.len(), 2);
+
+        let mut restored = Daemon::with_state_file(state_file.clone());
+        let start = Instant::now();
+        let _rx = restored.add_connection("conn_2".to_string());
+        register(&mut restored, "conn_2", "alpha");
+
+        assert!(restored.expire_restored_plugins(start).is_empty());
+        let late = start + DEFAULT_RESTORE_GRACE + Duration::from_secs(1);
+        assert_eq!(restored.expire_restored_plugins(late), vec!["beta"]);
+        assert!(restored.plugins.contains_key("alpha"));
+        assert!(!restored.plugins.contains_key("beta"));
+        assert!(restored.expire_restored_plugins(late).is_empty());
+
+        drop(restored);
+        let saved = load_plugins(&state_file).unwrap();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), vec!["alpha"]);
+    }
+
+    #[test]
+    fn test_corrupt_state_file_starts_empty() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state_file = temp_dir.path().join("state.json");
+        std::fs::write(&state_file, b"not json").unwrap();
+
+        let daemon = Daemon::with_state_file(state_file);
+        assert!(daemon.plugins.is_empty());
+    }
+
+    fn subscribe(daemon: &mut Daemon, connection_id: &str, topic: &str) {
+        let request = Request::Subscribe {
+            topics: vec![topic.to_string()],
+        };
+        daemon.handle_request(request, connection_id);
+    }
+
+    fn flood(daemon: &mut Daemon, count: usize) {
+        for i in 0..count {
+            let request = Request::Publish {
+                topic: "flood.tick".to_string(),
+                data: serde_json::json!({ "i": i }),
+                retain: false,
+            };
+            daemon.handle_request(request, "publisher");
+        }
+    }
+
+    #[test]
+    fn test_slow_subscriber_buffer_stays_bounded() {
+        let mut daemon = Daemon::new();
+        daemon.set_event_limits(8, OverflowPolicy::Drop);
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        let mut slow_rx = daemon.add_connection("slow".to_string());
+        register(&mut daemon, "slow", "slow");
+        subscribe(&mut daemon, "slow", "flood.#");
+
+        flood(&mut daemon, 100);
+
+        let mut buffered = 0;
+        while slow_rx.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, 8);
+        assert_eq!(daemon.event_bus.dropped_events, 92);
+        assert_eq!(daemon.collect_health_metrics().dropped_events, 92);
+        // Dropping events doesn't cost the subscriber its registration
+        assert!(daemon.plugins.contains_key("slow"));
+    }
+
+    #[test]
+    fn test_lagging_subscriber_is_evicted_and_deregistered() {
+        let mut daemon = Daemon::new();
+        daemon.set_event_limits(4, OverflowPolicy::Disconnect);
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        let mut observer_rx = daemon.add_connection("observer".to_string());
+        register(&mut daemon, "observer", "observer");
+        subscribe(&mut daemon, "observer", "plugin.deregistered");
+        let mut slow_rx = daemon.add_connection("slow".to_string());
+        register(&mut daemon, "slow", "slow");
+        subscribe(&mut daemon, "slow", "flood.#");
+
+        flood(&mut daemon, 10);
+
+        assert!(!daemon.connections.contains_key("slow"));
+        assert!(!daemon.plugins.contains_key("slow"));
+        assert!(!daemon.event_bus.subscribers.contains_key("slow"));
+        assert_eq!(daemon.event_bus.dropped_events, 0);
+
+        let event = observer_rx.try_recv().unwrap();
+        assert_eq!(event.topic, "plugin.deregistered");
+        assert_eq!(event.data, serde_json::json!({"name": "slow"}));
+
+        // The evicted connection sees its buffered events, then the channel closes
+        let mut buffered = 0;
+        while slow_rx.try_recv().is_ok() {
+            buffered += 1;
+        }
+        assert_eq!(buffered, 4);
+        assert!(matches!(
+            slow_rx.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn test_health_reports_per_plugin_detail() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        let _subscriber_rx = daemon.add_connection("subscriber".to_string());
+        register(&mut daemon, "subscriber", "subscriber");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["flood.#".to_string(), "plugin.#".to_string()],
+            },
+            "subscriber",
+        );
+        flood(&mut daemon, 3);
+        // Closing a connection without subscriptions leaves the plugin registered
+        daemon.remove_connection("publisher", None);
+
+        let health = daemon.collect_health_metrics();
+        assert_eq!(health.active_plugins, 2);
+        assert_eq!(health.plugins.len(), 2);
+
+        let publisher = &health.plugins[0];
+        assert_eq!(publisher.name, "publisher");
+        assert!(!publisher.connected);
+        assert_eq!(publisher.subscribed_topics, 0);
+        assert_eq!(publisher.events_delivered, 0);
+
+        let subscriber = &health.plugins[1];
+        assert_eq!(subscriber.name, "subscriber");
+        assert!(subscriber.registered_at.is_some());
+        assert!(subscriber.connected);
+        assert_eq!(subscriber.subscribed_topics, 2);
+        assert_eq!(subscriber.events_delivered, 3);
+    }
+
+    #[test]
+    fn test_connection_closed_reports_reason() {
+        let mut daemon = Daemon::new();
+        let mut observer_rx = daemon.add_connection("observer".to_string());
+        register(&mut daemon, "observer", "observer");
+        subscribe(&mut daemon, "observer", "plugin.connection_closed");
+        for name in ["crashed", "failed", "clean"] {
+            let _rx = daemon.add_connection(name.to_string());
+            register(&mut daemon, name, name);
+            subscribe(&mut daemon, name, "flood.#");
+        }
+        daemon.handle_request(
+            Request::Deregister {
+                name: "clean".to_string(),
+            },
+            "clean",
+        );
+
+        daemon.remove_connection("crashed", None);
+        daemon.remove_connection("failed", Some("Broken pipe"));
+        daemon.remove_connection("clean", None);
+
+        let reasons: Vec<_> = std::iter::from_fn(|| observer_rx.try_recv().ok())
+            .map(|event| {
+                assert_eq!(event.topic, "plugin.connection_closed");
+                event.data
+            })
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                serde_json::json!({"name": "crashed", "reason": "disconnected"}),
+                serde_json::json!({"name": "failed", "reason": "error", "error": "Broken pipe"}),
+                serde_json::json!({"name": "clean", "reason": "deregistered"}),
+            ]
+        );
+        assert!(!daemon.plugins.contains_key("crashed"));
+        assert!(!daemon.plugins.contains_key("failed"));
+    }
 }
@@ -1,64 +1,488 @@
 use pandemic_protocol::Event;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use tokio::sync::mpsc::error::TrySendError;
 use tracing::{info, warn};
 
 use crate::daemon::ConnectionContext;
 
+/// Match a topic against a subscription pattern.
+///
+/// Patterns are dot-segmented: `+` matches exactly one segment and a final `#`
+/// matches any number of remaining segments (including none). A trailing `*`
+/// keeps its original meaning of a plain string prefix match.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    if let Some(prefix) = pattern.strip_suffix('*') {
+        return topic.starts_with(prefix);
+    }
+
+    let mut topic_segments = topic.split('.');
+    for segment in pattern.split('.') {
+        match segment {
+            "#" => return true,
+            "+" => {
+                if topic_segments.next().is_none() {
+                    return false;
+                }
+            }
+            literal => {
+                if topic_segments.next() != Some(literal) {
+                    return false;
+                }
+            }
+        }
+    }
+    topic_segments.next().is_none()
+}
+
+/// Whether `pattern` only ever matches the topic spelled the same way
+fn is_literal(pattern: &str) -> bool {
+    !pattern.ends_with('*')
+        && !pattern
+            .split('.')
+            .any(|segment| segment == "+" || segment == "#")
+}
+
+/// Leading topic segments only the daemon itself may publish under
+pub const RESERVED_TOPIC_ROOTS: [&str; 2] = ["daemon", "plugin"];
+
+/// Whether `topic` belongs to the daemon's own `daemon.*`/`plugin.*` namespace
+pub fn is_reserved_topic(topic: &str) -> bool {
+    topic
+        .split('.')
+        .next()
+        .is_some_and(|root| RESERVED_TOPIC_ROOTS.contains(&root))
+}
+
+/// Default number of undelivered events buffered per connection
+pub const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Default number of recent events kept for history queries
+pub const DEFAULT_HISTORY_CAPACITY: usize = 256;
+
+/// What to do when a subscriber's event buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OverflowPolicy {
+    /// Drop the event for that subscriber and count it
+    Drop,
+    /// Disconnect the lagging subscriber
+    Disconnect,
+}
+
 pub struct EventBus {
-    pub subscribers: HashMap<String, Vec<String>>, // plugin_name -> topics
+    /// Read-only outside the bus; `subscribe`, `unsubscribe` and
+    /// `remove_connection` keep the indexes below in step with it
+    pub subscribers: HashMap<String, Vec<String>>, // connection_id -> topics
+    /// Connections subscribed to each wildcard-free topic, so publishing
+    /// finds them without checking every subscriber
+    literal_subscribers: HashMap<String, HashSet<String>>, // topic -> connection_ids
+    /// Connections with at least one wildcard pattern, matched one by one
+    wildcard_subscribers: HashSet<String>,
+    retained: HashMap<String, Event>, // topic -> last retained event
+    pub capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    pub dropped_events: u64,
+    pub delivered: HashMap<String, u64>, // plugin_name -> events delivered
+    /// Events published since start; never reset
+    pub total_published: u64,
+    /// Event copies delivered since start, surviving plugin re-registration
+    pub total_delivered: u64,
+    history: VecDeque<Event>, // oldest first
+    history_capacity: usize,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EventBus {
     pub fn new() -> Self {
         Self {
             subscribers: HashMap::new(),
+            literal_subscribers: HashMap::new(),
+            wildcard_subscribers: HashSet::new(),
+            retained: HashMap::new(),
+            capacity: DEFAULT_EVENT_CAPACITY,
+            overflow_policy: OverflowPolicy::Drop,
+            dropped_events: 0,
+            delivered: HashMap::new(),
+            total_published: 0,
+            total_delivered: 0,
+            history: VecDeque::new(),
+            history_capacity: DEFAULT_HISTORY_CAPACITY,
         }
     }
 
-    pub fn subscribe(&mut self, plugin_name: &str, topics: Vec<String>) {
-        self.subscribers.insert(plugin_name.to_string(), topics);
+    /// Keep at most `capacity` recent events, dropping the oldest beyond it.
+    /// Zero disables the history.
+    pub fn set_history_capacity(&mut self, capacity: usize) {
+        self.history_capacity = capacity;
+        while self.history.len() > capacity {
+            self.history.pop_front();
+        }
     }
 
-    pub fn unsubscribe(&mut self, plugin_name: &str, topics: &[String]) {
-        if let Some(current_topics) = self.subscribers.get_mut(plugin_name) {
+    pub fn history_capacity(&self) -> usize {
+        self.history_capacity
+    }
+
+    /// Up to `limit` recent events whose topic matches `topic_filter`,
+    /// newest first
+    pub fn history(&self, topic_filter: Option<&str>, limit: usize) -> Vec<Event> {
+        self.history
+            .iter()
+            .rev()
+            .filter(|event| topic_filter.is_none_or(|pattern| topic_matches(pattern, &event.topic)))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn record_history(&mut self, event: &Event) {
+        if self.history_capacity == 0 {
+            return;
+        }
+        if self.history.len() == self.history_capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(event.clone());
+    }
+
+    /// Keep `event` as the last value for its topic
+    pub fn retain(&mut self, event: Event) {
+        self.retained.insert(event.topic.clone(), event);
+    }
+
+    /// Retained events matching any of `topics`, for replay to a new subscriber
+    pub fn retained_for(&self, topics: &[String]) -> Vec<Event> {
+        self.retained
+            .values()
+            .filter(|event| {
+                topics
+                    .iter()
+                    .any(|pattern| topic_matches(pattern, &event.topic))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Drop retained events published by `source`
+    pub fn clear_retained_from(&mut self, source: &str) {
+        self.retained.retain(|_, event| event.source != source);
+    }
+
+    pub fn subscribe(&mut self, connection_id: &str, topics: Vec<String>) {
+        self.unindex(connection_id);
+        self.subscribers.insert(connection_id.to_string(), topics);
+        self.index(connection_id);
+    }
+
+    pub fn unsubscribe(&mut self, connection_id: &str, topics: &[String]) {
+        self.unindex(connection_id);
+        if let Some(current_topics) = self.subscribers.get_mut(connection_id) {
             current_topics.retain(|t| !topics.contains(t));
         }
+        self.index(connection_id);
     }
 
-    pub fn publish(&mut self, event: Event, connections: &HashMap<String, ConnectionContext>) {
-        for (plugin_name, topics) in &self.subscribers {
-            let matches = topics.iter().any(|topic| {
-                if topic.ends_with('*') {
-                    event.topic.starts_with(topic.trim_end_matches('*'))
-                } else {
-                    event.topic == *topic
+    /// Add the current subscriptions of `connection_id` to the indexes
+    fn index(&mut self, connection_id: &str) {
+        let Some(topics) = self.subscribers.get(connection_id) else {
+            return;
+        };
+        for topic in topics {
+            if is_literal(topic) {
+                self.literal_subscribers
+                    .entry(topic.clone())
+                    .or_default()
+                    .insert(connection_id.to_string());
+            } else {
+                self.wildcard_subscribers.insert(connection_id.to_string());
+            }
+        }
+    }
+
+    /// Remove the current subscriptions of `connection_id` from the indexes
+    fn unindex(&mut self, connection_id: &str) {
+        self.wildcard_subscribers.remove(connection_id);
+        let Some(topics) = self.subscribers.get(connection_id) else {
+            return;
+        };
+        for topic in topics.iter().filter(|topic| is_literal(topic)) {
+            if let Some(connection_ids) = self.literal_subscribers.get_mut(topic) {
+                connection_ids.remove(connection_id);
+                if connection_ids.is_empty() {
+                    self.literal_subscribers.remove(topic);
                 }
-            });
+            }
+        }
+    }
 
+    /// Deliver `event` to every connection subscribed to a matching topic,
+    /// returning the ids of connections that fell behind and should be
+    /// disconnected. Each connection gets one copy; a plugin name belongs to
+    /// at most one connection, so delivery counts per plugin follow from it.
+    ///
+    /// Subscribers to the exact topic are looked up directly, so the cost
+    /// grows with the matching subscribers plus those using wildcards, not
+    /// with every subscriber.
+    pub fn publish(
+        &mut self,
+        event: Event,
+        connections: &HashMap<String, ConnectionContext>,
+    ) -> Vec<String> {
+        self.total_published += 1;
+        self.record_history(&event);
+
+        let literal = self.literal_subscribers.get(&event.topic);
+        let mut matched: Vec<&String> = literal.into_iter().flatten().collect();
+        for connection_id in &self.wildcard_subscribers {
+            // Also subscribed to the exact topic, and already counted
+            if literal.is_some_and(|ids| ids.contains(connection_id)) {
+                continue;
+            }
+            let matches = self.subscribers.get(connection_id).is_some_and(|topics| {
+                topics
+                    .iter()
+                    .any(|pattern| topic_matches(pattern, &event.topic))
+            });
             if matches {
-                info!(
-                    "Matched event source {}, topic {} for plugin {}",
-                    event.source, event.topic, plugin_name
-                );
-
-                for context in connections.values() {
-                    if let Some(ref conn_plugin_name) = context.plugin_name {
-                        if conn_plugin_name == plugin_name {
-                            if context.event_sender.send(event.clone()).is_err() {
-                                warn!(
-                                    "Failed to send event to plugin {}, channel closed",
-                                    plugin_name
-                                );
-                            }
-                            break;
-                        }
+                matched.push(connection_id);
+            }
+        }
+
+        let mut lagging = Vec::new();
+        for connection_id in matched {
+            let Some(context) = connections.get(connection_id) else {
+                continue;
+            };
+            // Anonymous connections are logged by their connection id
+            let plugin_name = context.plugin_name.as_ref().unwrap_or(connection_id);
+
+            info!(
+                plugin = %plugin_name,
+                source = %event.source,
+                topic = %event.topic,
+                "Matched event for plugin"
+            );
+
+            match context.event_sender.try_send(event.clone()) {
+                Ok(()) => {
+                    if let Some(name) = &context.plugin_name {
+                        *self.delivered.entry(name.clone()).or_default() += 1;
+                    }
+                    self.total_delivered += 1;
+                }
+                Err(TrySendError::Full(_)) => match self.overflow_policy {
+                    OverflowPolicy::Drop => {
+                        self.dropped_events += 1;
+                        warn!(
+                            "Dropping event {} for plugin {}, buffer full",
+                            event.topic, plugin_name
+                        );
+                    }
+                    OverflowPolicy::Disconnect => {
+                        warn!("Disconnecting plugin {}, event buffer full", plugin_name);
+                        lagging.push(connection_id.clone());
                     }
+                },
+                Err(TrySendError::Closed(_)) => {
+                    warn!(
+                        "Failed to send event to plugin {}, channel closed",
+                        plugin_name
+                    );
                 }
             }
         }
+        lagging
+    }
+
+    /// Drop the subscriptions of `connection_id`, returning whether it had any
+    pub fn remove_connection(&mut self, connection_id: &str) -> bool {
+        self.unindex(connection_id);
+        self.subscribers.remove(connection_id).is_some()
     }
 
     pub fn remove_plugin(&mut self, plugin_name: &str) {
-        self.subscribers.remove(plugin_name);
+        self.delivered.remove(plugin_name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(topic: &str, source: &str) -> Event {
+        Event {
+            topic: topic.to_string(),
+            source: source.to_string(),
+            data: serde_json::json!({}),
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn test_retained_events_replay_by_pattern() {
+        let mut bus = EventBus::new();
+        bus.retain(event("health.a", "proxy-a"));
+        bus.retain(event("health.b", "proxy-b"));
+        bus.retain(event("plugin.registered", "pandemic"));
+
+        let mut topics: Vec<_> = bus
+            .retained_for(&["health.+".to_string()])
+            .into_iter()
+            .map(|e| e.topic)
+            .collect();
+        topics.sort();
+        assert_eq!(topics, vec!["health.a", "health.b"]);
+
+        bus.clear_retained_from("proxy-a");
+        let remaining = bus.retained_for(&["health.+".to_string()]);
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].topic, "health.b");
+    }
+
+    #[test]
+    fn test_retain_keeps_last_value() {
+        let mut bus = EventBus::new();
+        let mut first = event("health.a", "proxy-a");
+        first.data = serde_json::json!({"healthy": true});
+        let mut second = event("health.a", "proxy-a");
+        second.data = serde_json::json!({"healthy": false});
+        bus.retain(first);
+        bus.retain(second);
+
+        let retained = bus.retained_for(&["health.a".to_string()]);
+        assert_eq!(retained.len(), 1);
+        assert_eq!(retained[0].data["healthy"], false);
+    }
+
+    #[test]
+    fn test_history_is_bounded_and_newest_first() {
+        let mut bus = EventBus::new();
+        bus.set_history_capacity(3);
+        let connections = HashMap::new();
+        for topic in ["health.a", "plugin.registered", "health.b", "health.c"] {
+            bus.publish(event(topic, "test"), &connections);
+        }
+
+        let topics = |events: Vec<Event>| events.into_iter().map(|e| e.topic).collect::<Vec<_>>();
+        assert_eq!(
+            topics(bus.history(None, 10)),
+            vec!["health.c", "health.b", "plugin.registered"]
+        );
+        assert_eq!(topics(bus.history(Some("health.+"), 1)), vec!["health.c"]);
+
+        bus.set_history_capacity(0);
+        bus.publish(event("health.d", "test"), &connections);
+        assert!(bus.history(None, 10).is_empty());
+    }
+
+    #[test]
+    fn test_indexed_delivery_matches_every_pattern_once() {
+        let mut bus = EventBus::new();
+        let mut connections = HashMap::new();
+        let mut receivers = HashMap::new();
+        for (connection_id, topics) in [
+            ("exact", vec!["sensor.reading"]),
+            ("wildcard", vec!["sensor.+"]),
+            ("both", vec!["sensor.reading", "sensor.#", "sensor*"]),
+            ("other", vec!["health.cpu", "health.+"]),
+        ] {
+            let (event_sender, event_rx) = tokio::sync::mpsc::channel(16);
+            connections.insert(
+                connection_id.to_string(),
+                ConnectionContext {
+                    plugin_name: Some(connection_id.to_string()),
+                    event_sender,
+                    peer_uid: None,
+                },
+            );
+            receivers.insert(connection_id, event_rx);
+            bus.subscribe(
+                connection_id,
+                topics.into_iter().map(String::from).collect(),
+            );
+        }
+        let mut received = |bus: &mut EventBus, topic: &str| {
+            bus.publish(event(topic, "test"), &connections);
+            let mut ids: Vec<_> = receivers
+                .iter_mut()
+                .flat_map(|(id, rx)| std::iter::from_fn(|| rx.try_recv().ok()).map(|_| *id))
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(
+            received(&mut bus, "sensor.reading"),
+            vec!["both", "exact", "wildcard"]
+        );
+        assert_eq!(
+            received(&mut bus, "sensor.status"),
+            vec!["both", "wildcard"]
+        );
+        assert_eq!(received(&mut bus, "health.cpu"), vec!["other"]);
+        assert!(received(&mut bus, "udp.packet").is_empty());
+
+        // Subscribing again replaces the indexed topics
+        bus.subscribe("exact", vec!["udp.packet".to_string()]);
+        bus.unsubscribe("both", &["sensor.#".to_string(), "sensor*".to_string()]);
+        bus.remove_connection("wildcard");
+        assert_eq!(received(&mut bus, "sensor.reading"), vec!["both"]);
+        assert!(received(&mut bus, "sensor.status").is_empty());
+        assert_eq!(received(&mut bus, "udp.packet"), vec!["exact"]);
+        assert_eq!(bus.total_delivered, 8);
+
+        bus.remove_connection("both");
+        bus.remove_connection("exact");
+        bus.remove_connection("other");
+        assert!(bus.literal_subscribers.is_empty());
+        assert!(bus.wildcard_subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_exact_match() {
+        assert!(topic_matches("plugin.registered", "plugin.registered"));
+        assert!(!topic_matches("plugin.registered", "plugin.deregistered"));
+        assert!(!topic_matches("plugin", "plugin.registered"));
+    }
+
+    #[test]
+    fn test_multi_level_wildcard() {
+        assert!(topic_matches("plugin.#", "plugin.registered"));
+        assert!(topic_matches("plugin.#", "plugin.a.b"));
+        assert!(topic_matches("plugin.#", "plugin"));
+        assert!(topic_matches("#", "anything.at.all"));
+        assert!(!topic_matches("plugin.#", "health.cpu"));
+    }
+
+    #[test]
+    fn test_single_level_wildcard() {
+        assert!(topic_matches("health.+", "health.a"));
+        assert!(!topic_matches("health.+", "health.a.b"));
+        assert!(!topic_matches("health.+", "health"));
+        assert!(topic_matches("health.+.status", "health.cpu.status"));
+        assert!(!topic_matches("health.+.status", "health.cpu.load"));
+        assert!(topic_matches("+.+.cpu", "health.node1.cpu"));
+    }
+
+    #[test]
+    fn test_legacy_trailing_star() {
+        assert!(topic_matches("plugin.*", "plugin.registered"));
+        assert!(topic_matches("plugin.*", "plugin.a.b"));
+        assert!(topic_matches("*", "anything"));
+        assert!(!topic_matches("plugin.*", "health.cpu"));
+    }
+
+    #[test]
+    fn test_reserved_topics() {
+        assert!(is_reserved_topic("plugin.deregistered"));
+        assert!(is_reserved_topic("daemon.shutdown"));
+        assert!(is_reserved_topic("plugin"));
+        assert!(!is_reserved_topic("plugins.custom"));
+        assert!(!is_reserved_topic("health.foo"));
+        assert!(!is_reserved_topic("config.updated"));
     }
 }
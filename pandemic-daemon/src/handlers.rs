@@ -1,82 +1,369 @@
-use pandemic_protocol::{Event, Request, Response};
-use serde_json::json;
-use std::time::SystemTime;
-use tracing::info;
+use pandemic_common::init::glob_matches;
+use pandemic_common::{ConfigManager, FileConfigManager};
+use pandemic_protocol::{
+    is_compatible_version, ErrorCode, Event, Request, Response, Topic, PROTOCOL_VERSION,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
 
 use crate::daemon::Daemon;
+use crate::event_bus::is_reserved_topic;
+
+/// Longest heartbeat interval a plugin may register with, one day
+pub const MAX_HEARTBEAT_INTERVAL: u64 = 86_400;
+
+/// Longest glob `DeregisterMatching` accepts, in characters. Matching runs
+/// under the write lock for every plugin name, so patterns stay short.
+pub const MAX_PATTERN_LEN: usize = 256;
+
+/// Whether `request` leaves daemon state untouched, apart from request
+/// counters, and may run alongside other reads
+pub fn is_read_only(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Hello { .. }
+            | Request::ListPlugins
+            | Request::GetPlugin { .. }
+            | Request::ListSubscriptions
+            | Request::GetHealth
+            | Request::GetMetrics
+            | Request::Ping
+            | Request::GetEventHistory { .. }
+            | Request::Info
+            | Request::GetConfig { .. }
+    )
+}
+
+/// A change to a plugin's config override
+#[derive(Debug, Clone)]
+pub enum ConfigChange {
+    Set { plugin: String, config: Value },
+    Clear { plugin: String },
+}
+
+impl ConfigChange {
+    /// The change `request` asks for, if it is `SetConfigOverride` or
+    /// `ClearConfigOverride`
+    pub fn from_request(request: &Request) -> Option<Self> {
+        match request {
+            Request::SetConfigOverride { plugin, config } => Some(ConfigChange::Set {
+                plugin: plugin.clone(),
+                config: config.clone(),
+            }),
+            Request::ClearConfigOverride { plugin } => Some(ConfigChange::Clear {
+                plugin: plugin.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn plugin(&self) -> &str {
+        match self {
+            ConfigChange::Set { plugin, .. } | ConfigChange::Clear { plugin } => plugin,
+        }
+    }
+
+    /// Write the override through `manager` and load the merged config that
+    /// results. This touches the filesystem, so callers shouldn't hold the
+    /// daemon lock.
+    pub fn apply(&self, manager: &FileConfigManager) -> Result<Value, Response> {
+        let plugin = self.plugin();
+        match self {
+            ConfigChange::Set { config, .. } => {
+                manager.set_override(plugin, config).map_err(|e| {
+                    Response::error_with_code(
+                        ErrorCode::Internal,
+                        format!("Failed to set config override for '{}': {}", plugin, e),
+                    )
+                })?;
+                info!(plugin = %plugin, "Set config override");
+            }
+            ConfigChange::Clear { .. } => {
+                manager.clear_override(plugin).map_err(|e| {
+                    Response::error_with_code(
+                        ErrorCode::Internal,
+                        format!("Failed to clear config override for '{}': {}", plugin, e),
+                    )
+                })?;
+                info!(plugin = %plugin, "Cleared config override");
+            }
+        }
+        manager.get_config(plugin).map_err(|e| {
+            Response::error_with_code(
+                ErrorCode::Internal,
+                format!("Config for '{}' changed but failed to load: {}", plugin, e),
+            )
+        })
+    }
+}
 
 impl Daemon {
+    /// Handle any request, including ones that change daemon state
     pub fn handle_request(&mut self, request: Request, connection_id: &str) -> Response {
+        self.count_request(&request);
+
         match request {
-            Request::Register { mut plugin } => {
-                info!("Registering plugin: {}", plugin.name);
+            Request::Register {
+                mut plugin,
+                force,
+                ignore_dependencies,
+            } => {
+                if let Some(interval) = plugin.heartbeat_interval {
+                    if !(1..=MAX_HEARTBEAT_INTERVAL).contains(&interval) {
+                        return Response::error_with_code(
+                            ErrorCode::InvalidRequest,
+                            format!(
+                                "heartbeat_interval must be between 1 and {} seconds, got {}",
+                                MAX_HEARTBEAT_INTERVAL, interval
+                            ),
+                        );
+                    }
+                }
+
+                if let Some(acl) = &self.acl {
+                    let peer_uid = self
+                        .connections
+                        .get(connection_id)
+                        .and_then(|context| context.peer_uid);
+                    if !acl.can_register(peer_uid, &plugin.name) {
+                        warn!(plugin = %plugin.name, ?peer_uid, "Register denied by ACL");
+                        return Response::error_with_code(
+                            ErrorCode::PermissionDenied,
+                            format!("Not allowed to register plugin '{}'", plugin.name),
+                        );
+                    }
+                }
+
+                // Plugins restored from saved state haven't reconnected yet,
+                // so they don't satisfy a dependency
+                let missing: Vec<String> = plugin
+                    .dependencies
+                    .iter()
+                    .filter(|dependency| {
+                        !self.plugins.contains_key(*dependency)
+                            || self.restored.contains(*dependency)
+                    })
+                    .cloned()
+                    .collect();
+                if !missing.is_empty() {
+                    if !ignore_dependencies {
+                        return Response::dependencies_unmet(missing);
+                    }
+                    warn!(plugin = %plugin.name, ?missing, "Registering despite missing dependencies");
+                }
+
+                let owner = self.connections.iter().find_map(|(id, context)| {
+                    (id != connection_id && context.plugin_name.as_ref() == Some(&plugin.name))
+                        .then(|| id.clone())
+                });
+                if let Some(owner) = owner {
+                    if !force {
+                        return Response::conflict(format!(
+                            "Plugin '{}' is already registered by another connection",
+                            plugin.name
+                        ));
+                    }
+                    info!(plugin = %plugin.name, previous_connection = %owner, "Taking over plugin");
+                    // Detach the old owner so its disconnect can't remove the
+                    // new registration, and drop the subscriptions it made
+                    if let Some(context) = self.connections.get_mut(&owner) {
+                        context.plugin_name = None;
+                    }
+                    self.event_bus.remove_connection(&owner);
+                    self.event_bus.remove_plugin(&plugin.name);
+                }
+
+                info!(plugin = %plugin.name, "Registering plugin");
                 plugin.registered_at = Some(SystemTime::now());
 
+                if self.restored.remove(&plugin.name) {
+                    // Keep the original registration time for plugins that
+                    // were already known before a daemon restart
+                    info!(plugin = %plugin.name, "Reconciled plugin with saved state");
+                    if let Some(previous) = self.plugins.get(&plugin.name) {
+                        plugin.registered_at = previous.registered_at;
+                    }
+                }
+
                 if let Some(context) = self.connections.get_mut(connection_id) {
                     context.plugin_name = Some(plugin.name.clone());
                 }
 
+                // Delivery counts start over with each registration
+                self.event_bus.delivered.insert(plugin.name.clone(), 0);
+
+                if plugin.heartbeat_interval.is_some() {
+                    self.last_seen.insert(plugin.name.clone(), Instant::now());
+                } else {
+                    self.last_seen.remove(&plugin.name);
+                }
+
                 let event = Event {
                     topic: "plugin.registered".to_string(),
                     source: "pandemic".to_string(),
                     data: json!(plugin),
                     timestamp: Some(SystemTime::now()),
                 };
-                self.event_bus.publish(event, &self.connections);
+                self.publish(event);
 
                 self.plugins.insert(plugin.name.clone(), plugin);
+                self.save_state();
                 Response::success()
             }
-            Request::Deregister { name } => match self.plugins.remove(&name) {
-                Some(plugin) => {
-                    info!("Deregistered plugin: {}", plugin.name);
+            Request::Deregister { name } => {
+                if self.deregister(&name) {
+                    Response::success()
+                } else {
+                    Response::not_found(format!("Plugin '{}' not found", name))
+                }
+            }
+            Request::DeregisterMatching { pattern, confirm } => {
+                if pattern.chars().count() > MAX_PATTERN_LEN {
+                    return Response::error_with_code(
+                        ErrorCode::InvalidRequest,
+                        format!("Pattern is longer than {} characters", MAX_PATTERN_LEN),
+                    );
+                }
+                if !confirm && pattern.chars().all(|c| c == '*' || c == '?') {
+                    return Response::error_with_code(
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "Pattern '{}' matches any name; set confirm to deregister every match",
+                            pattern
+                        ),
+                    );
+                }
 
-                    let event = Event {
-                        topic: "plugin.deregistered".to_string(),
-                        source: "pandemic".to_string(),
-                        data: json!({"name": name}),
-                        timestamp: Some(SystemTime::now()),
-                    };
-                    self.event_bus.publish(event, &self.connections);
-                    self.event_bus.remove_plugin(&name);
+                let mut matching: Vec<String> = self
+                    .plugins
+                    .keys()
+                    .filter(|name| glob_matches(&pattern, name))
+                    .cloned()
+                    .collect();
+                matching.sort();
+                matching.retain(|name| self.deregister(name));
+                info!(pattern = %pattern, count = matching.len(), "Deregistered matching plugins");
+                Response::success_with_data(json!(matching))
+            }
+            Request::UpdatePluginConfig {
+                name,
+                config,
+                description,
+            } => {
+                let owned = self
+                    .connection_of(&name)
+                    .is_some_and(|id| id == connection_id);
+                let Some(plugin) = self.plugins.get_mut(&name) else {
+                    return Response::not_found(format!("Plugin '{}' not found", name));
+                };
+                if !owned {
+                    return Response::error_with_code(
+                        ErrorCode::PermissionDenied,
+                        format!("Plugin '{}' is owned by another connection", name),
+                    );
+                }
 
+                if let Some(config) = config {
+                    plugin.config = Some(config);
+                }
+                if let Some(description) = description {
+                    plugin.description = Some(description);
+                }
+                let event = Event {
+                    topic: "plugin.updated".to_string(),
+                    source: "pandemic".to_string(),
+                    data: json!(plugin),
+                    timestamp: Some(SystemTime::now()),
+                };
+                info!(plugin = %name, "Updated plugin metadata");
+                self.publish(event);
+                self.save_state();
+                Response::success()
+            }
+            Request::SetConfigOverride { plugin, config } => {
+                self.change_config(ConfigChange::Set { plugin, config }, connection_id)
+            }
+            Request::ClearConfigOverride { plugin } => {
+                self.change_config(ConfigChange::Clear { plugin }, connection_id)
+            }
+            Request::Heartbeat { name } => {
+                if !self.plugins.contains_key(&name) {
+                    Response::not_found(format!("Plugin '{}' not found", name))
+                } else if let Some(last_seen) = self.last_seen.get_mut(&name) {
+                    *last_seen = Instant::now();
                     Response::success()
+                } else {
+                    Response::error_with_code(
+                        ErrorCode::InvalidRequest,
+                        format!(
+                            "Plugin '{}' was registered without a heartbeat_interval",
+                            name
+                        ),
+                    )
                 }
-                None => Response::not_found(format!("Plugin '{}' not found", name)),
-            },
-            Request::ListPlugins => {
-                let plugins: Vec<&_> = self.plugins.values().collect();
-                Response::success_with_data(json!(plugins))
             }
-            Request::GetPlugin { name } => match self.plugins.get(&name) {
-                Some(plugin) => Response::success_with_data(json!(plugin)),
-                None => Response::not_found(format!("Plugin '{}' not found", name)),
-            },
             Request::Subscribe { topics } => {
-                if let Some(context) = self.connections.get(connection_id) {
-                    if let Some(plugin_name) = &context.plugin_name {
-                        self.event_bus.subscribe(plugin_name, topics);
-                        Response::success()
-                    } else {
-                        Response::error("Must register plugin before subscribing to events")
+                let topics = match topics
+                    .iter()
+                    .map(|topic| Topic::pattern(topic).map(Topic::into_string))
+                    .collect::<Result<Vec<_>, _>>()
+                {
+                    Ok(topics) => topics,
+                    Err(e) => {
+                        return Response::error_with_code(ErrorCode::InvalidRequest, e.to_string())
                     }
-                } else {
-                    Response::error("Connection not found")
+                };
+                let Some(context) = self.connections.get(connection_id) else {
+                    return Response::error_with_code(ErrorCode::Internal, "Connection not found");
+                };
+                // Connections that never registered are checked as `unknown`
+                let plugin_name = context.plugin_name.as_deref().unwrap_or("unknown");
+                if let Some(denied) = self.acl.as_ref().and_then(|acl| {
+                    topics
+                        .iter()
+                        .find(|topic| !acl.can_subscribe(plugin_name, topic))
+                }) {
+                    warn!(plugin = %plugin_name, topic = %denied, "Subscribe denied by ACL");
+                    return Response::error_with_code(
+                        ErrorCode::PermissionDenied,
+                        format!(
+                            "Plugin '{}' is not allowed to subscribe to '{}'",
+                            plugin_name, denied
+                        ),
+                    );
                 }
-            }
-            Request::Unsubscribe { topics } => {
-                if let Some(context) = self.connections.get(connection_id) {
-                    if let Some(plugin_name) = &context.plugin_name {
-                        self.event_bus.unsubscribe(plugin_name, &topics);
-                        Response::success()
-                    } else {
-                        Response::error("Must register plugin before unsubscribing from events")
+
+                // Replay last values so late subscribers see current state
+                for event in self.event_bus.retained_for(&topics) {
+                    if context.event_sender.try_send(event).is_err() {
+                        warn!("Skipping retained replay for {}, buffer full", plugin_name);
+                        break;
                     }
-                } else {
-                    Response::error("Connection not found")
+                    if let Some(name) = &context.plugin_name {
+                        *self.event_bus.delivered.entry(name.clone()).or_default() += 1;
+                    }
+                    self.event_bus.total_delivered += 1;
                 }
+                self.event_bus.subscribe(connection_id, topics);
+                Response::success()
             }
-            Request::Publish { topic, data } => {
+            Request::Unsubscribe { topics } => {
+                self.event_bus.unsubscribe(connection_id, &topics);
+                Response::success()
+            }
+            Request::Publish {
+                topic,
+                data,
+                retain,
+            } => {
+                let topic = match Topic::new(&topic) {
+                    Ok(topic) => topic.into_string(),
+                    Err(e) => {
+                        return Response::error_with_code(ErrorCode::InvalidRequest, e.to_string())
+                    }
+                };
                 let source = if let Some(context) = self.connections.get(connection_id) {
                     context
                         .plugin_name
@@ -86,19 +373,1364 @@ impl Daemon {
                     "unknown".to_string()
                 };
 
+                // Daemon events go through `Daemon::publish` directly, so only
+                // clients reach this check
+                if is_reserved_topic(&topic) {
+                    warn!(plugin = %source, topic = %topic, "Rejected publish to reserved topic");
+                    return Response::error_with_code(
+                        ErrorCode::PermissionDenied,
+                        format!("Cannot publish to reserved topic '{}'", topic),
+                    );
+                }
+
+                if let Some(acl) = &self.acl {
+                    if !acl.can_publish(&source, &topic) {
+                        warn!(plugin = %source, topic = %topic, "Publish denied by ACL");
+                        return Response::error_with_code(
+                            ErrorCode::PermissionDenied,
+                            format!(
+                                "Plugin '{}' is not allowed to publish to '{}'",
+                                source, topic
+                            ),
+                        );
+                    }
+                }
+
                 let event = Event {
                     topic,
                     source,
                     data,
                     timestamp: Some(SystemTime::now()),
                 };
-                self.event_bus.publish(event, &self.connections);
+                if retain {
+                    self.event_bus.retain(event.clone());
+                }
+                self.publish(event);
                 Response::success()
             }
+            request => self.answer_read(request),
+        }
+    }
+
+    /// Handle a request for which [`is_read_only`] holds, so callers only
+    /// need shared access to the daemon
+    pub fn handle_read_request(&self, request: Request) -> Response {
+        self.count_request(&request);
+        self.answer_read(request)
+    }
+
+    pub(crate) fn count_request(&self, request: &Request) {
+        *self
+            .requests_handled
+            .lock()
+            .unwrap()
+            .entry(request.kind().to_string())
+            .or_default() += 1;
+    }
+
+    fn answer_read(&self, request: Request) -> Response {
+        match request {
+            Request::Hello {
+                protocol_version,
+                client,
+                encoding,
+            } => {
+                if is_compatible_version(protocol_version) {
+                    info!(
+                        "Client {} connected with protocol version {}",
+                        client, protocol_version
+                    );
+                    // Every encoding is supported, so the request is granted
+                    // as is; the connection switches once this reply is sent
+                    Response::success_with_data(json!({
+                        "protocol_version": PROTOCOL_VERSION,
+                        "encoding": encoding.unwrap_or_default(),
+                    }))
+                } else {
+                    warn!(
+                        "Rejecting client {} with incompatible protocol version {}",
+                        client, protocol_version
+                    );
+                    Response::incompatible_version()
+                }
+            }
+            Request::ListPlugins => {
+                let plugins: Vec<&_> = self.plugins.values().collect();
+                Response::success_with_data(json!(plugins))
+            }
+            Request::GetPlugin { name } => match self.plugins.get(&name) {
+                Some(plugin) => Response::success_with_data(json!(plugin)),
+                None => Response::not_found(format!("Plugin '{}' not found", name)),
+            },
+            Request::ListSubscriptions => {
+                let subscriptions: BTreeMap<&String, &Vec<String>> = self
+                    .event_bus
+                    .subscribers
+                    .iter()
+                    .map(|(connection_id, topics)| {
+                        let name = self
+                            .connections
+                            .get(connection_id)
+                            .and_then(|context| context.plugin_name.as_ref())
+                            .unwrap_or(connection_id);
+                        (name, topics)
+                    })
+                    .collect();
+                Response::success_with_data(json!(subscriptions))
+            }
             Request::GetHealth => {
                 let health = self.collect_health_metrics();
                 Response::success_with_data(json!(health))
             }
+            Request::GetMetrics => Response::success_with_data(json!(self.metrics())),
+            Request::Ping => Response::success(),
+            Request::GetEventHistory {
+                topic_filter,
+                limit,
+            } => Response::success_with_data(json!(self
+                .event_bus
+                .history(topic_filter.as_deref(), limit))),
+            Request::Info => Response::success_with_data(json!(self.info())),
+            Request::GetConfig { plugin } => {
+                let manager = match self.config_manager(&plugin) {
+                    Ok(manager) => manager,
+                    Err(response) => return response,
+                };
+                match manager.get_config(&plugin) {
+                    Ok(config) => Response::success_with_data(config),
+                    Err(e) => Response::error_with_code(
+                        ErrorCode::Internal,
+                        format!("Failed to load config for '{}': {}", plugin, e),
+                    ),
+                }
+            }
+            other => Response::error_with_code(
+                ErrorCode::Internal,
+                format!("{} cannot be handled as a read", other.kind()),
+            ),
+        }
+    }
+
+    /// The config manager to serve `plugin` from, or the response refusing
+    /// the request
+    fn config_manager(&self, plugin: &str) -> Result<FileConfigManager, Response> {
+        let Some(manager) = &self.config else {
+            return Err(Response::error_with_code(
+                ErrorCode::Unavailable,
+                "Plugin configuration is not enabled on this daemon",
+            ));
+        };
+        // Plugin names become file names in the config directories
+        if plugin.is_empty() || plugin.starts_with('.') || plugin.contains(['/', '\\']) {
+            return Err(Response::error_with_code(
+                ErrorCode::InvalidRequest,
+                format!("Invalid plugin name '{}'", plugin),
+            ));
+        }
+        Ok(manager.clone())
+    }
+
+    /// Check that `connection_id` may make `change`, returning the config
+    /// manager to apply it with. A plugin may change its own override;
+    /// changing another's needs an ACL `configure` grant, either on the
+    /// plugin the connection registered as or on its peer uid.
+    pub fn authorize_config_change(
+        &self,
+        change: &ConfigChange,
+        connection_id: &str,
+    ) -> Result<FileConfigManager, Response> {
+        let plugin = change.plugin();
+        let manager = self.config_manager(plugin)?;
+        if let ConfigChange::Set { config, .. } = change {
+            if !config.is_object() {
+                return Err(Response::error_with_code(
+                    ErrorCode::InvalidRequest,
+                    format!("Config override for '{}' must be an object", plugin),
+                ));
+            }
+        }
+
+        let owned = self
+            .connection_of(plugin)
+            .is_some_and(|id| id == connection_id);
+        // Connections that never registered are checked as `unknown`
+        let caller = self
+            .connections
+            .get(connection_id)
+            .and_then(|context| context.plugin_name.as_deref())
+            .unwrap_or("unknown");
+        let peer_uid = self
+            .connections
+            .get(connection_id)
+            .and_then(|context| context.peer_uid);
+        let granted = self.acl.as_ref().is_some_and(|acl| {
+            acl.can_configure(caller, plugin) || acl.peer_can_configure(peer_uid, plugin)
+        });
+        if !owned && !granted {
+            warn!(plugin = %caller, target = %plugin, "Config change denied");
+            return Err(Response::error_with_code(
+                ErrorCode::PermissionDenied,
+                format!(
+                    "Plugin '{}' is not allowed to configure '{}'",
+                    caller, plugin
+                ),
+            ));
+        }
+        Ok(manager)
+    }
+
+    /// Authorize and apply `change` while holding the daemon. Connections
+    /// split these steps so the file I/O runs without the lock.
+    fn change_config(&mut self, change: ConfigChange, connection_id: &str) -> Response {
+        let config = match self
+            .authorize_config_change(&change, connection_id)
+            .and_then(|manager| change.apply(&manager))
+        {
+            Ok(config) => config,
+            Err(response) => return response,
+        };
+        self.config_changed(change.plugin(), config)
+    }
+
+    /// Announce the merged `config` of `plugin` on
+    /// `daemon.config.changed.<plugin>` after its override changed,
+    /// answering with the same config. The topic sits under the reserved
+    /// `daemon.` root so no client can forge a config change.
+    pub fn config_changed(&mut self, plugin: &str, config: Value) -> Response {
+        self.publish(Event {
+            topic: format!("daemon.config.changed.{}", plugin),
+            source: "pandemic".to_string(),
+            data: json!({ "plugin": plugin, "config": config }),
+            timestamp: Some(SystemTime::now()),
+        });
+        Response::success_with_data(config)
+    }
+
+    /// Remove a plugin and announce it on `plugin.deregistered`. Returns
+    /// false if no such plugin was registered.
+    pub fn deregister(&mut self, name: &str) -> bool {
+        let Some(plugin) = self.plugins.remove(name) else {
+            return false;
+        };
+        info!(plugin = %plugin.name, "Deregistered plugin");
+
+        let event = Event {
+            topic: "plugin.deregistered".to_string(),
+            source: "pandemic".to_string(),
+            data: json!({"name": name}),
+            timestamp: Some(SystemTime::now()),
+        };
+        self.publish(event);
+        // The owning connection stays open but stops receiving its events
+        if let Some(connection_id) = self.connection_of(name).cloned() {
+            self.event_bus.remove_connection(&connection_id);
+        }
+        self.event_bus.remove_plugin(name);
+        self.event_bus.clear_retained_from(name);
+        self.restored.remove(name);
+        self.last_seen.remove(name);
+        self.save_state();
+        true
+    }
+
+    /// Deregister every plugin that has gone two heartbeat intervals without
+    /// a heartbeat, returning their names
+    pub fn expire_stale_plugins(&mut self, now: Instant) -> Vec<String> {
+        let expired: Vec<String> = self
+            .last_seen
+            .iter()
+            .filter(|(name, last_seen)| {
+                self.plugins
+                    .get(*name)
+                    .and_then(|plugin| plugin.heartbeat_interval)
+                    .is_some_and(|interval| {
+                        // Saved state may predate the interval bounds
+                        now.saturating_duration_since(**last_seen)
+                            > Duration::from_secs(interval).saturating_mul(2)
+                    })
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in &expired {
+            warn!(plugin = %name, "Plugin missed its heartbeat, deregistering");
+            self.deregister(name);
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::{DaemonInfo, PluginInfo};
+    use std::collections::HashMap;
+
+    fn register(daemon: &mut Daemon, connection_id: &str, name: &str) {
+        register_with_heartbeat(daemon, connection_id, name, None);
+    }
+
+    fn register_with_heartbeat(
+        daemon: &mut Daemon,
+        connection_id: &str,
+        name: &str,
+        heartbeat_interval: Option<u64>,
+    ) -> Response {
+        let plugin = PluginInfo {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            config: None,
+            registered_at: None,
+            heartbeat_interval,
+            dependencies: vec![],
+        };
+        let request = Request::Register {
+            plugin,
+            force: false,
+            ignore_dependencies: false,
+        };
+        daemon.handle_request(request, connection_id)
+    }
+
+    fn heartbeat(daemon: &mut Daemon, name: &str) -> Response {
+        let request = Request::Heartbeat {
+            name: name.to_string(),
+        };
+        daemon.handle_request(request, "transient")
+    }
+
+    fn publish(daemon: &mut Daemon, connection_id: &str, topic: &str, retain: bool) {
+        let request = Request::Publish {
+            topic: topic.to_string(),
+            data: json!({}),
+            retain,
+        };
+        daemon.handle_request(request, connection_id);
+    }
+
+    #[test]
+    fn test_retained_event_replayed_to_late_subscriber() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        publish(&mut daemon, "publisher", "state.retained", true);
+        publish(&mut daemon, "publisher", "state.transient", false);
+
+        let mut late_rx = daemon.add_connection("late".to_string());
+        register(&mut daemon, "late", "late");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["state.#".to_string()],
+            },
+            "late",
+        );
+
+        let replayed = late_rx.try_recv().unwrap();
+        assert_eq!(replayed.topic, "state.retained");
+        assert_eq!(replayed.source, "publisher");
+        assert!(late_rx.try_recv().is_err());
+
+        // Live events still flow after the replay
+        publish(&mut daemon, "publisher", "state.transient", false);
+        assert_eq!(late_rx.try_recv().unwrap().topic, "state.transient");
+    }
+
+    #[test]
+    fn test_metrics_count_events_and_requests() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        let _subscriber_rx = daemon.add_connection("subscriber".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        register(&mut daemon, "subscriber", "subscriber");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["custom.#".to_string()],
+            },
+            "subscriber",
+        );
+
+        for _ in 0..3 {
+            publish(&mut daemon, "publisher", "custom.event", false);
+        }
+        publish(&mut daemon, "publisher", "other.event", false);
+
+        // Each registration also publishes a plugin.registered event
+        let metrics = daemon.metrics();
+        assert_eq!(metrics.events_published, 6);
+        assert_eq!(metrics.events_delivered, 3);
+        assert_eq!(metrics.events_dropped, 0);
+        assert_eq!(metrics.requests["Publish"], 4);
+        assert_eq!(metrics.requests["Register"], 2);
+        assert_eq!(metrics.requests["Subscribe"], 1);
+
+        match daemon.handle_request(Request::GetMetrics, "transient") {
+            Response::Success { data: Some(data) } => {
+                assert_eq!(data["events_published"], 6);
+                assert_eq!(data["requests"]["GetMetrics"], 1);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_event_history_returns_filtered_slice() {
+        let mut daemon = Daemon::new();
+        let _rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        for topic in ["health.a", "udp.packet", "health.b", "health.c"] {
+            publish(&mut daemon, "publisher", topic, false);
+        }
+
+        let request = Request::GetEventHistory {
+            topic_filter: Some("health.+".to_string()),
+            limit: 2,
+        };
+        match daemon.handle_request(request, "publisher") {
+            Response::Success { data: Some(data) } => {
+                let topics: Vec<_> = data
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|event| event["topic"].as_str().unwrap())
+                    .collect();
+                assert_eq!(topics, vec!["health.c", "health.b"]);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_info_reports_versions() {
+        let mut daemon = Daemon::new();
+        match daemon.handle_request(Request::Info, "client") {
+            Response::Success { data: Some(data) } => {
+                let info: DaemonInfo = serde_json::from_value(data).unwrap();
+                assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+                assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+                assert!(info.started_at.is_some());
+                assert!(info.features.contains(&"event-history".to_string()));
+                assert!(!info.features.contains(&"acl".to_string()));
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ping_succeeds_without_side_effects() {
+        let mut daemon = Daemon::new();
+        let _rx = daemon.add_connection("pinger".to_string());
+        assert!(matches!(
+            daemon.handle_request(Request::Ping, "pinger"),
+            Response::Success { data: None }
+        ));
+        assert!(daemon.plugins.is_empty());
+        assert_eq!(daemon.metrics().events_published, 0);
+    }
+
+    #[test]
+    fn test_reserved_topics_rejected_from_plugins() {
+        let mut daemon = Daemon::new();
+        let _spoofer_rx = daemon.add_connection("spoofer".to_string());
+        let mut victim_rx = daemon.add_connection("victim".to_string());
+        register(&mut daemon, "spoofer", "spoofer");
+        register(&mut daemon, "victim", "victim");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["#".to_string()],
+            },
+            "victim",
+        );
+
+        let publish = |topic: &str| Request::Publish {
+            topic: topic.to_string(),
+            data: json!({"name": "victim"}),
+            retain: false,
+        };
+        for topic in ["plugin.deregistered", "daemon.shutdown"] {
+            match daemon.handle_request(publish(topic), "spoofer") {
+                Response::Error { message, .. } => assert!(message.contains("reserved topic")),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+        assert!(victim_rx.try_recv().is_err());
+
+        assert!(matches!(
+            daemon.handle_request(publish("health.foo"), "spoofer"),
+            Response::Success { .. }
+        ));
+        assert_eq!(victim_rx.try_recv().unwrap().topic, "health.foo");
+    }
+
+    #[test]
+    fn test_invalid_topics_rejected() {
+        let mut daemon = Daemon::new();
+        let mut watcher_rx = daemon.add_connection("watcher".to_string());
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+
+        for topics in [vec!["health .foo"], vec!["sensor.#", "#.sensor"], vec![""]] {
+            let response = daemon.handle_request(
+                Request::Subscribe {
+                    topics: topics.iter().map(|t| t.to_string()).collect(),
+                },
+                "watcher",
+            );
+            assert!(
+                matches!(
+                    response,
+                    Response::Error {
+                        code: Some(ErrorCode::InvalidRequest),
+                        ..
+                    }
+                ),
+                "{:?}",
+                topics
+            );
+        }
+        assert!(daemon.event_bus.subscribers.is_empty());
+
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec![" sensor.+ ".to_string()],
+            },
+            "watcher",
+        );
+        let publish = |topic: &str| Request::Publish {
+            topic: topic.to_string(),
+            data: json!({}),
+            retain: false,
+        };
+        for topic in ["health .foo", "", "sensor..reading", "sensor.#"] {
+            assert!(
+                matches!(
+                    daemon.handle_request(publish(topic), "publisher"),
+                    Response::Error {
+                        code: Some(ErrorCode::InvalidRequest),
+                        ..
+                    }
+                ),
+                "{:?}",
+                topic
+            );
+        }
+        assert!(watcher_rx.try_recv().is_err());
+        assert_eq!(daemon.metrics().events_published, 0);
+
+        assert!(matches!(
+            daemon.handle_request(publish("sensor.reading\n"), "publisher"),
+            Response::Success { .. }
+        ));
+        assert_eq!(watcher_rx.try_recv().unwrap().topic, "sensor.reading");
+    }
+
+    #[test]
+    fn test_acl_allows_and_denies_publish() {
+        let mut daemon = Daemon::new();
+        daemon.acl = Some(
+            toml::from_str(
+                r#"
+                [plugins.sensor]
+                publish = ["sensor.#"]
+
+                [plugins.watcher]
+                subscribe = ["sensor.#"]
+
+                [peers."*"]
+                register = ["*"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let _sensor_rx = daemon.add_connection("sensor".to_string());
+        let mut watcher_rx = daemon.add_connection("watcher".to_string());
+        register(&mut daemon, "sensor", "sensor");
+        register(&mut daemon, "watcher", "watcher");
+        assert!(matches!(
+            daemon.handle_request(
+                Request::Subscribe {
+                    topics: vec!["sensor.#".to_string()],
+                },
+                "watcher",
+            ),
+            Response::Success { .. }
+        ));
+
+        let publish = |topic: &str| Request::Publish {
+            topic: topic.to_string(),
+            data: json!({}),
+            retain: false,
+        };
+        assert!(matches!(
+            daemon.handle_request(publish("sensor.reading"), "sensor"),
+            Response::Success { .. }
+        ));
+        assert_eq!(watcher_rx.try_recv().unwrap().topic, "sensor.reading");
+
+        assert!(matches!(
+            daemon.handle_request(publish("plugin.deregistered"), "sensor"),
+            Response::Error { .. }
+        ));
+        assert!(matches!(
+            daemon.handle_request(
+                Request::Subscribe {
+                    topics: vec!["#".to_string()],
+                },
+                "sensor",
+            ),
+            Response::Error { .. }
+        ));
+        assert!(watcher_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_name_takeover_cannot_widen_permissions() {
+        let mut daemon = Daemon::new();
+        daemon.acl = Some(
+            toml::from_str(
+                r#"
+                [plugins."pandemic-udp"]
+                publish = ["udp.#"]
+
+                [peers."100"]
+                register = ["pandemic-udp"]
+
+                [peers."200"]
+                register = ["sensor-*"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let _udp_rx = daemon.add_peer_connection("udp".to_string(), Some(100));
+        let _infection_rx = daemon.add_peer_connection("infection".to_string(), Some(200));
+        register(&mut daemon, "udp", "pandemic-udp");
+        assert!(daemon.plugins.contains_key("pandemic-udp"));
+
+        // Neither a fresh registration nor a forced takeover of the name works
+        // from a peer that isn't granted it
+        for force in [false, true] {
+            assert!(matches!(
+                daemon.handle_request(register_request("pandemic-udp", force), "infection"),
+                Response::Error {
+                    code: Some(ErrorCode::PermissionDenied),
+                    ..
+                }
+            ));
+        }
+        assert_eq!(
+            daemon.connection_of("pandemic-udp").map(String::as_str),
+            Some("udp")
+        );
+
+        let publish = Request::Publish {
+            topic: "udp.packet".to_string(),
+            data: json!({}),
+            retain: false,
+        };
+        assert!(matches!(
+            daemon.handle_request(publish.clone(), "infection"),
+            Response::Error {
+                code: Some(ErrorCode::PermissionDenied),
+                ..
+            }
+        ));
+        assert!(matches!(
+            daemon.handle_request(publish, "udp"),
+            Response::Success { .. }
+        ));
+
+        // Peers without credentials, like TCP clients, only get `*` entries
+        let _tcp_rx = daemon.add_connection("tcp".to_string());
+        assert!(matches!(
+            daemon.handle_request(register_request("sensor-1", false), "tcp"),
+            Response::Error {
+                code: Some(ErrorCode::PermissionDenied),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_retained_events_cleared_on_deregister() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        publish(&mut daemon, "publisher", "state.retained", true);
+        daemon.handle_request(
+            Request::Deregister {
+                name: "publisher".to_string(),
+            },
+            "publisher",
+        );
+
+        let mut late_rx = daemon.add_connection("late".to_string());
+        register(&mut daemon, "late", "late");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["state.#".to_string()],
+            },
+            "late",
+        );
+        assert!(late_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_deregister_matching_glob() {
+        let mut daemon = Daemon::new();
+        for name in ["foo-a", "foo-b", "bar"] {
+            let _rx = daemon.add_connection(name.to_string());
+            register(&mut daemon, name, name);
+        }
+        let mut watcher_rx = daemon.add_connection("watcher".to_string());
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["plugin.deregistered".to_string()],
+            },
+            "watcher",
+        );
+
+        let request = Request::DeregisterMatching {
+            pattern: "foo-*".to_string(),
+            confirm: false,
+        };
+        match daemon.handle_request(request, "watcher") {
+            Response::Success { data: Some(data) } => assert_eq!(data, json!(["foo-a", "foo-b"])),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert_eq!(daemon.plugins.keys().collect::<Vec<_>>(), vec!["bar"]);
+        let announced: Vec<_> = std::iter::from_fn(|| watcher_rx.try_recv().ok())
+            .map(|event| event.data["name"].clone())
+            .collect();
+        assert_eq!(announced, vec![json!("foo-a"), json!("foo-b")]);
+
+        let wipe = |confirm| Request::DeregisterMatching {
+            pattern: "*".to_string(),
+            confirm,
+        };
+        assert!(matches!(
+            daemon.handle_request(wipe(false), "watcher"),
+            Response::Error {
+                code: Some(ErrorCode::InvalidRequest),
+                ..
+            }
+        ));
+        assert!(daemon.plugins.contains_key("bar"));
+        assert!(matches!(
+            daemon.handle_request(wipe(true), "watcher"),
+            Response::Success { .. }
+        ));
+        assert!(daemon.plugins.is_empty());
+    }
+
+    #[test]
+    fn test_deregister_matching_rejects_long_and_handles_pathological_patterns() {
+        let mut daemon = Daemon::new();
+        let name = "a".repeat(40);
+        let _rx = daemon.add_connection("plugin".to_string());
+        register(&mut daemon, "plugin", &name);
+
+        let request = |pattern: String| Request::DeregisterMatching {
+            pattern,
+            confirm: false,
+        };
+        assert!(matches!(
+            daemon.handle_request(request("a".repeat(MAX_PATTERN_LEN + 1)), "plugin"),
+            Response::Error {
+                code: Some(ErrorCode::InvalidRequest),
+                ..
+            }
+        ));
+
+        let started = Instant::now();
+        let pathological = format!("{}b", "*a".repeat(MAX_PATTERN_LEN / 2 - 1));
+        match daemon.handle_request(request(pathological), "plugin") {
+            Response::Success { data: Some(data) } => assert_eq!(data, json!([])),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(daemon.plugins.contains_key(&name));
+    }
+
+    #[test]
+    fn test_update_plugin_config_by_owner() {
+        let mut daemon = Daemon::new();
+        let _owner_rx = daemon.add_connection("owner".to_string());
+        register(&mut daemon, "owner", "sensor");
+        let mut watcher_rx = daemon.add_connection("watcher".to_string());
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["plugin.updated".to_string()],
+            },
+            "watcher",
+        );
+
+        let update = |description: Option<&str>| Request::UpdatePluginConfig {
+            name: "sensor".to_string(),
+            config: None,
+            description: description.map(String::from),
+        };
+        assert!(matches!(
+            daemon.handle_request(update(Some("Hijacked")), "watcher"),
+            Response::Error {
+                code: Some(ErrorCode::PermissionDenied),
+                ..
+            }
+        ));
+        assert!(watcher_rx.try_recv().is_err());
+
+        let config = HashMap::from([("interval".to_string(), "5".to_string())]);
+        let request = Request::UpdatePluginConfig {
+            name: "sensor".to_string(),
+            config: Some(config.clone()),
+            description: None,
+        };
+        assert!(matches!(
+            daemon.handle_request(request, "owner"),
+            Response::Success { .. }
+        ));
+        assert!(matches!(
+            daemon.handle_request(update(Some("Temperature sensor")), "owner"),
+            Response::Success { .. }
+        ));
+
+        let request = Request::GetPlugin {
+            name: "sensor".to_string(),
+        };
+        let Response::Success { data: Some(data) } = daemon.handle_request(request, "watcher")
+        else {
+            panic!("expected plugin data");
+        };
+        assert_eq!(data["description"], "Temperature sensor");
+        assert_eq!(data["config"], json!(config));
+
+        let event = watcher_rx.try_recv().unwrap();
+        assert_eq!(event.topic, "plugin.updated");
+        assert_eq!(event.data["config"], json!(config));
+        assert_eq!(
+            watcher_rx.try_recv().unwrap().data["description"],
+            "Temperature sensor"
+        );
+
+        assert!(matches!(
+            daemon.handle_request(
+                Request::UpdatePluginConfig {
+                    name: "missing".to_string(),
+                    config: None,
+                    description: None,
+                },
+                "owner"
+            ),
+            Response::NotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn test_config_override_get_set_clear_cycle() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let defaults_dir = temp_dir.path().join("defaults");
+        std::fs::create_dir_all(&defaults_dir).unwrap();
+        std::fs::write(
+            defaults_dir.join("sensor.toml"),
+            "interval = 5\nunit = \"celsius\"\n",
+        )
+        .unwrap();
+
+        let mut daemon = Daemon::new();
+        daemon.config = Some(FileConfigManager::new(
+            &defaults_dir,
+            temp_dir.path().join("overrides"),
+        ));
+        let _sensor_rx = daemon.add_connection("sensor".to_string());
+        register(&mut daemon, "sensor", "sensor");
+        let mut watcher_rx = daemon.add_connection("watcher".to_string());
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["daemon.config.changed.#".to_string()],
+            },
+            "watcher",
+        );
+
+        let get = || Request::GetConfig {
+            plugin: "sensor".to_string(),
+        };
+        let Response::Success { data: Some(config) } = daemon.handle_request(get(), "watcher")
+        else {
+            panic!("expected config");
+        };
+        assert_eq!(config, json!({"interval": 5, "unit": "celsius"}));
+
+        let request = Request::SetConfigOverride {
+            plugin: "sensor".to_string(),
+            config: json!({"interval": 30}),
+        };
+        assert!(matches!(
+            daemon.handle_request(request, "sensor"),
+            Response::Success { .. }
+        ));
+        let event = watcher_rx.try_recv().unwrap();
+        assert_eq!(event.topic, "daemon.config.changed.sensor");
+        assert_eq!(
+            event.data["config"],
+            json!({"interval": 30, "unit": "celsius"})
+        );
+        let Response::Success { data: Some(config) } = daemon.handle_request(get(), "watcher")
+        else {
+            panic!("expected config");
+        };
+        assert_eq!(config["interval"], 30);
+
+        let request = Request::ClearConfigOverride {
+            plugin: "sensor".to_string(),
+        };
+        assert!(matches!(
+            daemon.handle_request(request, "sensor"),
+            Response::Success { .. }
+        ));
+        let event = watcher_rx.try_recv().unwrap();
+        assert_eq!(event.data["config"]["interval"], 5);
+        let Response::Success { data: Some(config) } = daemon.handle_request(get(), "watcher")
+        else {
+            panic!("expected config");
+        };
+        assert_eq!(config["interval"], 5);
+
+        let request = Request::SetConfigOverride {
+            plugin: "../sensor".to_string(),
+            config: json!({}),
+        };
+        assert!(matches!(
+            daemon.handle_request(request, "sensor"),
+            Response::Error {
+                code: Some(ErrorCode::InvalidRequest),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_config_override_requires_owner_or_acl() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut daemon = Daemon::new();
+        daemon.config = Some(FileConfigManager::new(
+            temp_dir.path().join("defaults"),
+            temp_dir.path().join("overrides"),
+        ));
+        let _sensor_rx = daemon.add_connection("sensor".to_string());
+        let _other_rx = daemon.add_connection("other".to_string());
+        let _anonymous_rx = daemon.add_connection("anonymous".to_string());
+        register(&mut daemon, "sensor", "sensor");
+        register(&mut daemon, "other", "other");
+
+        let set = || Request::SetConfigOverride {
+            plugin: "sensor".to_string(),
+            config: json!({"interval": 30}),
+        };
+        let clear = || Request::ClearConfigOverride {
+            plugin: "sensor".to_string(),
+        };
+        for connection_id in ["other", "anonymous"] {
+            for request in [set(), clear()] {
+                assert!(matches!(
+                    daemon.handle_request(request, connection_id),
+                    Response::Error {
+                        code: Some(ErrorCode::PermissionDenied),
+                        ..
+                    }
+                ));
+            }
+        }
+        assert!(!temp_dir.path().join("overrides").exists());
+
+        daemon.acl = Some(
+            toml::from_str(
+                r#"
+                [plugins.unknown]
+                configure = ["*"]
+                "#,
+            )
+            .unwrap(),
+        );
+        assert!(matches!(
+            daemon.handle_request(set(), "anonymous"),
+            Response::Success { .. }
+        ));
+        assert!(matches!(
+            daemon.handle_request(set(), "other"),
+            Response::Error {
+                code: Some(ErrorCode::PermissionDenied),
+                ..
+            }
+        ));
+        assert!(matches!(
+            daemon.handle_request(clear(), "anonymous"),
+            Response::Success { .. }
+        ));
+
+        // A peer grant follows the client's uid, not a name it can pick
+        daemon.acl = Some(
+            toml::from_str(
+                r#"
+                [peers."990"]
+                configure = ["sensor"]
+                "#,
+            )
+            .unwrap(),
+        );
+        let _rest_rx = daemon.add_peer_connection("rest".to_string(), Some(990));
+        let _stranger_rx = daemon.add_peer_connection("stranger".to_string(), Some(991));
+        assert!(matches!(
+            daemon.handle_request(set(), "rest"),
+            Response::Success { .. }
+        ));
+        assert!(matches!(
+            daemon.handle_request(clear(), "stranger"),
+            Response::Error {
+                code: Some(ErrorCode::PermissionDenied),
+                ..
+            }
+        ));
+        assert!(matches!(
+            daemon.handle_request(clear(), "rest"),
+            Response::Success { .. }
+        ));
+    }
+
+    #[test]
+    fn test_registration_waits_for_dependencies() {
+        let mut daemon = Daemon::new();
+        let _app_rx = daemon.add_connection("app".to_string());
+        let _db_rx = daemon.add_connection("db".to_string());
+
+        let app = |force: bool| {
+            let mut request = register_request("app", force);
+            if let Request::Register { plugin, .. } = &mut request {
+                plugin.dependencies = vec!["db".to_string(), "cache".to_string()];
+            }
+            request
+        };
+
+        let Response::DependenciesUnmet { missing } = daemon.handle_request(app(false), "app")
+        else {
+            panic!("expected unmet dependencies");
+        };
+        assert_eq!(missing, vec!["db".to_string(), "cache".to_string()]);
+        assert!(!daemon.plugins.contains_key("app"));
+
+        register(&mut daemon, "db", "db");
+        let Response::DependenciesUnmet { missing } = daemon.handle_request(app(false), "app")
+        else {
+            panic!("expected unmet dependencies");
+        };
+        assert_eq!(missing, vec!["cache".to_string()]);
+
+        let _cache_rx = daemon.add_connection("cache".to_string());
+        register(&mut daemon, "cache", "cache");
+        assert!(matches!(
+            daemon.handle_request(app(false), "app"),
+            Response::Success { .. }
+        ));
+        assert!(daemon.plugins.contains_key("app"));
+    }
+
+    #[test]
+    fn test_ignore_dependencies_skips_dependencies() {
+        let mut daemon = Daemon::new();
+        let _app_rx = daemon.add_connection("app".to_string());
+        let app = |force: bool, ignore: bool| {
+            let mut request = register_request("app", force);
+            if let Request::Register {
+                plugin,
+                ignore_dependencies,
+                ..
+            } = &mut request
+            {
+                plugin.dependencies = vec!["db".to_string()];
+                *ignore_dependencies = ignore;
+            }
+            request
+        };
+
+        // Forcing a takeover doesn't skip the dependency check
+        assert!(matches!(
+            daemon.handle_request(app(true, false), "app"),
+            Response::DependenciesUnmet { .. }
+        ));
+        assert!(matches!(
+            daemon.handle_request(app(false, true), "app"),
+            Response::Success { .. }
+        ));
+        assert!(daemon.plugins.contains_key("app"));
+    }
+
+    fn register_request(name: &str, force: bool) -> Request {
+        Request::Register {
+            plugin: PluginInfo {
+                name: name.to_string(),
+                version: "2.0.0".to_string(),
+                description: None,
+                config: None,
+                registered_at: None,
+                heartbeat_interval: None,
+                dependencies: vec![],
+            },
+            force,
+            ignore_dependencies: false,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_registration_conflicts_unless_forced() {
+        let mut daemon = Daemon::new();
+        let _first_rx = daemon.add_connection("first".to_string());
+        let _second_rx = daemon.add_connection("second".to_string());
+        register(&mut daemon, "first", "dup");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["custom.#".to_string()],
+            },
+            "first",
+        );
+
+        assert!(matches!(
+            daemon.handle_request(register_request("dup", false), "second"),
+            Response::Conflict { .. }
+        ));
+        assert_eq!(daemon.plugins["dup"].version, "1.0.0");
+
+        // The owning connection may re-register its own name
+        assert!(matches!(
+            daemon.handle_request(register_request("dup", false), "first"),
+            Response::Success { .. }
+        ));
+
+        assert!(matches!(
+            daemon.handle_request(register_request("dup", true), "second"),
+            Response::Success { .. }
+        ));
+        assert_eq!(daemon.plugins["dup"].version, "2.0.0");
+        assert_eq!(daemon.connections["first"].plugin_name, None);
+        assert_eq!(
+            daemon.connections["second"].plugin_name.as_deref(),
+            Some("dup")
+        );
+        assert!(!daemon.event_bus.subscribers.contains_key("first"));
+
+        // The previous owner disconnecting leaves the new registration alone
+        daemon.remove_connection("first", None);
+        assert!(daemon.plugins.contains_key("dup"));
+    }
+
+    #[test]
+    fn test_events_reach_only_the_current_owner_of_a_name() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        let mut first_rx = daemon.add_connection("first".to_string());
+        let mut second_rx = daemon.add_connection("second".to_string());
+        let subscribe = || Request::Subscribe {
+            topics: vec!["custom.#".to_string()],
+        };
+
+        register(&mut daemon, "first", "shared");
+        daemon.handle_request(subscribe(), "first");
+        assert!(matches!(
+            daemon.handle_request(register_request("shared", false), "second"),
+            Response::Conflict { .. }
+        ));
+        publish(&mut daemon, "publisher", "custom.one", false);
+        assert_eq!(first_rx.try_recv().unwrap().topic, "custom.one");
+        assert!(second_rx.try_recv().is_err());
+
+        daemon.handle_request(register_request("shared", true), "second");
+        daemon.handle_request(subscribe(), "second");
+        publish(&mut daemon, "publisher", "custom.two", false);
+        assert_eq!(second_rx.try_recv().unwrap().topic, "custom.two");
+        assert!(second_rx.try_recv().is_err());
+        assert!(first_rx.try_recv().is_err());
+        assert_eq!(daemon.event_bus.delivered["shared"], 1);
+    }
+
+    #[test]
+    fn test_anonymous_connection_can_subscribe() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        let mut anonymous_rx = daemon.add_connection("anonymous".to_string());
+
+        assert!(matches!(
+            daemon.handle_request(
+                Request::Subscribe {
+                    topics: vec!["custom.#".to_string()],
+                },
+                "anonymous",
+            ),
+            Response::Success { .. }
+        ));
+        publish(&mut daemon, "publisher", "custom.reading", false);
+        assert_eq!(anonymous_rx.try_recv().unwrap().topic, "custom.reading");
+
+        match daemon.handle_request(Request::ListSubscriptions, "anonymous") {
+            Response::Success { data: Some(data) } => {
+                assert_eq!(data["anonymous"], json!(["custom.#"]))
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+
+        daemon.remove_connection("anonymous", None);
+        assert!(daemon.event_bus.subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_connections_of_one_plugin_keep_their_own_topics() {
+        let mut daemon = Daemon::new();
+        let _publisher_rx = daemon.add_connection("publisher".to_string());
+        register(&mut daemon, "publisher", "publisher");
+        // One plugin process: a registered control connection and a second,
+        // unregistered connection for bulk data
+        let mut control_rx = daemon.add_connection("control".to_string());
+        register(&mut daemon, "control", "multi");
+        let mut data_rx = daemon.add_connection("data".to_string());
+        for (connection_id, topic) in [("control", "multi.control.#"), ("data", "multi.data.#")] {
+            daemon.handle_request(
+                Request::Subscribe {
+                    topics: vec![topic.to_string()],
+                },
+                connection_id,
+            );
+        }
+
+        publish(&mut daemon, "publisher", "multi.control.restart", false);
+        publish(&mut daemon, "publisher", "multi.data.sample", false);
+
+        assert_eq!(
+            control_rx.try_recv().unwrap().topic,
+            "multi.control.restart"
+        );
+        assert!(control_rx.try_recv().is_err());
+        assert_eq!(data_rx.try_recv().unwrap().topic, "multi.data.sample");
+        assert!(data_rx.try_recv().is_err());
+        assert_eq!(daemon.event_bus.delivered["multi"], 1);
+    }
+
+    #[test]
+    fn test_list_subscriptions() {
+        let mut daemon = Daemon::new();
+        for (name, topics) in [
+            ("alpha", vec!["plugin.#"]),
+            ("beta", vec!["health.+.status", "custom.event"]),
+        ] {
+            let _rx = daemon.add_connection(name.to_string());
+            register(&mut daemon, name, name);
+            daemon.handle_request(
+                Request::Subscribe {
+                    topics: topics.into_iter().map(String::from).collect(),
+                },
+                name,
+            );
         }
+
+        match daemon.handle_request(Request::ListSubscriptions, "alpha") {
+            Response::Success { data } => assert_eq!(
+                data,
+                Some(json!({
+                    "alpha": ["plugin.#"],
+                    "beta": ["health.+.status", "custom.event"],
+                }))
+            ),
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lapsed_heartbeat_deregisters_plugin() {
+        let mut daemon = Daemon::new();
+        let _plugin_rx = daemon.add_connection("plugin".to_string());
+        register_with_heartbeat(&mut daemon, "plugin", "flaky", Some(1));
+        register(&mut daemon, "plugin", "steady");
+
+        let mut observer_rx = daemon.add_connection("observer".to_string());
+        register(&mut daemon, "observer", "observer");
+        daemon.handle_request(
+            Request::Subscribe {
+                topics: vec!["plugin.deregistered".to_string()],
+            },
+            "observer",
+        );
+
+        // Within the 2x interval TTL nothing expires
+        let registered = daemon.last_seen["flaky"];
+        assert!(daemon
+            .expire_stale_plugins(registered + Duration::from_millis(1500))
+            .is_empty());
+
+        let expired = daemon.expire_stale_plugins(registered + Duration::from_secs(3));
+        assert_eq!(expired, vec!["flaky".to_string()]);
+        assert!(!daemon.plugins.contains_key("flaky"));
+        assert!(!daemon.last_seen.contains_key("flaky"));
+        // Plugins without a heartbeat interval are never expired
+        assert!(daemon.plugins.contains_key("steady"));
+
+        let event = observer_rx.try_recv().unwrap();
+        assert_eq!(event.topic, "plugin.deregistered");
+        assert_eq!(event.data, json!({"name": "flaky"}));
+    }
+
+    #[test]
+    fn test_heartbeat_refreshes_last_seen() {
+        let mut daemon = Daemon::new();
+        let _plugin_rx = daemon.add_connection("plugin".to_string());
+        register_with_heartbeat(&mut daemon, "plugin", "alive", Some(1));
+        let registered = daemon.last_seen["alive"];
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(matches!(
+            heartbeat(&mut daemon, "alive"),
+            Response::Success { .. }
+        ));
+        let refreshed = daemon.last_seen["alive"];
+        assert!(refreshed > registered);
+
+        // Measured from the refreshed heartbeat the plugin is still live
+        assert!(daemon
+            .expire_stale_plugins(refreshed + Duration::from_millis(1500))
+            .is_empty());
+        assert!(daemon.plugins.contains_key("alive"));
+
+        assert!(matches!(
+            heartbeat(&mut daemon, "missing"),
+            Response::NotFound { .. }
+        ));
+    }
+
+    #[test]
+    fn test_heartbeat_interval_must_be_in_range() {
+        let mut daemon = Daemon::new();
+        let _plugin_rx = daemon.add_connection("plugin".to_string());
+        for interval in [0, MAX_HEARTBEAT_INTERVAL + 1, u64::MAX] {
+            let response = register_with_heartbeat(&mut daemon, "plugin", "bad", Some(interval));
+            assert!(
+                matches!(
+                    response,
+                    Response::Error {
+                        code: Some(ErrorCode::InvalidRequest),
+                        ..
+                    }
+                ),
+                "{:?}",
+                response
+            );
+        }
+        assert!(!daemon.plugins.contains_key("bad"));
+        assert!(matches!(
+            register_with_heartbeat(&mut daemon, "plugin", "good", Some(MAX_HEARTBEAT_INTERVAL)),
+            Response::Success { .. }
+        ));
+
+        // An out-of-range interval restored from saved state doesn't panic
+        // the reaper, it just never expires
+        daemon.plugins.get_mut("good").unwrap().heartbeat_interval = Some(u64::MAX);
+        let registered = daemon.last_seen["good"];
+        assert!(daemon
+            .expire_stale_plugins(registered + Duration::from_secs(3600))
+            .is_empty());
+        assert!(daemon.plugins.contains_key("good"));
     }
 }
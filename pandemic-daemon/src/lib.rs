@@ -0,0 +1,11 @@
+//! Daemon internals, kept in a library so benchmarks can drive them
+//! directly; `main.rs` only parses arguments and starts the server.
+
+pub mod acl;
+pub mod connection;
+pub mod daemon;
+pub mod event_bus;
+pub mod handlers;
+pub mod notify;
+pub mod server;
+pub mod socket;
@@ -1,18 +1,20 @@
-mod connection;
-mod daemon;
-mod event_bus;
-mod handlers;
-
 use anyhow::Result;
 use clap::Parser;
+use pandemic_common::FileConfigManager;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::net::UnixListener;
-use tokio::sync::Mutex;
-use tracing::{error, info};
+use std::time::Duration;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::RwLock;
+use tracing::{info, warn};
 
-use connection::handle_connection;
-use daemon::Daemon;
+use pandemic_daemon::acl::TopicAcl;
+use pandemic_daemon::daemon::{Daemon, DEFAULT_RESTORE_GRACE};
+use pandemic_daemon::event_bus::{
+    OverflowPolicy, DEFAULT_EVENT_CAPACITY, DEFAULT_HISTORY_CAPACITY,
+};
+use pandemic_daemon::{notify, server, socket};
 
 #[derive(Parser)]
 #[command(name = "pandemic")]
@@ -20,11 +22,62 @@ use daemon::Daemon;
 struct Args {
     #[arg(long, default_value = "/var/run/pandemic/pandemic.sock")]
     socket_path: PathBuf,
+
+    /// Also accept connections on this TCP address, e.g. 127.0.0.1:7878.
+    /// TCP connections are not authenticated, so keep it on a trusted network.
+    #[arg(long)]
+    tcp_listen: Option<String>,
+
+    /// Octal mode for the socket file, e.g. 0660; the umask decides otherwise
+    #[arg(long, value_parser = socket::parse_mode)]
+    socket_mode: Option<u32>,
+
+    /// User that should own the socket file
+    #[arg(long)]
+    socket_user: Option<String>,
+
+    /// Group that should own the socket file
+    #[arg(long)]
+    socket_group: Option<String>,
+
+    /// Persist the plugin registry to this file across restarts
+    #[arg(long)]
+    state_file: Option<PathBuf>,
+
+    /// Seconds plugins restored from the state file get to register again
+    /// before they're dropped
+    #[arg(long, default_value_t = DEFAULT_RESTORE_GRACE.as_secs())]
+    restore_grace: u64,
+
+    /// Events buffered per connection before the overflow policy applies
+    #[arg(long, default_value_t = DEFAULT_EVENT_CAPACITY)]
+    event_buffer: usize,
+
+    /// What to do with a subscriber whose event buffer is full
+    #[arg(long, value_enum, default_value = "drop")]
+    event_overflow: OverflowPolicy,
+
+    /// Recent events kept for history queries; 0 disables the history
+    #[arg(long, default_value_t = DEFAULT_HISTORY_CAPACITY)]
+    event_history: usize,
+
+    /// Topic publish/subscribe ACL; every plugin may use any topic when the
+    /// file doesn't exist
+    #[arg(long, default_value = "/etc/pandemic/acl.toml")]
+    acl_file: PathBuf,
+
+    /// Default plugin configs served to GetConfig
+    #[arg(long, default_value = "/etc/pandemic/config")]
+    config_dir: PathBuf,
+
+    /// Where SetConfigOverride writes plugin overrides
+    #[arg(long, default_value = "/var/lib/pandemic/config")]
+    config_overrides_dir: PathBuf,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     if let Some(parent) = args.socket_path.parent() {
@@ -33,27 +86,52 @@ async fn main() -> Result<()> {
 
     let _ = tokio::fs::remove_file(&args.socket_path).await;
     let listener = UnixListener::bind(&args.socket_path)?;
+    socket::setup_socket_permissions(
+        &args.socket_path,
+        args.socket_mode,
+        args.socket_user.as_deref(),
+        args.socket_group.as_deref(),
+    )?;
     info!("Pandemic daemon listening on {:?}", args.socket_path);
 
-    let daemon = Arc::new(Mutex::new(Daemon::new()));
-    let mut connection_counter = 0u64;
-
-    while let Ok((stream, _)) = listener.accept().await {
-        connection_counter += 1;
-        let connection_id = format!("conn_{}", connection_counter);
+    let mut daemon = match args.state_file {
+        Some(state_file) => Daemon::with_state_file(state_file),
+        None => Daemon::new(),
+    };
+    daemon.restore_grace = Duration::from_secs(args.restore_grace);
+    daemon.set_event_limits(args.event_buffer, args.event_overflow);
+    daemon.event_bus.set_history_capacity(args.event_history);
+    daemon.socket_path = Some(args.socket_path.clone());
+    daemon.config = Some(FileConfigManager::new(
+        &args.config_dir,
+        &args.config_overrides_dir,
+    ));
+    daemon.notify_socket = notify::socket_from_env();
+    daemon.acl = TopicAcl::load(&args.acl_file)?;
+    if daemon.acl.is_some() {
+        info!("Enforcing topic ACL from {:?}", args.acl_file);
+    }
+    let daemon = Arc::new(RwLock::new(daemon));
 
-        let event_rx = {
-            let mut daemon_guard = daemon.lock().await;
-            daemon_guard.add_connection(connection_id.clone())
-        };
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let shutdown = async move {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+            _ = sigterm.recv() => info!("Received SIGTERM"),
+        }
+    };
 
-        let daemon_clone = Arc::clone(&daemon);
-        tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream, connection_id, daemon_clone, event_rx).await {
-                error!("Connection error: {}", e);
-            }
-        });
-    }
+    let tcp = match &args.tcp_listen {
+        Some(address) => {
+            let tcp = TcpListener::bind(address).await?;
+            warn!(
+                "Accepting unauthenticated TCP connections on {}",
+                tcp.local_addr()?
+            );
+            Some(tcp)
+        }
+        None => None,
+    };
 
-    Ok(())
+    server::serve(listener, tcp, &args.socket_path, daemon, shutdown).await
 }
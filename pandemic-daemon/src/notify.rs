@@ -0,0 +1,69 @@
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// The socket systemd listens for readiness on, which it passes in
+/// `NOTIFY_SOCKET` when it starts the daemon from a `Type=notify` unit
+pub fn socket_from_env() -> Option<PathBuf> {
+    std::env::var_os("NOTIFY_SOCKET").map(PathBuf::from)
+}
+
+/// Tell systemd the daemon accepts connections. Does nothing without a
+/// notify socket.
+pub fn ready(socket: Option<&Path>) {
+    send(socket, "READY=1");
+}
+
+/// Tell systemd a graceful shutdown has begun
+pub fn stopping(socket: Option<&Path>) {
+    send(socket, "STOPPING=1");
+}
+
+fn send(socket: Option<&Path>, state: &str) {
+    let Some(socket) = socket else {
+        return;
+    };
+    if let Err(e) = send_to(socket, &format!("{}\n", state)) {
+        warn!("Failed to notify systemd: {}", e);
+    }
+}
+
+fn send_to(socket: &Path, message: &str) -> io::Result<()> {
+    let datagram = UnixDatagram::unbound()?;
+    // A leading `@` names a socket in the abstract namespace
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::linux::net::SocketAddrExt;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::net::SocketAddr;
+
+        if let Some(name) = socket.as_os_str().as_bytes().strip_prefix(b"@") {
+            let address = SocketAddr::from_abstract_name(name)?;
+            datagram.send_to_addr(message.as_bytes(), &address)?;
+            return Ok(());
+        }
+    }
+    datagram.send_to(message.as_bytes(), socket)?;
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    #[test]
+    fn test_abstract_socket_is_notified() {
+        let name = format!("pandemic-notify-test-{}", std::process::id());
+        let address = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let notifications = UnixDatagram::bind_addr(&address).unwrap();
+
+        ready(Some(Path::new(&format!("@{}", name))));
+
+        let mut buf = [0u8; 64];
+        let len = notifications.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"READY=1\n");
+    }
+}
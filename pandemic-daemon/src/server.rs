@@ -0,0 +1,360 @@
+use anyhow::Result;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UnixListener};
+use tokio::sync::RwLock;
+use tokio::task::JoinSet;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::connection::handle_connection;
+use crate::daemon::Daemon;
+use crate::notify;
+
+/// How long open connections get to flush after shutdown is announced
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// Pause after a failed accept, which is usually transient (e.g. running out
+/// of file descriptors), before trying again
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How often plugins are checked for missed heartbeats
+const HEARTBEAT_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Periodically deregister plugins whose heartbeat has lapsed, and restored
+/// plugins that didn't register again in time
+async fn expire_stale_plugins(daemon: Arc<RwLock<Daemon>>) {
+    let mut interval = tokio::time::interval(HEARTBEAT_CHECK_PERIOD);
+    loop {
+        interval.tick().await;
+        let now = std::time::Instant::now();
+        let mut daemon = daemon.write().await;
+        daemon.expire_stale_plugins(now);
+        daemon.expire_restored_plugins(now);
+    }
+}
+
+/// Accept from `tcp` when there is one, otherwise never
+async fn accept_tcp(tcp: Option<&TcpListener>) -> std::io::Result<TcpStream> {
+    match tcp {
+        Some(listener) => {
+            let (stream, peer) = listener.accept().await?;
+            info!("Accepted TCP connection from {}", peer);
+            Ok(stream)
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Register a connection with the daemon and serve it on its own task
+async fn spawn_connection<S>(
+    stream: S,
+    connection_id: String,
+    peer_uid: Option<u32>,
+    daemon: &Arc<RwLock<Daemon>>,
+    connections: &mut JoinSet<()>,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let event_rx = daemon
+        .write()
+        .await
+        .add_peer_connection(connection_id.clone(), peer_uid);
+
+    let daemon = Arc::clone(daemon);
+    let span = info_span!("connection", connection_id = %connection_id);
+    connections.spawn(
+        async move {
+            if let Err(e) = handle_connection(stream, connection_id, daemon, event_rx).await {
+                error!("Connection error: {}", e);
+            }
+        }
+        .instrument(span),
+    );
+}
+
+/// Accept connections on the Unix socket, and on `tcp` when given, until
+/// `shutdown` resolves. Then announce the shutdown, close every connection
+/// and remove the socket file. systemd is told the daemon is ready once
+/// listening starts and that it is stopping once `shutdown` resolves.
+pub async fn serve(
+    listener: UnixListener,
+    tcp: Option<TcpListener>,
+    socket_path: &Path,
+    daemon: Arc<RwLock<Daemon>>,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let mut connections = JoinSet::new();
+    let mut connection_counter = 0u64;
+    tokio::pin!(shutdown);
+
+    let reaper = tokio::spawn(expire_stale_plugins(Arc::clone(&daemon)));
+    let notify_socket = daemon.read().await.notify_socket.clone();
+    // Both listeners are bound by now, so connections will be accepted
+    notify::ready(notify_socket.as_deref());
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                // The uid is what the topic ACL trusts to pick plugin names
+                let peer_uid = stream.peer_cred().ok().map(|cred| cred.uid());
+                connection_counter += 1;
+                let connection_id = format!("conn_{}", connection_counter);
+                spawn_connection(stream, connection_id, peer_uid, &daemon, &mut connections).await;
+            }
+            accepted = accept_tcp(tcp.as_ref()) => {
+                let stream = match accepted {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!("Failed to accept TCP connection: {}", e);
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                let _ = stream.set_nodelay(true);
+                connection_counter += 1;
+                let connection_id = format!("conn_{}", connection_counter);
+                spawn_connection(stream, connection_id, None, &daemon, &mut connections).await;
+            }
+            // Reap finished connections so the set only holds open ones
+            Some(finished) = connections.join_next(), if !connections.is_empty() => {
+                if let Err(e) = finished {
+                    error!("Connection task failed: {}", e);
+                }
+            }
+            _ = &mut shutdown => {
+                info!("Shutdown requested, no longer accepting connections");
+                notify::stopping(notify_socket.as_deref());
+                break;
+            }
+        }
+    }
+    drop(listener);
+    drop(tcp);
+    reaper.abort();
+
+    daemon.write().await.shutdown();
+
+    let drained = tokio::time::timeout(SHUTDOWN_GRACE, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            "Closing {} connection(s) still open after grace period",
+            connections.len()
+        );
+        connections.shutdown().await;
+    }
+
+    if let Err(e) = tokio::fs::remove_file(socket_path).await {
+        warn!("Failed to remove socket {:?}: {}", socket_path, e);
+    }
+    info!("Pandemic daemon stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_common::DaemonClient;
+    use pandemic_protocol::{Message, PluginInfo, Request, Response};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixStream;
+    use tokio::sync::oneshot;
+
+    async fn send(reader: &mut BufReader<UnixStream>, request: Request) {
+        let mut json = serde_json::to_vec(&request).unwrap();
+        json.push(b'\n');
+        reader.get_mut().write_all(&json).await.unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_notifies_subscribers() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let daemon = Arc::new(RwLock::new(Daemon::new()));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let server_path = socket_path.clone();
+        let server = tokio::spawn(async move {
+            serve(listener, None, &server_path, daemon, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+        });
+
+        let stream = UnixStream::connect(&socket_path).await.unwrap();
+        let mut reader = BufReader::new(stream);
+        send(
+            &mut reader,
+            Request::Register {
+                plugin: PluginInfo {
+                    name: "listener".to_string(),
+                    version: "1.0.0".to_string(),
+                    description: None,
+                    config: None,
+                    registered_at: None,
+                    heartbeat_interval: None,
+                    dependencies: vec![],
+                },
+                force: false,
+                ignore_dependencies: false,
+            },
+        )
+        .await;
+        send(
+            &mut reader,
+            Request::Subscribe {
+                topics: vec!["daemon.#".to_string()],
+            },
+        )
+        .await;
+
+        shutdown_tx.send(()).unwrap();
+
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        match serde_json::from_str::<Message>(line.trim()).unwrap() {
+            Message::Event(event) => assert_eq!(event.topic, "daemon.shutdown"),
+            other => panic!("Expected shutdown event, got {:?}", other),
+        }
+
+        // The daemon closes the connection once the event is delivered
+        line.clear();
+        assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+
+        server.await.unwrap().unwrap();
+        assert!(!socket_path.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_systemd_is_notified_once_listening() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let notify_path = temp_dir.path().join("notify.sock");
+        let notifications = tokio::net::UnixDatagram::bind(&notify_path).unwrap();
+
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let mut daemon = Daemon::new();
+        daemon.notify_socket = Some(notify_path);
+        let daemon = Arc::new(RwLock::new(daemon));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server_path = socket_path.clone();
+        let server = tokio::spawn(async move {
+            serve(listener, None, &server_path, daemon, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+        });
+
+        let wait_for = |expected: &'static str| {
+            let notifications = &notifications;
+            async move {
+                let mut buf = [0u8; 256];
+                loop {
+                    let len =
+                        tokio::time::timeout(Duration::from_secs(5), notifications.recv(&mut buf))
+                            .await
+                            .unwrap_or_else(|_| panic!("no {} notification", expected))
+                            .unwrap();
+                    if buf[..len].trim_ascii() == expected.as_bytes() {
+                        return;
+                    }
+                }
+            }
+        };
+
+        wait_for("READY=1").await;
+        // Ready means the socket already accepts connections
+        let stream = UnixStream::connect(&socket_path).await.unwrap();
+        drop(stream);
+
+        shutdown_tx.send(()).unwrap();
+        wait_for("STOPPING=1").await;
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unix_and_tcp_clients_share_one_daemon() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let tcp_addr = format!("tcp://{}", tcp.local_addr().unwrap());
+        let daemon = Arc::new(RwLock::new(Daemon::new()));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+
+        let server_path = socket_path.clone();
+        let server = tokio::spawn(async move {
+            serve(listener, Some(tcp), &server_path, daemon, async {
+                let _ = shutdown_rx.await;
+            })
+            .await
+        });
+
+        let unix_addr = format!("unix://{}", socket_path.display());
+        let mut unix_client = DaemonClient::connect_addr(&unix_addr).await.unwrap();
+        let mut tcp_client = DaemonClient::connect_addr(&tcp_addr).await.unwrap();
+
+        let plugin = PluginInfo {
+            name: "remote".to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            config: None,
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        };
+        let registered = tcp_client
+            .send_request(&Request::Register {
+                plugin,
+                force: false,
+                ignore_dependencies: false,
+            })
+            .await
+            .unwrap();
+        assert!(matches!(registered, Response::Success { .. }));
+        unix_client
+            .subscribe(vec!["remote.#".to_string()])
+            .await
+            .unwrap();
+
+        match unix_client
+            .send_request(&Request::ListPlugins)
+            .await
+            .unwrap()
+        {
+            Response::Success { data: Some(data) } => assert_eq!(data[0]["name"], "remote"),
+            other => panic!("unexpected response: {:?}", other),
+        }
+        tcp_client
+            .publish("remote.reading", serde_json::json!({"value": 7}))
+            .await
+            .unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), unix_client.read_event())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.topic, "remote.reading");
+        assert_eq!(event.source, "remote");
+
+        shutdown_tx.send(()).unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
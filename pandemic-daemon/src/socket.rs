@@ -0,0 +1,111 @@
+use anyhow::{anyhow, Result};
+use std::ffi::CString;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tracing::info;
+
+/// Parse an octal file mode such as `660`, `0660` or `0o660`
+pub fn parse_mode(value: &str) -> Result<u32, String> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| format!("'{}' is not an octal file mode", value))
+}
+
+/// Apply the requested mode and ownership to the freshly bound socket at
+/// `path`. Anything left unset keeps what `bind` produced.
+pub fn setup_socket_permissions(
+    path: &Path,
+    mode: Option<u32>,
+    user: Option<&str>,
+    group: Option<&str>,
+) -> Result<()> {
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+        info!("Socket mode set to {:o}", mode);
+    }
+    if user.is_some() || group.is_some() {
+        set_socket_ownership(path, user, group)?;
+    }
+    Ok(())
+}
+
+fn set_socket_ownership(path: &Path, user: Option<&str>, group: Option<&str>) -> Result<()> {
+    let path_cstr = CString::new(path.to_string_lossy().as_bytes())?;
+
+    // -1 leaves the owner or group unchanged
+    let uid = match user {
+        Some(user) => {
+            let user_cstr = CString::new(user.as_bytes())?;
+            let passwd = unsafe { libc::getpwnam(user_cstr.as_ptr()) };
+            if passwd.is_null() {
+                return Err(anyhow!("User '{}' not found", user));
+            }
+            unsafe { (*passwd).pw_uid }
+        }
+        None => libc::uid_t::MAX,
+    };
+    let gid = match group {
+        Some(group) => {
+            let group_cstr = CString::new(group.as_bytes())?;
+            let entry = unsafe { libc::getgrnam(group_cstr.as_ptr()) };
+            if entry.is_null() {
+                return Err(anyhow!("Group '{}' not found", group));
+            }
+            unsafe { (*entry).gr_gid }
+        }
+        None => libc::gid_t::MAX,
+    };
+
+    let result = unsafe { libc::chown(path_cstr.as_ptr(), uid, gid) };
+    if result != 0 {
+        return Err(anyhow!("chown failed: {}", std::io::Error::last_os_error()));
+    }
+
+    info!(
+        "Socket ownership changed to {}:{}",
+        user.unwrap_or("-"),
+        group.unwrap_or("-")
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("660"), Ok(0o660));
+        assert_eq!(parse_mode("0660"), Ok(0o660));
+        assert_eq!(parse_mode("0o600"), Ok(0o600));
+        assert!(parse_mode("689").is_err());
+        assert!(parse_mode("77777").is_err());
+    }
+
+    #[test]
+    fn test_socket_mode_applied_after_bind() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let _listener = UnixListener::bind(&socket_path).unwrap();
+
+        for mode in [0o660, 0o600] {
+            setup_socket_permissions(&socket_path, Some(mode), None, None).unwrap();
+            let metadata = std::fs::metadata(&socket_path).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o7777, mode);
+        }
+    }
+
+    #[test]
+    fn test_unknown_user_is_an_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let _listener = UnixListener::bind(&socket_path).unwrap();
+
+        let err = setup_socket_permissions(&socket_path, None, Some("no-such-pandemic-user"), None)
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+    }
+}
@@ -1,6 +1,12 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Session length requested from IAM Anywhere when none is configured
+pub const DEFAULT_SESSION_DURATION_SECONDS: i32 = 3600;
+const DEFAULT_REFRESH_CHECK_INTERVAL_SECONDS: u64 = 300;
+const DEFAULT_REFRESH_SKEW_SECONDS: u64 = 300;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IamConfig {
@@ -12,25 +18,89 @@ pub struct IamConfig {
 pub struct ServerConfig {
     pub bind_address: String,
     pub port: u16,
+    /// PEM certificate chain; serves HTTPS when set together with `tls_key`
+    #[serde(default)]
+    pub tls_cert: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_key: Option<PathBuf>,
+    /// Serve roles and credentials to IMDSv1 clients that send no session
+    /// token. Tokens that are sent are still checked.
+    #[serde(default)]
+    pub allow_imdsv1: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AwsConfig {
     pub certificate_path: String,
     pub private_key_path: String,
+    /// Passphrase for an encrypted PKCS#8 private key; falls back to the
+    /// `PANDEMIC_IAM_KEY_PASSPHRASE` environment variable
+    #[serde(default)]
+    pub private_key_passphrase: Option<String>,
     pub trust_anchor_arn: String,
     pub profile_arn: String,
     pub role_arn: String,
     pub session_duration_seconds: Option<i32>,
+    /// How often to check whether the credentials need refreshing
+    pub refresh_check_interval_seconds: Option<u64>,
+    /// Refresh credentials this long before they expire
+    pub refresh_skew_seconds: Option<u64>,
     pub session_name: Option<String>,
     pub region: Option<String>,
     pub endpoint: Option<String>,
 }
 
+impl AwsConfig {
+    pub fn session_duration_seconds(&self) -> i32 {
+        self.session_duration_seconds
+            .unwrap_or(DEFAULT_SESSION_DURATION_SECONDS)
+    }
+
+    pub fn refresh_check_interval(&self) -> Duration {
+        Duration::from_secs(
+            self.refresh_check_interval_seconds
+                .unwrap_or(DEFAULT_REFRESH_CHECK_INTERVAL_SECONDS),
+        )
+    }
+
+    pub fn refresh_skew(&self) -> Duration {
+        Duration::from_secs(
+            self.refresh_skew_seconds
+                .unwrap_or(DEFAULT_REFRESH_SKEW_SECONDS),
+        )
+    }
+
+    /// Check the refresh settings against each other and the session length
+    pub fn validate(&self) -> Result<()> {
+        if self.refresh_check_interval().is_zero() {
+            return Err(anyhow::anyhow!(
+                "aws.refresh_check_interval_seconds must be greater than zero"
+            ));
+        }
+        let session = self.session_duration_seconds();
+        if self.refresh_skew().as_secs() >= session.max(0) as u64 {
+            return Err(anyhow::anyhow!(
+                "aws.refresh_skew_seconds ({}) must be smaller than the session duration ({})",
+                self.refresh_skew().as_secs(),
+                session
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn key_passphrase(&self) -> Option<String> {
+        self.private_key_passphrase
+            .clone()
+            .or_else(|| std::env::var(crate::signer::KEY_PASSPHRASE_ENV).ok())
+    }
+}
+
 impl IamConfig {
     pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = tokio::fs::read_to_string(path).await?;
-        let config: IamConfig = toml::from_str(&content)?;
+        // Secrets may be given as ${env:NAME} or ${file:/path} references
+        let config: IamConfig = pandemic_common::parse_toml_with_secrets(&content)?;
+        config.aws.validate()?;
 
         // Validate required paths exist
         if !Path::new(&config.aws.certificate_path).exists() {
@@ -49,3 +119,40 @@ impl IamConfig {
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aws_config(extra: &str) -> AwsConfig {
+        toml::from_str(&format!(
+            r#"
+            certificate_path = "cert.pem"
+            private_key_path = "key.pem"
+            trust_anchor_arn = "arn:aws:rolesanywhere:us-east-1:123456789012:trust-anchor/ta"
+            profile_arn = "arn:aws:rolesanywhere:us-east-1:123456789012:profile/p"
+            role_arn = "arn:aws:iam::123456789012:role/device"
+            {}
+            "#,
+            extra
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_refresh_defaults() {
+        let config = aws_config("");
+        assert_eq!(config.refresh_check_interval(), Duration::from_secs(300));
+        assert_eq!(config.refresh_skew(), Duration::from_secs(300));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_skew_must_be_shorter_than_session() {
+        let config = aws_config("session_duration_seconds = 900\nrefresh_skew_seconds = 900");
+        assert!(config.validate().is_err());
+
+        let config = aws_config("session_duration_seconds = 900\nrefresh_skew_seconds = 60");
+        assert!(config.validate().is_ok());
+    }
+}
@@ -84,12 +84,13 @@ impl CredentialManager {
         }
     }
 
-    pub async fn needs_refresh(&self) -> bool {
+    /// Whether there are no credentials or they expire within `skew`
+    pub async fn needs_refresh(&self, skew: std::time::Duration) -> bool {
         let creds = self.credentials.read().await;
         match &*creds {
             Some(credentials) => {
-                // Refresh if expiring within 5 minutes
-                credentials.expiration < Utc::now() + chrono::Duration::minutes(5)
+                let skew = chrono::Duration::from_std(skew).unwrap_or(chrono::Duration::MAX);
+                credentials.expiration < Utc::now() + skew
             }
             None => true,
         }
@@ -115,7 +116,12 @@ impl CredentialManager {
         config: &crate::config::AwsConfig,
     ) -> Result<AwsCredentials> {
         // Load signer
-        let signer = FileSigner::new(&config.certificate_path, &config.private_key_path)?;
+        let passphrase = config.key_passphrase();
+        let signer = FileSigner::new(
+            &config.certificate_path,
+            &config.private_key_path,
+            passphrase.as_deref(),
+        )?;
 
         // Extract region from trust anchor ARN if not provided
         let region = config
@@ -149,7 +155,7 @@ impl CredentialManager {
 
         // Create request payload (only cert and duration)
         let request = CreateSessionRequest {
-            duration_seconds: config.session_duration_seconds.unwrap_or(3600),
+            duration_seconds: config.session_duration_seconds(),
             role_session_name: config.session_name.clone(),
         };
 
@@ -158,7 +164,7 @@ impl CredentialManager {
         let body = serde_json::to_string(&request)?;
 
         // Set up signing parameters
-        let signing_params = SigningParams::new(region.clone());
+        let signing_params = SigningParams::new(region.clone(), &signer);
         let mut headers = HeaderMap::new();
         headers.insert(
             "amz-sdk-invocation-id",
@@ -212,3 +218,28 @@ fn extract_region_from_arn(arn: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_needs_refresh_with_custom_skew() {
+        let manager = CredentialManager::new();
+        assert!(manager.needs_refresh(Duration::from_secs(60)).await);
+
+        manager
+            .update_credentials(AwsCredentials {
+                access_key_id: "AKIDEXAMPLE".to_string(),
+                secret_access_key: "secret".to_string(),
+                token: "session".to_string(),
+                expiration: Utc::now() + chrono::Duration::minutes(10),
+            })
+            .await;
+
+        assert!(!manager.needs_refresh(Duration::from_secs(60)).await);
+        assert!(!manager.needs_refresh(Duration::from_secs(300)).await);
+        assert!(manager.needs_refresh(Duration::from_secs(900)).await);
+    }
+}
@@ -98,6 +98,89 @@ async fn validate_token(headers: &HeaderMap, state: &AppState) -> bool {
         if let Ok(token) = token_header.to_str() {
             return state.credential_manager.validate_session_token(token).await;
         }
+        return false;
+    }
+    state.config.server.allow_imdsv1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credentials::AwsCredentials;
+    use axum::body::to_bytes;
+
+    async fn state(allow_imdsv1: bool) -> AppState {
+        let config: IamConfig = toml::from_str(&format!(
+            r#"
+            [server]
+            bind_address = "127.0.0.1"
+            port = 80
+            allow_imdsv1 = {}
+
+            [aws]
+            certificate_path = "cert.pem"
+            private_key_path = "key.pem"
+            trust_anchor_arn = "arn:aws:rolesanywhere:us-east-1:123456789012:trust-anchor/ta"
+            profile_arn = "arn:aws:rolesanywhere:us-east-1:123456789012:profile/p"
+            role_arn = "arn:aws:iam::123456789012:role/device"
+            "#,
+            allow_imdsv1
+        ))
+        .unwrap();
+        let credential_manager = CredentialManager::new();
+        credential_manager
+            .update_credentials(AwsCredentials {
+                access_key_id: "AKIDEXAMPLE".to_string(),
+                secret_access_key: "secret".to_string(),
+                token: "session".to_string(),
+                expiration: chrono::Utc::now() + chrono::Duration::hours(1),
+            })
+            .await;
+        AppState {
+            config,
+            credential_manager,
+        }
+    }
+
+    async fn credentials(state: &AppState, headers: HeaderMap) -> (StatusCode, String) {
+        let response =
+            get_role_credentials(Path("device".to_string()), headers, State(state.clone())).await;
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_token_required_by_default() {
+        let state = state(false).await;
+
+        let (status, _) = credentials(&state, HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let response = list_roles(HeaderMap::new(), State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let mut headers = HeaderMap::new();
+        let token = state.credential_manager.create_session_token().await;
+        headers.insert("X-aws-ec2-metadata-token", token.parse().unwrap());
+        let (status, body) = credentials(&state, headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("AKIDEXAMPLE"));
+    }
+
+    #[tokio::test]
+    async fn test_imdsv1_mode_serves_without_token() {
+        let state = state(true).await;
+
+        let response = list_roles(HeaderMap::new(), State(state.clone())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, body) = credentials(&state, HeaderMap::new()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("AKIDEXAMPLE"));
+
+        // A token that is sent must still be valid
+        let mut headers = HeaderMap::new();
+        headers.insert("X-aws-ec2-metadata-token", "bogus".parse().unwrap());
+        let (status, _) = credentials(&state, headers).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
     }
-    false
 }
@@ -11,6 +11,7 @@ use axum::{
     Router,
 };
 use clap::Parser;
+use pandemic_common::tls::{serve_app, Tls};
 use pandemic_common::DaemonClient;
 use pandemic_protocol::{PluginInfo, Request};
 use std::collections::HashMap;
@@ -32,11 +33,20 @@ struct Args {
 
     #[arg(long, default_value = "/etc/pandemic/iam-config.toml")]
     config_path: PathBuf,
+
+    /// PEM certificate chain; serves HTTPS when given with --tls-key.
+    /// Overrides `server.tls_cert`
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert. Overrides `server.tls_key`
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     // Load IAM configuration - fail if missing or invalid
@@ -63,12 +73,16 @@ async fn main() -> Result<()> {
             plugin_config
         }),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
     let mut client = DaemonClient::connect(&args.socket_path).await?;
     client
         .send_request(&Request::Register {
             plugin: plugin_info,
+            force: false,
+            ignore_dependencies: false,
         })
         .await?;
 
@@ -105,27 +119,38 @@ async fn main() -> Result<()> {
         .layer(ServiceBuilder::new().layer(TraceLayer::new_for_http()))
         .with_state(state);
 
+    // The flags replace the config file's pair as a whole, so a file key is
+    // never served with a flag certificate
+    let (tls_cert, tls_key) = match (args.tls_cert, args.tls_key) {
+        (Some(cert), Some(key)) => (Some(cert), Some(key)),
+        _ => (
+            config.server.tls_cert.clone(),
+            config.server.tls_key.clone(),
+        ),
+    };
+    let tls = Tls::load(tls_cert.as_deref(), tls_key.as_deref(), None)?;
+
     // Start the server
     let bind_addr = format!("{}:{}", config.server.bind_address, config.server.port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
+    let listener = std::net::TcpListener::bind(&bind_addr)?;
     info!("IAM Anywhere server listening on {}", bind_addr);
     info!(
-        "IMDSv2-compatible endpoint available at http://{}",
+        "IMDSv2-compatible endpoint available at {}://{}",
+        if tls.is_some() { "https" } else { "http" },
         bind_addr
     );
 
-    axum::serve(listener, app).await?;
-
-    Ok(())
+    serve_app(listener, app, tls).await
 }
 
 async fn credential_refresh_loop(manager: CredentialManager, config: config::AwsConfig) {
-    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // Check every 5 minutes
+    let mut interval = tokio::time::interval(config.refresh_check_interval());
+    let skew = config.refresh_skew();
 
     loop {
         interval.tick().await;
 
-        if manager.needs_refresh().await {
+        if manager.needs_refresh(skew).await {
             info!("Refreshing AWS credentials...");
             if let Err(e) = manager.refresh_credentials(&config).await {
                 error!("Failed to refresh credentials: {}", e);
@@ -1,18 +1,46 @@
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose, Engine};
-use rsa::{pkcs1v15::Pkcs1v15Sign, pkcs8::DecodePrivateKey, RsaPrivateKey};
-use rustls_pemfile::{certs, pkcs8_private_keys};
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use pkcs8::{der::Decode, EncryptedPrivateKeyInfo};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs1v15::Pkcs1v15Sign, pkcs8::DecodePrivateKey, RsaPrivateKey,
+};
+use rustls_pemfile::{certs, ec_private_keys, pkcs8_private_keys, rsa_private_keys};
 use sha2::{Digest, Sha256};
 use std::fs;
 use x509_parser::prelude::*;
 
+/// The private key backing the certificate, which decides the signing algorithm.
+#[allow(clippy::large_enum_variant)]
+pub enum SignerKey {
+    Rsa(RsaPrivateKey),
+    Ecdsa(SigningKey),
+}
+
+impl SignerKey {
+    /// SigV4-X509 algorithm name to use in the string to sign and authorization header
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            SignerKey::Rsa(_) => "AWS4-X509-RSA-SHA256",
+            SignerKey::Ecdsa(_) => "AWS4-X509-ECDSA-SHA256",
+        }
+    }
+}
+
 pub struct FileSigner {
     pub certificate_der: Vec<u8>,
-    pub rsa_key: Option<RsaPrivateKey>,
+    pub key: SignerKey,
 }
 
+/// Environment variable consulted for the key passphrase when the config has none
+pub const KEY_PASSPHRASE_ENV: &str = "PANDEMIC_IAM_KEY_PASSPHRASE";
+
+const ENCRYPTED_KEY_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+
 impl FileSigner {
-    pub fn new(cert_path: &str, key_path: &str) -> Result<Self> {
+    /// Load the certificate and private key. `passphrase` decrypts an encrypted
+    /// PKCS#8 key (BEGIN ENCRYPTED PRIVATE KEY) and is ignored for plain keys.
+    pub fn new(cert_path: &str, key_path: &str, passphrase: Option<&str>) -> Result<Self> {
         // Load certificate
         let cert_pem = fs::read_to_string(cert_path)?;
         let mut cert_reader = cert_pem.as_bytes();
@@ -24,20 +52,23 @@ impl FileSigner {
 
         // Load private key
         let key_pem = fs::read_to_string(key_path)?;
-        let mut key_reader = key_pem.as_bytes();
-        let private_keys: Vec<_> =
-            pkcs8_private_keys(&mut key_reader).collect::<Result<Vec<_>, _>>()?;
-
-        if private_keys.is_empty() {
-            return Err(anyhow!("No private key found"));
-        }
-
-        // Try to parse RSA private key
-        let rsa_key = RsaPrivateKey::from_pkcs8_der(private_keys[0].secret_pkcs8_der()).ok();
+        let key = if key_pem.contains(&format!("-----BEGIN {}-----", ENCRYPTED_KEY_LABEL)) {
+            let passphrase = passphrase.ok_or_else(|| {
+                anyhow!(
+                    "Private key {} is encrypted but no passphrase was given, set \
+                     aws.private_key_passphrase or {}",
+                    key_path,
+                    KEY_PASSPHRASE_ENV
+                )
+            })?;
+            decrypt_private_key(&key_pem, passphrase)?
+        } else {
+            parse_private_key(&key_pem)?
+        };
 
         Ok(FileSigner {
             certificate_der: cert_der[0].as_ref().to_vec(),
-            rsa_key,
+            key,
         })
     }
 
@@ -52,21 +83,167 @@ impl FileSigner {
     }
 
     pub fn sign_string_to_sign(&self, string_to_sign: &str) -> Result<Vec<u8>> {
-        if let Some(rsa_key) = &self.rsa_key {
-            // Hash the string to sign with SHA256
-            let mut hasher = Sha256::new();
-            hasher.update(string_to_sign.as_bytes());
-            let hash = hasher.finalize();
-
-            // Sign with PKCS1v15 padding and SHA256 (with proper ASN.1 DigestInfo)
-            let padding = Pkcs1v15Sign::new::<Sha256>();
-            let signature = rsa_key
-                .sign(padding, &hash)
-                .map_err(|e| anyhow!("Failed to sign: {}", e))?;
-
-            Ok(signature)
-        } else {
-            Err(anyhow!("RSA key not available for signing"))
+        match &self.key {
+            SignerKey::Rsa(rsa_key) => {
+                // Hash the string to sign with SHA256
+                let mut hasher = Sha256::new();
+                hasher.update(string_to_sign.as_bytes());
+                let hash = hasher.finalize();
+
+                // Sign with PKCS1v15 padding and SHA256 (with proper ASN.1 DigestInfo)
+                let padding = Pkcs1v15Sign::new::<Sha256>();
+                let signature = rsa_key
+                    .sign(padding, &hash)
+                    .map_err(|e| anyhow!("Failed to sign: {}", e))?;
+
+                Ok(signature)
+            }
+            SignerKey::Ecdsa(signing_key) => {
+                // ECDSA P-256 over SHA256, DER encoded as Roles Anywhere expects
+                let signature: Signature = signing_key.sign(string_to_sign.as_bytes());
+                Ok(signature.to_der().as_bytes().to_vec())
+            }
         }
     }
 }
+
+/// Decrypt an encrypted PKCS#8 PEM key with `passphrase`
+fn decrypt_private_key(key_pem: &str, passphrase: &str) -> Result<SignerKey> {
+    let (label, doc) = pkcs8::Document::from_pem(key_pem)
+        .map_err(|e| anyhow!("Invalid encrypted private key PEM: {}", e))?;
+    if label != ENCRYPTED_KEY_LABEL {
+        return Err(anyhow!("Expected {}, found {}", ENCRYPTED_KEY_LABEL, label));
+    }
+    let encrypted = EncryptedPrivateKeyInfo::from_der(doc.as_bytes())
+        .map_err(|e| anyhow!("Invalid encrypted private key: {}", e))?;
+    let decrypted = encrypted
+        .decrypt(passphrase)
+        .map_err(|e| anyhow!("Failed to decrypt private key, wrong passphrase? {}", e))?;
+    parse_pkcs8_der(decrypted.as_bytes())
+}
+
+fn parse_pkcs8_der(der: &[u8]) -> Result<SignerKey> {
+    if let Ok(rsa_key) = RsaPrivateKey::from_pkcs8_der(der) {
+        return Ok(SignerKey::Rsa(rsa_key));
+    }
+    if let Ok(ec_key) = SigningKey::from_pkcs8_der(der) {
+        return Ok(SignerKey::Ecdsa(ec_key));
+    }
+    Err(anyhow!(
+        "Unsupported PKCS#8 private key, expected RSA or EC P-256"
+    ))
+}
+
+/// Parse an RSA or P-256 EC private key from PEM (PKCS#8, PKCS#1 for RSA keys,
+/// or SEC1 for EC keys)
+fn parse_private_key(key_pem: &str) -> Result<SignerKey> {
+    let mut key_reader = key_pem.as_bytes();
+    if let Some(key) = pkcs8_private_keys(&mut key_reader).next() {
+        return parse_pkcs8_der(key?.secret_pkcs8_der());
+    }
+
+    let mut key_reader = key_pem.as_bytes();
+    if let Some(key) = rsa_private_keys(&mut key_reader).next() {
+        let rsa_key = RsaPrivateKey::from_pkcs1_der(key?.secret_pkcs1_der())
+            .map_err(|e| anyhow!("Invalid PKCS#1 RSA private key: {}", e))?;
+        return Ok(SignerKey::Rsa(rsa_key));
+    }
+
+    let mut key_reader = key_pem.as_bytes();
+    if let Some(key) = ec_private_keys(&mut key_reader).next() {
+        let secret = p256::SecretKey::from_sec1_der(key?.secret_sec1_der())
+            .map_err(|e| anyhow!("Unsupported EC private key, expected P-256: {}", e))?;
+        return Ok(SignerKey::Ecdsa(SigningKey::from(secret)));
+    }
+
+    Err(anyhow!(
+        "No private key found, tried PKCS#8 (BEGIN PRIVATE KEY), PKCS#1 (BEGIN RSA PRIVATE KEY) \
+         and SEC1 (BEGIN EC PRIVATE KEY)"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+
+    const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+
+    fn testdata(name: &str) -> String {
+        format!("{}/{}", TESTDATA, name)
+    }
+
+    #[test]
+    fn test_load_ec_pkcs8_key() {
+        let signer =
+            FileSigner::new(&testdata("ec-cert.pem"), &testdata("ec-key.pem"), None).unwrap();
+        assert!(matches!(signer.key, SignerKey::Ecdsa(_)));
+        assert_eq!(signer.key.algorithm(), "AWS4-X509-ECDSA-SHA256");
+    }
+
+    #[test]
+    fn test_load_ec_sec1_key() {
+        let signer =
+            FileSigner::new(&testdata("ec-cert.pem"), &testdata("ec-key-sec1.pem"), None).unwrap();
+        assert!(matches!(signer.key, SignerKey::Ecdsa(_)));
+    }
+
+    #[test]
+    fn test_load_rsa_pkcs1_key() {
+        let signer = FileSigner::new(
+            &testdata("rsa-cert.pem"),
+            &testdata("rsa-key-pkcs1.pem"),
+            None,
+        )
+        .unwrap();
+        assert!(matches!(signer.key, SignerKey::Rsa(_)));
+        assert_eq!(signer.key.algorithm(), "AWS4-X509-RSA-SHA256");
+
+        let signature = signer.sign_string_to_sign("string to sign").unwrap();
+        assert_eq!(signature.len(), 256);
+    }
+
+    #[test]
+    fn test_missing_key_lists_formats() {
+        let temp =
+            std::env::temp_dir().join(format!("pandemic-iam-empty-{}.pem", std::process::id()));
+        fs::write(&temp, "not a key").unwrap();
+        let error = match FileSigner::new(&testdata("rsa-cert.pem"), temp.to_str().unwrap(), None) {
+            Ok(_) => panic!("Expected missing key error"),
+            Err(e) => e.to_string(),
+        };
+        let _ = fs::remove_file(&temp);
+        assert!(error.contains("PKCS#8"));
+        assert!(error.contains("PKCS#1"));
+        assert!(error.contains("SEC1"));
+    }
+
+    #[test]
+    fn test_load_encrypted_pkcs8_key() {
+        let cert = testdata("ec-cert.pem");
+        let key = testdata("ec-key-encrypted.pem");
+
+        let signer = FileSigner::new(&cert, &key, Some("pandemic-test")).unwrap();
+        assert!(matches!(signer.key, SignerKey::Ecdsa(_)));
+
+        let error = FileSigner::new(&cert, &key, Some("wrong")).err().unwrap();
+        assert!(error.to_string().contains("wrong passphrase"));
+
+        let error = FileSigner::new(&cert, &key, None).err().unwrap();
+        assert!(error.to_string().contains(KEY_PASSPHRASE_ENV));
+    }
+
+    #[test]
+    fn test_ecdsa_signature_verifies() {
+        let signer =
+            FileSigner::new(&testdata("ec-cert.pem"), &testdata("ec-key.pem"), None).unwrap();
+        let signature = signer.sign_string_to_sign("string to sign").unwrap();
+
+        let SignerKey::Ecdsa(signing_key) = &signer.key else {
+            panic!("Expected EC key");
+        };
+        let verifying_key = VerifyingKey::from(signing_key);
+        let signature = Signature::from_der(&signature).unwrap();
+        assert!(verifying_key.verify(b"string to sign", &signature).is_ok());
+    }
+}
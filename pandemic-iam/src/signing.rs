@@ -14,11 +14,11 @@ pub struct SigningParams {
 }
 
 impl SigningParams {
-    pub fn new(region: String) -> Self {
+    pub fn new(region: String, signer: &FileSigner) -> Self {
         Self {
             region,
             service: "rolesanywhere".to_string(),
-            algorithm: "AWS4-X509-RSA-SHA256".to_string(), // Default to RSA
+            algorithm: signer.key.algorithm().to_string(),
             timestamp: Utc::now(),
         }
     }
@@ -67,7 +67,7 @@ pub fn sign_request(
     // Create string to sign
     let string_to_sign = create_string_to_sign(params, &canonical_request_hash);
 
-    // Sign the string to sign using RSA PKCS1v15 with SHA256
+    // Sign the string to sign with the certificate's key (RSA or ECDSA, SHA256)
     let signature_bytes = signer.sign_string_to_sign(&string_to_sign)?;
     let signature = hex::encode(signature_bytes);
 
@@ -102,7 +102,7 @@ fn create_canonical_request(
 ) -> Result<String> {
     let url = reqwest::Url::parse(uri)?;
     let path = url.path();
-    let query = url.query().unwrap_or("");
+    let query = canonical_query_string(url.query().unwrap_or(""));
 
     let canonical_headers = create_canonical_headers(headers);
     let signed_headers = get_signed_headers(headers);
@@ -114,6 +114,32 @@ fn create_canonical_request(
     ))
 }
 
+/// Query parameters as SigV4 expects them: each key and value decoded and
+/// re-encoded with only unreserved characters left bare, sorted by key and
+/// then by value, with `=` kept on parameters that have no value
+fn canonical_query_string(query: &str) -> String {
+    let encode = |part: &str| {
+        let decoded = urlencoding::decode_binary(part.as_bytes());
+        urlencoding::encode_binary(&decoded).into_owned()
+    };
+
+    let mut params: Vec<(String, String)> = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (encode(key), encode(value))
+        })
+        .collect();
+    params.sort();
+
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
 fn create_canonical_headers(headers: &HeaderMap) -> String {
     let mut canonical: BTreeMap<String, String> = BTreeMap::new();
 
@@ -162,3 +188,85 @@ fn create_string_to_sign(params: &SigningParams, canonical_request_hash: &str) -
         canonical_request_hash
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ecdsa_authorization_header() {
+        let testdata = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let signer = FileSigner::new(
+            &format!("{}/ec-cert.pem", testdata),
+            &format!("{}/ec-key.pem", testdata),
+            None,
+        )
+        .unwrap();
+        let params = SigningParams::new("us-east-1".to_string(), &signer);
+        assert_eq!(params.algorithm, "AWS4-X509-ECDSA-SHA256");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        sign_request(
+            "POST",
+            "https://rolesanywhere.us-east-1.amazonaws.com/sessions",
+            &mut headers,
+            "{}",
+            &params,
+            &signer.certificate_base64(),
+            &signer.get_serial_number().unwrap(),
+            &signer,
+        )
+        .unwrap();
+
+        let authorization = headers["authorization"].to_str().unwrap();
+        assert!(authorization.starts_with("AWS4-X509-ECDSA-SHA256 Credential="));
+        assert!(authorization.contains("/us-east-1/rolesanywhere/aws4_request"));
+        assert!(authorization.contains("SignedHeaders=content-type;host;x-amz-date;x-amz-x509"));
+    }
+
+    fn canonical_query(uri: &str) -> String {
+        let url = reqwest::Url::parse(uri).unwrap();
+        canonical_query_string(url.query().unwrap_or(""))
+    }
+
+    /// Vectors from the AWS Signature Version 4 test suite
+    #[test]
+    fn test_canonical_query_matches_aws_vectors() {
+        let host = "https://example.amazonaws.com/";
+        let cases = [
+            // get-vanilla-query-order-key-case
+            ("?Param2=value2&Param1=value1", "Param1=value1&Param2=value2"),
+            // get-vanilla-query-order-key
+            ("?Param1=value2&Param1=value1", "Param1=value1&Param1=value2"),
+            // get-vanilla-query-order-value
+            ("?Param1=value2&Param1=Value1", "Param1=Value1&Param1=value2"),
+            // get-vanilla-query-unreserved
+            (
+                "?-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz=-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+                "-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz=-._~0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+            ),
+            // get-vanilla-utf8-query
+            ("?\u{1234}=bar", "%E1%88%B4=bar"),
+            // get-vanilla-empty-query-key
+            ("?Param1=value1", "Param1=value1"),
+            ("", ""),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(
+                canonical_query(&format!("{}{}", host, query)),
+                expected,
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_query_reencodes_reserved_characters() {
+        assert_eq!(
+            canonical_query("https://example.amazonaws.com/?b=a b&a=x%2fy&flag"),
+            "a=x%2Fy&b=a%20b&flag="
+        );
+    }
+}
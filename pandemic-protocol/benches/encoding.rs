@@ -0,0 +1,42 @@
+//! Compares JSON and MessagePack on a typical event, both for payload size
+//! (printed once up front) and encode/decode time.
+//!
+//! Run with `cargo bench -p pandemic-protocol`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pandemic_protocol::{Encoding, Event, Message};
+use std::time::SystemTime;
+
+fn sample_event() -> Message {
+    Message::Event(Event {
+        topic: "health.sensor.status".to_string(),
+        source: "pandemic-udp".to_string(),
+        data: serde_json::json!({
+            "sensor_id": 4021,
+            "healthy": true,
+            "temperature_c": 21.75,
+            "readings": (0..32).map(|i| i * 3).collect::<Vec<_>>(),
+            "labels": {"site": "lab-2", "rack": "b", "slot": 7},
+        }),
+        timestamp: Some(SystemTime::now()),
+    })
+}
+
+fn bench_encodings(c: &mut Criterion) {
+    let event = sample_event();
+
+    for (name, encoding) in [("json", Encoding::Json), ("msgpack", Encoding::MessagePack)] {
+        let body = encoding.encode(&event).unwrap();
+        println!("{} event payload: {} bytes", name, body.len());
+
+        c.bench_function(&format!("encode_event_{}", name), |b| {
+            b.iter(|| encoding.encode(black_box(&event)).unwrap())
+        });
+        c.bench_function(&format!("decode_event_{}", name), |b| {
+            b.iter(|| encoding.decode::<Message>(black_box(&body)).unwrap())
+        });
+    }
+}
+
+criterion_group!(benches, bench_encodings);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::Framing;
+
+/// How message bodies are serialized on a daemon socket.
+///
+/// Every connection starts out speaking `Json`. A client can ask for
+/// `MessagePack` in its `Hello`; once the daemon's reply confirms it, both
+/// sides switch to MessagePack bodies in length-prefixed frames, since a
+/// MessagePack body may contain newline bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Json(serde_json::Error),
+    MessagePackEncode(rmp_serde::encode::Error),
+    MessagePackDecode(rmp_serde::decode::Error),
+}
+
+impl std::fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodingError::Json(e) => write!(f, "{}", e),
+            EncodingError::MessagePackEncode(e) => write!(f, "{}", e),
+            EncodingError::MessagePackDecode(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl Encoding {
+    /// Serialize a message body. Structs are written as MessagePack maps so
+    /// the tagged and flattened protocol types keep their field names.
+    pub fn encode<T: Serialize + ?Sized>(&self, message: &T) -> Result<Vec<u8>, EncodingError> {
+        match self {
+            Encoding::Json => serde_json::to_vec(message).map_err(EncodingError::Json),
+            Encoding::MessagePack => {
+                rmp_serde::to_vec_named(message).map_err(EncodingError::MessagePackEncode)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, EncodingError> {
+        match self {
+            Encoding::Json => serde_json::from_slice(body).map_err(EncodingError::Json),
+            Encoding::MessagePack => {
+                rmp_serde::from_slice(body).map_err(EncodingError::MessagePackDecode)
+            }
+        }
+    }
+
+    /// The framing to use once this encoding is agreed, given the framing the
+    /// connection started with
+    pub fn framing(&self, initial: Framing) -> Framing {
+        match self {
+            Encoding::Json => initial,
+            Encoding::MessagePack => Framing::LengthPrefixed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Message, Request, RequestEnvelope, Response, ResponseEnvelope};
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_message_pack_round_trip() {
+        let envelope = RequestEnvelope {
+            id: Some("7".to_string()),
+            request: Request::Publish {
+                topic: "sensor.reading".to_string(),
+                data: serde_json::json!({"value": 21.5, "tags": ["a", "b"], "ok": true}),
+                retain: true,
+            },
+        };
+        let body = Encoding::MessagePack.encode(&envelope).unwrap();
+        let decoded: RequestEnvelope = Encoding::MessagePack.decode(&body).unwrap();
+        assert_eq!(decoded.id.as_deref(), Some("7"));
+        match decoded.request {
+            Request::Publish {
+                topic,
+                data,
+                retain,
+            } => {
+                assert_eq!(topic, "sensor.reading");
+                assert_eq!(data["value"], 21.5);
+                assert_eq!(data["tags"][1], "b");
+                assert!(retain);
+            }
+            other => panic!("Expected Publish request, got {:?}", other),
+        }
+
+        let response = ResponseEnvelope {
+            id: None,
+            response: Response::success_with_data(serde_json::json!({"count": 3})),
+        };
+        let body = Encoding::MessagePack.encode(&response).unwrap();
+        let decoded: ResponseEnvelope = Encoding::MessagePack.decode(&body).unwrap();
+        match decoded.response {
+            Response::Success { data } => assert_eq!(data.unwrap()["count"], 3),
+            other => panic!("Expected Success response, got {:?}", other),
+        }
+
+        let event = Message::Event(Event {
+            topic: "sensor.reading".to_string(),
+            source: "test".to_string(),
+            data: serde_json::json!({"value": 1}),
+            timestamp: Some(SystemTime::UNIX_EPOCH),
+        });
+        let body = Encoding::MessagePack.encode(&event).unwrap();
+        match Encoding::MessagePack.decode::<Message>(&body).unwrap() {
+            Message::Event(event) => {
+                assert_eq!(event.data["value"], 1);
+                assert_eq!(event.timestamp, Some(SystemTime::UNIX_EPOCH));
+            }
+            other => panic!("Expected event, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_message_pack_bodies_need_length_prefix() {
+        assert_eq!(Encoding::Json.framing(Framing::Newline), Framing::Newline);
+        assert_eq!(
+            Encoding::MessagePack.framing(Framing::Newline),
+            Framing::LengthPrefixed
+        );
+        assert_eq!(
+            serde_json::to_value(Encoding::MessagePack).unwrap(),
+            "msgpack"
+        );
+    }
+}
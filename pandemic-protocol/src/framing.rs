@@ -0,0 +1,165 @@
+/// How messages are delimited on a daemon socket.
+///
+/// `Newline` is the original newline-delimited JSON. `LengthPrefixed` writes a
+/// 4-byte big-endian length followed by the body, so payloads may contain raw
+/// newlines (e.g. pretty-printed JSON).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    Newline,
+    LengthPrefixed,
+}
+
+/// Largest frame body accepted in length-prefixed mode.
+pub const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameError {
+    TooLarge(usize),
+}
+
+impl std::fmt::Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrameError::TooLarge(len) => write!(
+                f,
+                "frame of {} bytes exceeds maximum of {} bytes",
+                len, MAX_FRAME_LEN
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+impl Framing {
+    /// Guess the framing from the first byte a peer sent.
+    ///
+    /// JSON always starts with `{` or whitespace, while a length prefix for any
+    /// frame under 16MB starts with a zero byte.
+    pub fn detect(first_byte: u8) -> Self {
+        if first_byte == 0 {
+            Framing::LengthPrefixed
+        } else {
+            Framing::Newline
+        }
+    }
+
+    /// Wrap a message body for the wire.
+    pub fn encode(&self, body: &[u8]) -> Vec<u8> {
+        match self {
+            Framing::Newline => {
+                let mut frame = Vec::with_capacity(body.len() + 1);
+                frame.extend_from_slice(body);
+                frame.push(b'\n');
+                frame
+            }
+            Framing::LengthPrefixed => {
+                let mut frame = Vec::with_capacity(body.len() + 4);
+                frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+                frame.extend_from_slice(body);
+                frame
+            }
+        }
+    }
+
+    /// Pop the next complete frame body off the front of `buf`, if one is buffered.
+    pub fn decode(&self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>, FrameError> {
+        match self {
+            Framing::Newline => match buf.iter().position(|b| *b == b'\n') {
+                Some(pos) => {
+                    let mut frame: Vec<u8> = buf.drain(..=pos).collect();
+                    frame.pop();
+                    Ok(Some(frame))
+                }
+                None => Ok(None),
+            },
+            Framing::LengthPrefixed => {
+                if buf.len() < 4 {
+                    return Ok(None);
+                }
+                let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+                if len > MAX_FRAME_LEN {
+                    return Err(FrameError::TooLarge(len));
+                }
+                if buf.len() < 4 + len {
+                    return Ok(None);
+                }
+                let frame = buf[4..4 + len].to_vec();
+                buf.drain(..4 + len);
+                Ok(Some(frame))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Request;
+
+    #[test]
+    fn test_detect_framing() {
+        assert_eq!(Framing::detect(b'{'), Framing::Newline);
+        assert_eq!(Framing::detect(0), Framing::LengthPrefixed);
+    }
+
+    #[test]
+    fn test_length_prefixed_round_trip_with_newlines() {
+        let request = Request::Publish {
+            topic: "test.topic".to_string(),
+            data: serde_json::json!({"text": "line one\nline two"}),
+            retain: false,
+        };
+        let body = serde_json::to_vec_pretty(&request).unwrap();
+        assert!(body.contains(&b'\n'));
+
+        let mut buf = Framing::LengthPrefixed.encode(&body);
+        buf.extend(Framing::LengthPrefixed.encode(&body));
+
+        for _ in 0..2 {
+            let frame = Framing::LengthPrefixed.decode(&mut buf).unwrap().unwrap();
+            match serde_json::from_slice::<Request>(&frame).unwrap() {
+                Request::Publish { data, .. } => assert_eq!(data["text"], "line one\nline two"),
+                _ => panic!("Expected Publish request"),
+            }
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_newline_round_trip_escapes_embedded_newlines() {
+        let request = Request::Publish {
+            topic: "test.topic".to_string(),
+            data: serde_json::json!({"text": "line one\nline two"}),
+            retain: false,
+        };
+        let body = serde_json::to_vec(&request).unwrap();
+
+        let mut buf = Framing::Newline.encode(&body);
+        let frame = Framing::Newline.decode(&mut buf).unwrap().unwrap();
+        match serde_json::from_slice::<Request>(&frame).unwrap() {
+            Request::Publish { data, .. } => assert_eq!(data["text"], "line one\nline two"),
+            _ => panic!("Expected Publish request"),
+        }
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_partial_frames_wait_for_more_data() {
+        let frame = Framing::LengthPrefixed.encode(b"{}");
+        let mut buf = frame[..3].to_vec();
+        assert_eq!(Framing::LengthPrefixed.decode(&mut buf).unwrap(), None);
+
+        buf.extend_from_slice(&frame[3..]);
+        assert_eq!(
+            Framing::LengthPrefixed.decode(&mut buf).unwrap(),
+            Some(b"{}".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_oversized_frame_rejected() {
+        let mut buf = ((MAX_FRAME_LEN + 1) as u32).to_be_bytes().to_vec();
+        assert!(Framing::LengthPrefixed.decode(&mut buf).is_err());
+    }
+}
@@ -1,10 +1,31 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 use std::time::SystemTime;
 
+pub mod encoding;
+pub mod framing;
+pub mod topic;
+
+pub use encoding::{Encoding, EncodingError};
+pub use framing::{FrameError, Framing, MAX_FRAME_LEN};
+pub use topic::{Topic, TopicError};
+
+/// Version of the daemon wire protocol spoken by this crate.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest client protocol version the daemon still accepts.
+pub const MIN_SUPPORTED_PROTOCOL_VERSION: u32 = 1;
+
+/// Timestamps go over the wire as RFC 3339 in UTC (`2024-01-02T03:04:05Z`)
+/// so any client can parse them. The `2024-01-02 03:04:05 UTC` form older
+/// daemons wrote, e.g. in state files, is still accepted.
 mod time_format {
-    use serde::{Deserialize, Deserializer, Serializer};
-    use std::time::{SystemTime, UNIX_EPOCH};
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    const LEGACY_FORMAT: &str = "%Y-%m-%d %H:%M:%S UTC";
 
     pub fn serialize<S>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -13,11 +34,9 @@ mod time_format {
         match time {
             Some(t) => {
                 let duration = t.duration_since(UNIX_EPOCH).unwrap();
-                let datetime =
-                    chrono::DateTime::<chrono::Utc>::from_timestamp(duration.as_secs() as i64, 0)
-                        .unwrap()
-                        .format("%Y-%m-%d %H:%M:%S UTC")
-                        .to_string();
+                let datetime = DateTime::<Utc>::from_timestamp(duration.as_secs() as i64, 0)
+                    .unwrap()
+                    .to_rfc3339_opts(SecondsFormat::Secs, true);
                 serializer.serialize_str(&datetime)
             }
             None => serializer.serialize_none(),
@@ -30,12 +49,37 @@ mod time_format {
     {
         let opt: Option<String> = Option::deserialize(deserializer)?;
         match opt {
-            Some(_) => Ok(Some(SystemTime::now())), // Simplified for now
+            Some(s) => {
+                let timestamp = match DateTime::parse_from_rfc3339(&s) {
+                    Ok(datetime) => datetime.timestamp(),
+                    Err(_) => NaiveDateTime::parse_from_str(&s, LEGACY_FORMAT)
+                        .map_err(|e| {
+                            de::Error::custom(format!("invalid timestamp '{}': {}", s, e))
+                        })?
+                        .and_utc()
+                        .timestamp(),
+                };
+                let secs = u64::try_from(timestamp)
+                    .map_err(|_| de::Error::custom(format!("timestamp '{}' before epoch", s)))?;
+                Ok(Some(UNIX_EPOCH + Duration::from_secs(secs)))
+            }
             None => Ok(None),
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginHealth {
+    pub name: String,
+    #[serde(with = "time_format")]
+    pub registered_at: Option<SystemTime>,
+    /// Whether the plugin currently holds an open connection to the daemon
+    pub connected: bool,
+    pub subscribed_topics: usize,
+    /// Events delivered to the plugin since it registered
+    pub events_delivered: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthMetrics {
     // Daemon metrics
@@ -43,6 +87,11 @@ pub struct HealthMetrics {
     pub total_connections: usize,
     pub event_bus_subscribers: usize,
     pub uptime_seconds: u64,
+    /// Events dropped because a subscriber's buffer was full
+    #[serde(default)]
+    pub dropped_events: u64,
+    #[serde(default)]
+    pub plugins: Vec<PluginHealth>,
 
     // System metrics
     pub memory_used_mb: u64,
@@ -51,6 +100,37 @@ pub struct HealthMetrics {
     pub load_average: Option<f32>,
 }
 
+/// Counters that only grow while the daemon runs; they start at zero on
+/// every daemon start and are never reset
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonMetrics {
+    pub uptime_seconds: u64,
+    /// Events accepted for publishing, whether or not anyone was subscribed
+    pub events_published: u64,
+    /// Event copies handed to subscribers, including retained replays
+    pub events_delivered: u64,
+    /// Event copies lost because a subscriber's buffer was full
+    pub events_dropped: u64,
+    /// Requests handled, keyed by request type
+    pub requests: BTreeMap<String, u64>,
+}
+
+/// Build and runtime details of a running daemon
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonInfo {
+    /// Daemon build version
+    pub version: String,
+    pub protocol_version: u32,
+    pub uptime_seconds: u64,
+    /// Optional behaviour enabled on this daemon, e.g. `acl` or `state-file`
+    pub features: Vec<String>,
+    #[serde(with = "time_format")]
+    pub started_at: Option<SystemTime>,
+    /// Socket the daemon listens on, when it knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket_path: Option<PathBuf>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginInfo {
     pub name: String,
@@ -59,17 +139,72 @@ pub struct PluginInfo {
     pub config: Option<HashMap<String, String>>,
     #[serde(with = "time_format")]
     pub registered_at: Option<SystemTime>,
+    /// Seconds between heartbeats; the daemon deregisters the plugin after
+    /// two intervals without one. `None` disables liveness tracking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat_interval: Option<u64>,
+    /// Plugins that must already be registered before this one may register
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Request {
+    Hello {
+        protocol_version: u32,
+        client: String,
+        /// Body encoding the client wants for the rest of the connection;
+        /// JSON when absent
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encoding: Option<Encoding>,
+    },
     Register {
         plugin: PluginInfo,
+        /// Take the name over even if another live connection owns it
+        #[serde(default)]
+        force: bool,
+        /// Register even when declared dependencies aren't registered
+        #[serde(default)]
+        ignore_dependencies: bool,
     },
     Deregister {
         name: String,
     },
+    /// Deregister every plugin whose name matches a shell-style glob,
+    /// answering with the removed names
+    DeregisterMatching {
+        pattern: String,
+        /// Required when the pattern has no literal characters, such as `*`
+        #[serde(default)]
+        confirm: bool,
+    },
+    /// Change the metadata of a plugin registered by this connection; fields
+    /// left unset keep their current value
+    UpdatePluginConfig {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        config: Option<HashMap<String, String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+    },
+    /// A plugin's configuration: its defaults merged with any override
+    GetConfig {
+        plugin: String,
+    },
+    /// Replace a plugin's configuration override with `config`, an object
+    SetConfigOverride {
+        plugin: String,
+        config: serde_json::Value,
+    },
+    /// Remove a plugin's configuration override, leaving its defaults
+    ClearConfigOverride {
+        plugin: String,
+    },
+    /// Refresh the liveness of a plugin registered with a `heartbeat_interval`
+    Heartbeat {
+        name: String,
+    },
     ListPlugins,
     GetPlugin {
         name: String,
@@ -80,11 +215,58 @@ pub enum Request {
     Unsubscribe {
         topics: Vec<String>,
     },
+    /// Topics each connection is subscribed to, keyed by plugin name, or by
+    /// connection id for connections that never registered
+    ListSubscriptions,
     Publish {
         topic: String,
         data: serde_json::Value,
+        /// Keep this as the topic's last value and replay it to later subscribers
+        #[serde(default)]
+        retain: bool,
     },
     GetHealth,
+    /// Cumulative event and request counters
+    GetMetrics,
+    /// Answered with an empty success and no side effects, for liveness checks
+    Ping,
+    /// Recently published events, newest first
+    GetEventHistory {
+        /// Subscription-style pattern the topic must match; every topic when unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        topic_filter: Option<String>,
+        limit: usize,
+    },
+    /// Daemon version, protocol version, uptime and enabled features
+    Info,
+}
+
+impl Request {
+    /// The variant name, as it appears in the `type` field on the wire
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::Hello { .. } => "Hello",
+            Request::Register { .. } => "Register",
+            Request::Deregister { .. } => "Deregister",
+            Request::DeregisterMatching { .. } => "DeregisterMatching",
+            Request::UpdatePluginConfig { .. } => "UpdatePluginConfig",
+            Request::GetConfig { .. } => "GetConfig",
+            Request::SetConfigOverride { .. } => "SetConfigOverride",
+            Request::ClearConfigOverride { .. } => "ClearConfigOverride",
+            Request::Heartbeat { .. } => "Heartbeat",
+            Request::ListPlugins => "ListPlugins",
+            Request::GetPlugin { .. } => "GetPlugin",
+            Request::Subscribe { .. } => "Subscribe",
+            Request::Unsubscribe { .. } => "Unsubscribe",
+            Request::ListSubscriptions => "ListSubscriptions",
+            Request::Publish { .. } => "Publish",
+            Request::GetHealth => "GetHealth",
+            Request::GetMetrics => "GetMetrics",
+            Request::Ping => "Ping",
+            Request::GetEventHistory { .. } => "GetEventHistory",
+            Request::Info => "Info",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -92,11 +274,24 @@ pub enum Request {
 pub enum AgentRequest {
     GetHealth,
     GetCapabilities,
-    ListServices,
+    /// Pandemic services known to the init system
+    ListServices {
+        /// Shell-style glob on the service name, e.g. `pandemic-udp*`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filter: Option<String>,
+    },
     SystemdControl {
         action: String,
         service: String,
     },
+    /// The last `lines` journal entries of a service
+    GetServiceLogs {
+        service: String,
+        lines: u32,
+        /// journalctl time such as `2025-01-01 12:00` or `1 hour ago`
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<String>,
+    },
 
     // User management
     UserCreate {
@@ -128,6 +323,8 @@ pub enum AgentRequest {
         username: String,
     },
     ListGroups,
+    /// Effective user/group blocklist the agent enforces
+    GetBlocklist,
 
     // Service configuration
     ServiceConfigOverride {
@@ -152,6 +349,32 @@ pub enum AgentRequest {
         name: String,
         target_path: Option<String>,
     },
+
+    // Background jobs
+    /// State of a job started with [`AgentMessage::Submit`]
+    GetJob {
+        job_id: String,
+    },
+}
+
+/// Whether a background agent job is still running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    /// The job stopped without producing a response, e.g. it panicked
+    Failed,
+}
+
+/// A background agent job and, once it has completed, the response its
+/// request produced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobInfo {
+    pub job_id: String,
+    pub state: JobState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Response>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -160,11 +383,17 @@ pub struct UserConfig {
     pub home_dir: Option<String>,
     pub groups: Option<Vec<String>>,
     pub system_user: Option<bool>,
+    /// Pre-hashed crypt(3) password such as `$6$salt$hash`; plaintext is rejected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceOverrides {
     pub environment: Option<HashMap<String, String>>,
+    /// `${NAME}` is replaced with `environment[NAME]` when the override is
+    /// written; other names are left for the init system, and `$${` writes
+    /// a literal `${`
     pub exec_start: Option<String>,
     pub restart: Option<String>,
     pub user: Option<String>,
@@ -182,6 +411,10 @@ pub enum Message {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum AgentMessage {
     Request(AgentRequest),
+    /// Run the request as a background job; the agent answers
+    /// [`Response::Accepted`] right away and the outcome is read with
+    /// [`AgentRequest::GetJob`]
+    Submit(AgentRequest),
     Response(Response),
 }
 
@@ -193,12 +426,48 @@ pub struct Event {
     pub timestamp: Option<SystemTime>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Why a request failed, so callers can react without parsing the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    PermissionDenied,
+    InvalidRequest,
+    Conflict,
+    Internal,
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status")]
 pub enum Response {
-    Success { data: Option<serde_json::Value> },
-    Error { message: String },
-    NotFound { message: String },
+    Success {
+        data: Option<serde_json::Value>,
+    },
+    Error {
+        message: String,
+        /// Absent from errors sent by older daemons and agents
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<ErrorCode>,
+    },
+    NotFound {
+        message: String,
+    },
+    Conflict {
+        message: String,
+    },
+    IncompatibleVersion {
+        server: u32,
+        min_supported: u32,
+    },
+    /// The request runs in the background as job `job_id`
+    Accepted {
+        job_id: String,
+    },
+    /// A plugin declared dependencies that aren't registered
+    DependenciesUnmet {
+        missing: Vec<String>,
+    },
 }
 
 impl Response {
@@ -213,6 +482,14 @@ impl Response {
     pub fn error(message: impl Into<String>) -> Self {
         Self::Error {
             message: message.into(),
+            code: None,
+        }
+    }
+
+    pub fn error_with_code(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self::Error {
+            message: message.into(),
+            code: Some(code),
         }
     }
 
@@ -221,12 +498,58 @@ impl Response {
             message: message.into(),
         }
     }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::Conflict {
+            message: message.into(),
+        }
+    }
+
+    pub fn accepted(job_id: impl Into<String>) -> Self {
+        Self::Accepted {
+            job_id: job_id.into(),
+        }
+    }
+
+    pub fn dependencies_unmet(missing: Vec<String>) -> Self {
+        Self::DependenciesUnmet { missing }
+    }
+
+    pub fn incompatible_version() -> Self {
+        Self::IncompatibleVersion {
+            server: PROTOCOL_VERSION,
+            min_supported: MIN_SUPPORTED_PROTOCOL_VERSION,
+        }
+    }
+}
+
+/// A request with an optional correlation id, flattened onto the wire so
+/// that messages without an `id` look exactly like a bare `Request`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+/// A response echoing the correlation id of the request it answers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub response: Response,
+}
+
+/// Returns true when a client speaking `version` can talk to this daemon.
+pub fn is_compatible_version(version: u32) -> bool {
+    (MIN_SUPPORTED_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json;
     use std::collections::HashMap;
 
     #[test]
@@ -240,6 +563,8 @@ mod tests {
             description: Some("Test description".to_string()),
             config: Some(config),
             registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
         };
 
         let json = serde_json::to_string(&plugin).unwrap();
@@ -258,9 +583,15 @@ mod tests {
             description: None,
             config: None,
             registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
         };
 
-        let request = Request::Register { plugin };
+        let request = Request::Register {
+            plugin,
+            force: false,
+            ignore_dependencies: false,
+        };
         let json = serde_json::to_string(&request).unwrap();
 
         assert!(json.contains(r#""type":"Register""#));
@@ -269,7 +600,7 @@ mod tests {
 
         let deserialized: Request = serde_json::from_str(&json).unwrap();
         match deserialized {
-            Request::Register { plugin } => assert_eq!(plugin.name, "test-plugin"),
+            Request::Register { plugin, .. } => assert_eq!(plugin.name, "test-plugin"),
             _ => panic!("Expected Register request"),
         }
     }
@@ -336,6 +667,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_error_code_serialization() {
+        let json = serde_json::to_value(Response::error_with_code(
+            ErrorCode::PermissionDenied,
+            "Denied",
+        ))
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"status": "Error", "message": "Denied", "code": "permission_denied"})
+        );
+
+        // Errors without a code keep the old wire format
+        let json = serde_json::to_value(Response::error("Oops")).unwrap();
+        assert!(json.get("code").is_none());
+        let parsed: Response =
+            serde_json::from_str(r#"{"status": "Error", "message": "Oops"}"#).unwrap();
+        assert!(matches!(parsed, Response::Error { code: None, .. }));
+    }
+
     #[test]
     fn test_success_with_data_response_serialization() {
         let data = serde_json::json!({"test": "value"});
@@ -361,7 +712,10 @@ mod tests {
 
         let deserialized: Response = serde_json::from_str(&json).unwrap();
         match deserialized {
-            Response::Error { message } => assert_eq!(message, "Test error"),
+            Response::Error { message, code } => {
+                assert_eq!(message, "Test error");
+                assert_eq!(code, None);
+            }
             _ => panic!("Expected Error response"),
         }
     }
@@ -381,6 +735,53 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hello_request_serialization() {
+        let request = Request::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            client: "test-client".to_string(),
+            encoding: None,
+        };
+        let json = serde_json::to_string(&request).unwrap();
+
+        assert!(json.contains(r#""type":"Hello""#));
+        assert!(json.contains(r#""protocol_version":1"#));
+        assert!(!json.contains("encoding"));
+
+        let deserialized: Request = serde_json::from_str(&json).unwrap();
+        match deserialized {
+            Request::Hello { client, .. } => assert_eq!(client, "test-client"),
+            _ => panic!("Expected Hello request"),
+        }
+    }
+
+    #[test]
+    fn test_incompatible_version_response_serialization() {
+        let response = Response::incompatible_version();
+        let json = serde_json::to_string(&response).unwrap();
+
+        assert!(json.contains(r#""status":"IncompatibleVersion""#));
+
+        let deserialized: Response = serde_json::from_str(&json).unwrap();
+        match deserialized {
+            Response::IncompatibleVersion {
+                server,
+                min_supported,
+            } => {
+                assert_eq!(server, PROTOCOL_VERSION);
+                assert_eq!(min_supported, MIN_SUPPORTED_PROTOCOL_VERSION);
+            }
+            _ => panic!("Expected IncompatibleVersion response"),
+        }
+    }
+
+    #[test]
+    fn test_version_compatibility() {
+        assert!(is_compatible_version(PROTOCOL_VERSION));
+        assert!(!is_compatible_version(PROTOCOL_VERSION + 1));
+        assert!(!is_compatible_version(MIN_SUPPORTED_PROTOCOL_VERSION - 1));
+    }
+
     #[test]
     fn test_timestamp_serialization() {
         let plugin = PluginInfo {
@@ -389,12 +790,89 @@ mod tests {
             description: None,
             config: None,
             registered_at: Some(SystemTime::now()),
+            heartbeat_interval: None,
+            dependencies: vec![],
         };
 
-        let json = serde_json::to_string(&plugin).unwrap();
-        assert!(json.contains("UTC"));
+        let json: serde_json::Value = serde_json::to_value(&plugin).unwrap();
+        let registered_at = json["registered_at"].as_str().unwrap();
+        assert!(registered_at.ends_with('Z'), "{}", registered_at);
+        assert!(chrono::DateTime::parse_from_rfc3339(registered_at).is_ok());
+
+        let deserialized: PluginInfo = serde_json::from_value(json).unwrap();
+        let original = plugin.registered_at.unwrap();
+        let restored = deserialized.registered_at.unwrap();
+        let drift = original
+            .duration_since(restored)
+            .unwrap_or_else(|e| e.duration());
+        assert!(drift < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_legacy_timestamps_still_deserialize() {
+        let json = r#"{"name":"test","version":"1.0.0","description":null,"config":null,"registered_at":"2024-01-02 03:04:05 UTC"}"#;
+        let legacy: PluginInfo = serde_json::from_str(json).unwrap();
+        let json = r#"{"name":"test","version":"1.0.0","description":null,"config":null,"registered_at":"2024-01-02T03:04:05Z"}"#;
+        let rfc3339: PluginInfo = serde_json::from_str(json).unwrap();
+
+        let expected = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_704_164_645);
+        assert_eq!(legacy.registered_at, Some(expected));
+        assert_eq!(rfc3339.registered_at, Some(expected));
+    }
+
+    #[test]
+    fn test_timestamp_deserialization_rejects_malformed() {
+        let json = r#"{"name":"test","version":"1.0.0","description":null,"config":null,"registered_at":"yesterday"}"#;
+        assert!(serde_json::from_str::<PluginInfo>(json).is_err());
 
-        // Should deserialize without error
-        let _: PluginInfo = serde_json::from_str(&json).unwrap();
+        let json = r#"{"name":"test","version":"1.0.0","description":null,"config":null,"registered_at":null}"#;
+        let plugin: PluginInfo = serde_json::from_str(json).unwrap();
+        assert!(plugin.registered_at.is_none());
+    }
+
+    #[test]
+    fn test_envelope_id_is_optional() {
+        let bare: RequestEnvelope = serde_json::from_str(r#"{"type": "ListPlugins"}"#).unwrap();
+        assert!(bare.id.is_none());
+        assert!(matches!(bare.request, Request::ListPlugins));
+
+        let tagged: RequestEnvelope =
+            serde_json::from_str(r#"{"type": "GetPlugin", "name": "a", "id": "7"}"#).unwrap();
+        assert_eq!(tagged.id.as_deref(), Some("7"));
+        assert!(matches!(tagged.request, Request::GetPlugin { ref name } if name == "a"));
+
+        let untagged = ResponseEnvelope {
+            id: None,
+            response: Response::success(),
+        };
+        assert_eq!(
+            serde_json::to_value(&untagged).unwrap(),
+            serde_json::to_value(Response::success()).unwrap()
+        );
+
+        let echoed = ResponseEnvelope {
+            id: Some("7".to_string()),
+            response: Response::not_found("missing"),
+        };
+        let json = serde_json::to_string(&echoed).unwrap();
+        let parsed: ResponseEnvelope = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.id.as_deref(), Some("7"));
+        assert!(matches!(parsed.response, Response::NotFound { .. }));
+    }
+
+    #[test]
+    fn test_list_services_filter_is_optional() {
+        let bare: AgentRequest = serde_json::from_str(r#"{"type": "ListServices"}"#).unwrap();
+        assert!(matches!(bare, AgentRequest::ListServices { filter: None }));
+        assert_eq!(
+            serde_json::to_string(&bare).unwrap(),
+            r#"{"type":"ListServices"}"#
+        );
+
+        let filtered: AgentRequest =
+            serde_json::from_str(r#"{"type": "ListServices", "filter": "pandemic-udp*"}"#).unwrap();
+        assert!(
+            matches!(filtered, AgentRequest::ListServices { filter: Some(ref f) } if f == "pandemic-udp*")
+        );
     }
 }
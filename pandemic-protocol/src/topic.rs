@@ -0,0 +1,187 @@
+use std::fmt;
+
+/// A validated event topic or subscription pattern.
+///
+/// Topics are dot-separated segments of `[a-zA-Z0-9_-]`, with no empty
+/// segments. Patterns may also use `+` for one whole segment, a final `#`
+/// for any remaining segments, or a trailing `*` for a plain prefix match.
+/// Both constructors trim surrounding whitespace before validating.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Topic(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicError {
+    Empty,
+    /// A segment between two dots, or at either end, is empty
+    EmptySegment(String),
+    InvalidCharacter(String, char),
+    /// `+`, `#` or `*` used where it isn't allowed
+    MisplacedWildcard(String),
+}
+
+impl fmt::Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicError::Empty => write!(f, "Topic must not be empty"),
+            TopicError::EmptySegment(topic) => {
+                write!(f, "Topic '{}' has an empty segment", topic)
+            }
+            TopicError::InvalidCharacter(topic, c) => {
+                write!(f, "Topic '{}' contains invalid character {:?}", topic, c)
+            }
+            TopicError::MisplacedWildcard(topic) => {
+                write!(f, "Topic '{}' has a misplaced wildcard", topic)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TopicError {}
+
+impl Topic {
+    /// A concrete topic to publish to
+    pub fn new(topic: &str) -> Result<Self, TopicError> {
+        let topic = topic.trim();
+        validate(topic, false)?;
+        Ok(Self(topic.to_string()))
+    }
+
+    /// A subscription pattern, which may contain wildcards
+    pub fn pattern(pattern: &str) -> Result<Self, TopicError> {
+        let pattern = pattern.trim();
+        validate(pattern, true)?;
+        Ok(Self(pattern.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn validate(topic: &str, wildcards: bool) -> Result<(), TopicError> {
+    if topic.is_empty() {
+        return Err(TopicError::Empty);
+    }
+
+    // A trailing `*` matches by string prefix, so what precedes it may end
+    // mid-segment or with a dot
+    let (topic_segments, prefix_match) = match topic.strip_suffix('*') {
+        Some(prefix) if wildcards => (prefix.strip_suffix('.').unwrap_or(prefix), true),
+        _ => (topic, false),
+    };
+    if prefix_match && topic_segments.is_empty() {
+        return Ok(());
+    }
+
+    let segments: Vec<&str> = topic_segments.split('.').collect();
+    for (i, segment) in segments.iter().enumerate() {
+        match *segment {
+            "" => return Err(TopicError::EmptySegment(topic.to_string())),
+            "+" | "#" if !wildcards || prefix_match => {
+                return Err(TopicError::MisplacedWildcard(topic.to_string()))
+            }
+            "+" => {}
+            "#" if i + 1 == segments.len() => {}
+            "#" => return Err(TopicError::MisplacedWildcard(topic.to_string())),
+            literal => {
+                if let Some(c) = literal
+                    .chars()
+                    .find(|c| !(c.is_ascii_alphanumeric() || *c == '_' || *c == '-'))
+                {
+                    return Err(match c {
+                        '+' | '#' | '*' => TopicError::MisplacedWildcard(topic.to_string()),
+                        c => TopicError::InvalidCharacter(topic.to_string(), c),
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_topics_are_normalized() {
+        for topic in [
+            "health",
+            "health.sensor-1.status",
+            "udp.packet",
+            "log.my_plugin",
+        ] {
+            assert_eq!(Topic::new(topic).unwrap().as_str(), topic);
+        }
+        assert_eq!(
+            Topic::new("  sensor.reading\n").unwrap().as_str(),
+            "sensor.reading"
+        );
+    }
+
+    #[test]
+    fn test_invalid_topics_are_rejected() {
+        assert_eq!(Topic::new(""), Err(TopicError::Empty));
+        assert_eq!(Topic::new("   "), Err(TopicError::Empty));
+        assert_eq!(
+            Topic::new("health..foo"),
+            Err(TopicError::EmptySegment("health..foo".to_string()))
+        );
+        assert_eq!(
+            Topic::new(".health"),
+            Err(TopicError::EmptySegment(".health".to_string()))
+        );
+        assert_eq!(
+            Topic::new("health."),
+            Err(TopicError::EmptySegment("health.".to_string()))
+        );
+        assert_eq!(
+            Topic::new("health .foo"),
+            Err(TopicError::InvalidCharacter("health .foo".to_string(), ' '))
+        );
+        assert_eq!(
+            Topic::new("health/foo"),
+            Err(TopicError::InvalidCharacter("health/foo".to_string(), '/'))
+        );
+        for topic in ["health.#", "health.+", "health*", "he#alth"] {
+            assert_eq!(
+                Topic::new(topic),
+                Err(TopicError::MisplacedWildcard(topic.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_patterns_allow_wildcards() {
+        for pattern in [
+            "#",
+            "*",
+            "health.#",
+            "health.+.status",
+            "+",
+            "health*",
+            "health.*",
+        ] {
+            assert_eq!(Topic::pattern(pattern).unwrap().as_str(), pattern);
+        }
+        for pattern in [
+            "#.health",
+            "health.#.status",
+            "health.+*",
+            "he*alth",
+            "health..#",
+        ] {
+            assert!(Topic::pattern(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
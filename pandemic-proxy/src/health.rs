@@ -0,0 +1,201 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+/// Upper bound on a single HTTP or TCP probe
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the proxy decides whether the wrapped process is healthy.
+///
+/// A bare array such as `health_check = ["curl", "-f", "..."]` is still read
+/// as a command check; the other modes are tables tagged with `type`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "HealthCheckSpec")]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HealthCheck {
+    /// Healthy when the command exits successfully
+    Command { command: Vec<String> },
+    /// Healthy when a GET to `url` returns `expect_status`
+    Http { url: String, expect_status: u16 },
+    /// Healthy when a TCP connection to `address` succeeds
+    Tcp { address: String },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum HealthCheckSpec {
+    Legacy(Vec<String>),
+    Typed(TypedHealthCheck),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum TypedHealthCheck {
+    Command {
+        command: Vec<String>,
+    },
+    Http {
+        url: String,
+        #[serde(default = "default_expect_status")]
+        expect_status: u16,
+    },
+    Tcp {
+        address: String,
+    },
+}
+
+fn default_expect_status() -> u16 {
+    200
+}
+
+impl From<HealthCheckSpec> for HealthCheck {
+    fn from(spec: HealthCheckSpec) -> Self {
+        match spec {
+            HealthCheckSpec::Legacy(command) => HealthCheck::Command { command },
+            HealthCheckSpec::Typed(TypedHealthCheck::Command { command }) => {
+                HealthCheck::Command { command }
+            }
+            HealthCheckSpec::Typed(TypedHealthCheck::Http { url, expect_status }) => {
+                HealthCheck::Http { url, expect_status }
+            }
+            HealthCheckSpec::Typed(TypedHealthCheck::Tcp { address }) => {
+                HealthCheck::Tcp { address }
+            }
+        }
+    }
+}
+
+impl HealthCheck {
+    /// Run the probe. `Ok(false)` means the target answered but is unhealthy;
+    /// errors mean the probe itself could not be carried out.
+    pub async fn check(&self) -> Result<bool> {
+        match self {
+            HealthCheck::Command { command } => {
+                let Some((program, args)) = command.split_first() else {
+                    return Ok(true);
+                };
+                let output = Command::new(program).args(args).output().await?;
+                Ok(output.status.success())
+            }
+            HealthCheck::Http { url, expect_status } => {
+                let client = reqwest::Client::builder().timeout(CHECK_TIMEOUT).build()?;
+                let response = client.get(url).send().await?;
+                Ok(response.status().as_u16() == *expect_status)
+            }
+            HealthCheck::Tcp { address } => {
+                match timeout(CHECK_TIMEOUT, TcpStream::connect(address)).await {
+                    Ok(Ok(_)) => Ok(true),
+                    Ok(Err(_)) | Err(_) => Ok(false),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[derive(Deserialize)]
+    struct Runtime {
+        health_check: HealthCheck,
+    }
+
+    fn parse(toml: &str) -> HealthCheck {
+        toml::from_str::<Runtime>(toml).unwrap().health_check
+    }
+
+    /// Answer every HTTP request with `status`, returning the base URL
+    async fn stub_http(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_parses_legacy_and_tagged_checks() {
+        assert_eq!(
+            parse(r#"health_check = ["curl", "-f", "http://localhost"]"#),
+            HealthCheck::Command {
+                command: vec!["curl".into(), "-f".into(), "http://localhost".into()]
+            }
+        );
+        assert_eq!(
+            parse(r#"health_check = { type = "http", url = "http://localhost/health" }"#),
+            HealthCheck::Http {
+                url: "http://localhost/health".into(),
+                expect_status: 200
+            }
+        );
+        assert_eq!(
+            parse(r#"health_check = { type = "tcp", address = "127.0.0.1:8080" }"#),
+            HealthCheck::Tcp {
+                address: "127.0.0.1:8080".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_check() {
+        let healthy = HealthCheck::Command {
+            command: vec!["true".into()],
+        };
+        let unhealthy = HealthCheck::Command {
+            command: vec!["false".into()],
+        };
+
+        assert!(healthy.check().await.unwrap());
+        assert!(!unhealthy.check().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_http_check_compares_status() {
+        let ok = HealthCheck::Http {
+            url: stub_http("200 OK").await,
+            expect_status: 200,
+        };
+        let unavailable = HealthCheck::Http {
+            url: stub_http("503 Service Unavailable").await,
+            expect_status: 200,
+        };
+        let expected_unavailable = HealthCheck::Http {
+            url: stub_http("503 Service Unavailable").await,
+            expect_status: 503,
+        };
+
+        assert!(ok.check().await.unwrap());
+        assert!(!unavailable.check().await.unwrap());
+        assert!(expected_unavailable.check().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_check_connects() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = HealthCheck::Tcp {
+            address: listener.local_addr().unwrap().to_string(),
+        };
+        assert!(open.check().await.unwrap());
+
+        let closed_address = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        let closed = HealthCheck::Tcp {
+            address: closed_address,
+        };
+        assert!(!closed.check().await.unwrap());
+    }
+}
@@ -1,15 +1,31 @@
+mod health;
+mod output;
+mod supervisor;
+
 use anyhow::Result;
 use clap::Parser;
-use pandemic_common::DaemonClient;
+use health::HealthCheck;
+use output::{output_channel, OutputLine, RateLimiter};
+use pandemic_common::{DaemonClient, PersistentClient};
 use pandemic_protocol::{PluginInfo, Request};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use std::process::Stdio;
-use tokio::process::Command;
-use tokio::time::{sleep, Duration};
+use supervisor::{RestartPolicy, Supervisor};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::time::{interval_at, sleep_until, Duration, Instant, MissedTickBehavior};
 use tracing::{error, info, warn};
 
+/// How long to wait for the daemon to acknowledge deregistration on shutdown
+const DEREGISTER_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to keep publishing captured output after the process exits
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Seconds the process gets to exit after a forwarded SIGTERM or SIGINT
+/// before it is killed, unless `runtime.stop_timeout` says otherwise
+const DEFAULT_STOP_TIMEOUT: u64 = 10;
+
 #[derive(Parser)]
 #[command(name = "pandemic-proxy")]
 #[command(about = "Universal infection wrapper for arbitrary executables")]
@@ -37,13 +53,28 @@ struct InfectionConfig {
 #[derive(Debug, Deserialize, Serialize)]
 struct RuntimeConfig {
     pub command: Vec<String>,
-    pub health_check: Option<Vec<String>>,
+    pub health_check: Option<HealthCheck>,
     pub health_interval: Option<u64>,
+    /// Re-run the command when it exits: "always", "on-failure" or "no"
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    /// Give up after this many restarts; unlimited when unset
+    pub max_restarts: Option<u32>,
+    /// Seconds before the first restart, doubling on each one after
+    pub restart_delay: Option<u64>,
+    /// Seconds the command gets to exit after a forwarded SIGTERM or SIGINT
+    /// before it is sent SIGKILL
+    pub stop_timeout: Option<u64>,
+    /// Publish stdout/stderr lines as `log.<name>` events
+    #[serde(default)]
+    pub capture_output: bool,
+    /// Most captured lines published per second; the rest are dropped
+    pub output_rate_limit: Option<u32>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     let config = load_config(&args.config).await?;
@@ -61,53 +92,142 @@ async fn main() -> Result<()> {
             plugin_config
         }),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
     let mut client = DaemonClient::connect(&args.socket_path).await?;
     client
         .send_request(&Request::Register {
             plugin: plugin_info,
+            force: false,
+            ignore_dependencies: false,
         })
         .await?;
     info!("Registered {} with pandemic daemon", config.infection.name);
 
     // Start the wrapped process
-    let mut child = Command::new(&config.runtime.command[0])
-        .args(&config.runtime.command[1..])
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()?;
+    let (output_tx, mut output_rx) = output_channel();
+    let mut supervisor = Supervisor::spawn(
+        config.runtime.command.clone(),
+        config.runtime.restart,
+        config.runtime.max_restarts,
+        Duration::from_secs(config.runtime.restart_delay.unwrap_or(1)),
+        config.runtime.capture_output.then_some(output_tx),
+    )?;
+    let mut rate_limiter = RateLimiter::new(config.runtime.output_rate_limit.unwrap_or(100));
 
     info!("Started process: {:?}", config.runtime.command);
 
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+    let mut shutting_down = false;
+    let stop_timeout =
+        Duration::from_secs(config.runtime.stop_timeout.unwrap_or(DEFAULT_STOP_TIMEOUT));
+    // Set while the process is down and waiting out its restart backoff
+    let mut restart_at: Option<Instant> = None;
+    let mut last_exit_code: Option<i32> = None;
+    // Set once the process has been asked to stop, to kill it if it doesn't
+    let mut kill_at: Option<Instant> = None;
+
     // Health check loop
     let health_interval = Duration::from_secs(config.runtime.health_interval.unwrap_or(30));
+    let mut health_ticks = interval_at(Instant::now() + health_interval, health_interval);
+    health_ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
     let mut last_health_status: Option<bool> = None;
 
     loop {
         tokio::select! {
             // Check if child process is still running
-            status = child.wait() => {
-                match status {
-                    Ok(exit_status) => {
-                        if exit_status.success() {
-                            info!("Process exited successfully");
-                        } else {
-                            error!("Process exited with status: {}", exit_status);
-                        }
-                        break;
-                    }
+            status = supervisor.wait(), if restart_at.is_none() => {
+                let exit_status = match status {
+                    Ok(exit_status) => exit_status,
                     Err(e) => {
                         error!("Error waiting for process: {}", e);
                         break;
                     }
+                };
+                if exit_status.success() {
+                    info!("Process exited successfully");
+                } else {
+                    error!("Process exited with status: {}", exit_status);
+                }
+                if shutting_down || !supervisor.should_restart(&exit_status) {
+                    break;
+                }
+
+                // Wait out the backoff in its own arm so signals and health
+                // checks are still handled meanwhile
+                let backoff = supervisor.backoff();
+                info!("Restarting process in {:?}", backoff);
+                last_exit_code = exit_status.code();
+                restart_at = Some(Instant::now() + backoff);
+            }
+
+            _ = sleep_until(restart_at.unwrap_or_else(Instant::now)), if restart_at.is_some() => {
+                restart_at = None;
+                if let Err(e) = supervisor.restart() {
+                    error!("Failed to restart process: {}", e);
+                    break;
+                }
+                info!(
+                    "Restarted process ({} restart(s)): {:?}",
+                    supervisor.restarts(),
+                    config.runtime.command
+                );
+
+                let data = serde_json::json!({
+                    "service": config.infection.name,
+                    "restarts": supervisor.restarts(),
+                    "exit_code": last_exit_code,
+                    "timestamp": chrono::Utc::now().to_rfc3339()
+                });
+                if let Err(e) = client.publish("proxy.restarted", data).await {
+                    warn!("Failed to publish restart event: {}", e);
                 }
             }
 
+            Some(output) = output_rx.recv() => {
+                publish_output(&mut client, &config.infection.name, &mut rate_limiter, output).await;
+            }
+
+            // Relay signals, letting the child exit on its own terms until
+            // the stop timeout runs out
+            _ = sigterm.recv() => {
+                if restart_at.is_some() {
+                    info!("Received SIGTERM while waiting to restart, stopping");
+                    break;
+                }
+                info!("Received SIGTERM, forwarding to process");
+                shutting_down = true;
+                supervisor.signal(libc::SIGTERM);
+                kill_at.get_or_insert_with(|| Instant::now() + stop_timeout);
+            }
+            _ = sigint.recv() => {
+                if restart_at.is_some() {
+                    info!("Received SIGINT while waiting to restart, stopping");
+                    break;
+                }
+                info!("Received SIGINT, forwarding to process");
+                shutting_down = true;
+                supervisor.signal(libc::SIGINT);
+                kill_at.get_or_insert_with(|| Instant::now() + stop_timeout);
+            }
+            _ = sleep_until(kill_at.unwrap_or_else(Instant::now)), if kill_at.is_some() => {
+                warn!("Process still running {:?} after being asked to stop, killing it", stop_timeout);
+                kill_at = None;
+                supervisor.signal(libc::SIGKILL);
+            }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP, forwarding to process");
+                supervisor.signal(libc::SIGHUP);
+            }
+
             // Periodic health check
-            _ = sleep(health_interval) => {
-                if let Some(health_cmd) = &config.runtime.health_check {
-                    match run_health_check(health_cmd).await {
+            _ = health_ticks.tick() => {
+                if let Some(health_check) = &config.runtime.health_check {
+                    match health_check.check().await {
                         Ok(is_healthy) => {
                             // Check if health status changed
                             if last_health_status != Some(is_healthy) {
@@ -123,7 +243,7 @@ async fn main() -> Result<()> {
                                     "timestamp": chrono::Utc::now().to_rfc3339()
                                 });
 
-                                if let Err(e) = client.send_request(&Request::Publish { topic, data }).await {
+                                if let Err(e) = client.send_request(&Request::Publish { topic, data, retain: true }).await {
                                     warn!("Failed to publish health event: {}", e);
                                 }
 
@@ -147,7 +267,7 @@ async fn main() -> Result<()> {
                                     "timestamp": chrono::Utc::now().to_rfc3339()
                                 });
 
-                                if let Err(e) = client.send_request(&Request::Publish { topic, data }).await {
+                                if let Err(e) = client.send_request(&Request::Publish { topic, data, retain: true }).await {
                                     warn!("Failed to publish health error event: {}", e);
                                 }
 
@@ -161,26 +281,64 @@ async fn main() -> Result<()> {
     }
 
     // Cleanup
-    let _ = child.kill().await;
+    supervisor.kill().await;
+    drop(supervisor);
+
+    // Flush output the process wrote just before exiting
+    while let Ok(Some(output)) = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, output_rx.recv()).await
+    {
+        publish_output(
+            &mut client,
+            &config.infection.name,
+            &mut rate_limiter,
+            output,
+        )
+        .await;
+    }
+
+    deregister(&mut client, &config.infection.name).await;
     info!("Proxy shutting down");
     Ok(())
 }
 
-async fn load_config(path: &PathBuf) -> Result<ProxyConfig> {
-    let content = tokio::fs::read_to_string(path).await?;
-    let config: ProxyConfig = toml::from_str(&content)?;
-    Ok(config)
-}
+async fn publish_output(
+    client: &mut PersistentClient,
+    name: &str,
+    rate_limiter: &mut RateLimiter,
+    output: OutputLine,
+) {
+    let (allowed, dropped) = rate_limiter.allow();
+    if let Some(dropped) = dropped {
+        warn!("Dropped {} output line(s) over the rate limit", dropped);
+    }
+    if !allowed {
+        return;
+    }
 
-async fn run_health_check(command: &[String]) -> Result<bool> {
-    if command.is_empty() {
-        return Ok(true);
+    let topic = format!("log.{}", name);
+    let data = serde_json::json!({
+        "stream": output.stream.as_str(),
+        "line": output.line,
+    });
+    if let Err(e) = client.publish(topic, data).await {
+        warn!("Failed to publish output event: {}", e);
     }
+}
 
-    let output = Command::new(&command[0])
-        .args(&command[1..])
-        .output()
-        .await?;
+/// Remove our registration, without hanging if the daemon is already gone
+async fn deregister(client: &mut PersistentClient, name: &str) {
+    let request = Request::Deregister {
+        name: name.to_string(),
+    };
+    match tokio::time::timeout(DEREGISTER_TIMEOUT, client.send_request(&request)).await {
+        Ok(Ok(_)) => info!("Deregistered {} from pandemic daemon", name),
+        Ok(Err(e)) => warn!("Failed to deregister {}: {}", name, e),
+        Err(_) => warn!("Timed out deregistering {}", name),
+    }
+}
 
-    Ok(output.status.success())
+async fn load_config(path: &PathBuf) -> Result<ProxyConfig> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let config: ProxyConfig = toml::from_str(&content)?;
+    Ok(config)
 }
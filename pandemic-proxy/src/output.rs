@@ -0,0 +1,125 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Child;
+use tokio::sync::mpsc;
+
+/// Lines buffered between the pipe readers and the publisher; a full buffer
+/// applies backpressure to the wrapped process
+const OUTPUT_BUFFER: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputStream::Stdout => "stdout",
+            OutputStream::Stderr => "stderr",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+pub fn output_channel() -> (mpsc::Sender<OutputLine>, mpsc::Receiver<OutputLine>) {
+    mpsc::channel(OUTPUT_BUFFER)
+}
+
+/// Read the child's piped stdout and stderr line by line into `tx`, echoing
+/// each line to the proxy's own stdout/stderr so nothing is lost locally
+pub fn capture(child: &mut Child, tx: &mpsc::Sender<OutputLine>) {
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(forward_lines(stdout, OutputStream::Stdout, tx.clone()));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(forward_lines(stderr, OutputStream::Stderr, tx.clone()));
+    }
+}
+
+async fn forward_lines<R>(reader: R, stream: OutputStream, tx: mpsc::Sender<OutputLine>)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let _ = match stream {
+            OutputStream::Stdout => writeln!(std::io::stdout(), "{}", line),
+            OutputStream::Stderr => writeln!(std::io::stderr(), "{}", line),
+        };
+        if tx.send(OutputLine { stream, line }).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Allows at most `per_second` lines through in each one-second window
+pub struct RateLimiter {
+    per_second: u32,
+    window_start: Instant,
+    sent: u32,
+    dropped: u64,
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            per_second,
+            window_start: Instant::now(),
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Whether another line may be published now. When a new window opens,
+    /// also returns how many lines the previous windows dropped.
+    pub fn allow(&mut self) -> (bool, Option<u64>) {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&mut self, now: Instant) -> (bool, Option<u64>) {
+        let mut dropped = None;
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.sent = 0;
+            if self.dropped > 0 {
+                dropped = Some(std::mem::take(&mut self.dropped));
+            }
+        }
+
+        if self.sent < self.per_second {
+            self.sent += 1;
+            (true, dropped)
+        } else {
+            self.dropped += 1;
+            (false, dropped)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_drops_excess_per_window() {
+        let mut limiter = RateLimiter::new(2);
+        let start = limiter.window_start;
+
+        assert_eq!(limiter.allow_at(start), (true, None));
+        assert_eq!(limiter.allow_at(start), (true, None));
+        assert_eq!(limiter.allow_at(start), (false, None));
+        assert_eq!(limiter.allow_at(start), (false, None));
+
+        let next_window = start + Duration::from_secs(1);
+        assert_eq!(limiter.allow_at(next_window), (true, Some(2)));
+        assert_eq!(limiter.allow_at(next_window), (true, None));
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::process::{ExitStatus, Stdio};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+use crate::output::{self, OutputLine};
+
+/// Longest wait between restarts, however many times the command has failed
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// When to re-run the wrapped command after it exits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Always,
+    OnFailure,
+    #[default]
+    No,
+}
+
+/// Runs the wrapped command, restarting it according to a [`RestartPolicy`]
+pub struct Supervisor {
+    command: Vec<String>,
+    policy: RestartPolicy,
+    max_restarts: Option<u32>,
+    restart_delay: Duration,
+    restarts: u32,
+    output: Option<mpsc::Sender<OutputLine>>,
+    child: Child,
+}
+
+impl Supervisor {
+    /// Start `command`. `max_restarts` caps restarts over the proxy's lifetime
+    /// and the delay before each restart doubles from `restart_delay`. With
+    /// `output`, stdout and stderr are captured into it instead of inherited.
+    pub fn spawn(
+        command: Vec<String>,
+        policy: RestartPolicy,
+        max_restarts: Option<u32>,
+        restart_delay: Duration,
+        output: Option<mpsc::Sender<OutputLine>>,
+    ) -> Result<Self> {
+        let child = spawn_command(&command, output.as_ref())?;
+        Ok(Self {
+            command,
+            policy,
+            max_restarts,
+            restart_delay,
+            restarts: 0,
+            output,
+            child,
+        })
+    }
+
+    pub async fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        self.child.wait().await
+    }
+
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    pub fn should_restart(&self, status: &ExitStatus) -> bool {
+        let wanted = match self.policy {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::No => false,
+        };
+        wanted && self.max_restarts.is_none_or(|max| self.restarts < max)
+    }
+
+    /// How long to wait before the next restart, doubling with each one
+    pub fn backoff(&self) -> Duration {
+        self.restart_delay
+            .saturating_mul(2u32.saturating_pow(self.restarts))
+            .min(MAX_RESTART_DELAY)
+    }
+
+    /// Run the command again. Callers wait out [`Supervisor::backoff`] first.
+    pub fn restart(&mut self) -> Result<()> {
+        self.child = spawn_command(&self.command, self.output.as_ref())?;
+        self.restarts += 1;
+        Ok(())
+    }
+
+    /// Relay `signal` to the running command
+    pub fn signal(&self, signal: libc::c_int) {
+        if let Some(pid) = self.child.id() {
+            // SAFETY: kill(2) has no memory safety requirements
+            unsafe {
+                libc::kill(pid as libc::pid_t, signal);
+            }
+        }
+    }
+
+    pub async fn kill(&mut self) {
+        let _ = self.child.kill().await;
+    }
+}
+
+fn spawn_command(command: &[String], output: Option<&mpsc::Sender<OutputLine>>) -> Result<Child> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("runtime.command must not be empty"))?;
+    let stdio = || match output {
+        Some(_) => Stdio::piped(),
+        None => Stdio::inherit(),
+    };
+    let mut child = Command::new(program)
+        .args(args)
+        .stdout(stdio())
+        .stderr(stdio())
+        .spawn()?;
+    if let Some(tx) = output {
+        output::capture(&mut child, tx);
+    }
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use tempfile::TempDir;
+
+    fn supervisor(policy: RestartPolicy, max_restarts: Option<u32>) -> Supervisor {
+        Supervisor::spawn(
+            vec!["true".to_string()],
+            policy,
+            max_restarts,
+            Duration::ZERO,
+            None,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_policy_decides_restart() {
+        let success = ExitStatus::from_raw(0);
+        let failure = ExitStatus::from_raw(1 << 8);
+
+        let always = supervisor(RestartPolicy::Always, None);
+        assert!(always.should_restart(&success));
+        assert!(always.should_restart(&failure));
+
+        let on_failure = supervisor(RestartPolicy::OnFailure, None);
+        assert!(!on_failure.should_restart(&success));
+        assert!(on_failure.should_restart(&failure));
+
+        let no = supervisor(RestartPolicy::No, None);
+        assert!(!no.should_restart(&failure));
+    }
+
+    #[tokio::test]
+    async fn test_failing_command_restarts_up_to_max() {
+        let temp_dir = TempDir::new().unwrap();
+        let runs = temp_dir.path().join("runs");
+        let script = format!("echo run >> {}; exit 3", runs.display());
+
+        let mut supervisor = Supervisor::spawn(
+            vec!["sh".to_string(), "-c".to_string(), script],
+            RestartPolicy::OnFailure,
+            Some(2),
+            Duration::ZERO,
+            None,
+        )
+        .unwrap();
+
+        let status = loop {
+            let status = supervisor.wait().await.unwrap();
+            if !supervisor.should_restart(&status) {
+                break status;
+            }
+            supervisor.restart().unwrap();
+        };
+
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(supervisor.restarts(), 2);
+        assert_eq!(std::fs::read_to_string(runs).unwrap().lines().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_backoff_doubles_up_to_the_cap() {
+        let mut supervisor = Supervisor::spawn(
+            vec!["true".to_string()],
+            RestartPolicy::Always,
+            None,
+            Duration::from_secs(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(supervisor.backoff(), Duration::from_secs(1));
+        supervisor.restart().unwrap();
+        assert_eq!(supervisor.backoff(), Duration::from_secs(2));
+        supervisor.restart().unwrap();
+        assert_eq!(supervisor.backoff(), Duration::from_secs(4));
+        supervisor.restarts = 10;
+        assert_eq!(supervisor.backoff(), MAX_RESTART_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_signal_is_forwarded() {
+        let mut supervisor = Supervisor::spawn(
+            vec!["sleep".to_string(), "30".to_string()],
+            RestartPolicy::Always,
+            None,
+            Duration::ZERO,
+            None,
+        )
+        .unwrap();
+
+        supervisor.signal(libc::SIGTERM);
+        let status = supervisor.wait().await.unwrap();
+
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+}
@@ -0,0 +1,62 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::path::Path;
+use std::process::{Child, Command};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// Acknowledge every request, reporting each one as it arrives
+pub fn mock_daemon(socket_path: &Path) -> mpsc::Receiver<serde_json::Value> {
+    let listener = UnixListener::bind(socket_path).unwrap();
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                    let request: serde_json::Value = serde_json::from_str(&line).unwrap();
+                    let _ = tx.send(request);
+                    writeln!(stream, r#"{{"status":"Success","data":null}}"#).unwrap();
+                    line.clear();
+                }
+            });
+        }
+    });
+
+    rx
+}
+
+/// Run the proxy against the mock daemon with `runtime` as the `[runtime]` table
+pub fn spawn_proxy(temp_dir: &TempDir, runtime: &str) -> Child {
+    let config_path = temp_dir.path().join("infection.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[infection]\nname = \"wrapped\"\nversion = \"1.0.0\"\n\n[runtime]\n{}\n",
+            runtime
+        ),
+    )
+    .unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_pandemic-proxy"))
+        .arg("--socket-path")
+        .arg(temp_dir.path().join("pandemic.sock"))
+        .arg("--config")
+        .arg(config_path)
+        .spawn()
+        .unwrap()
+}
+
+pub fn wait_for_exit(child: &mut Child) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        assert!(Instant::now() < deadline, "proxy did not exit");
+        thread::sleep(Duration::from_millis(20));
+    }
+}
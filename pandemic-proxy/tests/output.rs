@@ -0,0 +1,54 @@
+use tempfile::TempDir;
+
+mod common;
+
+use common::{mock_daemon, spawn_proxy, wait_for_exit};
+
+#[test]
+fn test_captured_output_is_published_as_log_events() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(
+        &temp_dir,
+        r#"command = ["sh", "-c", "echo first; echo oops >&2; echo second"]
+capture_output = true"#,
+    );
+    wait_for_exit(&mut proxy);
+
+    let events: Vec<(String, String)> = requests
+        .try_iter()
+        .filter(|request| request["type"] == "Publish")
+        .map(|request| {
+            assert_eq!(request["topic"], "log.wrapped");
+            assert_eq!(request["retain"], false);
+            (
+                request["data"]["stream"].as_str().unwrap().to_string(),
+                request["data"]["line"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+
+    let lines = |stream: &str| -> Vec<&str> {
+        events
+            .iter()
+            .filter(|(s, _)| s == stream)
+            .map(|(_, line)| line.as_str())
+            .collect()
+    };
+    assert_eq!(lines("stdout"), ["first", "second"], "{:?}", events);
+    assert_eq!(lines("stderr"), ["oops"], "{:?}", events);
+}
+
+#[test]
+fn test_output_is_not_published_without_capture() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(&temp_dir, r#"command = ["echo", "hello"]"#);
+    wait_for_exit(&mut proxy);
+
+    assert!(requests
+        .try_iter()
+        .all(|request| request["type"] != "Publish"));
+}
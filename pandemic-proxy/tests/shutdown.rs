@@ -0,0 +1,90 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+mod common;
+
+use common::{mock_daemon, spawn_proxy, wait_for_exit};
+
+fn next_request(requests: &mpsc::Receiver<serde_json::Value>) -> String {
+    let request = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+    request["type"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn test_deregisters_after_wrapped_process_exits() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(&temp_dir, r#"command = ["true"]"#);
+    wait_for_exit(&mut proxy);
+
+    let received: Vec<String> = requests
+        .try_iter()
+        .map(|request| request["type"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(received, ["Hello", "Register", "Deregister"]);
+}
+
+#[test]
+fn test_deregisters_on_sigterm() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(&temp_dir, r#"command = ["sleep", "30"]"#);
+    assert_eq!(next_request(&requests), "Hello");
+    assert_eq!(next_request(&requests), "Register");
+
+    // Give the proxy a moment to install its signal handlers
+    thread::sleep(Duration::from_millis(200));
+    unsafe {
+        libc::kill(proxy.id() as libc::pid_t, libc::SIGTERM);
+    }
+    wait_for_exit(&mut proxy);
+
+    assert_eq!(next_request(&requests), "Deregister");
+}
+
+#[test]
+fn test_process_ignoring_sigterm_is_killed_after_stop_timeout() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(
+        &temp_dir,
+        "command = [\"sh\", \"-c\", \"trap '' TERM; exec sleep 30\"]\nstop_timeout = 1",
+    );
+    assert_eq!(next_request(&requests), "Hello");
+    assert_eq!(next_request(&requests), "Register");
+
+    thread::sleep(Duration::from_millis(200));
+    unsafe {
+        libc::kill(proxy.id() as libc::pid_t, libc::SIGTERM);
+    }
+    wait_for_exit(&mut proxy);
+
+    assert_eq!(next_request(&requests), "Deregister");
+}
+
+#[test]
+fn test_sigterm_during_restart_backoff_stops_promptly() {
+    let temp_dir = TempDir::new().unwrap();
+    let requests = mock_daemon(&temp_dir.path().join("pandemic.sock"));
+
+    let mut proxy = spawn_proxy(
+        &temp_dir,
+        "command = [\"false\"]\nrestart = \"always\"\nrestart_delay = 60",
+    );
+    assert_eq!(next_request(&requests), "Hello");
+    assert_eq!(next_request(&requests), "Register");
+
+    // Let the command fail so the proxy is waiting out its backoff
+    thread::sleep(Duration::from_millis(500));
+    unsafe {
+        libc::kill(proxy.id() as libc::pid_t, libc::SIGTERM);
+    }
+    wait_for_exit(&mut proxy);
+
+    assert_eq!(next_request(&requests), "Deregister");
+}
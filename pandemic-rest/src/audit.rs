@@ -0,0 +1,44 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use pandemic_common::{RotatingFile, Rotation};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One privileged change made through the API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub identity: String,
+    pub action: String,
+    pub target: String,
+    /// `success`, or the error the action failed with
+    pub result: String,
+}
+
+/// Appends [`AuditRecord`]s to a JSON-lines file
+pub struct AuditLog {
+    file: RotatingFile,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self::with_rotation(path, None)
+    }
+
+    /// Roll the log over by size; a record is never split across files
+    pub fn with_rotation(path: impl Into<PathBuf>, rotation: Option<Rotation>) -> Self {
+        Self {
+            file: RotatingFile::new(path, rotation),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub async fn record(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        self.file.append(line.as_bytes()).await
+    }
+}
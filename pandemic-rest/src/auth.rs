@@ -1,55 +1,429 @@
 use anyhow::Result;
+use axum::http::{header, HeaderValue, Method};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use pandemic_common::api_key;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
+use tokio::sync::Semaphore;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::debug;
+use x509_parser::extensions::GeneralName;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Identity {
-    pub api_key: String,
+    /// Plaintext key, only consulted when no `api_key_hash` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Argon2 PHC string, as produced by `pandemic-cli auth hash-key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_hash: Option<String>,
     pub roles: Vec<String>,
 }
 
+/// Argon2 verifications that may run at once. Requests with unknown keys
+/// queue for a permit rather than occupying more blocking threads.
+const MAX_CONCURRENT_HASH_CHECKS: usize = 2;
+
+fn hash_check_permits() -> Arc<Semaphore> {
+    Arc::new(Semaphore::new(MAX_CONCURRENT_HASH_CHECKS))
+}
+
+/// Roles for clients authenticated by certificate, keyed by the subject
+/// common name or a DNS subject alternative name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MtlsIdentity {
+    pub roles: Vec<String>,
+}
+
+/// The names a verified client certificate was issued to: its subject
+/// common names, then its DNS subject alternative names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    pub names: Vec<String>,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8]) -> Result<Self> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)
+            .map_err(|e| anyhow::anyhow!("Invalid client certificate: {}", e))?;
+        let mut names: Vec<String> = cert
+            .subject()
+            .iter_common_name()
+            .filter_map(|name| name.as_str().ok())
+            .map(str::to_string)
+            .collect();
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            names.extend(
+                san.value
+                    .general_names
+                    .iter()
+                    .filter_map(|name| match name {
+                        GeneralName::DNSName(dns) => Some(dns.to_string()),
+                        _ => None,
+                    }),
+            );
+        }
+        Ok(Self { names })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Role {
     pub scopes: Vec<String>,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+}
+
+/// Validates bearer tokens as JWTs instead of looking them up as API keys.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtConfig {
+    pub algorithm: JwtAlgorithm,
+    /// Shared secret for HS256
+    pub secret: Option<String>,
+    /// PEM encoded public key for RS256
+    pub public_key: Option<String>,
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+}
+
+impl JwtConfig {
+    /// Parse the key and set up validation, failing on a missing or
+    /// malformed key
+    fn verifier(&self) -> Result<JwtVerifier> {
+        let (algorithm, key) = match self.algorithm {
+            JwtAlgorithm::HS256 => {
+                let secret = self
+                    .secret
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("HS256 requires jwt.secret"))?;
+                (
+                    Algorithm::HS256,
+                    DecodingKey::from_secret(secret.as_bytes()),
+                )
+            }
+            JwtAlgorithm::RS256 => {
+                let public_key = self
+                    .public_key
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("RS256 requires jwt.public_key"))?;
+                (
+                    Algorithm::RS256,
+                    DecodingKey::from_rsa_pem(public_key.as_bytes())
+                        .map_err(|e| anyhow::anyhow!("Invalid jwt.public_key: {}", e))?,
+                )
+            }
+        };
+
+        let mut validation = Validation::new(algorithm);
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        Ok(JwtVerifier { key, validation })
+    }
+}
+
+/// A `[jwt]` section's decoded key and validation rules, built once by
+/// [`AuthConfig::parse`]
+#[derive(Clone)]
+struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl fmt::Debug for JwtVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtVerifier")
+            .field("validation", &self.validation)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Cross-origin access for browser clients. Without a `[cors]` section no
+/// origin is allowed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://console.example.com`, or `"*"`
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Defaults to GET, POST, PUT and DELETE
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec()
+}
+
+impl CorsConfig {
+    pub fn layer(&self) -> Result<CorsLayer> {
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_uppercase().as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut layer = CorsLayer::new()
+            .allow_methods(methods)
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+            .allow_credentials(self.allow_credentials);
+
+        if self.allowed_origins.iter().any(|origin| origin == "*") {
+            if self.allow_credentials {
+                anyhow::bail!("cors.allow_credentials cannot be used with a \"*\" origin");
+            }
+            layer = layer.allow_origin(AllowOrigin::any());
+        } else {
+            let origins = self
+                .allowed_origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin))
+                .collect::<Result<Vec<_>, _>>()?;
+            layer = layer.allow_origin(origins);
+        }
+        Ok(layer)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ScopeClaim {
+    /// OAuth style space-delimited scopes
+    Delimited(String),
+    List(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: Option<String>,
+    scope: Option<ScopeClaim>,
+}
+
+/// Whether `error` means the token isn't a JWT at all, as opposed to a JWT
+/// that failed validation
+fn is_malformed_jwt(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<jsonwebtoken::errors::Error>()
+        .is_some_and(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::InvalidToken
+                    | ErrorKind::Base64(_)
+                    | ErrorKind::Json(_)
+                    | ErrorKind::Utf8(_)
+            )
+        })
+}
+
+/// The authenticated identity behind a request: the `[identities]` key for
+/// API keys, the `sub` claim for JWTs, or the `[mtls_identities]` key for
+/// client certificates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Caller(pub String);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthConfig {
+    #[serde(default)]
     pub identities: HashMap<String, Identity>,
+    #[serde(default)]
     pub roles: HashMap<String, Role>,
+    /// Client certificate identities, used when the server requires them
+    #[serde(default)]
+    pub mtls_identities: HashMap<String, MtlsIdentity>,
+    pub jwt: Option<JwtConfig>,
+    pub cors: Option<CorsConfig>,
+    /// Identities already matched by an `api_key_hash`, keyed by
+    /// [`api_key::key_digest`], so a known key pays for Argon2 once
+    #[serde(skip)]
+    verified: Arc<Mutex<HashMap<[u8; 32], String>>>,
+    #[serde(skip, default = "hash_check_permits")]
+    hash_checks: Arc<Semaphore>,
+    #[serde(skip)]
+    jwt_verifier: Option<JwtVerifier>,
 }
 
 impl AuthConfig {
     pub async fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = tokio::fs::read_to_string(path).await?;
-        let config: AuthConfig = toml::from_str(&content)?;
-        Ok(config)
+        Self::parse(&content)
     }
 
-    pub fn authenticate(&self, api_key: &str) -> Option<Vec<String>> {
-        // Find identity by API key
-        let identity = self.identities.values().find(|id| id.api_key == api_key)?;
+    /// Parse a config file's contents, checking the `[jwt]` key up front
+    pub fn parse(content: &str) -> Result<Self> {
+        // Keys may be given as ${env:NAME} or ${file:/path} references
+        let mut config: Self = pandemic_common::parse_toml_with_secrets(content)?;
+        config.jwt_verifier = config.jwt.as_ref().map(JwtConfig::verifier).transpose()?;
+        Ok(config)
+    }
 
-        // Collect all scopes from user's roles
-        let mut scopes = Vec::new();
-        for role_name in &identity.roles {
-            if let Some(role) = self.roles.get(role_name) {
-                scopes.extend(role.scopes.clone());
+    /// Authenticate an `Authorization: Bearer` value. With a `[jwt]`
+    /// section, anything shaped like a JWT is validated as one; a value
+    /// that doesn't even decode as a JWT is tried as an API key instead.
+    pub async fn authenticate_bearer(&self, token: &str) -> Option<(Caller, Vec<String>)> {
+        if self.jwt_verifier.is_some() && token.split('.').count() == 3 {
+            match self.authenticate_jwt_caller(token) {
+                Ok(authenticated) => return Some(authenticated),
+                Err(e) if !is_malformed_jwt(&e) => {
+                    debug!("Rejected JWT: {}", e);
+                    return None;
+                }
+                Err(_) => {}
             }
         }
+        self.authenticate_caller(token).await
+    }
 
-        Some(scopes)
+    pub async fn authenticate(&self, api_key: &str) -> Option<Vec<String>> {
+        self.authenticate_caller(api_key)
+            .await
+            .map(|(_, scopes)| scopes)
     }
 
-    pub fn authorize(&self, scopes: &[String], required_scope: &str) -> bool {
-        // Check for wildcard admin access
-        if scopes.contains(&"*".to_string()) {
-            return true;
+    /// Like [`AuthConfig::authenticate`], also naming the matched identity.
+    ///
+    /// Plaintext keys and keys that verified before are compared in constant
+    /// time. Anything else is verified against each `api_key_hash` on a
+    /// blocking thread, at most [`MAX_CONCURRENT_HASH_CHECKS`] keys at once,
+    /// so unknown keys can't starve the runtime.
+    pub async fn authenticate_caller(&self, api_key: &str) -> Option<(Caller, Vec<String>)> {
+        let digest = api_key::key_digest(api_key);
+        if let Some(name) = self.known_identity(&digest) {
+            return self.caller(&name);
         }
 
-        // Check for exact scope match
-        scopes.contains(&required_scope.to_string())
+        let hashed: Vec<(String, String)> = self
+            .identities
+            .iter()
+            .filter_map(|(name, identity)| Some((name.clone(), identity.api_key_hash.clone()?)))
+            .collect();
+        if hashed.is_empty() {
+            return None;
+        }
+        let _permit = self.hash_checks.acquire().await.ok()?;
+        let api_key = api_key.to_string();
+        let name = tokio::task::spawn_blocking(move || {
+            hashed
+                .into_iter()
+                .find(|(_, hash)| api_key::verify_key(&api_key, hash))
+                .map(|(name, _)| name)
+        })
+        .await
+        .ok()??;
+        self.verified.lock().unwrap().insert(digest, name.clone());
+        self.caller(&name)
+    }
+
+    /// The identity for a key with `digest` that needs no Argon2: one that
+    /// verified before, or one whose plaintext `api_key` it matches
+    fn known_identity(&self, digest: &[u8; 32]) -> Option<String> {
+        if let Some(name) = self.verified.lock().unwrap().get(digest) {
+            return Some(name.clone());
+        }
+        self.identities
+            .iter()
+            .find(
+                |(_, identity)| match (&identity.api_key_hash, &identity.api_key) {
+                    (None, Some(key)) => api_key::key_digest(key)[..].ct_eq(&digest[..]).into(),
+                    _ => false,
+                },
+            )
+            .map(|(name, _)| name.clone())
+    }
+
+    fn caller(&self, name: &str) -> Option<(Caller, Vec<String>)> {
+        let identity = self.identities.get(name)?;
+        Some((Caller(name.to_string()), self.role_scopes(&identity.roles)))
+    }
+
+    /// Resolve a verified client certificate through `[mtls_identities]`,
+    /// trying each of its names in turn
+    pub fn authenticate_certificate(
+        &self,
+        certificate: &ClientCertificate,
+    ) -> Option<(Caller, Vec<String>)> {
+        certificate.names.iter().find_map(|name| {
+            let identity = self.mtls_identities.get(name)?;
+            Some((Caller(name.clone()), self.role_scopes(&identity.roles)))
+        })
+    }
+
+    /// Every scope granted by `roles`, trimmed, sorted and without duplicates
+    fn role_scopes(&self, roles: &[String]) -> Vec<String> {
+        let scopes: BTreeSet<String> = roles
+            .iter()
+            .filter_map(|role_name| self.roles.get(role_name))
+            .flat_map(|role| &role.scopes)
+            .map(|scope| scope.trim())
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string)
+            .collect();
+        scopes.into_iter().collect()
+    }
+
+    /// Validate a JWT bearer token and return the scopes from its `scope` claim
+    pub fn authenticate_jwt(&self, token: &str) -> Result<Vec<String>> {
+        self.authenticate_jwt_caller(token)
+            .map(|(_, scopes)| scopes)
+    }
+
+    /// Like [`AuthConfig::authenticate_jwt`], also naming the token's subject
+    pub fn authenticate_jwt_caller(&self, token: &str) -> Result<(Caller, Vec<String>)> {
+        let verifier = self
+            .jwt_verifier
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("JWT authentication is not configured"))?;
+
+        let claims = decode::<Claims>(token, &verifier.key, &verifier.validation)?.claims;
+        let scopes = match claims.scope {
+            Some(ScopeClaim::Delimited(scopes)) => {
+                scopes.split_whitespace().map(str::to_string).collect()
+            }
+            Some(ScopeClaim::List(scopes)) => scopes,
+            None => Vec::new(),
+        };
+        let caller = Caller(claims.sub.unwrap_or_else(|| "jwt".to_string()));
+        Ok((caller, scopes))
+    }
+
+    /// The CORS layer for this config, denying cross-origin requests when
+    /// there is no `[cors]` section
+    pub fn cors_layer(&self) -> Result<CorsLayer> {
+        match &self.cors {
+            Some(cors) => cors.layer(),
+            None => Ok(CorsLayer::new()),
+        }
+    }
+
+    pub fn authorize(&self, scopes: &[String], required_scope: &str) -> bool {
+        scopes
+            .iter()
+            .any(|scope| scope_grants(scope, required_scope))
+    }
+}
+
+/// Whether a granted scope satisfies a required one. `*` grants everything and
+/// a trailing `:*` grants every scope below that colon-separated prefix, so
+/// `admin:*` covers `admin:users:create`.
+fn scope_grants(granted: &str, required: &str) -> bool {
+    if granted == "*" || granted == required {
+        return true;
+    }
+    match granted.strip_suffix('*') {
+        Some(prefix) if prefix.ends_with(':') => required.starts_with(prefix),
+        _ => false,
     }
 }
 
@@ -84,14 +458,404 @@ scopes = ["plugins:read", "health:read"]
         let config = AuthConfig::load(temp_file.path()).await.unwrap();
 
         // Test authentication
-        let admin_scopes = config.authenticate("admin-key").unwrap();
+        let admin_scopes = config.authenticate("admin-key").await.unwrap();
         assert!(config.authorize(&admin_scopes, "plugins:write"));
 
-        let reader_scopes = config.authenticate("reader-key").unwrap();
+        let reader_scopes = config.authenticate("reader-key").await.unwrap();
         assert!(config.authorize(&reader_scopes, "plugins:read"));
         assert!(!config.authorize(&reader_scopes, "plugins:write"));
 
+        let (caller, _) = config.authenticate_caller("reader-key").await.unwrap();
+        assert_eq!(caller, Caller("reader".to_string()));
+
         // Test invalid key
-        assert!(config.authenticate("invalid-key").is_none());
+        assert!(config.authenticate("invalid-key").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_from_env_and_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key_file = temp_dir.path().join("reader.key");
+        std::fs::write(&key_file, "file-key\n").unwrap();
+        std::env::set_var("PANDEMIC_TEST_ADMIN_KEY", "env-key");
+
+        let config_path = temp_dir.path().join("rest-auth.toml");
+        std::fs::write(
+            &config_path,
+            format!(
+                r#"
+[identities.admin]
+api_key = "${{env:PANDEMIC_TEST_ADMIN_KEY}}"
+roles = ["admin"]
+
+[identities.reader]
+api_key = "${{file:{}}}"
+roles = ["admin"]
+
+[roles.admin]
+scopes = ["*"]
+"#,
+                key_file.display()
+            ),
+        )
+        .unwrap();
+
+        let config = AuthConfig::load(&config_path).await.unwrap();
+        let (caller, _) = config.authenticate_caller("env-key").await.unwrap();
+        assert_eq!(caller, Caller("admin".to_string()));
+        let (caller, _) = config.authenticate_caller("file-key").await.unwrap();
+        assert_eq!(caller, Caller("reader".to_string()));
+        assert!(config
+            .authenticate("${env:PANDEMIC_TEST_ADMIN_KEY}")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hashed_api_key() {
+        let hash = api_key::hash_key("secret-key");
+        assert!(hash.starts_with("$argon2"));
+        assert!(!hash.contains("secret-key"));
+
+        let config: AuthConfig = toml::from_str(&format!(
+            r#"
+[identities.hashed]
+api_key_hash = "{}"
+roles = ["reader"]
+
+[roles.reader]
+scopes = ["plugins:read"]
+"#,
+            hash
+        ))
+        .unwrap();
+
+        assert_eq!(
+            config.authenticate("secret-key").await,
+            Some(vec!["plugins:read".to_string()])
+        );
+        assert!(config.authenticate("wrong-key").await.is_none());
+        assert!(config.authenticate(&hash).await.is_none());
+
+        // The verified key is remembered, and still authenticates from there
+        assert_eq!(config.verified.lock().unwrap().len(), 1);
+        assert_eq!(
+            config.authenticate("secret-key").await,
+            Some(vec!["plugins:read".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unknown_keys_share_a_few_hash_checks() {
+        let config: AuthConfig = toml::from_str(&format!(
+            r#"
+[identities.hashed]
+api_key_hash = "{}"
+roles = []
+"#,
+            api_key::hash_key("secret-key")
+        ))
+        .unwrap();
+        let config = Arc::new(config);
+
+        let checks: Vec<_> = (0..4)
+            .map(|i| {
+                let config = Arc::clone(&config);
+                tokio::spawn(async move { config.authenticate(&format!("guess-{}", i)).await })
+            })
+            .collect();
+        // The hashing runs elsewhere, so this task keeps running while every
+        // permit is taken
+        tokio::task::yield_now().await;
+        assert_eq!(config.hash_checks.available_permits(), 0);
+
+        for check in checks {
+            assert!(check.await.unwrap().is_none());
+        }
+        assert_eq!(
+            config.hash_checks.available_permits(),
+            MAX_CONCURRENT_HASH_CHECKS
+        );
+        assert!(config.verified.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_hash_takes_precedence_over_plaintext() {
+        let config: AuthConfig = toml::from_str(&format!(
+            r#"
+[identities.both]
+api_key = "old-key"
+api_key_hash = "{}"
+roles = []
+"#,
+            api_key::hash_key("new-key")
+        ))
+        .unwrap();
+
+        assert!(config.authenticate("new-key").await.is_some());
+        assert!(config.authenticate("old-key").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_roles_yield_each_scope_once() {
+        let config: AuthConfig = toml::from_str(
+            r#"
+[identities.operator]
+api_key = "operator-key"
+roles = ["reader", "publisher"]
+
+[roles.reader]
+scopes = ["plugins:read", " health:read "]
+
+[roles.publisher]
+scopes = ["health:read", "events:publish", "plugins:read"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.authenticate("operator-key").await.unwrap(),
+            vec!["events:publish", "health:read", "plugins:read"]
+        );
+    }
+
+    #[test]
+    fn test_hierarchical_scopes() {
+        let config: AuthConfig = toml::from_str("").unwrap();
+        let plugins = vec!["plugins:*".to_string()];
+        assert!(config.authorize(&plugins, "plugins:read"));
+        assert!(config.authorize(&plugins, "plugins:write"));
+        assert!(!config.authorize(&plugins, "health:read"));
+        assert!(!config.authorize(&plugins, "pluginsx:read"));
+
+        let admin = vec!["admin:*".to_string()];
+        assert!(config.authorize(&admin, "admin:users:create"));
+        assert!(!config.authorize(&admin, "admin"));
+
+        let everything = vec!["*".to_string()];
+        assert!(config.authorize(&everything, "health:read"));
+
+        let exact = vec!["health:read".to_string()];
+        assert!(config.authorize(&exact, "health:read"));
+        assert!(!config.authorize(&exact, "health:write"));
+    }
+
+    mod jwt {
+        use super::*;
+        use jsonwebtoken::{encode, EncodingKey, Header};
+        use serde_json::json;
+
+        const SECRET: &str = "test-secret";
+
+        fn now() -> u64 {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+        }
+
+        fn config(algorithm: JwtAlgorithm) -> AuthConfig {
+            let public_key = include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/testdata/jwt-rs256.pub.pem"
+            ));
+            AuthConfig::parse(&format!(
+                r#"
+[identities.dotted]
+api_key = "key.with.dots"
+roles = ["reader"]
+
+[roles.reader]
+scopes = ["plugins:read"]
+
+[jwt]
+algorithm = "{:?}"
+secret = "{}"
+public_key = '''
+{}'''
+"#,
+                algorithm, SECRET, public_key
+            ))
+            .unwrap()
+        }
+
+        fn hs256_token(secret: &str, exp: u64) -> String {
+            let claims = json!({"sub": "tester", "exp": exp, "scope": "plugins:read health:read"});
+            encode(
+                &Header::new(Algorithm::HS256),
+                &claims,
+                &EncodingKey::from_secret(secret.as_bytes()),
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn test_valid_hs256_token() {
+            let config = config(JwtAlgorithm::HS256);
+            let scopes = config
+                .authenticate_jwt(&hs256_token(SECRET, now() + 300))
+                .unwrap();
+            assert_eq!(scopes, vec!["plugins:read", "health:read"]);
+            assert!(config.authorize(&scopes, "plugins:read"));
+            assert!(!config.authorize(&scopes, "plugins:write"));
+        }
+
+        #[test]
+        fn test_expired_token_rejected() {
+            let config = config(JwtAlgorithm::HS256);
+            assert!(config
+                .authenticate_jwt(&hs256_token(SECRET, now() - 300))
+                .is_err());
+        }
+
+        #[test]
+        fn test_wrong_signature_rejected() {
+            let config = config(JwtAlgorithm::HS256);
+            assert!(config
+                .authenticate_jwt(&hs256_token("other-secret", now() + 300))
+                .is_err());
+        }
+
+        #[test]
+        fn test_valid_rs256_token_with_scope_list() {
+            let private_key = include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/testdata/jwt-rs256.pem"
+            ));
+            let claims = json!({"exp": now() + 300, "scope": ["events:publish"]});
+            let token = encode(
+                &Header::new(Algorithm::RS256),
+                &claims,
+                &EncodingKey::from_rsa_pem(private_key.as_bytes()).unwrap(),
+            )
+            .unwrap();
+
+            let config = config(JwtAlgorithm::RS256);
+            assert_eq!(
+                config.authenticate_jwt(&token).unwrap(),
+                vec!["events:publish"]
+            );
+            // An HS256 token must not pass RS256 validation
+            assert!(config
+                .authenticate_jwt(&hs256_token(SECRET, now() + 300))
+                .is_err());
+        }
+
+        #[test]
+        fn test_bad_rs256_key_fails_to_load() {
+            let error = AuthConfig::parse(
+                r#"
+[jwt]
+algorithm = "RS256"
+public_key = "not a key"
+"#,
+            )
+            .unwrap_err();
+            assert!(error.to_string().contains("jwt.public_key"), "{}", error);
+        }
+
+        #[tokio::test]
+        async fn test_dotted_api_key_falls_back_from_jwt() {
+            let config = config(JwtAlgorithm::HS256);
+            let (caller, scopes) = config.authenticate_bearer("key.with.dots").await.unwrap();
+            assert_eq!(caller, Caller("dotted".to_string()));
+            assert_eq!(scopes, vec!["plugins:read"]);
+
+            // A real JWT that fails validation is not tried as an API key
+            assert!(config
+                .authenticate_bearer(&hs256_token("other-secret", now() + 300))
+                .await
+                .is_none());
+            assert!(config
+                .authenticate_bearer(&hs256_token(SECRET, now() + 300))
+                .await
+                .is_some());
+        }
+
+        #[test]
+        fn test_jwt_section_parses_without_identities() {
+            let config: AuthConfig = toml::from_str(
+                r#"
+[jwt]
+algorithm = "HS256"
+secret = "test-secret"
+"#,
+            )
+            .unwrap();
+            assert!(config.identities.is_empty());
+            assert_eq!(config.jwt.unwrap().algorithm, JwtAlgorithm::HS256);
+        }
+    }
+    mod cors {
+        use super::*;
+        use axum::{routing::get, Router};
+
+        /// Serve a single route behind the CORS layer from `config`,
+        /// returning its URL
+        async fn serve(config: &str) -> String {
+            let config: AuthConfig = toml::from_str(config).unwrap();
+            let app = Router::new()
+                .route("/ping", get(|| async { "pong" }))
+                .layer(config.cors_layer().unwrap());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/ping", listener.local_addr().unwrap());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            url
+        }
+
+        async fn allowed_origin(url: &str, origin: &str) -> Option<String> {
+            let response = reqwest::Client::new()
+                .get(url)
+                .header("Origin", origin)
+                .send()
+                .await
+                .unwrap();
+            response
+                .headers()
+                .get("access-control-allow-origin")
+                .map(|value| value.to_str().unwrap().to_string())
+        }
+
+        #[tokio::test]
+        async fn test_only_configured_origins_are_allowed() {
+            let url = serve(
+                r#"
+[cors]
+allowed_origins = ["https://console.example.com"]
+allow_credentials = true
+"#,
+            )
+            .await;
+
+            assert_eq!(
+                allowed_origin(&url, "https://console.example.com")
+                    .await
+                    .as_deref(),
+                Some("https://console.example.com")
+            );
+            assert_eq!(allowed_origin(&url, "https://evil.example.com").await, None);
+        }
+
+        #[tokio::test]
+        async fn test_no_cors_section_denies_cross_origin() {
+            let url = serve("").await;
+            assert_eq!(
+                allowed_origin(&url, "https://console.example.com").await,
+                None
+            );
+        }
+
+        #[test]
+        fn test_wildcard_origin_rejects_credentials() {
+            let config: AuthConfig = toml::from_str(
+                r#"
+[cors]
+allowed_origins = ["*"]
+allow_credentials = true
+"#,
+            )
+            .unwrap();
+            assert!(config.cors_layer().is_err());
+        }
     }
 }
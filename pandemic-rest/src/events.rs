@@ -1,15 +1,51 @@
-use axum::{extract::State, http::StatusCode, response::Json, Extension};
-use pandemic_common::DaemonClient;
-use pandemic_protocol::{Request, Response as PandemicResponse};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+    Extension,
+};
+use pandemic_protocol::Request;
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::handlers::{ApiResult, AppState};
+use crate::handlers::{format_pandemic_response, ApiResult, AppState};
 
 #[derive(Deserialize)]
 pub struct PublishEventRequest {
     pub topic: String,
     pub data: serde_json::Value,
+    #[serde(default)]
+    pub retain: bool,
+}
+
+/// Events returned by the history endpoint when no `limit` is given
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct EventHistoryParams {
+    /// Subscription-style topic pattern, e.g. `health.#`
+    pub topic: Option<String>,
+    pub limit: Option<usize>,
+}
+
+pub async fn get_event_history(
+    State(state): State<AppState>,
+    Query(params): Query<EventHistoryParams>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    if !state.auth_config.authorize(&scopes, "events:read") {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(json!({"status": "error", "message": "Insufficient permissions"})),
+        ));
+    }
+
+    let request = Request::GetEventHistory {
+        topic_filter: params.topic,
+        limit: params.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+    };
+
+    format_pandemic_response(state.daemon.send_request(&request).await)
 }
 
 pub async fn publish_event(
@@ -27,25 +63,8 @@ pub async fn publish_event(
     let request = Request::Publish {
         topic: payload.topic,
         data: payload.data,
+        retain: payload.retain,
     };
 
-    match DaemonClient::send_request(&state.socket_path, &request).await {
-        Ok(PandemicResponse::Success { data }) => {
-            Ok(Json(json!({"status": "success", "data": data})))
-        }
-        Ok(PandemicResponse::Error { message }) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error", "message": message})),
-        )),
-        Ok(PandemicResponse::NotFound { message }) => Err((
-            StatusCode::NOT_FOUND,
-            Json(json!({"status": "not_found", "message": message})),
-        )),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(
-                json!({"status": "error", "message": format!("Daemon communication error: {}", e)}),
-            ),
-        )),
-    }
+    format_pandemic_response(state.daemon.send_request(&request).await)
 }
@@ -5,17 +5,19 @@ use axum::{
     response::Json,
     Extension,
 };
-use pandemic_common::{AgentClient, AgentStatus, DaemonClient};
+use pandemic_common::{AgentClient, AgentStatus, ClientError, DaemonPool};
 use pandemic_protocol::{
-    AgentRequest, Request, Response as PandemicResponse, ServiceOverrides, UserConfig,
+    AgentRequest, ErrorCode, Request, Response as PandemicResponse, ServiceOverrides, UserConfig,
 };
 use serde::Deserialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::auth::AuthConfig;
+use crate::audit::{AuditLog, AuditRecord};
+use crate::auth::{AuthConfig, Caller};
 
 macro_rules! require_scope {
     ($auth_config:expr, $scopes:expr, $required:expr) => {
@@ -31,25 +33,123 @@ macro_rules! require_scope {
 #[derive(Clone)]
 pub struct AppState {
     pub socket_path: PathBuf,
+    /// Reused connections to the daemon at `socket_path`
+    pub daemon: Arc<DaemonPool>,
+    /// Admin socket of the pandemic agent
+    pub agent_socket_path: PathBuf,
     pub auth_config: AuthConfig,
     pub agent_status: Arc<Mutex<AgentStatus>>,
+    pub audit: Arc<AuditLog>,
+    pub keepalive: Keepalive,
 }
 
+/// How often the server pings WebSocket clients, and how long a client has
+/// to answer before its socket and daemon connection are closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keepalive {
+    pub interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl AppState {
+    pub fn agent_client(&self) -> AgentClient {
+        AgentClient::with_socket_path(&self.agent_socket_path)
+    }
+
+    /// Whether the agent answered a ping and what it can do, re-checked once
+    /// the cached status goes stale
+    pub async fn agent_status(&self) -> (bool, Vec<String>) {
+        let needs_refresh = self.agent_status.lock().unwrap().is_stale();
+
+        if needs_refresh {
+            let client = self.agent_client();
+            let new_status =
+                tokio::time::timeout(AGENT_PING_TIMEOUT, AgentStatus::refresh(&client))
+                    .await
+                    // A hung agent counts as unavailable, cached like any other failure
+                    .unwrap_or_default();
+            *self.agent_status.lock().unwrap() = new_status;
+        }
+
+        let agent_status = self.agent_status.lock().unwrap();
+        (agent_status.available, agent_status.capabilities.clone())
+    }
+}
+
+/// How long a health or capability check waits for the agent to answer
+const AGENT_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub type ApiResult = Result<Json<Value>, (StatusCode, Json<Value>)>;
 
-fn format_pandemic_response(result: Result<PandemicResponse, Error>) -> ApiResult {
+/// HTTP status for a daemon or agent error; uncoded errors stay 500
+pub(crate) fn error_status(code: Option<ErrorCode>) -> StatusCode {
+    match code {
+        Some(ErrorCode::NotFound) => StatusCode::NOT_FOUND,
+        Some(ErrorCode::PermissionDenied) => StatusCode::FORBIDDEN,
+        Some(ErrorCode::InvalidRequest) => StatusCode::BAD_REQUEST,
+        Some(ErrorCode::Conflict) => StatusCode::CONFLICT,
+        Some(ErrorCode::Unavailable) => StatusCode::SERVICE_UNAVAILABLE,
+        Some(ErrorCode::Internal) | None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub(crate) fn format_pandemic_response(result: Result<PandemicResponse, Error>) -> ApiResult {
     match result {
         Ok(PandemicResponse::Success { data }) => {
             Ok(Json(json!({"status": "success", "data": data})))
         }
-        Ok(PandemicResponse::Error { message }) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"status": "error", "message": message})),
-        )),
+        Ok(PandemicResponse::Error { message, code }) => {
+            let mut body = json!({"status": "error", "message": message});
+            if let Some(code) = code {
+                body["code"] = json!(code);
+            }
+            Err((error_status(code), Json(body)))
+        }
         Ok(PandemicResponse::NotFound { message }) => Err((
             StatusCode::NOT_FOUND,
             Json(json!({"status": "not_found", "message": message})),
         )),
+        Ok(PandemicResponse::Conflict { message }) => Err((
+            StatusCode::CONFLICT,
+            Json(json!({"status": "conflict", "message": message})),
+        )),
+        Ok(PandemicResponse::Accepted { job_id }) => {
+            Ok(Json(json!({"status": "accepted", "job_id": job_id})))
+        }
+        Ok(PandemicResponse::DependenciesUnmet { missing }) => Err((
+            StatusCode::FAILED_DEPENDENCY,
+            Json(json!({
+                "status": "dependencies_unmet",
+                "message": format!("Missing dependencies: {}", missing.join(", ")),
+                "missing": missing,
+            })),
+        )),
+        Ok(PandemicResponse::IncompatibleVersion {
+            server,
+            min_supported,
+        }) => Err((
+            StatusCode::BAD_GATEWAY,
+            Json(json!({
+                "status": "error",
+                "message": format!(
+                    "Incompatible daemon protocol version {} (minimum supported {})",
+                    server, min_supported
+                )
+            })),
+        )),
+        Err(e) if matches!(e.downcast_ref(), Some(ClientError::Timeout(_))) => Err((
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(json!({"status": "error", "message": e.to_string()})),
+        )),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(
@@ -59,15 +159,109 @@ fn format_pandemic_response(result: Result<PandemicResponse, Error>) -> ApiResul
     }
 }
 
+/// `?limit=&offset=&filter=` for list endpoints. `filter` keeps items whose
+/// name contains it; pagination applies after filtering.
+#[derive(Debug, Default, Deserialize)]
+pub struct ListParams {
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: usize,
+    pub filter: Option<String>,
+}
+
+impl ListParams {
+    fn matches(&self, name: &str) -> bool {
+        self.filter
+            .as_deref()
+            .is_none_or(|filter| name.contains(filter))
+    }
+
+    /// Filter `items` by the name `name_of` picks out, then cut the
+    /// requested page. Returns the page and the filtered total.
+    fn paginate<T>(&self, items: Vec<T>, name_of: impl Fn(&T) -> Option<&str>) -> (Vec<T>, usize) {
+        let matching: Vec<T> = items
+            .into_iter()
+            .filter(|item| name_of(item).is_some_and(|name| self.matches(name)))
+            .collect();
+        let total = matching.len();
+        let page = matching
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect();
+        (page, total)
+    }
+
+    fn response(&self, data: Value, total: usize) -> Json<Value> {
+        Json(json!({
+            "status": "success",
+            "data": data,
+            "total": total,
+            "limit": self.limit,
+            "offset": self.offset,
+        }))
+    }
+}
+
+/// Record `action` on `target` in the audit log and pass `result` through.
+/// If the record can't be written the response becomes an error, so a
+/// privileged change is never made without anyone hearing about it.
+async fn audited(
+    state: &AppState,
+    caller: &Caller,
+    action: &str,
+    target: &str,
+    result: ApiResult,
+) -> ApiResult {
+    let outcome = match &result {
+        Ok(_) => "success".to_string(),
+        Err((_, Json(body))) => body["message"].as_str().unwrap_or("error").to_string(),
+    };
+    let record = AuditRecord {
+        timestamp: chrono::Utc::now(),
+        identity: caller.0.clone(),
+        action: action.to_string(),
+        target: target.to_string(),
+        result: outcome,
+    };
+    if let Err(e) = state.audit.record(&record).await {
+        tracing::error!(
+            "Failed to write audit record to {}: {}",
+            state.audit.path().display(),
+            e
+        );
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+                "status": "error",
+                "message": format!(
+                    "{} on {} finished ({}) but could not be audited: {}",
+                    action, target, record.result, e
+                )
+            })),
+        ));
+    }
+    result
+}
+
 pub async fn list_plugins(
     State(state): State<AppState>,
+    Query(params): Query<ListParams>,
     Extension(scopes): Extension<Vec<String>>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "plugins:read");
 
     let request = Request::ListPlugins;
-    let response = DaemonClient::send_request(&state.socket_path, &request);
-    format_pandemic_response(response.await)
+    match state.daemon.send_request(&request).await {
+        Ok(PandemicResponse::Success { data }) => {
+            let plugins: Vec<Value> =
+                serde_json::from_value(data.unwrap_or_default()).unwrap_or_default();
+            let (page, total) =
+                params.paginate(plugins, |plugin| plugin.get("name").and_then(Value::as_str));
+            Ok(params.response(json!(page), total))
+        }
+        response => format_pandemic_response(response),
+    }
 }
 
 pub async fn get_plugin(
@@ -78,7 +272,7 @@ pub async fn get_plugin(
     require_scope!(&state.auth_config, &scopes, "plugins:read");
 
     let request = Request::GetPlugin { name };
-    let response = DaemonClient::send_request(&state.socket_path, &request);
+    let response = state.daemon.send_request(&request);
     format_pandemic_response(response.await)
 }
 
@@ -90,7 +284,64 @@ pub async fn deregister_plugin(
     require_scope!(&state.auth_config, &scopes, "plugins:write");
 
     let request = Request::Deregister { name };
-    let response = DaemonClient::send_request(&state.socket_path, &request);
+    let response = state.daemon.send_request(&request);
+    format_pandemic_response(response.await)
+}
+
+pub async fn get_plugin_config(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "plugins:read");
+
+    let request = Request::GetConfig { plugin: name };
+    let response = state.daemon.send_request(&request);
+    format_pandemic_response(response.await)
+}
+
+pub async fn set_plugin_config(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
+    Json(config): Json<Value>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "plugins:write");
+
+    let target = name.clone();
+    let request = Request::SetConfigOverride {
+        plugin: name,
+        config,
+    };
+    let response = state.daemon.send_request(&request);
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "config.set", &target, result).await
+}
+
+pub async fn clear_plugin_config(
+    Path(name): Path<String>,
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "plugins:write");
+
+    let target = name.clone();
+    let request = Request::ClearConfigOverride { plugin: name };
+    let response = state.daemon.send_request(&request);
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "config.clear", &target, result).await
+}
+
+pub async fn list_subscriptions(
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "subscriptions:read");
+
+    let request = Request::ListSubscriptions;
+    let response = state.daemon.send_request(&request);
     format_pandemic_response(response.await)
 }
 
@@ -101,7 +352,41 @@ pub async fn get_health(
     require_scope!(&state.auth_config, &scopes, "health:read");
 
     let request = Request::GetHealth;
-    let response = DaemonClient::send_request(&state.socket_path, &request);
+    let response = state.daemon.send_request(&request);
+    let (response, (agent_available, _)) = tokio::join!(response, state.agent_status());
+
+    // An unreachable agent is reported, not treated as a failed health check
+    let Json(mut body) = format_pandemic_response(response)?;
+    match body["data"].as_object_mut() {
+        Some(data) => {
+            data.insert("agent_available".to_string(), json!(agent_available));
+        }
+        None => body["data"] = json!({ "agent_available": agent_available }),
+    }
+    Ok(Json(body))
+}
+
+/// Liveness probe: answering at all means the process is up
+pub async fn livez() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness probe: ready while the daemon socket accepts connections
+pub async fn readyz(State(state): State<AppState>) -> StatusCode {
+    match tokio::net::UnixStream::connect(&state.socket_path).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+pub async fn get_metrics(
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "metrics:read");
+
+    let request = Request::GetMetrics;
+    let response = state.daemon.send_request(&request);
     format_pandemic_response(response.await)
 }
 
@@ -111,21 +396,7 @@ pub async fn get_admin_capabilities(
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
-    let needs_refresh = {
-        let agent_status = state.agent_status.lock().unwrap();
-        agent_status.is_stale()
-    };
-
-    if needs_refresh {
-        let new_status = AgentStatus::refresh().await;
-        let mut agent_status = state.agent_status.lock().unwrap();
-        *agent_status = new_status;
-    }
-
-    let (available, capabilities) = {
-        let agent_status = state.agent_status.lock().unwrap();
-        (agent_status.available, agent_status.capabilities.clone())
-    };
+    let (available, capabilities) = state.agent_status().await;
 
     Ok(Json(json!({
         "status": "success",
@@ -142,8 +413,8 @@ pub async fn list_system_services(
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
-    let request = AgentRequest::ListServices;
-    let agent_client = AgentClient::default();
+    let request = AgentRequest::ListServices { filter: None };
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -160,7 +431,32 @@ pub async fn get_system_service(
         service: name,
     };
 
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
+    let response = agent_client.send_agent_request(&request);
+    format_pandemic_response(response.await)
+}
+
+#[derive(Deserialize)]
+pub struct LogParams {
+    lines: Option<u32>,
+    since: Option<String>,
+}
+
+pub async fn get_service_logs(
+    Path(name): Path<String>,
+    Query(params): Query<LogParams>,
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "admin");
+
+    let request = AgentRequest::GetServiceLogs {
+        service: name,
+        lines: params.lines.unwrap_or(100),
+        since: params.since,
+    };
+
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -174,47 +470,65 @@ pub async fn control_system_service(
     Path(name): Path<String>,
     State(state): State<AppState>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
     Json(payload): Json<ServiceAction>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let action = format!("service.{}", payload.action);
+    let target = name.clone();
+
     let request = AgentRequest::SystemdControl {
         action: payload.action,
         service: name,
     };
 
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, &action, &target, result).await
 }
 
 // User management handlers
 pub async fn list_users(
     State(state): State<AppState>,
+    Query(params): Query<ListParams>,
     Extension(scopes): Extension<Vec<String>>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
     let request = AgentRequest::ListUsers;
-    let agent_client = AgentClient::default();
-    let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let agent_client = state.agent_client();
+    match agent_client.send_agent_request(&request).await {
+        Ok(PandemicResponse::Success { data }) => {
+            let users: Vec<String> = data
+                .and_then(|data| serde_json::from_value(data["users"].clone()).ok())
+                .unwrap_or_default();
+            let (page, total) = params.paginate(users, |user| Some(user.as_str()));
+            Ok(params.response(json!({ "users": page }), total))
+        }
+        response => format_pandemic_response(response),
+    }
 }
 
 pub async fn create_user(
     State(state): State<AppState>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
     Json(payload): Json<CreateUserPayload>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = payload.username.clone();
+
     let request = AgentRequest::UserCreate {
         username: payload.username,
         config: payload.config,
     };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "user.create", &target, result).await
 }
 
 #[derive(serde::Deserialize)]
@@ -227,27 +541,35 @@ pub async fn delete_user(
     State(state): State<AppState>,
     Path(username): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = username.clone();
+
     let request = AgentRequest::UserDelete { username };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "user.delete", &target, result).await
 }
 
 pub async fn modify_user(
     State(state): State<AppState>,
     Path(username): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
     Json(config): Json<UserConfig>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = username.clone();
+
     let request = AgentRequest::UserModify { username, config };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "user.modify", &target, result).await
 }
 
 // Group management handlers
@@ -258,7 +580,19 @@ pub async fn list_groups(
     require_scope!(&state.auth_config, &scopes, "admin");
 
     let request = AgentRequest::ListGroups;
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
+    let response = agent_client.send_agent_request(&request);
+    format_pandemic_response(response.await)
+}
+
+pub async fn get_blocklist(
+    State(state): State<AppState>,
+    Extension(scopes): Extension<Vec<String>>,
+) -> ApiResult {
+    require_scope!(&state.auth_config, &scopes, "admin");
+
+    let request = AgentRequest::GetBlocklist;
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -267,58 +601,74 @@ pub async fn create_group(
     State(state): State<AppState>,
     Path(groupname): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = groupname.clone();
+
     let request = AgentRequest::GroupCreate { groupname };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "group.create", &target, result).await
 }
 
 pub async fn delete_group(
     State(state): State<AppState>,
     Path(groupname): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = groupname.clone();
+
     let request = AgentRequest::GroupDelete { groupname };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "group.delete", &target, result).await
 }
 
 pub async fn add_user_to_group(
     State(state): State<AppState>,
     Path((groupname, username)): Path<(String, String)>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = format!("{}/{}", groupname, username);
+
     let request = AgentRequest::GroupAddUser {
         groupname,
         username,
     };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "group.add_user", &target, result).await
 }
 
 pub async fn remove_user_from_group(
     State(state): State<AppState>,
     Path((groupname, username)): Path<(String, String)>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = format!("{}/{}", groupname, username);
+
     let request = AgentRequest::GroupRemoveUser {
         groupname,
         username,
     };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "group.remove_user", &target, result).await
 }
 
 // Service configuration handlers
@@ -330,7 +680,7 @@ pub async fn get_service_config(
     require_scope!(&state.auth_config, &scopes, "admin");
 
     let request = AgentRequest::GetServiceConfig { service };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -339,27 +689,35 @@ pub async fn set_service_config(
     State(state): State<AppState>,
     Path(service): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
     Json(overrides): Json<ServiceOverrides>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = service.clone();
+
     let request = AgentRequest::ServiceConfigOverride { service, overrides };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "service_config.set", &target, result).await
 }
 
 pub async fn reset_service_config(
     State(state): State<AppState>,
     Path(service): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = service.clone();
+
     let request = AgentRequest::ServiceConfigReset { service };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "service_config.reset", &target, result).await
 }
 // Registry handlers
 pub async fn search_infections(
@@ -371,7 +729,7 @@ pub async fn search_infections(
 
     let query = params.get("q").unwrap_or(&String::new()).clone();
     let request = AgentRequest::SearchInfections { query };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -384,7 +742,7 @@ pub async fn get_infection_manifest(
     require_scope!(&state.auth_config, &scopes, "admin");
 
     let request = AgentRequest::GetInfectionManifest { name };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
     format_pandemic_response(response.await)
 }
@@ -398,15 +756,359 @@ pub async fn install_infection(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Extension(scopes): Extension<Vec<String>>,
+    Extension(caller): Extension<Caller>,
     Json(payload): Json<InstallPayload>,
 ) -> ApiResult {
     require_scope!(&state.auth_config, &scopes, "admin");
 
+    let target = name.clone();
+
     let request = AgentRequest::InstallInfection {
         name,
         target_path: payload.target_path,
     };
-    let agent_client = AgentClient::default();
+    let agent_client = state.agent_client();
     let response = agent_client.send_agent_request(&request);
-    format_pandemic_response(response.await)
+    let result = format_pandemic_response(response.await);
+    audited(&state, &caller, "infection.install", &target, result).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(query: &str) -> ListParams {
+        let uri = format!("/?{}", query).parse().unwrap();
+        Query::try_from_uri(&uri).unwrap().0
+    }
+
+    fn state(audit_path: &std::path::Path) -> AppState {
+        AppState {
+            socket_path: audit_path.with_file_name("pandemic.sock"),
+            daemon: Arc::new(DaemonPool::new(
+                audit_path.with_file_name("pandemic.sock"),
+                1,
+            )),
+            agent_socket_path: audit_path.with_file_name("admin.sock"),
+            auth_config: toml::from_str("").unwrap(),
+            agent_status: Arc::new(Mutex::new(AgentStatus::new())),
+            audit: Arc::new(AuditLog::new(audit_path)),
+            keepalive: Keepalive::default(),
+        }
+    }
+
+    fn read_records(path: &std::path::Path) -> Vec<AuditRecord> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_error_codes_map_to_http_status() {
+        for (code, status, name) in [
+            (ErrorCode::NotFound, StatusCode::NOT_FOUND, "not_found"),
+            (
+                ErrorCode::PermissionDenied,
+                StatusCode::FORBIDDEN,
+                "permission_denied",
+            ),
+            (
+                ErrorCode::InvalidRequest,
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+            ),
+            (ErrorCode::Conflict, StatusCode::CONFLICT, "conflict"),
+            (
+                ErrorCode::Internal,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+            ),
+            (
+                ErrorCode::Unavailable,
+                StatusCode::SERVICE_UNAVAILABLE,
+                "unavailable",
+            ),
+        ] {
+            let response = PandemicResponse::error_with_code(code, "failed");
+            let (actual, Json(body)) = format_pandemic_response(Ok(response)).unwrap_err();
+            assert_eq!(actual, status, "{:?}", code);
+            assert_eq!(body["code"], name);
+            assert_eq!(body["message"], "failed");
+        }
+
+        let (status, Json(body)) =
+            format_pandemic_response(Ok(PandemicResponse::error("failed"))).unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.get("code").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_create_user_is_audited_with_caller() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        let payload: CreateUserPayload = serde_json::from_value(json!({
+            "username": "root",
+            "config": {"groups": []}
+        }))
+        .unwrap();
+
+        // Fails whether or not an agent is running (root is blocklisted), but
+        // is still recorded against the caller
+        let result = create_user(
+            State(state(&audit_path)),
+            Extension(vec!["admin".to_string()]),
+            Extension(Caller("ops".to_string())),
+            Json(payload),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let records = read_records(&audit_path);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].identity, "ops");
+        assert_eq!(records[0].action, "user.create");
+        assert_eq!(records[0].target, "root");
+        assert_ne!(records[0].result, "success");
+    }
+
+    #[tokio::test]
+    async fn test_admin_handlers_use_configured_agent_socket() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state = state(&temp_dir.path().join("audit.jsonl"));
+        let agent = tokio::net::UnixListener::bind(&state.agent_socket_path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = agent.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).await.unwrap();
+            assert!(line.contains("ListServices"), "{}", line);
+            let response = PandemicResponse::success_with_data(json!(["pandemic-udp"]));
+            let mut body = serde_json::to_vec(&response).unwrap();
+            body.push(b'\n');
+            reader.get_mut().write_all(&body).await.unwrap();
+        });
+
+        let Json(body) = list_system_services(State(state), Extension(vec!["admin".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(body["data"], json!(["pandemic-udp"]));
+    }
+
+    #[tokio::test]
+    async fn test_health_reports_missing_agent() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // No agent is listening on state.agent_socket_path
+        let state = state(&temp_dir.path().join("audit.jsonl"));
+        let daemon = tokio::net::UnixListener::bind(&state.socket_path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = daemon.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let response = match serde_json::from_str(line.trim()).unwrap() {
+                    Request::Hello { .. } => PandemicResponse::success_with_data(
+                        json!({ "protocol_version": pandemic_protocol::PROTOCOL_VERSION }),
+                    ),
+                    Request::GetHealth => {
+                        PandemicResponse::success_with_data(json!({"active_plugins": 2}))
+                    }
+                    other => panic!("unexpected request: {:?}", other),
+                };
+                line.clear();
+                let mut body = serde_json::to_vec(&response).unwrap();
+                body.push(b'\n');
+                reader.get_mut().write_all(&body).await.unwrap();
+            }
+        });
+
+        let Json(body) = get_health(State(state), Extension(vec!["health:read".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(body["status"], "success");
+        assert_eq!(body["data"]["active_plugins"], 2);
+        assert_eq!(body["data"]["agent_available"], false);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_registration_time_is_rfc3339() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let state = state(&temp_dir.path().join("audit.jsonl"));
+        let registered_at = std::time::UNIX_EPOCH + Duration::from_secs(1_704_164_645);
+        let daemon = tokio::net::UnixListener::bind(&state.socket_path).unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = daemon.accept().await.unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).await.unwrap() > 0 {
+                let response = match serde_json::from_str(line.trim()).unwrap() {
+                    Request::Hello { .. } => PandemicResponse::success_with_data(
+                        json!({ "protocol_version": pandemic_protocol::PROTOCOL_VERSION }),
+                    ),
+                    Request::GetPlugin { name } => PandemicResponse::success_with_data(
+                        serde_json::to_value(pandemic_protocol::PluginInfo {
+                            name,
+                            version: "1.0.0".to_string(),
+                            description: None,
+                            config: None,
+                            registered_at: Some(registered_at),
+                            heartbeat_interval: None,
+                            dependencies: vec![],
+                        })
+                        .unwrap(),
+                    ),
+                    other => panic!("unexpected request: {:?}", other),
+                };
+                line.clear();
+                let mut body = serde_json::to_vec(&response).unwrap();
+                body.push(b'\n');
+                reader.get_mut().write_all(&body).await.unwrap();
+            }
+        });
+
+        let Json(body) = get_plugin(
+            Path("sensor".to_string()),
+            State(state),
+            Extension(vec!["plugins:read".to_string()]),
+        )
+        .await
+        .unwrap();
+        let timestamp = body["data"]["registered_at"].as_str().unwrap();
+        let parsed = chrono::DateTime::parse_from_rfc3339(timestamp).unwrap();
+        assert_eq!(parsed.timestamp(), 1_704_164_645);
+    }
+
+    #[tokio::test]
+    async fn test_plugin_config_set_and_clear_through_daemon() {
+        use pandemic_common::FileConfigManager;
+        use pandemic_daemon::daemon::Daemon;
+        use std::os::unix::fs::MetadataExt;
+        use tokio::sync::RwLock;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let audit_path = temp_dir.path().join("audit.jsonl");
+        let state = state(&audit_path);
+        let defaults_dir = temp_dir.path().join("defaults");
+        std::fs::create_dir_all(&defaults_dir).unwrap();
+        std::fs::write(defaults_dir.join("sensor.toml"), "interval = 5\n").unwrap();
+
+        // The REST API connects without registering, so the daemon trusts it
+        // by the uid it runs as, here our own
+        let uid = std::fs::metadata(temp_dir.path()).unwrap().uid();
+        let mut daemon = Daemon::new();
+        daemon.config = Some(FileConfigManager::new(
+            &defaults_dir,
+            temp_dir.path().join("overrides"),
+        ));
+        daemon.acl =
+            Some(toml::from_str(&format!("[peers.\"{}\"]\nconfigure = [\"*\"]\n", uid)).unwrap());
+        let listener = tokio::net::UnixListener::bind(&state.socket_path).unwrap();
+        let socket_path = state.socket_path.clone();
+        tokio::spawn(async move {
+            pandemic_daemon::server::serve(
+                listener,
+                None,
+                &socket_path,
+                Arc::new(RwLock::new(daemon)),
+                std::future::pending(),
+            )
+            .await
+        });
+
+        let Json(body) = set_plugin_config(
+            Path("sensor".to_string()),
+            State(state.clone()),
+            Extension(vec!["plugins:write".to_string()]),
+            Extension(Caller("ops".to_string())),
+            Json(json!({"interval": 30})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["data"], json!({"interval": 30}));
+
+        let Json(body) = clear_plugin_config(
+            Path("sensor".to_string()),
+            State(state.clone()),
+            Extension(vec!["plugins:write".to_string()]),
+            Extension(Caller("ops".to_string())),
+        )
+        .await
+        .unwrap();
+        assert_eq!(body["data"], json!({"interval": 5}));
+
+        let records = read_records(&audit_path);
+        let actions: Vec<_> = records
+            .iter()
+            .map(|record| (record.action.as_str(), record.result.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![("config.set", "success"), ("config.clear", "success")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unwritable_audit_log_is_surfaced() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // A directory can't be opened for appending
+        let state = state(temp_dir.path());
+
+        let result = audited(
+            &state,
+            &Caller("ops".to_string()),
+            "group.create",
+            "wheel",
+            Ok(Json(json!({"status": "success"}))),
+        )
+        .await;
+
+        let (status, Json(body)) = result.unwrap_err();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("could not be audited"));
+    }
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("plugin-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_paginate_slices_after_filtering() {
+        let (page, total) = params("limit=2&offset=1").paginate(names(5), |n| Some(n.as_str()));
+        assert_eq!(page, vec!["plugin-1", "plugin-2"]);
+        assert_eq!(total, 5);
+
+        let (page, total) = params("").paginate(names(3), |n| Some(n.as_str()));
+        assert_eq!(page.len(), 3);
+        assert_eq!(total, 3);
+
+        let mut items = names(12);
+        items.push("other".to_string());
+        let (page, total) = params("filter=plugin-1&limit=2").paginate(items, |n| Some(n.as_str()));
+        assert_eq!(page, vec!["plugin-1", "plugin-10"]);
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn test_offset_past_end_returns_empty_page() {
+        let params = params("limit=10&offset=50");
+        let (page, total) = params.paginate(names(5), |n| Some(n.as_str()));
+        assert!(page.is_empty());
+        assert_eq!(total, 5);
+
+        let Json(body) = params.response(json!(page), total);
+        assert_eq!(body["data"], json!([]));
+        assert_eq!(body["total"], 5);
+        assert_eq!(body["limit"], 10);
+        assert_eq!(body["offset"], 50);
+    }
 }
@@ -1,35 +1,41 @@
-mod auth;
 mod events;
 mod handlers;
 mod middleware;
 mod websocket;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use axum::{
     middleware::from_fn_with_state,
     routing::{delete, get, post},
     Router,
 };
 use clap::Parser;
-use pandemic_common::{AgentStatus, DaemonClient};
+use pandemic_common::tls::{serve_app, Tls};
+use pandemic_common::{AgentStatus, DaemonClient, DaemonPool, Rotation};
 use pandemic_protocol::{PluginInfo, Request};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use tower::ServiceBuilder;
-use tower_http::{cors::CorsLayer, trace::TraceLayer};
-use tracing::{error, info};
+use tower_http::trace::TraceLayer;
+use tracing::info;
 
-use auth::AuthConfig;
-use events::publish_event;
+// Share the library's auth module rather than compiling a second copy
+use pandemic_rest::audit::{self, AuditLog};
+use pandemic_rest::auth::{self, AuthConfig};
+
+use events::{get_event_history, publish_event};
 use handlers::{
-    add_user_to_group, control_system_service, create_group, create_user, delete_group,
-    delete_user, deregister_plugin, get_admin_capabilities, get_health, get_infection_manifest,
-    get_plugin, get_service_config, get_system_service, install_infection, list_groups,
-    list_plugins, list_system_services, list_users, modify_user, remove_user_from_group,
-    reset_service_config, search_infections, set_service_config, AppState,
+    add_user_to_group, clear_plugin_config, control_system_service, create_group, create_user,
+    delete_group, delete_user, deregister_plugin, get_admin_capabilities, get_blocklist,
+    get_health, get_infection_manifest, get_metrics, get_plugin, get_plugin_config,
+    get_service_config, get_service_logs, get_system_service, install_infection, list_groups,
+    list_plugins, list_subscriptions, list_system_services, list_users, livez, modify_user, readyz,
+    remove_user_from_group, reset_service_config, search_infections, set_plugin_config,
+    set_service_config, AppState, Keepalive,
 };
 use middleware::auth_middleware;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use websocket::websocket_handler;
 
 #[derive(Parser)]
@@ -39,6 +45,10 @@ struct Args {
     #[arg(long, default_value = "/var/run/pandemic/pandemic.sock")]
     socket_path: PathBuf,
 
+    /// Admin socket of the pandemic agent
+    #[arg(long, default_value = "/var/run/pandemic/admin.sock")]
+    agent_socket: PathBuf,
+
     #[arg(long, default_value = "127.0.0.1")]
     bind_address: String,
 
@@ -47,26 +57,63 @@ struct Args {
 
     #[arg(long, default_value = "/etc/pandemic/rest-auth.toml")]
     auth_config: PathBuf,
+
+    /// Daemon connections kept open for API requests; requests beyond this
+    /// many at once use a connection of their own
+    #[arg(long, default_value = "8")]
+    daemon_pool_size: usize,
+
+    /// Append-only JSON-lines record of admin changes
+    #[arg(long, default_value = "/var/log/pandemic/rest-audit.jsonl")]
+    audit_log: PathBuf,
+
+    /// Roll the audit log over once it would pass this size; 0 never rotates
+    #[arg(long, default_value = "10485760")]
+    audit_max_bytes: u64,
+
+    /// Rolled-over audit logs to keep (rest-audit.jsonl.1, .2, ...)
+    #[arg(long, default_value = "5")]
+    audit_max_files: usize,
+
+    /// PEM certificate chain; serves HTTPS when given with --tls-key
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// PEM CA bundle; clients must then present a certificate it issued,
+    /// and are identified through [mtls_identities] instead of API keys
+    #[arg(long, requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
+
+    /// Seconds between pings sent to each WebSocket client
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    ws_ping_interval: u64,
+
+    /// Seconds a WebSocket client has to answer a ping before it is disconnected
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+    ws_pong_timeout: u64,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     // Load authentication configuration
-    let auth_config = match AuthConfig::load(&args.auth_config).await {
-        Ok(config) => {
-            info!("Loaded auth config from {:?}", args.auth_config);
-            config
-        }
-        Err(e) => {
-            error!("Failed to load auth config: {}", e);
-            info!("Creating default auth config...");
-            create_default_auth_config(&args.auth_config).await?;
-            AuthConfig::load(&args.auth_config).await?
-        }
-    };
+    // A config that exists but doesn't load, e.g. over a bad JWT key, stops
+    // startup rather than being replaced
+    if !tokio::fs::try_exists(&args.auth_config).await? {
+        info!("Creating default auth config...");
+        create_default_auth_config(&args.auth_config).await?;
+    }
+    let auth_config = AuthConfig::load(&args.auth_config)
+        .await
+        .with_context(|| format!("Failed to load auth config {:?}", args.auth_config))?;
+    info!("Loaded auth config from {:?}", args.auth_config);
+    let cors = auth_config.cors_layer()?;
 
     // Register with pandemic daemon
     let plugin_info = PluginInfo {
@@ -80,12 +127,16 @@ async fn main() -> Result<()> {
             config
         }),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
     let mut client = DaemonClient::connect(&args.socket_path).await?;
     client
         .send_request(&Request::Register {
             plugin: plugin_info,
+            force: false,
+            ignore_dependencies: false,
         })
         .await?;
 
@@ -93,9 +144,22 @@ async fn main() -> Result<()> {
 
     // Set up application state
     let state = AppState {
+        daemon: Arc::new(DaemonPool::new(&args.socket_path, args.daemon_pool_size)),
         socket_path: args.socket_path,
+        agent_socket_path: args.agent_socket,
         auth_config,
         agent_status: Arc::new(Mutex::new(AgentStatus::new())),
+        audit: Arc::new(AuditLog::with_rotation(
+            args.audit_log,
+            (args.audit_max_bytes > 0).then_some(Rotation {
+                max_bytes: args.audit_max_bytes,
+                max_files: args.audit_max_files,
+            }),
+        )),
+        keepalive: Keepalive {
+            interval: Duration::from_secs(args.ws_ping_interval),
+            timeout: Duration::from_secs(args.ws_pong_timeout),
+        },
     };
 
     // Build the router with auth-protected routes
@@ -103,14 +167,24 @@ async fn main() -> Result<()> {
         .route("/api/plugins", get(list_plugins))
         .route("/api/plugins/:name", get(get_plugin))
         .route("/api/plugins/:name", delete(deregister_plugin))
+        .route(
+            "/api/plugins/:name/config",
+            get(get_plugin_config)
+                .put(set_plugin_config)
+                .delete(clear_plugin_config),
+        )
         .route("/api/health", get(get_health))
+        .route("/api/metrics", get(get_metrics))
+        .route("/api/subscriptions", get(list_subscriptions))
         .route("/api/events", post(publish_event))
+        .route("/api/events/history", get(get_event_history))
         .route("/api/admin/services", get(list_system_services))
         .route("/api/admin/services/:name", get(get_system_service))
         .route(
             "/api/admin/services/:name/action",
             post(control_system_service),
         )
+        .route("/api/admin/services/:name/logs", get(get_service_logs))
         .route("/api/admin/capabilities", get(get_admin_capabilities))
         // Admin user management routes
         .route("/api/admin/users", post(create_user).get(list_users))
@@ -128,6 +202,7 @@ async fn main() -> Result<()> {
             "/api/admin/groups/:groupname/users/:username",
             post(add_user_to_group).delete(remove_user_from_group),
         )
+        .route("/api/admin/blocklist", get(get_blocklist))
         // Admin service configuration routes
         .route(
             "/api/admin/services/:service/config",
@@ -153,25 +228,44 @@ async fn main() -> Result<()> {
     let app = Router::new()
         .merge(protected_routes)
         .merge(websocket_routes)
+        .merge(probe_routes())
         .layer(
             ServiceBuilder::new()
                 .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
+                .layer(cors),
         )
         .with_state(state);
 
+    // API keys and JWTs authenticate clients unless they must present a
+    // certificate, which then identifies them instead
+    let tls = Tls::load(
+        args.tls_cert.as_deref(),
+        args.tls_key.as_deref(),
+        args.tls_client_ca.as_deref(),
+    )?;
+
     // Start the server
     let bind_addr = format!("{}:{}", args.bind_address, args.port);
-    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-    info!("REST API server listening on {}", bind_addr);
+    let listener = std::net::TcpListener::bind(&bind_addr)?;
+    info!(
+        "REST API server listening on {}://{}",
+        if tls.is_some() { "https" } else { "http" },
+        bind_addr
+    );
 
-    axum::serve(listener, app).await?;
+    serve_app(listener, app, tls).await
+}
 
-    Ok(())
+/// Unauthenticated container probes
+fn probe_routes() -> Router<AppState> {
+    Router::new()
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
 }
 
 async fn create_default_auth_config(path: &PathBuf) -> Result<()> {
-    let default_config = r#"[identities.admin]
+    let default_config = r#"# Prefer api_key_hash over api_key, generated with `pandemic-cli auth hash-key`
+[identities.admin]
 api_key = "pandemic-admin-key-change-me"
 roles = ["admin"]
 
@@ -183,7 +277,17 @@ roles = ["reader"]
 scopes = ["*"]
 
 [roles.reader]
-scopes = ["plugins:read", "health:read", "events:subscribe"]
+scopes = ["plugins:read", "health:read", "metrics:read", "subscriptions:read", "events:subscribe"]
+
+# With --tls-client-ca, clients are identified by the certificate's common name
+# or a DNS subject alternative name instead
+# [mtls_identities."monitor.example.com"]
+# roles = ["reader"]
+
+# Optionally accept JWT bearer tokens, taking scopes from the "scope" claim
+# [jwt]
+# algorithm = "HS256"   # or "RS256" with public_key = "-----BEGIN PUBLIC KEY-----..."
+# secret = "change-me"
 "#;
 
     if let Some(parent) = path.parent() {
@@ -196,3 +300,173 @@ scopes = ["plugins:read", "health:read", "events:subscribe"]
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serve the probe routes for a daemon socket at `socket_path`, returning
+    /// the base URL
+    fn serve_probes(socket_path: PathBuf) -> String {
+        let state = AppState {
+            agent_socket_path: socket_path.with_file_name("admin.sock"),
+            daemon: Arc::new(DaemonPool::new(&socket_path, 1)),
+            socket_path,
+            auth_config: toml::from_str("").unwrap(),
+            agent_status: Arc::new(Mutex::new(AgentStatus::new())),
+            audit: Arc::new(AuditLog::new("/dev/null")),
+            keepalive: Keepalive::default(),
+        };
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(serve_app(listener, probe_routes().with_state(state), None));
+        url
+    }
+
+    #[test]
+    fn test_keepalive_periods_must_be_positive() {
+        for flag in ["--ws-ping-interval", "--ws-pong-timeout"] {
+            assert!(Args::try_parse_from(["pandemic-rest", flag, "0"]).is_err());
+            assert!(Args::try_parse_from(["pandemic-rest", flag, "1"]).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probes_need_no_credentials() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let daemon = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        let url = serve_probes(socket_path);
+
+        let livez = reqwest::get(format!("{}/livez", url)).await.unwrap();
+        assert_eq!(livez.status(), reqwest::StatusCode::OK);
+        let readyz = reqwest::get(format!("{}/readyz", url)).await.unwrap();
+        assert_eq!(readyz.status(), reqwest::StatusCode::OK);
+
+        // Without the daemon socket only readiness fails
+        drop(daemon);
+        let livez = reqwest::get(format!("{}/livez", url)).await.unwrap();
+        assert_eq!(livez.status(), reqwest::StatusCode::OK);
+        let readyz = reqwest::get(format!("{}/readyz", url)).await.unwrap();
+        assert_eq!(readyz.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_tls_handshake() {
+        let testdata = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+        let cert_path = format!("{}/tls-cert.pem", testdata);
+        let config = pandemic_common::tls::load_server_config(
+            &cert_path,
+            &format!("{}/tls-key.pem", testdata),
+        )
+        .unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = Router::new().route("/ping", get(|| async { "pong" }));
+        tokio::spawn(serve_app(listener, app, Some(Tls::Server(config))));
+
+        let cert = reqwest::Certificate::from_pem(&std::fs::read(&cert_path).unwrap()).unwrap();
+        let client = reqwest::Client::builder()
+            .add_root_certificate(cert)
+            .build()
+            .unwrap();
+        let response = client
+            .get(format!("https://localhost:{}/ping", port))
+            .send()
+            .await
+            .unwrap();
+
+        assert!(response.status().is_success());
+        assert_eq!(response.text().await.unwrap(), "pong");
+
+        // Plain HTTP against the TLS listener must fail
+        assert!(reqwest::get(format!("http://localhost:{}/ping", port))
+            .await
+            .is_err());
+    }
+
+    mod mtls {
+        use super::*;
+        use axum::Extension;
+        use pandemic_rest::auth::Caller;
+
+        const TESTDATA: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata");
+
+        /// Serve a route answering with the caller's identity behind an
+        /// mTLS listener, returning its URL
+        fn serve_whoami() -> String {
+            let state = AppState {
+                agent_socket_path: PathBuf::from("/nonexistent/admin.sock"),
+                daemon: Arc::new(DaemonPool::new("/nonexistent/pandemic.sock", 1)),
+                socket_path: PathBuf::from("/nonexistent/pandemic.sock"),
+                auth_config: toml::from_str(
+                    r#"
+[mtls_identities."monitor.example.com"]
+roles = ["reader"]
+
+[roles.reader]
+scopes = ["health:read"]
+"#,
+                )
+                .unwrap(),
+                agent_status: Arc::new(Mutex::new(AgentStatus::new())),
+                audit: Arc::new(AuditLog::new("/dev/null")),
+                keepalive: Keepalive::default(),
+            };
+            let app = Router::new()
+                .route(
+                    "/whoami",
+                    get(|Extension(caller): Extension<Caller>| async move { caller.0 }),
+                )
+                .layer(from_fn_with_state(state.clone(), auth_middleware))
+                .with_state(state);
+
+            let config = pandemic_common::tls::load_mtls_server_config(
+                format!("{}/tls-cert.pem", TESTDATA),
+                format!("{}/tls-key.pem", TESTDATA),
+                format!("{}/mtls-ca.pem", TESTDATA),
+            )
+            .unwrap();
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(serve_app(listener, app, Some(Tls::Mutual(config))));
+            format!("https://localhost:{}/whoami", port)
+        }
+
+        async fn get_as(url: &str, identity: &str) -> reqwest::Result<reqwest::Response> {
+            let server_cert = std::fs::read(format!("{}/tls-cert.pem", TESTDATA)).unwrap();
+            let identity = std::fs::read(format!("{}/{}", TESTDATA, identity)).unwrap();
+            reqwest::Client::builder()
+                .add_root_certificate(reqwest::Certificate::from_pem(&server_cert).unwrap())
+                .identity(reqwest::Identity::from_pem(&identity).unwrap())
+                .build()
+                .unwrap()
+                .get(url)
+                .send()
+                .await
+        }
+
+        #[tokio::test]
+        async fn test_mapped_certificate_is_authenticated() {
+            let url = serve_whoami();
+            let response = get_as(&url, "mtls-client.pem").await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+            // Matched through the subject alternative name, not the CN
+            assert_eq!(response.text().await.unwrap(), "monitor.example.com");
+        }
+
+        #[tokio::test]
+        async fn test_untrusted_certificate_is_rejected() {
+            let url = serve_whoami();
+            assert!(get_as(&url, "mtls-untrusted.pem").await.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_unmapped_certificate_is_forbidden() {
+            let url = serve_whoami();
+            let response = get_as(&url, "mtls-unmapped.pem").await.unwrap();
+            assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+        }
+    }
+}
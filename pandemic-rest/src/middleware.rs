@@ -4,8 +4,10 @@ use axum::{
     middleware::Next,
     response::{Json, Response},
 };
+use pandemic_common::tls::PeerCertificate;
 use serde_json::json;
 
+use crate::auth::ClientCertificate;
 use crate::handlers::AppState;
 
 pub async fn auth_middleware(
@@ -14,6 +16,32 @@ pub async fn auth_middleware(
     mut request: Request,
     next: Next,
 ) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    // Clients of an mTLS listener are identified by their certificate
+    if let Some(PeerCertificate(der)) = request.extensions().get::<PeerCertificate>() {
+        let certificate = ClientCertificate::from_der(der).map_err(|e| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(json!({"status": "error", "message": e.to_string()})),
+            )
+        })?;
+        let Some((caller, scopes)) = state.auth_config.authenticate_certificate(&certificate)
+        else {
+            return Err((
+                StatusCode::FORBIDDEN,
+                Json(json!({
+                    "status": "error",
+                    "message": format!(
+                        "Client certificate for {} is not mapped to an identity",
+                        certificate.names.join(", ")
+                    )
+                })),
+            ));
+        };
+        request.extensions_mut().insert(scopes);
+        request.extensions_mut().insert(caller);
+        return Ok(next.run(request).await);
+    }
+
     // Extract API key from Authorization header
     let auth_header = headers
         .get("authorization")
@@ -32,19 +60,18 @@ pub async fn auth_middleware(
         }
     };
 
-    // Authenticate and get scopes
-    let scopes = match state.auth_config.authenticate(api_key) {
-        Some(scopes) => scopes,
-        None => {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(json!({"status": "error", "message": "Invalid API key"})),
-            ));
-        }
+    // Authenticate and get scopes. The message doesn't say why a token
+    // failed, so it can't be used to probe keys or JWT validation.
+    let Some((caller, scopes)) = state.auth_config.authenticate_bearer(api_key).await else {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(json!({"status": "error", "message": "Invalid API key or token"})),
+        ));
     };
 
-    // Add scopes to request extensions for handlers to use
+    // Add scopes and the caller to request extensions for handlers to use
     request.extensions_mut().insert(scopes);
+    request.extensions_mut().insert(caller);
 
     Ok(next.run(request).await)
 }
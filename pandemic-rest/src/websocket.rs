@@ -1,19 +1,26 @@
+use anyhow::Result;
 use axum::{
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
         Query, State,
     },
     response::{IntoResponse, Response},
+    Extension,
 };
 use futures_util::{sink::SinkExt, stream::StreamExt};
-
+use pandemic_common::tls::PeerCertificate;
+use pandemic_common::{DaemonClient, MultiplexedClient};
+use pandemic_protocol::{Request, Response as PandemicResponse};
 use serde::Deserialize;
 use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info, warn};
 
-use crate::handlers::AppState;
+use crate::auth::ClientCertificate;
+use crate::handlers::{AppState, Keepalive};
 
 #[derive(Deserialize)]
 pub struct WebSocketQuery {
@@ -25,30 +32,46 @@ pub async fn websocket_handler(
     ws: WebSocketUpgrade,
     Query(params): Query<WebSocketQuery>,
     State(state): State<AppState>,
+    certificate: Option<Extension<PeerCertificate>>,
 ) -> Response {
-    // Authenticate using token from query params
-    let api_key = match params.token {
-        Some(token) => token,
-        None => {
-            error!("WebSocket upgrade failed: missing token");
-            return axum::http::Response::builder()
-                .status(401)
-                .body(axum::body::Body::from("Missing token"))
-                .unwrap()
-                .into_response();
-        }
-    };
-
-    let scopes = match state.auth_config.authenticate(&api_key) {
-        Some(scopes) => scopes,
-        None => {
-            error!("WebSocket upgrade failed: invalid token");
-            return axum::http::Response::builder()
-                .status(401)
-                .body(axum::body::Body::from("Invalid token"))
-                .unwrap()
-                .into_response();
+    // Clients of an mTLS listener are identified by their certificate
+    let scopes = match certificate {
+        Some(Extension(PeerCertificate(der))) => {
+            let certificate = match ClientCertificate::from_der(&der) {
+                Ok(certificate) => certificate,
+                Err(e) => {
+                    error!("WebSocket upgrade failed: {}", e);
+                    return axum::http::Response::builder()
+                        .status(401)
+                        .body(axum::body::Body::from(e.to_string()))
+                        .unwrap()
+                        .into_response();
+                }
+            };
+            match state.auth_config.authenticate_certificate(&certificate) {
+                Some((_, scopes)) => scopes,
+                None => {
+                    error!("WebSocket upgrade failed: unmapped client certificate");
+                    return axum::http::Response::builder()
+                        .status(403)
+                        .body(axum::body::Body::from(
+                            "Client certificate is not mapped to an identity",
+                        ))
+                        .unwrap()
+                        .into_response();
+                }
+            }
         }
+        None => match token_scopes(&state, params.token).await {
+            Ok(scopes) => scopes,
+            Err(reason) => {
+                return axum::http::Response::builder()
+                    .status(401)
+                    .body(axum::body::Body::from(reason))
+                    .unwrap()
+                    .into_response();
+            }
+        },
     };
 
     // Check if user has events:subscribe scope
@@ -71,45 +94,126 @@ pub async fn websocket_handler(
 
     info!("WebSocket connection established with topics: {:?}", topics);
 
-    ws.on_upgrade(move |socket| handle_websocket(socket, state, topics))
+    ws.on_upgrade(move |socket| handle_websocket(socket, state, topics, scopes))
+}
+
+/// Authenticate the API key passed as the `token` query parameter, or say
+/// why it was refused
+async fn token_scopes(
+    state: &AppState,
+    token: Option<String>,
+) -> Result<Vec<String>, &'static str> {
+    let Some(api_key) = token else {
+        error!("WebSocket upgrade failed: missing token");
+        return Err("Missing token");
+    };
+    state
+        .auth_config
+        .authenticate(&api_key)
+        .await
+        .ok_or_else(|| {
+            error!("WebSocket upgrade failed: invalid token");
+            "Invalid token"
+        })
+}
+
+/// Subscription changes a client may send over an open socket
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum ClientAction {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+}
+
+/// Messages queued for a client before it counts as too slow and is closed
+const SEND_BUFFER: usize = 256;
+
+fn error_message(message: impl std::fmt::Display) -> Message {
+    Message::Text(json!({"type": "error", "message": message.to_string()}).to_string())
+}
+
+/// Register a transient plugin for this socket and subscribe it to `topics`
+async fn connect_subscriber(state: &AppState, topics: &[String]) -> Result<MultiplexedClient> {
+    let mut client = DaemonClient::connect(&state.socket_path).await?;
+    client
+        .subscribe_transient(
+            "pandemic-rest-ws",
+            "REST WebSocket event subscriber",
+            topics.to_vec(),
+        )
+        .await?;
+    Ok(client.into_multiplexed())
+}
+
+fn expect_success(response: PandemicResponse) -> Result<()> {
+    match response {
+        PandemicResponse::Success { .. } => Ok(()),
+        other => Err(anyhow::anyhow!("Daemon rejected request: {:?}", other)),
+    }
+}
+
+/// Apply a client's subscription change to the daemon and the live topic set
+async fn apply_action(
+    client: &MultiplexedClient,
+    topics: &mut Vec<String>,
+    action: ClientAction,
+) -> Result<serde_json::Value> {
+    match action {
+        ClientAction::Subscribe { topics: added } => {
+            let mut updated = topics.clone();
+            for topic in added {
+                if !updated.contains(&topic) {
+                    updated.push(topic);
+                }
+            }
+            // The daemon replaces a plugin's topics on subscribe, so send the full set
+            expect_success(
+                client
+                    .send_request_multiplexed(&Request::Subscribe {
+                        topics: updated.clone(),
+                    })
+                    .await?,
+            )?;
+            *topics = updated;
+            Ok(json!({"type": "subscribed", "topics": topics}))
+        }
+        ClientAction::Unsubscribe { topics: removed } => {
+            expect_success(
+                client
+                    .send_request_multiplexed(&Request::Unsubscribe {
+                        topics: removed.clone(),
+                    })
+                    .await?,
+            )?;
+            topics.retain(|t| !removed.contains(t));
+            Ok(json!({"type": "unsubscribed", "topics": topics}))
+        }
+    }
 }
 
-async fn handle_websocket(socket: WebSocket, state: AppState, topics: Vec<String>) {
+async fn handle_websocket(
+    socket: WebSocket,
+    state: AppState,
+    topics: Vec<String>,
+    scopes: Vec<String>,
+) {
     let (mut sender, mut receiver) = socket.split();
 
     // Create a persistent connection to the daemon
-    let mut daemon_client = match pandemic_common::DaemonClient::connect(&state.socket_path).await {
+    let daemon_client = match connect_subscriber(&state, &topics).await {
         Ok(client) => client,
         Err(e) => {
-            error!("Failed to connect to daemon: {}", e);
+            error!("Failed to subscribe to topics: {}", e);
             let _ = sender
-                .send(Message::Text(
-                    json!({
-                        "type": "error",
-                        "message": format!("Failed to connect to daemon: {}", e)
-                    })
-                    .to_string(),
-                ))
+                .send(error_message(format!(
+                    "Failed to subscribe to topics: {}",
+                    e
+                )))
                 .await;
             return;
         }
     };
 
-    // Subscribe to topics
-    if let Err(e) = daemon_client.subscribe(topics.clone()).await {
-        error!("Failed to subscribe to topics: {}", e);
-        let _ = sender
-            .send(Message::Text(
-                json!({
-                    "type": "error",
-                    "message": format!("Failed to subscribe to topics: {}", e)
-                })
-                .to_string(),
-            ))
-            .await;
-        return;
-    }
-
     info!("Subscribed to topics: {:?}", topics);
 
     // Send connection success message
@@ -124,30 +228,55 @@ async fn handle_websocket(socket: WebSocket, state: AppState, topics: Vec<String
         .await;
 
     // Create channels for handling WebSocket messages and daemon events
-    let (ws_tx, mut ws_rx) = mpsc::unbounded_channel::<Message>();
+    let (ws_tx, mut ws_rx) = mpsc::channel::<Message>(SEND_BUFFER);
+    // Only whether a pong arrived matters, so extra pongs are dropped
+    let (pong_tx, pong_rx) = mpsc::channel::<()>(1);
     let cancel_token = CancellationToken::new();
+    let overflowed = Arc::new(AtomicBool::new(false));
 
-    // Task to handle incoming WebSocket messages (for future subscription management)
+    // Task to handle incoming WebSocket messages, including subscription changes
     let ws_sender = ws_tx.clone();
     let cancel_token_clone = cancel_token.clone();
+    let client = daemon_client.clone();
+    let auth_config = state.auth_config.clone();
     let ws_receiver_task = tokio::spawn(async move {
+        let mut topics = topics;
         tokio::select! {
             _ = async {
                 while let Some(msg) = receiver.next().await {
                     match msg {
                         Ok(Message::Text(text)) => {
-                            // Handle subscription management messages
-                            if let Ok(request) = serde_json::from_str::<serde_json::Value>(&text) {
-                                info!("Received WebSocket message: {}", request);
-                                // Future: handle subscribe/unsubscribe requests
+                            let action = match serde_json::from_str::<ClientAction>(&text) {
+                                Ok(action) => action,
+                                Err(e) => {
+                                    let _ = ws_sender.send(error_message(format!("Invalid message: {}", e))).await;
+                                    continue;
+                                }
+                            };
+
+                            if !auth_config.authorize(&scopes, "events:subscribe") {
+                                let _ = ws_sender.send(error_message("Insufficient permissions")).await;
+                                continue;
                             }
+
+                            let reply = match apply_action(&client, &mut topics, action).await {
+                                Ok(reply) => Message::Text(reply.to_string()),
+                                Err(e) => {
+                                    warn!("Failed to update subscription: {}", e);
+                                    error_message(format!("Failed to update subscription: {}", e))
+                                }
+                            };
+                            let _ = ws_sender.send(reply).await;
                         }
                         Ok(Message::Close(_)) => {
                             info!("WebSocket connection closed by client");
                             break;
                         }
                         Ok(Message::Ping(data)) => {
-                            let _ = ws_sender.send(Message::Pong(data));
+                            let _ = ws_sender.send(Message::Pong(data)).await;
+                        }
+                        Ok(Message::Pong(_)) => {
+                            let _ = pong_tx.try_send(());
                         }
                         Err(e) => {
                             warn!("WebSocket error: {}", e);
@@ -169,42 +298,35 @@ async fn handle_websocket(socket: WebSocket, state: AppState, topics: Vec<String
     // Task to read events from daemon and forward to WebSocket
     let ws_sender = ws_tx.clone();
     let cancel_token_clone = cancel_token.clone();
+    let client = daemon_client.clone();
+    let overflow = overflowed.clone();
     let daemon_reader_task = tokio::spawn(async move {
         tokio::select! {
             _ = async {
                 loop {
-                    match daemon_client.read_event().await {
-                        Ok(Some(event)) => {
+                    match client.recv_event().await {
+                        Some(event) => {
                             let message = json!({
                                 "type": "event",
                                 "data": event
                             });
 
-                            if ws_sender.send(Message::Text(message.to_string())).is_err() {
-                                info!("WebSocket channel closed, stopping event forwarding");
-                                break;
+                            match ws_sender.try_send(Message::Text(message.to_string())) {
+                                Ok(()) => {}
+                                Err(mpsc::error::TrySendError::Full(_)) => {
+                                    warn!("WebSocket client fell {} messages behind, closing", SEND_BUFFER);
+                                    overflow.store(true, Ordering::Relaxed);
+                                    break;
+                                }
+                                Err(mpsc::error::TrySendError::Closed(_)) => {
+                                    info!("WebSocket channel closed, stopping event forwarding");
+                                    break;
+                                }
                             }
                         }
-                        Ok(None) => {
+                        None => {
                             info!("Daemon connection closed");
-                            let _ = ws_sender.send(Message::Text(
-                                json!({
-                                    "type": "error",
-                                    "message": "Daemon connection closed"
-                                })
-                                .to_string(),
-                            ));
-                            break;
-                        }
-                        Err(e) => {
-                            error!("Error reading event from daemon: {}", e);
-                            let _ = ws_sender.send(Message::Text(
-                                json!({
-                                    "type": "error",
-                                    "message": format!("Error reading events: {}", e)
-                                })
-                                .to_string(),
-                            ));
+                            let _ = ws_sender.send(error_message("Daemon connection closed")).await;
                             break;
                         }
                     }
@@ -219,14 +341,26 @@ async fn handle_websocket(socket: WebSocket, state: AppState, topics: Vec<String
         cancel_token_clone.cancel();
     });
 
+    // Task to ping the client and give up on it when a pong doesn't come back
+    let keepalive_task = tokio::spawn(keep_alive(
+        state.keepalive,
+        ws_tx.clone(),
+        pong_rx,
+        cancel_token.clone(),
+    ));
+
     // Main loop to send messages to WebSocket client
     tokio::select! {
         _ = async {
             while let Some(message) = ws_rx.recv().await {
+                let closing = matches!(message, Message::Close(_));
                 if sender.send(message).await.is_err() {
                     info!("WebSocket connection closed");
                     break;
                 }
+                if closing {
+                    break;
+                }
             }
         } => {
             info!("WebSocket sender finished");
@@ -239,9 +373,353 @@ async fn handle_websocket(socket: WebSocket, state: AppState, topics: Vec<String
     // Signal all tasks to stop
     cancel_token.cancel();
 
+    if overflowed.load(Ordering::Relaxed) {
+        let close = sender.send(Message::Close(Some(CloseFrame {
+            code: close_code::AGAIN,
+            reason: "Too far behind on events".into(),
+        })));
+        // A client this slow may not take the close frame either
+        let _ = tokio::time::timeout(state.keepalive.timeout, close).await;
+    }
+
     // Wait for tasks to finish
-    let _ = tokio::join!(ws_receiver_task, daemon_reader_task);
+    let _ = tokio::join!(ws_receiver_task, daemon_reader_task, keepalive_task);
 
-    // The daemon_client will be dropped here, which should close the connection
+    // Closing our side lets the daemon drop the connection and its transient plugin
+    if let Err(e) = daemon_client.close().await {
+        warn!("Failed to close daemon connection: {}", e);
+    }
     info!("WebSocket handler finished, daemon connection cleaned up");
 }
+
+/// Ping the client every `keepalive.interval` and close the socket once a
+/// ping goes unanswered for `keepalive.timeout`
+async fn keep_alive(
+    keepalive: Keepalive,
+    ws_sender: mpsc::Sender<Message>,
+    mut pong_rx: mpsc::Receiver<()>,
+    cancel_token: CancellationToken,
+) {
+    tokio::select! {
+        _ = async {
+            loop {
+                tokio::time::sleep(keepalive.interval).await;
+                // Only a pong to this ping counts
+                while pong_rx.try_recv().is_ok() {}
+                if ws_sender.send(Message::Ping(Vec::new())).await.is_err() {
+                    return;
+                }
+                match tokio::time::timeout(keepalive.timeout, pong_rx.recv()).await {
+                    Ok(Some(())) => continue,
+                    Ok(None) => return,
+                    Err(_) => break,
+                }
+            }
+
+            warn!("WebSocket client missed a pong for {:?}, closing", keepalive.timeout);
+            let _ = ws_sender.send(Message::Close(Some(CloseFrame {
+                code: close_code::AWAY,
+                reason: "Ping timeout".into(),
+            }))).await;
+            // A dead client may never take the close frame
+            tokio::time::sleep(keepalive.timeout).await;
+        } => {
+            info!("WebSocket keepalive task finished");
+        }
+        _ = cancel_token.cancelled() => {}
+    }
+    cancel_token.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditLog;
+    use crate::auth::AuthConfig;
+    use axum::{routing::get, Router};
+    use futures_util::stream::SplitStream;
+    use pandemic_common::{AgentStatus, DaemonPool};
+    use pandemic_protocol::{Event, RequestEnvelope, ResponseEnvelope, PROTOCOL_VERSION};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpStream, UnixListener};
+    use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
+
+    type ClientStream = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+
+    /// Tracks one plugin's subscriptions like the daemon does and emits an
+    /// event for each topic and data sent on `publish_rx` whose topic is
+    /// currently subscribed
+    async fn mock_daemon(
+        listener: UnixListener,
+        mut publish_rx: mpsc::UnboundedReceiver<(String, serde_json::Value)>,
+    ) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut subscribed: Vec<String> = Vec::new();
+        let mut line = String::new();
+
+        loop {
+            let outgoing = tokio::select! {
+                read = reader.read_line(&mut line) => {
+                    if read.unwrap() == 0 {
+                        break;
+                    }
+                    let envelope: RequestEnvelope = serde_json::from_str(line.trim()).unwrap();
+                    line.clear();
+                    let response = match envelope.request {
+                        Request::Hello { .. } => PandemicResponse::success_with_data(
+                            json!({ "protocol_version": PROTOCOL_VERSION }),
+                        ),
+                        Request::Subscribe { topics } => {
+                            subscribed = topics;
+                            PandemicResponse::success()
+                        }
+                        Request::Unsubscribe { topics } => {
+                            subscribed.retain(|t| !topics.contains(t));
+                            PandemicResponse::success()
+                        }
+                        _ => PandemicResponse::success(),
+                    };
+                    serde_json::to_vec(&ResponseEnvelope { id: envelope.id, response }).unwrap()
+                }
+                Some((topic, data)) = publish_rx.recv() => {
+                    if !subscribed.contains(&topic) {
+                        continue;
+                    }
+                    let event = pandemic_protocol::Message::Event(Event {
+                        topic,
+                        source: "test".to_string(),
+                        data,
+                        timestamp: None,
+                    });
+                    serde_json::to_vec(&event).unwrap()
+                }
+            };
+            reader.get_mut().write_all(&outgoing).await.unwrap();
+            reader.get_mut().write_all(b"\n").await.unwrap();
+        }
+    }
+
+    async fn next_json(stream: &mut ClientStream) -> serde_json::Value {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), stream.next())
+                .await
+                .expect("timed out waiting for websocket message")
+                .unwrap()
+                .unwrap();
+            if let tungstenite::Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    struct TestServer {
+        addr: std::net::SocketAddr,
+        publish_tx: mpsc::UnboundedSender<(String, serde_json::Value)>,
+        /// Finishes when the socket's daemon connection is closed
+        daemon: tokio::task::JoinHandle<()>,
+        _temp_dir: tempfile::TempDir,
+    }
+
+    async fn serve(keepalive: Keepalive) -> TestServer {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let (publish_tx, publish_rx) = mpsc::unbounded_channel();
+        let daemon = tokio::spawn(mock_daemon(
+            UnixListener::bind(&socket_path).unwrap(),
+            publish_rx,
+        ));
+
+        let auth_config: AuthConfig = toml::from_str(
+            r#"
+[identities.watcher]
+api_key = "watcher-key"
+roles = ["watcher"]
+
+[roles.watcher]
+scopes = ["events:subscribe"]
+"#,
+        )
+        .unwrap();
+        let state = AppState {
+            daemon: Arc::new(DaemonPool::new(&socket_path, 1)),
+            socket_path,
+            agent_socket_path: temp_dir.path().join("admin.sock"),
+            auth_config,
+            agent_status: Arc::new(Mutex::new(AgentStatus::new())),
+            audit: Arc::new(AuditLog::new("/dev/null")),
+            keepalive,
+        };
+        let app = Router::new()
+            .route("/api/events/stream", get(websocket_handler))
+            .with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        TestServer {
+            addr,
+            publish_tx,
+            daemon,
+            _temp_dir: temp_dir,
+        }
+    }
+
+    async fn connect(addr: std::net::SocketAddr) -> WebSocketStream<MaybeTlsStream<TcpStream>> {
+        let (socket, _) = connect_async(format!(
+            "ws://{}/api/events/stream?token=watcher-key&topics=alpha",
+            addr
+        ))
+        .await
+        .unwrap();
+        socket
+    }
+
+    #[tokio::test]
+    async fn test_unresponsive_client_is_disconnected() {
+        let server = serve(Keepalive {
+            interval: Duration::from_millis(100),
+            timeout: Duration::from_millis(100),
+        })
+        .await;
+        let (_sink, mut stream) = connect(server.addr).await.split();
+        assert_eq!(next_json(&mut stream).await["type"], "connected");
+
+        // Not reading the socket means the client never answers the ping,
+        // and the server gives up on it and its daemon connection
+        tokio::time::timeout(Duration::from_secs(5), server.daemon)
+            .await
+            .expect("daemon connection was not closed")
+            .unwrap();
+
+        let mut closed = false;
+        while let Ok(Some(message)) =
+            tokio::time::timeout(Duration::from_secs(5), stream.next()).await
+        {
+            match message {
+                Ok(tungstenite::Message::Close(frame)) => {
+                    assert_eq!(frame.unwrap().reason, "Ping timeout");
+                    closed = true;
+                }
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        assert!(closed, "server never sent a close frame");
+    }
+
+    #[tokio::test]
+    async fn test_responsive_client_stays_connected() {
+        let server = serve(Keepalive {
+            interval: Duration::from_millis(50),
+            timeout: Duration::from_millis(200),
+        })
+        .await;
+        let (_sink, mut stream) = connect(server.addr).await.split();
+        assert_eq!(next_json(&mut stream).await["type"], "connected");
+
+        // Reading answers each ping, so several intervals later the socket
+        // still delivers events
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(500);
+        while let Ok(message) = tokio::time::timeout_at(deadline, stream.next()).await {
+            assert!(
+                matches!(message, Some(Ok(tungstenite::Message::Ping(_)))),
+                "{:?}",
+                message
+            );
+        }
+        server
+            .publish_tx
+            .send(("alpha".to_string(), json!({})))
+            .unwrap();
+        assert_eq!(next_json(&mut stream).await["data"]["topic"], "alpha");
+        assert!(!server.daemon.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_subscribe_and_unsubscribe() {
+        let server = serve(Keepalive::default()).await;
+        let publish_tx = server.publish_tx.clone();
+        let socket = connect(server.addr).await;
+        let (mut sink, mut stream) = socket.split();
+        assert_eq!(next_json(&mut stream).await["type"], "connected");
+
+        publish_tx.send(("alpha".to_string(), json!({}))).unwrap();
+        assert_eq!(next_json(&mut stream).await["data"]["topic"], "alpha");
+
+        sink.send(tungstenite::Message::Text(
+            json!({"action": "subscribe", "topics": ["beta"]}).to_string(),
+        ))
+        .await
+        .unwrap();
+        let ack = next_json(&mut stream).await;
+        assert_eq!(ack["type"], "subscribed");
+        assert_eq!(ack["topics"], json!(["alpha", "beta"]));
+
+        publish_tx.send(("beta".to_string(), json!({}))).unwrap();
+        assert_eq!(next_json(&mut stream).await["data"]["topic"], "beta");
+
+        sink.send(tungstenite::Message::Text(
+            json!({"action": "unsubscribe", "topics": ["alpha"]}).to_string(),
+        ))
+        .await
+        .unwrap();
+        let ack = next_json(&mut stream).await;
+        assert_eq!(ack["type"], "unsubscribed");
+        assert_eq!(ack["topics"], json!(["beta"]));
+
+        // alpha is no longer delivered, so the next event seen is beta
+        publish_tx.send(("alpha".to_string(), json!({}))).unwrap();
+        publish_tx.send(("beta".to_string(), json!({}))).unwrap();
+        assert_eq!(next_json(&mut stream).await["data"]["topic"], "beta");
+
+        sink.send(tungstenite::Message::Text(
+            json!({"action": "shout"}).to_string(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(next_json(&mut stream).await["type"], "error");
+    }
+
+    #[tokio::test]
+    async fn test_client_too_far_behind_is_closed() {
+        let server = serve(Keepalive {
+            interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(1),
+        })
+        .await;
+        let (_sink, mut stream) = connect(server.addr).await.split();
+        assert_eq!(next_json(&mut stream).await["type"], "connected");
+
+        // Far more than the send queue and socket buffers hold while the
+        // client isn't reading
+        let padding = "x".repeat(64 * 1024);
+        for _ in 0..1024 {
+            server
+                .publish_tx
+                .send(("alpha".to_string(), json!({ "padding": padding })))
+                .unwrap();
+        }
+
+        // Long before a keepalive ping, the server gives up on the client
+        // and its daemon connection
+        tokio::time::timeout(Duration::from_secs(30), server.daemon)
+            .await
+            .expect("daemon connection was not closed")
+            .unwrap();
+
+        let mut events = 0;
+        while let Ok(Some(Ok(message))) =
+            tokio::time::timeout(Duration::from_secs(5), stream.next()).await
+        {
+            if let tungstenite::Message::Close(Some(frame)) = message {
+                assert_eq!(frame.reason, "Too far behind on events");
+            } else if message.is_text() {
+                events += 1;
+            }
+        }
+        assert!(events < 1024, "{} events", events);
+    }
+}
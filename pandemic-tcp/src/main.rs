@@ -0,0 +1,343 @@
+use anyhow::Result;
+use clap::Parser;
+use futures_util::StreamExt;
+use pandemic_common::{DaemonClient, PersistentClient};
+use pandemic_protocol::{FrameError, Framing, PluginInfo, Request, Response, MAX_FRAME_LEN};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+#[derive(Parser)]
+#[command(name = "pandemic-tcp")]
+#[command(about = "TCP proxy for pandemic daemon")]
+struct Args {
+    #[arg(long, default_value = "/var/run/pandemic/pandemic.sock")]
+    socket_path: PathBuf,
+
+    #[arg(long, default_value = "0.0.0.0:8081")]
+    bind_addr: SocketAddr,
+}
+
+async fn create_persistent_client(
+    socket_path: &PathBuf,
+    bind_addr: &SocketAddr,
+) -> Result<PersistentClient> {
+    let mut config = HashMap::new();
+    config.insert("bind_address".to_string(), bind_addr.to_string());
+    config.insert("protocol".to_string(), "TCP".to_string());
+
+    let plugin = PluginInfo {
+        name: "pandemic-tcp".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        description: Some("TCP proxy for pandemic daemon".to_string()),
+        config: Some(config),
+        registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
+    };
+
+    let mut client = DaemonClient::connect(socket_path).await?;
+    let request = Request::Register {
+        plugin,
+        force: false,
+        ignore_dependencies: false,
+    };
+    let response = client.send_request(&request).await?;
+    info!("Registration response: {:?}", response);
+
+    // Subscribe to plugin deregister events
+    client
+        .subscribe(vec!["plugin.deregistered".to_string()])
+        .await?;
+
+    Ok(client)
+}
+
+/// Read one length-prefixed frame, or `None` once the peer hangs up
+async fn read_frame(stream: &mut TcpStream) -> Result<Option<Vec<u8>>> {
+    let len = match stream.read_u32().await {
+        Ok(len) => len as usize,
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_FRAME_LEN {
+        return Err(FrameError::TooLarge(len).into());
+    }
+    // Grow the buffer as the body arrives rather than trusting the length
+    // up front, so idle peers announcing big frames cost nothing
+    let mut body = Vec::new();
+    (&mut *stream)
+        .take(len as u64)
+        .read_to_end(&mut body)
+        .await?;
+    if body.len() < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(Some(body))
+}
+
+async fn proxy_request(client: &mut PersistentClient, request_data: &[u8]) -> Result<Response> {
+    let request: Request = serde_json::from_slice(request_data)?;
+    client.send_request(&request).await
+}
+
+/// Bridge one TCP client to its own daemon connection until either side closes
+async fn handle_connection(mut stream: TcpStream, socket_path: Arc<PathBuf>) -> Result<()> {
+    let mut client = DaemonClient::connect(socket_path.as_path()).await?;
+
+    while let Some(request_data) = read_frame(&mut stream).await? {
+        let response = match proxy_request(&mut client, &request_data).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Proxy request failed: {}", e);
+                Response::error(format!("Proxy error: {}", e))
+            }
+        };
+        let body = serde_json::to_vec(&response)?;
+        stream
+            .write_all(&Framing::LengthPrefixed.encode(&body))
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn run_tcp_server(
+    listener: TcpListener,
+    socket_path: &Path,
+    mut shutdown_rx: mpsc::Receiver<()>,
+) -> Result<()> {
+    let socket_path = Arc::new(socket_path.to_path_buf());
+
+    loop {
+        tokio::select! {
+            // Each TCP client is served by its own task and daemon connection
+            result = listener.accept() => {
+                match result {
+                    Ok((stream, addr)) => {
+                        let socket_path = Arc::clone(&socket_path);
+                        tokio::spawn(async move {
+                            if let Err(e) = handle_connection(stream, socket_path).await {
+                                warn!("TCP connection from {} failed: {}", addr, e);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("TCP accept error: {}", e);
+                    }
+                }
+            }
+            // Handle shutdown signal
+            _ = shutdown_rx.recv() => {
+                info!("Received shutdown signal, stopping TCP server");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    pandemic_common::init_logging();
+    let args = Args::parse();
+
+    // Create persistent connection and register
+    let client = create_persistent_client(&args.socket_path, &args.bind_addr).await?;
+
+    info!("TCP proxy registered and maintaining connection to daemon");
+
+    // Create shutdown channel
+    let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+
+    // Spawn task to monitor for deregister events
+    tokio::spawn(async move {
+        info!("Monitoring for deregister events");
+        let mut events = pin!(client.event_stream());
+        while let Some(event) = events.next().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    error!("Error reading event: {:?}", e);
+                    let _ = shutdown_tx.send(()).await;
+                    return;
+                }
+            };
+            info!("Received event: {}", event.topic);
+            if event.topic == "plugin.deregistered"
+                && event.data.get("name").and_then(|v| v.as_str()) == Some("pandemic-tcp")
+            {
+                info!("Received deregister event for pandemic-tcp, initiating shutdown");
+                let _ = shutdown_tx.send(()).await;
+                return;
+            }
+        }
+        info!("Connection closed, shutting down");
+        let _ = shutdown_tx.send(()).await;
+    });
+
+    let listener = TcpListener::bind(args.bind_addr).await?;
+    info!("TCP proxy listening on {}", args.bind_addr);
+    run_tcp_server(listener, &args.socket_path, shutdown_rx).await?;
+
+    info!("TCP proxy shutdown complete");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::RequestEnvelope;
+    use serde_json::json;
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::net::UnixListener;
+
+    /// Keeps a separate plugin list per daemon connection, so responses show
+    /// which connection a request went through
+    async fn mock_daemon(listener: UnixListener) {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (read_half, mut write_half) = stream.into_split();
+                let mut lines = BufReader::new(read_half).lines();
+                let mut plugins = Vec::new();
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let envelope: RequestEnvelope = serde_json::from_str(&line).unwrap();
+                    let response = match envelope.request {
+                        Request::Register { plugin, .. } => {
+                            plugins.push(plugin.name);
+                            Response::success()
+                        }
+                        Request::ListPlugins => Response::success_with_data(json!(plugins)),
+                        _ => Response::success(),
+                    };
+                    let mut reply = serde_json::to_vec(&response).unwrap();
+                    reply.push(b'\n');
+                    write_half.write_all(&reply).await.unwrap();
+                }
+            });
+        }
+    }
+
+    async fn roundtrip(stream: &mut TcpStream, request: &Request) -> Response {
+        let body = serde_json::to_vec(request).unwrap();
+        stream
+            .write_all(&Framing::LengthPrefixed.encode(&body))
+            .await
+            .unwrap();
+        let body = tokio::time::timeout(Duration::from_secs(5), read_frame(stream))
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn plugin(name: &str) -> PluginInfo {
+        PluginInfo {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            description: None,
+            config: None,
+            registered_at: None,
+            heartbeat_interval: None,
+            dependencies: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_clients_are_isolated() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        tokio::spawn(mock_daemon(UnixListener::bind(&socket_path).unwrap()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            run_tcp_server(listener, &socket_path, shutdown_rx).await
+        });
+
+        let mut handles = Vec::new();
+        for name in ["alpha", "beta"] {
+            handles.push(tokio::spawn(async move {
+                let mut stream = TcpStream::connect(server_addr).await.unwrap();
+                let register = Request::Register {
+                    plugin: plugin(name),
+                    force: false,
+                    ignore_dependencies: false,
+                };
+                assert!(matches!(
+                    roundtrip(&mut stream, &register).await,
+                    Response::Success { .. }
+                ));
+                roundtrip(&mut stream, &Request::ListPlugins).await
+            }));
+        }
+
+        for (name, handle) in ["alpha", "beta"].into_iter().zip(handles) {
+            match handle.await.unwrap() {
+                Response::Success { data } => assert_eq!(data, Some(json!([name]))),
+                other => panic!("unexpected response: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_request_gets_error_response() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        tokio::spawn(mock_daemon(UnixListener::bind(&socket_path).unwrap()));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            run_tcp_server(listener, &socket_path, shutdown_rx).await
+        });
+
+        let mut stream = TcpStream::connect(server_addr).await.unwrap();
+        stream
+            .write_all(&Framing::LengthPrefixed.encode(b"not json"))
+            .await
+            .unwrap();
+        let body = read_frame(&mut stream).await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_slice(&body).unwrap(),
+            Response::Error { .. }
+        ));
+
+        // The connection stays usable after a bad request
+        assert!(matches!(
+            roundtrip(&mut stream, &Request::ListPlugins).await,
+            Response::Success { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // Announce a large frame, then hang up after a few bytes
+            stream.write_u32(MAX_FRAME_LEN as u32).await.unwrap();
+            stream.write_all(b"{}").await.unwrap();
+        });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let error = read_frame(&mut stream).await.unwrap_err();
+        let error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,240 @@
+//! Application-level chunking for messages that don't fit in one datagram.
+//!
+//! A chunked message is split into datagrams that each start with a 6-byte
+//! header: the magic bytes `PC`, then the big-endian `u16` sequence number
+//! (starting at 0) and the big-endian `u16` total chunk count. The payloads
+//! concatenated in sequence order form the JSON message.
+//!
+//! Messages that fit in a single datagram are sent without a header, so plain
+//! JSON datagrams (which always start with `{`) keep working unchanged.
+
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub const MAGIC: &[u8; 2] = b"PC";
+pub const HEADER_LEN: usize = 6;
+
+/// Largest message a sender may split into chunks
+pub const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+/// How long a partial message may wait for its remaining chunks
+pub const PARTIAL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Senders that may have a partial message pending at once
+pub const MAX_PARTIALS: usize = 1024;
+
+/// Split `message` into datagrams no larger than `max_datagram` bytes
+pub fn split(message: &[u8], max_datagram: usize) -> Result<Vec<Vec<u8>>> {
+    if message.len() <= max_datagram {
+        return Ok(vec![message.to_vec()]);
+    }
+    if max_datagram <= HEADER_LEN {
+        bail!(
+            "max datagram of {} bytes leaves no room for payload",
+            max_datagram
+        );
+    }
+
+    let chunks: Vec<&[u8]> = message.chunks(max_datagram - HEADER_LEN).collect();
+    let total = u16::try_from(chunks.len())
+        .map_err(|_| anyhow::anyhow!("message of {} bytes needs too many chunks", message.len()))?;
+
+    Ok(chunks
+        .into_iter()
+        .enumerate()
+        .map(|(seq, payload)| {
+            let mut datagram = Vec::with_capacity(HEADER_LEN + payload.len());
+            datagram.extend_from_slice(MAGIC);
+            datagram.extend_from_slice(&(seq as u16).to_be_bytes());
+            datagram.extend_from_slice(&total.to_be_bytes());
+            datagram.extend_from_slice(payload);
+            datagram
+        })
+        .collect())
+}
+
+/// Parse a chunk header, returning `(seq, total, payload)`
+pub fn parse(datagram: &[u8]) -> Option<(u16, u16, &[u8])> {
+    if datagram.len() < HEADER_LEN || &datagram[..2] != MAGIC {
+        return None;
+    }
+    let seq = u16::from_be_bytes([datagram[2], datagram[3]]);
+    let total = u16::from_be_bytes([datagram[4], datagram[5]]);
+    if total == 0 || seq >= total {
+        return None;
+    }
+    Some((seq, total, &datagram[HEADER_LEN..]))
+}
+
+struct Partial {
+    total: u16,
+    chunks: Vec<Option<Vec<u8>>>,
+    first_seen: Instant,
+}
+
+impl Partial {
+    fn new(total: u16, now: Instant) -> Self {
+        Self {
+            total,
+            chunks: vec![None; total as usize],
+            first_seen: now,
+        }
+    }
+}
+
+/// Collects chunked datagrams per sender until a message is complete.
+///
+/// Headers come from untrusted, possibly spoofed senders, so a chunk count
+/// that couldn't fit in `MAX_MESSAGE_LEN` is dropped, at most `MAX_PARTIALS`
+/// senders are tracked, and partials older than `PARTIAL_TIMEOUT` are
+/// forgotten.
+pub struct Reassembler {
+    partials: HashMap<SocketAddr, Partial>,
+    max_chunks: usize,
+}
+
+impl Reassembler {
+    /// Reassemble chunks of datagrams up to `max_datagram` bytes
+    pub fn new(max_datagram: usize) -> Self {
+        let payload_len = max_datagram.saturating_sub(HEADER_LEN).max(1);
+        Self {
+            partials: HashMap::new(),
+            max_chunks: MAX_MESSAGE_LEN.div_ceil(payload_len),
+        }
+    }
+
+    /// Feed a datagram from `addr`, returning the full message once available.
+    /// Unchunked datagrams are returned as-is.
+    pub fn push(&mut self, addr: SocketAddr, datagram: &[u8]) -> Option<Vec<u8>> {
+        self.push_at(addr, datagram, Instant::now())
+    }
+
+    fn push_at(&mut self, addr: SocketAddr, datagram: &[u8], now: Instant) -> Option<Vec<u8>> {
+        let Some((seq, total, payload)) = parse(datagram) else {
+            return Some(datagram.to_vec());
+        };
+        if total as usize > self.max_chunks {
+            warn!("Dropping chunk from {}: {} chunks is too many", addr, total);
+            return None;
+        }
+
+        self.partials
+            .retain(|_, partial| now.duration_since(partial.first_seen) < PARTIAL_TIMEOUT);
+        if !self.partials.contains_key(&addr) && self.partials.len() >= MAX_PARTIALS {
+            warn!("Dropping chunk from {}: too many partial messages", addr);
+            return None;
+        }
+
+        let partial = self
+            .partials
+            .entry(addr)
+            .or_insert_with(|| Partial::new(total, now));
+        // A differing total means the sender started over with a new message
+        if partial.total != total {
+            *partial = Partial::new(total, now);
+        }
+        partial.chunks[seq as usize] = Some(payload.to_vec());
+
+        if partial.chunks.iter().any(Option::is_none) {
+            return None;
+        }
+        let partial = self.partials.remove(&addr)?;
+        Some(partial.chunks.into_iter().flatten().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_message_is_not_chunked() {
+        let datagrams = split(b"{\"type\":\"ListPlugins\"}", 64).unwrap();
+        assert_eq!(datagrams, vec![b"{\"type\":\"ListPlugins\"}".to_vec()]);
+    }
+
+    #[test]
+    fn test_split_and_reassemble_out_of_order() {
+        let message: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let mut datagrams = split(&message, 100).unwrap();
+        assert_eq!(datagrams.len(), 11);
+        assert!(datagrams.iter().all(|d| d.len() <= 100));
+
+        datagrams.reverse();
+        let addr: SocketAddr = "127.0.0.1:9000".parse().unwrap();
+        let mut reassembler = Reassembler::new(100);
+        let (last, rest) = datagrams.split_last().unwrap();
+        for datagram in rest {
+            assert!(reassembler.push(addr, datagram).is_none());
+        }
+        assert_eq!(reassembler.push(addr, last), Some(message));
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_stale_partials_are_evicted() {
+        let datagrams = split(&[7u8; 300], 100).unwrap();
+        let mut reassembler = Reassembler::new(100);
+        let start = Instant::now();
+        assert!(reassembler.push_at(addr(1), &datagrams[0], start).is_none());
+
+        // The rest arrive too late, so the first chunk is gone and the
+        // message never completes
+        let late = start + PARTIAL_TIMEOUT;
+        for datagram in &datagrams[1..] {
+            assert!(reassembler.push_at(addr(1), datagram, late).is_none());
+        }
+        assert_eq!(reassembler.partials.len(), 1);
+        assert!(reassembler.partials[&addr(1)].chunks[0].is_none());
+    }
+
+    #[test]
+    fn test_pending_partials_are_capped() {
+        let datagrams = split(&[7u8; 300], 100).unwrap();
+        let mut reassembler = Reassembler::new(100);
+        let now = Instant::now();
+        for port in 0..MAX_PARTIALS as u16 {
+            assert!(reassembler
+                .push_at(addr(port), &datagrams[0], now)
+                .is_none());
+        }
+        let newcomer = addr(MAX_PARTIALS as u16);
+        for datagram in &datagrams {
+            assert!(reassembler.push_at(newcomer, datagram, now).is_none());
+        }
+        assert_eq!(reassembler.partials.len(), MAX_PARTIALS);
+
+        // A sender already being tracked can still finish
+        for datagram in &datagrams[1..datagrams.len() - 1] {
+            assert!(reassembler.push_at(addr(0), datagram, now).is_none());
+        }
+        assert_eq!(
+            reassembler.push_at(addr(0), datagrams.last().unwrap(), now),
+            Some(vec![7u8; 300])
+        );
+    }
+
+    #[test]
+    fn test_oversized_chunk_count_is_rejected() {
+        let mut reassembler = Reassembler::new(100);
+        let total = u16::try_from(MAX_MESSAGE_LEN.div_ceil(100 - HEADER_LEN) + 1).unwrap();
+        let mut datagram = MAGIC.to_vec();
+        datagram.extend_from_slice(&0u16.to_be_bytes());
+        datagram.extend_from_slice(&total.to_be_bytes());
+        datagram.extend_from_slice(b"payload");
+
+        assert!(reassembler.push(addr(1), &datagram).is_none());
+        assert!(reassembler.partials.is_empty());
+    }
+
+    #[test]
+    fn test_split_rejects_tiny_datagrams() {
+        assert!(split(&[0u8; 32], HEADER_LEN).is_err());
+    }
+}
@@ -1,13 +1,17 @@
+mod chunking;
+
 use anyhow::Result;
+use chunking::Reassembler;
 use clap::Parser;
-use pandemic_common::{DaemonClient, PersistentClient};
+use pandemic_common::{DaemonClient, MultiplexedClient};
 use pandemic_protocol::{PluginInfo, Request, Response};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio::net::UdpSocket;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, Semaphore};
 use tracing::{error, info, warn};
 
 #[derive(Parser)]
@@ -19,12 +23,54 @@ struct Args {
 
     #[arg(long, default_value = "0.0.0.0:8080")]
     bind_addr: SocketAddr,
+
+    /// Number of daemon connections shared by in-flight UDP requests
+    #[arg(long, default_value = "4")]
+    pool_size: usize,
+
+    /// Largest datagram to receive or send; bigger messages are chunked
+    #[arg(long, default_value = "65507")]
+    max_datagram: usize,
+
+    /// Requests proxied at once; datagrams arriving beyond this are dropped.
+    /// Defaults to 64 per pooled connection.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    max_inflight: Option<u64>,
+}
+
+/// In-flight requests allowed per pooled daemon connection by default
+const INFLIGHT_PER_CONNECTION: usize = 64;
+
+/// Daemon connections handed out round-robin. Each connection is multiplexed,
+/// so requests only wait on the daemon, never on each other.
+struct ClientPool {
+    clients: Vec<MultiplexedClient>,
+    next: AtomicUsize,
+}
+
+impl ClientPool {
+    /// Build a pool around `primary`, opening extra connections up to `size`
+    async fn connect(primary: MultiplexedClient, socket_path: &Path, size: usize) -> Result<Self> {
+        let mut clients = vec![primary];
+        while clients.len() < size {
+            clients.push(DaemonClient::connect(socket_path).await?.into_multiplexed());
+        }
+        Ok(Self {
+            clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn get(&self) -> &MultiplexedClient {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        &self.clients[index]
+    }
 }
 
 async fn create_persistent_client(
     socket_path: &PathBuf,
     bind_addr: &SocketAddr,
-) -> Result<PersistentClient> {
+) -> Result<MultiplexedClient> {
     let mut config = HashMap::new();
     config.insert("bind_address".to_string(), bind_addr.to_string());
     config.insert("protocol".to_string(), "UDP".to_string());
@@ -35,10 +81,16 @@ async fn create_persistent_client(
         description: Some("UDP proxy for pandemic daemon".to_string()),
         config: Some(config),
         registered_at: None,
+        heartbeat_interval: None,
+        dependencies: vec![],
     };
 
     let mut client = DaemonClient::connect(socket_path).await?;
-    let request = Request::Register { plugin };
+    let request = Request::Register {
+        plugin,
+        force: false,
+        ignore_dependencies: false,
+    };
     let response = client.send_request(&request).await?;
     info!("Registration response: {:?}", response);
 
@@ -47,54 +99,75 @@ async fn create_persistent_client(
         .subscribe(vec!["plugin.deregistered".to_string()])
         .await?;
 
-    Ok(client)
+    Ok(client.into_multiplexed())
 }
 
-async fn proxy_request(
-    client: &Arc<Mutex<PersistentClient>>,
-    request_data: &[u8],
-) -> Result<Vec<u8>> {
+async fn proxy_request(client: &MultiplexedClient, request_data: &[u8]) -> Result<Vec<u8>> {
     let request: Request = serde_json::from_slice(request_data)?;
-    let response = {
-        let mut client_guard = client.lock().await;
-        client_guard.send_request(&request).await?
-    };
+    let response = client.send_request_multiplexed(&request).await?;
     let response_json = serde_json::to_string(&response)?;
     Ok(response_json.into_bytes())
 }
 
 async fn run_udp_server(
-    client: Arc<Mutex<PersistentClient>>,
-    bind_addr: SocketAddr,
+    pool: Arc<ClientPool>,
+    udp_socket: UdpSocket,
+    max_datagram: usize,
+    max_inflight: usize,
     mut shutdown_rx: mpsc::Receiver<()>,
 ) -> Result<()> {
-    let udp_socket = UdpSocket::bind(bind_addr).await?;
-    info!("UDP proxy listening on {}", bind_addr);
-
-    let mut buf = vec![0u8; 4096];
+    let udp_socket = Arc::new(udp_socket);
+    let inflight = Arc::new(Semaphore::new(max_inflight));
+    let mut buf = vec![0u8; max_datagram];
+    let mut reassembler = Reassembler::new(max_datagram);
 
     loop {
         tokio::select! {
-            // Handle UDP requests
+            // Handle UDP requests, each in its own task so slow ones don't block the rest
             result = udp_socket.recv_from(&mut buf) => {
                 match result {
                     Ok((len, addr)) => {
-                        let request_data = &buf[..len];
+                        let Some(request_data) = reassembler.push(addr, &buf[..len]) else {
+                            continue;
+                        };
+                        // Sources are spoofable, so shed load silently instead
+                        // of replying to whoever the datagram claims to be
+                        let Ok(permit) = Arc::clone(&inflight).try_acquire_owned() else {
+                            warn!("Dropping request from {}: {} requests in flight", addr, max_inflight);
+                            continue;
+                        };
+                        let pool = Arc::clone(&pool);
+                        let udp_socket = Arc::clone(&udp_socket);
 
-                        match proxy_request(&client, request_data).await {
-                            Ok(response) => {
-                                if let Err(e) = udp_socket.send_to(&response, addr).await {
-                                    error!("Failed to send UDP response to {}: {}", addr, e);
+                        tokio::spawn(async move {
+                            let _permit = permit;
+                            let response = match proxy_request(pool.get(), &request_data).await {
+                                Ok(response) => response,
+                                Err(e) => {
+                                    warn!("Proxy request failed: {}", e);
+                                    match serde_json::to_vec(&Response::error(format!("Proxy error: {}", e))) {
+                                        Ok(response) => response,
+                                        Err(e) => {
+                                            error!("Failed to encode error response: {}", e);
+                                            return;
+                                        }
+                                    }
                                 }
-                            }
-                            Err(e) => {
-                                warn!("Proxy request failed: {}", e);
-                                let error_response = serde_json::to_string(&Response::error(format!("Proxy error: {}", e)))?;
-                                if let Err(e) = udp_socket.send_to(error_response.as_bytes(), addr).await {
-                                    error!("Failed to send error response to {}: {}", addr, e);
+                            };
+                            let datagrams = match chunking::split(&response, max_datagram) {
+                                Ok(datagrams) => datagrams,
+                                Err(e) => {
+                                    error!("Failed to chunk UDP response to {}: {}", addr, e);
+                                    return;
+                                }
+                            };
+                            for datagram in datagrams {
+                                if let Err(e) = udp_socket.send_to(&datagram, addr).await {
+                                    error!("Failed to send UDP response to {}: {}", addr, e);
+                                    return;
                                 }
                             }
-                        }
+                        });
                     }
                     Err(e) => {
                         error!("UDP receive error: {}", e);
@@ -114,30 +187,27 @@ async fn run_udp_server(
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    pandemic_common::init_logging();
     let args = Args::parse();
 
     // Create persistent connection and register
     let client = create_persistent_client(&args.socket_path, &args.bind_addr).await?;
-    let client = Arc::new(Mutex::new(client));
+    let pool = ClientPool::connect(client.clone(), &args.socket_path, args.pool_size).await?;
 
-    info!("UDP proxy registered and maintaining connection to daemon");
+    info!(
+        "UDP proxy registered and maintaining {} connection(s) to daemon",
+        pool.clients.len()
+    );
 
     // Create shutdown channel
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
 
     // Spawn task to monitor for deregister events
-    let client_clone = Arc::clone(&client);
     tokio::spawn(async move {
         info!("Monitoring for deregister events");
         loop {
-            let event_result = {
-                let mut client_guard = client_clone.lock().await;
-                client_guard.read_event().await
-            };
-
-            match event_result {
-                Ok(Some(event)) => {
+            match client.recv_event().await {
+                Some(event) => {
                     info!("Received event: {}", event.topic);
                     if event.topic == "plugin.deregistered" {
                         if let Some(data) = event.data.as_object() {
@@ -151,22 +221,221 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
-                Ok(None) => {
+                None => {
                     info!("Connection closed, shutting down");
                     let _ = shutdown_tx.send(()).await;
                     break;
                 }
-                Err(e) => {
-                    error!("Error reading event: {:?}", e);
-                    break;
-                }
             }
         }
     });
 
-    // Run UDP server with persistent daemon connection
-    run_udp_server(client, args.bind_addr, shutdown_rx).await?;
+    // Run UDP server over the pooled daemon connections
+    let udp_socket = UdpSocket::bind(args.bind_addr).await?;
+    info!("UDP proxy listening on {}", args.bind_addr);
+    let max_inflight = args
+        .max_inflight
+        .map(|max| max as usize)
+        .unwrap_or(pool.clients.len() * INFLIGHT_PER_CONNECTION);
+    run_udp_server(
+        Arc::new(pool),
+        udp_socket,
+        args.max_datagram,
+        max_inflight,
+        shutdown_rx,
+    )
+    .await?;
 
     info!("UDP proxy shutdown complete");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pandemic_protocol::{RequestEnvelope, ResponseEnvelope};
+    use std::time::{Duration, Instant};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::UnixListener;
+
+    const DAEMON_LATENCY: Duration = Duration::from_millis(200);
+
+    /// Answers every request with `response` after `delay`, concurrently per request
+    async fn mock_daemon(listener: UnixListener, delay: Duration, response: Response) {
+        while let Ok((stream, _)) = listener.accept().await {
+            let response = response.clone();
+            tokio::spawn(async move {
+                let (read_half, write_half) = stream.into_split();
+                let writer = Arc::new(tokio::sync::Mutex::new(write_half));
+                let mut lines = BufReader::new(read_half).lines();
+
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let envelope: RequestEnvelope = serde_json::from_str(&line).unwrap();
+                    let writer = Arc::clone(&writer);
+                    let response = response.clone();
+                    tokio::spawn(async move {
+                        let response = if matches!(envelope.request, Request::Hello { .. }) {
+                            Response::success()
+                        } else {
+                            tokio::time::sleep(delay).await;
+                            response
+                        };
+                        let mut reply = serde_json::to_vec(&ResponseEnvelope {
+                            id: envelope.id,
+                            response,
+                        })
+                        .unwrap();
+                        reply.push(b'\n');
+                        let _ = writer.lock().await.write_all(&reply).await;
+                    });
+                }
+            });
+        }
+    }
+
+    /// Start a proxy in front of `socket_path`, returning its UDP address
+    async fn start_proxy(
+        socket_path: &Path,
+        max_datagram: usize,
+        max_inflight: usize,
+    ) -> SocketAddr {
+        let primary = DaemonClient::connect(socket_path)
+            .await
+            .unwrap()
+            .into_multiplexed();
+        let pool = ClientPool::connect(primary, socket_path, 2).await.unwrap();
+
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = udp_socket.local_addr().unwrap();
+        let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
+        tokio::spawn(async move {
+            let _shutdown_tx = shutdown_tx;
+            run_udp_server(
+                Arc::new(pool),
+                udp_socket,
+                max_datagram,
+                max_inflight,
+                shutdown_rx,
+            )
+            .await
+        });
+        server_addr
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_do_not_serialize() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(mock_daemon(listener, DAEMON_LATENCY, Response::success()));
+        let server_addr = start_proxy(&socket_path, 4096, 64).await;
+
+        const REQUESTS: usize = 8;
+        let started = Instant::now();
+        let mut handles = Vec::new();
+        for _ in 0..REQUESTS {
+            handles.push(tokio::spawn(async move {
+                let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                let request = serde_json::to_vec(&Request::ListPlugins).unwrap();
+                socket.send_to(&request, server_addr).await.unwrap();
+
+                let mut buf = vec![0u8; 4096];
+                let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                serde_json::from_slice::<Response>(&buf[..len]).unwrap()
+            }));
+        }
+        for handle in handles {
+            assert!(matches!(handle.await.unwrap(), Response::Success { .. }));
+        }
+
+        // Serialized handling would take REQUESTS * DAEMON_LATENCY
+        let elapsed = started.elapsed();
+        assert!(
+            elapsed < DAEMON_LATENCY * 3,
+            "{} requests took {:?}",
+            REQUESTS,
+            elapsed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_response_is_chunked_and_reassembled() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let plugins: Vec<serde_json::Value> = (0..200)
+            .map(|i| serde_json::json!({"name": format!("plugin-{}", i), "version": "1.0.0"}))
+            .collect();
+        let expected = Response::success_with_data(serde_json::json!(plugins));
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(mock_daemon(listener, Duration::ZERO, expected.clone()));
+
+        const MAX_DATAGRAM: usize = 512;
+        let server_addr = start_proxy(&socket_path, MAX_DATAGRAM, 64).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = serde_json::to_vec(&Request::ListPlugins).unwrap();
+        socket.send_to(&request, server_addr).await.unwrap();
+
+        let peer = socket.local_addr().unwrap();
+        let mut reassembler = Reassembler::new(MAX_DATAGRAM);
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+        let mut datagrams = 0;
+        let message = loop {
+            let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(len <= MAX_DATAGRAM);
+            datagrams += 1;
+            if let Some(message) = reassembler.push(peer, &buf[..len]) {
+                break message;
+            }
+        };
+
+        assert!(datagrams > 1);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&message).unwrap(),
+            serde_json::to_value(&expected).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_requests_beyond_max_inflight_are_dropped() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let socket_path = temp_dir.path().join("pandemic.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(mock_daemon(listener, DAEMON_LATENCY, Response::success()));
+        let server_addr = start_proxy(&socket_path, 4096, 2).await;
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let request = serde_json::to_vec(&Request::ListPlugins).unwrap();
+        for _ in 0..5 {
+            socket.send_to(&request, server_addr).await.unwrap();
+        }
+
+        let mut buf = vec![0u8; 4096];
+        let mut answered = 0;
+        while let Ok(received) =
+            tokio::time::timeout(DAEMON_LATENCY * 3, socket.recv(&mut buf)).await
+        {
+            let len = received.unwrap();
+            assert!(matches!(
+                serde_json::from_slice::<Response>(&buf[..len]).unwrap(),
+                Response::Success { .. }
+            ));
+            answered += 1;
+        }
+        assert_eq!(answered, 2);
+
+        // Permits come back once requests finish
+        socket.send_to(&request, server_addr).await.unwrap();
+        let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(serde_json::from_slice::<Response>(&buf[..len]).is_ok());
+    }
+}